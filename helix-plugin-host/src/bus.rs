@@ -0,0 +1,72 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+
+/// Event published by a plugin on the host bus.
+#[derive(Debug, Clone)]
+pub struct BusMessage {
+    /// Name of the publishing plugin.
+    pub source: String,
+    /// Topic the event was published on.
+    pub topic: String,
+    /// Arbitrary payload forwarded to subscribers.
+    pub payload: Value,
+}
+
+/// Sending half handed to every plugin process for publishing events.
+pub type BusSender = mpsc::UnboundedSender<BusMessage>;
+
+/// Receiving half drained by the host dispatcher.
+pub type BusReceiver = mpsc::UnboundedReceiver<BusMessage>;
+
+/// Create a new bus channel.
+pub fn channel() -> (BusSender, BusReceiver) {
+    mpsc::unbounded_channel()
+}
+
+/// Topic subscriptions keyed by topic name.
+#[derive(Debug, Default)]
+pub struct Subscriptions {
+    topics: HashMap<String, Vec<String>>,
+}
+
+impl Subscriptions {
+    /// Subscribe `plugin` to `topic`.
+    pub fn subscribe(&mut self, plugin: &str, topic: &str) {
+        let subscribers = self.topics.entry(topic.to_string()).or_default();
+        if !subscribers.iter().any(|name| name == plugin) {
+            subscribers.push(plugin.to_string());
+        }
+    }
+
+    /// Whether `plugin` subscribed to `topic`.
+    pub fn is_subscribed(&self, plugin: &str, topic: &str) -> bool {
+        self.topics
+            .get(topic)
+            .is_some_and(|subscribers| subscribers.iter().any(|name| name == plugin))
+    }
+
+    /// Remove every subscription held by `plugin`.
+    pub fn remove_plugin(&mut self, plugin: &str) {
+        self.topics.retain(|_, subscribers| {
+            subscribers.retain(|name| name != plugin);
+            !subscribers.is_empty()
+        });
+    }
+
+    /// Plugins that should receive `message`. Publishers never receive their
+    /// own events.
+    pub fn recipients<'a>(&'a self, message: &'a BusMessage) -> impl Iterator<Item = &'a str> {
+        self.topics
+            .get(&message.topic)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .filter(move |name| *name != message.source)
+    }
+
+    /// Drop all subscriptions.
+    pub fn clear(&mut self) {
+        self.topics.clear();
+    }
+}
@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use std::{collections::HashMap, fs, path::PathBuf};
+
+/// Commands advertised by plugins the last time they ran.
+///
+/// Lazy plugins are only started once one of their commands is executed, so
+/// the host needs to know their commands up front. Entries may declare them in
+/// the manifest; otherwise the list recorded the last time the plugin ran is
+/// used. The commands of crashed plugins stay known while they restart.
+#[derive(Debug, Default)]
+pub struct CommandCache {
+    path: PathBuf,
+    plugins: HashMap<String, Vec<String>>,
+}
+
+impl CommandCache {
+    /// Load the cache from the Helix cache directory. A missing or corrupt
+    /// cache resolves to an empty one.
+    pub fn load() -> Self {
+        let path = helix_loader::cache_dir().join("plugin-commands.json");
+        let plugins = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self { path, plugins }
+    }
+
+    /// Commands recorded for `plugin`.
+    pub fn commands(&self, plugin: &str) -> &[String] {
+        self.plugins
+            .get(plugin)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Record the commands advertised by `plugin` and persist the cache.
+    pub fn update(&mut self, plugin: &str, commands: Vec<String>) -> Result<()> {
+        if self.commands(plugin) == commands.as_slice() {
+            return Ok(());
+        }
+        self.plugins.insert(plugin.to_string(), commands);
+        self.persist()
+    }
+
+    fn persist(&self) -> Result<()> {
+        let path = &self.path;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create `{}`", parent.display()))?;
+        }
+        let contents = serde_json::to_string_pretty(&self.plugins)?;
+        fs::write(path, contents)
+            .with_context(|| format!("failed to write plugin command cache `{}`", path.display()))
+    }
+}
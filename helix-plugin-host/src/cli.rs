@@ -0,0 +1,608 @@
+//! Subcommands running plugins outside of the language server, for
+//! debugging manifests and plugins from a terminal.
+
+use crate::{
+    cache::CommandCache,
+    install::{self, InstallSource, Installed},
+    manifest::PluginEntry,
+    plugin::PluginProcess,
+    registry::{self, RegistryPlugin},
+    scaffold::{self, Language},
+    server::{HostOptions, Launcher},
+    trace::{self, Direction, TraceRecord},
+    validate::validate,
+};
+use anyhow::{anyhow, Context, Result};
+use helix_plugin_sdk::protocol::{HostRequestPayload, PluginResponse};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::Command,
+};
+
+/// Time a replayed plugin gets to answer each request.
+const REPLAY_RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time a replayed plugin gets to exit once its input is closed.
+const REPLAY_EXIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Validate the manifest, handshake every enabled plugin and print a summary
+/// table. Fails when any problem was found.
+pub async fn check(options: &HostOptions) -> Result<()> {
+    let manifest = options.load_manifest()?;
+    let workspace_root = std::env::current_dir().ok();
+    let problems = validate(
+        &manifest,
+        &options.manifest_dir(),
+        workspace_root.as_deref(),
+    );
+
+    let launcher = Launcher::detached(options, workspace_root);
+    let results = handshake_all(&launcher, &manifest.plugins).await;
+
+    let mut failures = 0;
+    let mut rows = Vec::new();
+    for (entry, result) in manifest.plugins.iter().zip(results) {
+        let (status, details) = match result {
+            None => ("disabled", String::new()),
+            Some(Ok((process, response))) => {
+                let row = match response {
+                    PluginResponse::Initialized {
+                        commands,
+                        protocol_version,
+                        ..
+                    } => match entry.check_protocol(protocol_version) {
+                        Ok(()) => {
+                            let ids: Vec<String> =
+                                commands.into_iter().map(|command| command.id).collect();
+                            ("ok", ids.join(", "))
+                        }
+                        Err(message) => {
+                            failures += 1;
+                            ("incompatible", message)
+                        }
+                    },
+                    other => {
+                        failures += 1;
+                        (
+                            "failed",
+                            format!("unexpected handshake response: {other:?}"),
+                        )
+                    }
+                };
+                process.shutdown().await;
+                row
+            }
+            Some(Err(err)) => {
+                failures += 1;
+                ("failed", format!("{err:#}"))
+            }
+        };
+        rows.push([entry.name.clone(), status.to_string(), details]);
+    }
+
+    if !problems.is_empty() {
+        println!(
+            "{} problem(s) in `{}`:",
+            problems.len(),
+            options.manifest_display()
+        );
+        for problem in &problems {
+            println!("  - {problem}");
+        }
+        println!();
+    }
+    print_table(["PLUGIN", "STATUS", "COMMANDS"], &rows);
+
+    if !problems.is_empty() || failures > 0 {
+        anyhow::bail!(
+            "{} manifest problem(s), {failures} plugin(s) failed",
+            problems.len()
+        );
+    }
+    Ok(())
+}
+
+/// Handshake every enabled plugin and print its version and commands, as
+/// text or as a JSON array.
+pub async fn list(options: &HostOptions, as_json: bool) -> Result<()> {
+    let manifest = options.load_manifest()?;
+    let launcher = Launcher::detached(options, std::env::current_dir().ok());
+    let results = handshake_all(&launcher, &manifest.plugins).await;
+
+    let mut plugins = Vec::new();
+    for (entry, result) in manifest.plugins.iter().zip(results) {
+        let plugin = match result {
+            None => json!({ "name": entry.name, "state": "disabled", "commands": [] }),
+            Some(Ok((process, response))) => {
+                process.shutdown().await;
+                match response {
+                    PluginResponse::Initialized {
+                        commands,
+                        protocol_version,
+                        version,
+                        ..
+                    } => json!({
+                        "name": entry.name,
+                        "state": "ok",
+                        "version": version,
+                        "protocol_version": protocol_version,
+                        "commands": commands,
+                    }),
+                    other => json!({
+                        "name": entry.name,
+                        "state": "failed",
+                        "error": format!("unexpected handshake response: {other:?}"),
+                        "commands": [],
+                    }),
+                }
+            }
+            Some(Err(err)) => json!({
+                "name": entry.name,
+                "state": "failed",
+                "error": format!("{err:#}"),
+                "commands": [],
+            }),
+        };
+        plugins.push(plugin);
+    }
+
+    if as_json {
+        println!("{}", serde_json::to_string_pretty(&plugins)?);
+        return Ok(());
+    }
+
+    for plugin in &plugins {
+        let name = plugin["name"].as_str().unwrap_or_default();
+        match plugin["state"].as_str() {
+            Some("ok") => println!(
+                "{name} {}",
+                plugin["version"].as_str().unwrap_or("(unknown version)")
+            ),
+            Some("disabled") => println!("{name} (disabled)"),
+            _ => println!(
+                "{name} (failed: {})",
+                plugin["error"].as_str().unwrap_or_default()
+            ),
+        }
+        let rows: Vec<[String; 2]> = plugin["commands"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|command| {
+                [
+                    format!("  {}", command["id"].as_str().unwrap_or_default()),
+                    command["title"].as_str().unwrap_or_default().to_string(),
+                ]
+            })
+            .collect();
+        if !rows.is_empty() {
+            print_rows(&rows);
+        }
+    }
+    Ok(())
+}
+
+/// Print the registry plugins matching `term`.
+pub async fn search(options: &HostOptions, term: &str, registry: Option<String>) -> Result<()> {
+    let location = registry_location(options, registry)?.context(
+        "no plugin registry configured; set `registry` in the manifest or pass `--registry`",
+    )?;
+    let plugins = registry::fetch(&location).await?;
+
+    let rows: Vec<[String; 4]> = registry::search(&plugins, term)
+        .map(|plugin| {
+            [
+                plugin.name.clone(),
+                plugin.version.clone().unwrap_or_default(),
+                plugin.description.clone().unwrap_or_default(),
+                plugin.source.clone(),
+            ]
+        })
+        .collect();
+    if rows.is_empty() {
+        println!("no plugins matching `{term}` in `{location}`");
+        return Ok(());
+    }
+    print_table(["NAME", "VERSION", "DESCRIPTION", "SOURCE"], &rows);
+    Ok(())
+}
+
+/// Install a plugin from `source` and add it to the manifest. Sources that
+/// are neither paths nor git URLs are looked up in the registry first and
+/// installed from crates.io otherwise.
+pub async fn install(
+    options: &HostOptions,
+    source: &str,
+    mut name: Option<String>,
+    mut bin: Option<String>,
+    registry: Option<String>,
+) -> Result<()> {
+    let source = match InstallSource::parse(source) {
+        InstallSource::Crate(crate_name) => {
+            match find_in_registry(options, registry, &crate_name).await? {
+                Some(plugin) => {
+                    name.get_or_insert(plugin.name);
+                    bin = bin.or(plugin.bin);
+                    InstallSource::parse(&plugin.source)
+                }
+                None => InstallSource::Crate(crate_name),
+            }
+        }
+        source => source,
+    };
+    let name = install::install(options, source, name.as_deref(), bin.as_deref()).await?;
+    println!(
+        "installed plugin `{name}` into `{}`",
+        options.manifest_path().display()
+    );
+    Ok(())
+}
+
+/// Reinstall `name`, or every installed plugin with an update available,
+/// from the source it was installed from.
+pub async fn upgrade(
+    options: &HostOptions,
+    name: Option<String>,
+    registry: Option<String>,
+) -> Result<()> {
+    let installed = Installed::load(options)?;
+    let location = registry_location(options, registry)?;
+    let index = registry::fetch_optional(location.as_deref()).await;
+
+    let upgrades = match name {
+        Some(name) => {
+            let plugin = installed.plugins.get(&name).with_context(|| {
+                format!("plugin `{name}` was not installed with `helix-plugin-host install`")
+            })?;
+            let update = install::check_update(&name, plugin, &index)
+                .await
+                .unwrap_or_else(|err| {
+                    log::warn!("failed to check plugin `{name}` for updates: {err:#}");
+                    None
+                });
+            vec![(name, update)]
+        }
+        None => install::updates(&installed, &index)
+            .await
+            .into_iter()
+            .map(|(name, update)| (name, Some(update)))
+            .collect(),
+    };
+    if upgrades.is_empty() {
+        println!("all installed plugins are up to date");
+        return Ok(());
+    }
+
+    for (name, update) in upgrades {
+        let plugin = &installed.plugins[&name];
+        match update {
+            Some(update) => println!("upgrading plugin `{name}` ({update})"),
+            None => println!("reinstalling plugin `{name}`"),
+        }
+        install::install(
+            options,
+            plugin.source.clone(),
+            Some(&name),
+            plugin.bin.as_deref(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Generate a plugin skeleton in `path` (`./<name>` by default) and print
+/// the manifest entry running it.
+pub fn new(
+    name: &str,
+    language: Language,
+    path: Option<PathBuf>,
+    sdk: Option<&Path>,
+) -> Result<()> {
+    let dir = path.unwrap_or_else(|| PathBuf::from(name));
+    let scaffold = scaffold::generate(name, language, &dir, sdk)?;
+    for file in &scaffold.files {
+        println!("created {}", file.display());
+    }
+    if let Some(steps) = &scaffold.next_steps {
+        println!("\nbuild the plugin with:\n\n    {steps}");
+    }
+    println!(
+        "\nadd the plugin to your manifest:\n\n{}",
+        scaffold.manifest_entry
+    );
+    Ok(())
+}
+
+/// Registry location from the command line or the manifest.
+fn registry_location(options: &HostOptions, registry: Option<String>) -> Result<Option<String>> {
+    match registry {
+        Some(registry) => Ok(Some(registry)),
+        None => Ok(options.load_manifest()?.registry),
+    }
+}
+
+/// Look up the plugin called `name` in the configured registry, if any.
+async fn find_in_registry(
+    options: &HostOptions,
+    registry: Option<String>,
+    name: &str,
+) -> Result<Option<RegistryPlugin>> {
+    let Some(location) = registry_location(options, registry)? else {
+        return Ok(None);
+    };
+    match registry::fetch(&location).await {
+        Ok(plugins) => Ok(plugins.into_iter().find(|plugin| plugin.name == name)),
+        Err(err) => {
+            log::warn!("{err:#}; treating `{name}` as a crate name");
+            Ok(None)
+        }
+    }
+}
+
+/// Run `command` once and print its JSON result.
+///
+/// Plugins are started one at a time until one advertises the command,
+/// beginning with those declaring it in the manifest or the command cache, so
+/// usually only the owning plugin is spawned.
+pub async fn exec(
+    options: &HostOptions,
+    command: &str,
+    arguments: Option<&str>,
+    language_id: Option<String>,
+) -> Result<()> {
+    let arguments = match arguments {
+        Some(arguments) => {
+            match serde_json::from_str(arguments).context("command arguments must be valid JSON")? {
+                Value::Array(arguments) => arguments,
+                argument => vec![argument],
+            }
+        }
+        None => Vec::new(),
+    };
+
+    let manifest = options.load_manifest()?;
+    let cache = CommandCache::load();
+    let mut candidates: Vec<&PluginEntry> = manifest
+        .plugins
+        .iter()
+        .filter(|entry| entry.enabled)
+        .collect();
+    candidates.sort_by_key(|entry| {
+        let mut declared = entry.commands.iter().chain(cache.commands(&entry.name));
+        !declared.any(|id| id == command)
+    });
+
+    let launcher = Launcher::detached(options, std::env::current_dir().ok());
+    for entry in candidates {
+        let (process, response) = match launcher.launch(entry).await {
+            Ok(launched) => launched,
+            Err(err) => {
+                log::warn!("skipping plugin `{}`: {err:#}", entry.name);
+                continue;
+            }
+        };
+        let provides = matches!(
+            &response,
+            PluginResponse::Initialized { commands, .. }
+                if commands.iter().any(|candidate| candidate.id == command)
+        );
+        if !provides {
+            process.shutdown().await;
+            continue;
+        }
+
+        let response = process
+            .send_request(HostRequestPayload::Execute {
+                command: command.to_string(),
+                arguments,
+                language_id,
+            })
+            .await;
+        process.shutdown().await;
+        return match response? {
+            PluginResponse::CommandResult { result, .. } => {
+                let result = result.unwrap_or(Value::Null);
+                println!("{}", serde_json::to_string_pretty(&result)?);
+                Ok(())
+            }
+            PluginResponse::CommandError { message } => {
+                anyhow::bail!("`{command}` failed: {message}")
+            }
+            other => anyhow::bail!(
+                "plugin `{}` sent an unexpected response: {other:?}",
+                entry.name
+            ),
+        };
+    }
+
+    anyhow::bail!(
+        "no plugin in `{}` provides `{command}`",
+        options.manifest_display()
+    )
+}
+
+/// Feed the requests of a plugin session recorded with `--trace` into
+/// `command`, one at a time, and compare the responses with the recorded
+/// ones. Fails when any response differs.
+pub async fn replay(
+    trace: &Path,
+    plugin: Option<&str>,
+    session: usize,
+    command: &[String],
+) -> Result<()> {
+    let records = trace::load(trace)?;
+    let plugin = match plugin {
+        Some(plugin) => plugin.to_string(),
+        None => {
+            let mut plugins: Vec<&str> = records
+                .iter()
+                .map(|record| record.plugin.as_str())
+                .collect();
+            plugins.sort_unstable();
+            plugins.dedup();
+            match plugins.as_slice() {
+                [plugin] => plugin.to_string(),
+                [] => anyhow::bail!("trace `{}` holds no messages", trace.display()),
+                _ => anyhow::bail!(
+                    "trace `{}` holds several plugins ({}); pick one with `--plugin`",
+                    trace.display(),
+                    plugins.join(", ")
+                ),
+            }
+        }
+    };
+
+    let mut instances = Vec::new();
+    for record in records.iter().filter(|record| record.plugin == plugin) {
+        if !instances.contains(&record.instance) {
+            instances.push(record.instance);
+        }
+    }
+    let Some(instance) = session
+        .checked_sub(1)
+        .and_then(|index| instances.get(index))
+    else {
+        anyhow::bail!(
+            "plugin `{plugin}` has {} recorded sessions in `{}`",
+            instances.len(),
+            trace.display()
+        );
+    };
+    let session: Vec<&TraceRecord> = records
+        .iter()
+        .filter(|record| record.instance == *instance)
+        .collect();
+    let recorded: HashMap<u64, &Value> = session
+        .iter()
+        .filter(|record| record.direction == Direction::FromPlugin)
+        .filter(|record| record.message["type"] == "response")
+        .filter_map(|record| Some((record.message["id"].as_u64()?, &record.message["result"])))
+        .collect();
+
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("missing plugin command"))?;
+    let mut child = Command::new(program)
+        .args(args)
+        .env("HELIX_PLUGIN_NAME", &plugin)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("failed to spawn `{program}`"))?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("plugin stdin unavailable"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow!("plugin stdout unavailable"))?;
+    let mut lines = BufReader::new(stdout).lines();
+
+    let (mut matched, mut differed) = (0, 0);
+    for request in session
+        .iter()
+        .filter(|record| record.direction == Direction::ToPlugin)
+    {
+        let line = serde_json::to_string(&request.message)?;
+        stdin.write_all(line.as_bytes()).await?;
+        stdin.write_all(b"\n").await?;
+        stdin.flush().await?;
+
+        // Requests the plugin never answered during the session are sent
+        // without waiting for a response.
+        let Some(id) = request.message["id"].as_u64() else {
+            continue;
+        };
+        let Some(expected) = recorded.get(&id) else {
+            continue;
+        };
+        let kind = request.message["payload"]["type"]
+            .as_str()
+            .unwrap_or("request");
+        let actual = loop {
+            let line = tokio::time::timeout(REPLAY_RESPONSE_TIMEOUT, lines.next_line())
+                .await
+                .map_err(|_| anyhow!("plugin did not answer `{kind}` request {id} in time"))??
+                .ok_or_else(|| anyhow!("plugin closed its output before answering request {id}"))?;
+            let message: Value = serde_json::from_str(&line).unwrap_or_else(|_| Value::from(line));
+            if message["type"] == "response" && message["id"].as_u64() == Some(id) {
+                break message["result"].clone();
+            }
+            println!("  {message}");
+        };
+        if actual == **expected {
+            matched += 1;
+            println!("request {id} ({kind}): ok");
+        } else {
+            differed += 1;
+            println!(
+                "request {id} ({kind}): differs\n  recorded: {expected}\n  replayed: {actual}"
+            );
+        }
+    }
+
+    drop(stdin);
+    if tokio::time::timeout(REPLAY_EXIT_TIMEOUT, child.wait())
+        .await
+        .is_err()
+    {
+        child.kill().await?;
+    }
+
+    println!("{matched} responses matched, {differed} differed");
+    if differed > 0 {
+        anyhow::bail!("{differed} responses differ from the trace");
+    }
+    Ok(())
+}
+
+/// Spawn and handshake every enabled plugin in `entries` concurrently.
+/// Disabled plugins yield `None`.
+async fn handshake_all(
+    launcher: &Launcher,
+    entries: &[PluginEntry],
+) -> Vec<Option<Result<(PluginProcess, PluginResponse)>>> {
+    let launches = entries.iter().map(|entry| async move {
+        if entry.enabled {
+            Some(launcher.launch(entry).await)
+        } else {
+            None
+        }
+    });
+    futures::future::join_all(launches).await
+}
+
+/// Print `rows` below `header` with left-aligned columns.
+fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let mut table = vec![header.map(str::to_string)];
+    table.extend_from_slice(rows);
+    print_rows(&table);
+}
+
+/// Print `rows` with left-aligned columns.
+fn print_rows<const N: usize>(rows: &[[String; N]]) {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
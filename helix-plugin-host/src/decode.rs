@@ -0,0 +1,35 @@
+//! Decoding of the messages plugins write to stdout.
+//!
+//! With the `simd-json` feature, lines are parsed with SIMD instructions into
+//! buffers reused across messages. Lines it rejects are parsed again with
+//! `serde_json`, which reports the error.
+
+use helix_plugin_sdk::protocol::PluginMessage;
+
+/// Decoder of the message lines of one plugin, owning the buffers reused
+/// between lines.
+#[derive(Default)]
+pub struct Decoder {
+    #[cfg(feature = "simd-json")]
+    scratch: Vec<u8>,
+    #[cfg(feature = "simd-json")]
+    buffers: simd_json::Buffers,
+}
+
+impl Decoder {
+    /// Decode a line holding a single message.
+    pub fn decode(&mut self, line: &str) -> serde_json::Result<PluginMessage> {
+        #[cfg(feature = "simd-json")]
+        {
+            // The SIMD parser rewrites its input in place.
+            self.scratch.clear();
+            self.scratch.extend_from_slice(line.as_bytes());
+            if let Ok(message) =
+                simd_json::serde::from_slice_with_buffers(&mut self.scratch, &mut self.buffers)
+            {
+                return Ok(message);
+            }
+        }
+        serde_json::from_str(line)
+    }
+}
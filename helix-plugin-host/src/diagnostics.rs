@@ -0,0 +1,79 @@
+//! Diagnostics plugins publish for files, e.g. review comments on lines.
+//!
+//! The host is a single language server to the editor, whose diagnostics for
+//! a file replace the ones published before. Diagnostics are therefore kept
+//! per plugin and the editor receives those of every plugin for a file at
+//! once.
+
+use crate::symbols::range_to_lsp;
+use helix_plugin_sdk::protocol::{Diagnostic, DiagnosticSeverity};
+use parking_lot::Mutex;
+use std::{collections::BTreeMap, sync::Arc};
+use tower_lsp::lsp_types as lsp;
+
+/// Diagnostics of a file, keyed by plugin name.
+type FileDiagnostics = BTreeMap<String, Vec<lsp::Diagnostic>>;
+
+/// Diagnostics of every plugin, keyed by file. Cloning shares the
+/// diagnostics.
+#[derive(Debug, Clone, Default)]
+pub struct PluginDiagnostics(Arc<Mutex<BTreeMap<lsp::Url, FileDiagnostics>>>);
+
+impl PluginDiagnostics {
+    /// Replace the diagnostics of `plugin` for `uri`, returning the
+    /// diagnostics of every plugin for it.
+    pub fn set(
+        &self,
+        plugin: &str,
+        uri: &lsp::Url,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Vec<lsp::Diagnostic> {
+        let mut files = self.0.lock();
+        let plugins = files.entry(uri.clone()).or_default();
+        if diagnostics.is_empty() {
+            plugins.remove(plugin);
+        } else {
+            let diagnostics = diagnostics
+                .into_iter()
+                .map(|diagnostic| to_lsp(diagnostic, plugin))
+                .collect();
+            plugins.insert(plugin.to_string(), diagnostics);
+        }
+        let merged = plugins.values().flatten().cloned().collect();
+        if plugins.is_empty() {
+            files.remove(uri);
+        }
+        merged
+    }
+
+    /// Drop the diagnostics of `plugin`, returning the files it had
+    /// diagnostics for with those of the other plugins.
+    pub fn remove_plugin(&self, plugin: &str) -> Vec<(lsp::Url, Vec<lsp::Diagnostic>)> {
+        let mut files = self.0.lock();
+        let mut changed = Vec::new();
+        files.retain(|uri, plugins| {
+            if plugins.remove(plugin).is_some() {
+                changed.push((uri.clone(), plugins.values().flatten().cloned().collect()));
+            }
+            !plugins.is_empty()
+        });
+        changed
+    }
+}
+
+/// Convert a diagnostic of `plugin` to its LSP form. Diagnostics without a
+/// source name the plugin.
+fn to_lsp(diagnostic: Diagnostic, plugin: &str) -> lsp::Diagnostic {
+    lsp::Diagnostic {
+        range: range_to_lsp(diagnostic.range),
+        severity: diagnostic.severity.map(|severity| match severity {
+            DiagnosticSeverity::Error => lsp::DiagnosticSeverity::ERROR,
+            DiagnosticSeverity::Warning => lsp::DiagnosticSeverity::WARNING,
+            DiagnosticSeverity::Information => lsp::DiagnosticSeverity::INFORMATION,
+            DiagnosticSeverity::Hint => lsp::DiagnosticSeverity::HINT,
+        }),
+        source: Some(diagnostic.source.unwrap_or_else(|| plugin.to_string())),
+        message: diagnostic.message,
+        ..Default::default()
+    }
+}
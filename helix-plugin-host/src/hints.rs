@@ -0,0 +1,23 @@
+//! Inlay hints plugins show in documents, e.g. the latest version of a
+//! dependency next to the required one.
+
+use crate::symbols::position_to_lsp;
+use helix_plugin_sdk::protocol::{InlayHint, InlayHintKind};
+use tower_lsp::lsp_types as lsp;
+
+/// Convert a hint to its LSP form.
+pub fn to_lsp(hint: InlayHint) -> lsp::InlayHint {
+    lsp::InlayHint {
+        position: position_to_lsp(hint.position),
+        label: lsp::InlayHintLabel::String(hint.label),
+        kind: hint.kind.map(|kind| match kind {
+            InlayHintKind::Type => lsp::InlayHintKind::TYPE,
+            InlayHintKind::Parameter => lsp::InlayHintKind::PARAMETER,
+        }),
+        text_edits: None,
+        tooltip: hint.tooltip.map(lsp::InlayHintTooltip::String),
+        padding_left: hint.padding_left.then_some(true),
+        padding_right: hint.padding_right.then_some(true),
+        data: None,
+    }
+}
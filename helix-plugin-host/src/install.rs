@@ -0,0 +1,370 @@
+//! Installation of plugins into a directory managed by the host.
+//!
+//! Plugins are built with `cargo install` into a `plugins` directory next to
+//! the primary manifest, recorded in `plugins/installed.json` and appended to
+//! the manifest. The recorded versions and git revisions are compared with
+//! the registry, the git remote or crates.io to find updates.
+
+use crate::{registry::RegistryPlugin, server::HostOptions};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tokio::process::Command;
+
+/// Directory holding installed plugins, relative to the manifest directory.
+const INSTALL_DIR: &str = "plugins";
+
+/// File recording installed plugins inside [`INSTALL_DIR`].
+const INSTALLED_FILE: &str = "installed.json";
+
+/// Where a plugin is installed from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "location", rename_all = "lowercase")]
+pub enum InstallSource {
+    /// Local Cargo project.
+    Path(PathBuf),
+    /// Git repository containing a Cargo project.
+    Git(String),
+    /// Crate published on crates.io.
+    Crate(String),
+}
+
+impl InstallSource {
+    /// Classify `source` as an existing path, a git URL or a crate name.
+    pub fn parse(source: &str) -> Self {
+        let path = Path::new(source);
+        if path.exists() {
+            let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            Self::Path(path)
+        } else if source.contains("://") || source.starts_with("git@") || source.ends_with(".git") {
+            Self::Git(source.to_string())
+        } else {
+            Self::Crate(source.to_string())
+        }
+    }
+
+    fn cargo_args(&self, command: &mut Command) {
+        match self {
+            Self::Path(path) => command.arg("--path").arg(path),
+            Self::Git(url) => command.arg("--git").arg(url),
+            Self::Crate(name) => command.arg(name),
+        };
+    }
+}
+
+/// Record of a plugin installed by the host.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledPlugin {
+    pub source: InstallSource,
+    /// Installed executable.
+    pub binary: PathBuf,
+    /// Name of the binary target passed to `cargo install --bin`, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bin: Option<String>,
+    /// Package version that was installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Commit that was installed, for git sources.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+}
+
+/// Newer release of an installed plugin.
+#[derive(Debug, Clone)]
+pub struct Update {
+    pub current: String,
+    pub latest: String,
+}
+
+impl fmt::Display for Update {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Git revisions are shortened the way `git log --oneline` does.
+        let short = |version: &str| {
+            if version.len() == 40 && version.bytes().all(|b| b.is_ascii_hexdigit()) {
+                version[..7].to_string()
+            } else {
+                version.to_string()
+            }
+        };
+        write!(f, "{} -> {}", short(&self.current), short(&self.latest))
+    }
+}
+
+/// Installed plugins keyed by plugin name.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Installed {
+    #[serde(flatten)]
+    pub plugins: BTreeMap<String, InstalledPlugin>,
+}
+
+impl Installed {
+    /// Load the record of installed plugins next to the manifest.
+    pub fn load(options: &HostOptions) -> Result<Self> {
+        let path = install_dir(options).join(INSTALLED_FILE);
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("failed to parse `{}`", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("failed to read `{}`", path.display())),
+        }
+    }
+
+    /// Persist the record of installed plugins.
+    pub fn save(&self, options: &HostOptions) -> Result<()> {
+        let path = install_dir(options).join(INSTALLED_FILE);
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(&path, contents).with_context(|| format!("failed to write `{}`", path.display()))
+    }
+}
+
+/// Directory installed plugins are placed in.
+pub fn install_dir(options: &HostOptions) -> PathBuf {
+    let dir = options.manifest_dir().join(INSTALL_DIR);
+    std::path::absolute(&dir).unwrap_or(dir)
+}
+
+/// Build the plugin from `source`, place its binary in the managed
+/// directory and declare it in the primary manifest. Returns the plugin
+/// name.
+pub async fn install(
+    options: &HostOptions,
+    source: InstallSource,
+    name: Option<&str>,
+    bin: Option<&str>,
+) -> Result<String> {
+    let dir = install_dir(options);
+    fs::create_dir_all(&dir).with_context(|| format!("failed to create `{}`", dir.display()))?;
+
+    let started = SystemTime::now();
+    let binary = cargo_install(&dir, &source, bin, started).await?;
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => binary
+            .file_stem()
+            .context("installed binary has no file name")?
+            .to_string_lossy()
+            .into_owned(),
+    };
+
+    declare(options, &name, &binary)?;
+
+    let (version, revision) = installed_package(&dir, &binary).unwrap_or_default();
+    let mut installed = Installed::load(options)?;
+    installed.plugins.insert(
+        name.clone(),
+        InstalledPlugin {
+            source,
+            binary,
+            bin: bin.map(str::to_string),
+            version,
+            revision,
+        },
+    );
+    installed.save(options)?;
+    Ok(name)
+}
+
+/// Run `cargo install` into `root` and return the installed binary.
+async fn cargo_install(
+    root: &Path,
+    source: &InstallSource,
+    bin: Option<&str>,
+    started: SystemTime,
+) -> Result<PathBuf> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut command = Command::new(cargo);
+    command
+        .arg("install")
+        .arg("--force")
+        .arg("--root")
+        .arg(root);
+    if let Some(bin) = bin {
+        command.arg("--bin").arg(bin);
+    }
+    source.cargo_args(&mut command);
+
+    let status = command
+        .status()
+        .await
+        .context("failed to run `cargo install`")?;
+    anyhow::ensure!(status.success(), "`cargo install` failed ({status})");
+
+    // Binaries written by this installation are the ones modified since it
+    // started; `--force` makes cargo rewrite them even when up to date.
+    let bin_dir = root.join("bin");
+    let mut binaries = Vec::new();
+    for entry in
+        fs::read_dir(&bin_dir).with_context(|| format!("failed to read `{}`", bin_dir.display()))?
+    {
+        let entry = entry?;
+        let modified = entry.metadata()?.modified()?;
+        if modified >= started {
+            binaries.push(entry.path());
+        }
+    }
+
+    match binaries.len() {
+        0 => anyhow::bail!("`cargo install` did not produce a binary"),
+        1 => Ok(binaries.remove(0)),
+        _ => {
+            let names: Vec<String> = binaries
+                .iter()
+                .filter_map(|binary| binary.file_stem())
+                .map(|name| name.to_string_lossy().into_owned())
+                .collect();
+            anyhow::bail!(
+                "the package provides several binaries ({}); pick one with `--bin`",
+                names.join(", ")
+            )
+        }
+    }
+}
+
+/// Version and git revision of the package providing `binary`, read from the
+/// metadata `cargo install` keeps in `root`.
+fn installed_package(root: &Path, binary: &Path) -> Option<(Option<String>, Option<String>)> {
+    let contents = fs::read_to_string(root.join(".crates2.json")).ok()?;
+    let metadata: Value = serde_json::from_str(&contents).ok()?;
+    let file_name = binary.file_name()?.to_str()?;
+
+    let (package, _) = metadata["installs"]
+        .as_object()?
+        .iter()
+        .find(|(_, install)| {
+            install["bins"]
+                .as_array()
+                .is_some_and(|bins| bins.iter().any(|bin| bin == file_name))
+        })?;
+    // Packages are keyed as `<name> <version> (<source>)`.
+    let mut parts = package.splitn(3, ' ');
+    let _name = parts.next();
+    let version = parts.next().map(str::to_string);
+    let revision = parts
+        .next()
+        .and_then(|source| source.strip_prefix("(git+"))
+        .and_then(|source| source.strip_suffix(')'))
+        .and_then(|source| source.rsplit_once('#'))
+        .map(|(_, revision)| revision.to_string());
+    Some((version, revision))
+}
+
+/// Installed plugins with a newer release available. Plugins whose check
+/// fails are logged and skipped.
+pub async fn updates(installed: &Installed, registry: &[RegistryPlugin]) -> Vec<(String, Update)> {
+    let mut updates = Vec::new();
+    for (name, plugin) in &installed.plugins {
+        match check_update(name, plugin, registry).await {
+            Ok(Some(update)) => updates.push((name.clone(), update)),
+            Ok(None) => {}
+            Err(err) => log::warn!("failed to check plugin `{name}` for updates: {err:#}"),
+        }
+    }
+    updates
+}
+
+/// Look for a newer release of the installed plugin `name`. Versions listed
+/// in `registry` take precedence; otherwise git sources are compared with
+/// the remote `HEAD` and crates with the latest version on crates.io.
+pub async fn check_update(
+    name: &str,
+    plugin: &InstalledPlugin,
+    registry: &[RegistryPlugin],
+) -> Result<Option<Update>> {
+    let listed = registry
+        .iter()
+        .find(|listed| listed.name == name)
+        .and_then(|listed| listed.version.clone());
+    let (current, latest) = match (&plugin.source, listed) {
+        (_, Some(latest)) => (plugin.version.clone(), latest),
+        (InstallSource::Git(url), None) => (plugin.revision.clone(), git_head(url).await?),
+        (InstallSource::Crate(name), None) => (plugin.version.clone(), crate_version(name).await?),
+        (InstallSource::Path(_), None) => return Ok(None),
+    };
+    let Some(current) = current else {
+        return Ok(None);
+    };
+    Ok((current != latest).then_some(Update { current, latest }))
+}
+
+/// Commit at the `HEAD` of the git repository at `url`.
+async fn git_head(url: &str) -> Result<String> {
+    let output = Command::new("git")
+        .args(["ls-remote", url, "HEAD"])
+        .output()
+        .await
+        .context("failed to run `git ls-remote`")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "`git ls-remote {url}` failed ({})",
+        output.status
+    );
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()
+        .map(str::to_string)
+        .with_context(|| format!("`{url}` has no `HEAD`"))
+}
+
+/// Latest stable version of `name` published on crates.io.
+async fn crate_version(name: &str) -> Result<String> {
+    let url = format!("https://crates.io/api/v1/crates/{name}");
+    let response = reqwest::Client::new()
+        .get(&url)
+        .header(reqwest::header::USER_AGENT, "helix-plugin-host")
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("failed to query `{url}`"))?
+        .text()
+        .await
+        .with_context(|| format!("failed to read `{url}`"))?;
+    let response: Value = serde_json::from_str(&response)
+        .with_context(|| format!("invalid response from `{url}`"))?;
+    response["crate"]["max_stable_version"]
+        .as_str()
+        .or_else(|| response["crate"]["max_version"].as_str())
+        .map(str::to_string)
+        .with_context(|| format!("crates.io does not list a version of `{name}`"))
+}
+
+/// Append a manifest entry running `binary` as plugin `name`, unless the
+/// manifest already declares it with that command.
+fn declare(options: &HostOptions, name: &str, binary: &Path) -> Result<()> {
+    let command = binary.to_string_lossy();
+    let manifest = options.load_manifest()?;
+    if let Some(entry) = manifest.plugins.iter().find(|entry| entry.name == name) {
+        anyhow::ensure!(
+            entry.command == command,
+            "plugin `{name}` is already declared with command `{}`; pick another name with `--name`",
+            entry.command
+        );
+        return Ok(());
+    }
+
+    let path = options.manifest_path();
+    let existing = fs::read_to_string(path).unwrap_or_default();
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open `{}`", path.display()))?;
+    let separator = match existing.as_str() {
+        "" => "",
+        contents if contents.ends_with('\n') => "\n",
+        _ => "\n\n",
+    };
+    write!(
+        file,
+        "{separator}[[plugins]]\nname = {}\ncommand = {}\n",
+        toml::Value::String(name.to_string()),
+        toml::Value::String(command.into_owned()),
+    )
+    .with_context(|| format!("failed to update `{}`", path.display()))
+}
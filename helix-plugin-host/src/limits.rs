@@ -0,0 +1,102 @@
+//! Per-plugin resource budgets.
+//!
+//! CPU priority is lowered and the data segment capped with `RLIMIT_DATA` in
+//! the child before `exec`, so allocations beyond the memory budget fail
+//! right away. Resident memory is still sampled periodically: plugins whose
+//! usage exceeds the budget anyway, e.g. through mappings the limit does not
+//! cover, are killed by the monitor task so the exit is reported as an
+//! overrun and the plugin restarted.
+
+use std::time::Duration;
+use tokio::{process::Command, sync::oneshot};
+
+/// Interval between two memory usage samples.
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Run the plugin with the given niceness and memory budget in megabytes.
+#[cfg(unix)]
+pub fn restrict(command: &mut Command, nice: Option<i32>, max_memory_mb: Option<u64>) {
+    if nice.is_none() && max_memory_mb.is_none() {
+        return;
+    }
+    let max_data = max_memory_mb.map(|mb| {
+        let bytes = mb.saturating_mul(1024 * 1024);
+        libc::rlim_t::try_from(bytes).unwrap_or(libc::RLIM_INFINITY)
+    });
+    // SAFETY: `setpriority` and `setrlimit` are async-signal-safe and only
+    // affect the child.
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(nice) = nice {
+                if libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if let Some(max_data) = max_data {
+                let limit = libc::rlimit {
+                    rlim_cur: max_data,
+                    rlim_max: max_data,
+                };
+                if libc::setrlimit(libc::RLIMIT_DATA, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Run the plugin with the given niceness and memory budget in megabytes.
+/// Only the memory budget is sampled on this platform.
+#[cfg(not(unix))]
+pub fn restrict(_command: &mut Command, nice: Option<i32>, _max_memory_mb: Option<u64>) {
+    if nice.is_some() {
+        log::warn!("`cpu_nice` is not supported on this platform; ignoring it");
+    }
+}
+
+/// Sample the memory usage of `pid` until the returned receiver is dropped.
+/// The receiver resolves with a description of the overrun once the process
+/// uses more than `max_memory_mb` megabytes.
+pub fn watch_memory(name: &str, pid: u32, max_memory_mb: u64) -> oneshot::Receiver<String> {
+    let (tx, rx) = oneshot::channel();
+    let limit = max_memory_mb.saturating_mul(1024 * 1024);
+    if resident_memory(pid).is_none() {
+        log::warn!("cannot measure memory usage of plugin `{name}`; `max_memory_mb` is ignored");
+        return rx;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(MEMORY_SAMPLE_INTERVAL);
+        while !tx.is_closed() {
+            interval.tick().await;
+            let Some(used) = resident_memory(pid) else {
+                return;
+            };
+            if used > limit {
+                let _ = tx.send(format!(
+                    "exceeded its memory budget of {max_memory_mb} MB with {} MB in use",
+                    used / (1024 * 1024)
+                ));
+                return;
+            }
+        }
+    });
+    rx
+}
+
+/// Resident set size of `pid` in bytes.
+#[cfg(target_os = "linux")]
+fn resident_memory(pid: u32) -> Option<u64> {
+    let statm = std::fs::read_to_string(format!("/proc/{pid}/statm")).ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: `sysconf` has no preconditions.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * u64::try_from(page_size).ok()?)
+}
+
+/// Resident set size of `pid` in bytes.
+#[cfg(not(target_os = "linux"))]
+fn resident_memory(_pid: u32) -> Option<u64> {
+    None
+}
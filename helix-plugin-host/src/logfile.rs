@@ -0,0 +1,132 @@
+//! Per-plugin log files.
+//!
+//! Every plugin process records its lifecycle, events, stderr and protocol
+//! traffic in `plugin-logs/<name>.log` under the Helix cache directory. Files
+//! are rotated once they grow past [`MAX_LOG_SIZE`], keeping the previous
+//! generation as `<name>.log.1`.
+
+use log::{Level, LevelFilter};
+use parking_lot::Mutex;
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+
+/// Size in bytes after which a log file is rotated.
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+/// Level of records written when the entry does not set `log_level`.
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// Path of the log file of `plugin`.
+pub fn path(plugin: &str) -> PathBuf {
+    helix_loader::cache_dir()
+        .join("plugin-logs")
+        .join(format!("{}.log", file_name(plugin)))
+}
+
+/// `name` with the characters that are unsafe in file names replaced.
+pub fn file_name(name: &str) -> String {
+    // Instance names of per-workspace plugins may contain path separators.
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | '@' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Parse the `log_level` of a manifest entry.
+pub fn parse_level(level: Option<&str>) -> Result<LevelFilter, String> {
+    match level {
+        Some(level) => level
+            .parse()
+            .map_err(|_| format!("invalid `log_level` `{level}`")),
+        None => Ok(DEFAULT_LEVEL),
+    }
+}
+
+/// Log file of a single plugin process. Cloning shares the file.
+#[derive(Clone)]
+pub struct PluginLog {
+    level: LevelFilter,
+    file: Option<Arc<Mutex<LogFile>>>,
+}
+
+impl PluginLog {
+    /// Open the log file of `plugin`, appending to an existing one. Failing
+    /// to open it only disables the log file.
+    pub fn open(plugin: &str, level: Option<&str>) -> Self {
+        let level = parse_level(level).unwrap_or_else(|err| {
+            log::warn!("plugin `{plugin}`: {err}; logging at `{DEFAULT_LEVEL}`");
+            DEFAULT_LEVEL
+        });
+        if level == LevelFilter::Off {
+            return Self { level, file: None };
+        }
+        let file = match LogFile::open(path(plugin)) {
+            Ok(file) => Some(Arc::new(Mutex::new(file))),
+            Err(err) => {
+                log::warn!("failed to open the log file of plugin `{plugin}`: {err}");
+                None
+            }
+        };
+        Self { level, file }
+    }
+
+    /// Whether records at `level` are written.
+    pub fn enabled(&self, level: Level) -> bool {
+        self.file.is_some() && level <= self.level
+    }
+
+    /// Append a record to the log file.
+    pub fn write(&self, level: Level, message: fmt::Arguments<'_>) {
+        let Some(file) = self.file.as_ref().filter(|_| level <= self.level) else {
+            return;
+        };
+        let line = format!(
+            "{} {level:<5} {message}\n",
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        );
+        if let Err(err) = file.lock().append(line.as_bytes()) {
+            log::debug!("failed to write plugin log: {err}");
+        }
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size })
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + bytes.len() as u64 > MAX_LOG_SIZE {
+            self.rotate()?;
+        }
+        self.file.write_all(bytes)?;
+        self.size += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let mut previous = self.path.clone().into_os_string();
+        previous.push(".1");
+        fs::rename(&self.path, previous)?;
+        *self = Self::open(std::mem::take(&mut self.path))?;
+        Ok(())
+    }
+}
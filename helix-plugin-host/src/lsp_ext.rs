@@ -0,0 +1,183 @@
+//! Custom LSP methods of the host.
+//!
+//! They are meant for editor-side integrations that want more than the
+//! standard `window/showMessage` and `window/logMessage` allow, such as
+//! plugin specific UI.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tower_lsp::lsp_types::{notification::Notification, request::Request, ClientCapabilities};
+
+/// Event a plugin forwarded to the editor.
+#[derive(Debug)]
+pub enum PluginEventNotification {}
+
+impl Notification for PluginEventNotification {
+    type Params = PluginEventParams;
+    const METHOD: &'static str = "$/helix/pluginEvent";
+}
+
+/// Parameters of [`PluginEventNotification`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginEventParams {
+    /// Logical name of the plugin that emitted the event.
+    pub plugin: String,
+    /// Event kind chosen by the plugin.
+    pub kind: String,
+    /// Arbitrary event payload.
+    pub payload: Value,
+}
+
+/// Kind and payload of an event whose type the host does not know, so newer
+/// plugins can reach editor integrations through older hosts.
+pub fn unrecognized_event(line: &str) -> Option<(String, Value)> {
+    let mut message: Value = serde_json::from_str(line).ok()?;
+    if message.get("type")?.as_str()? != "event" {
+        return None;
+    }
+    let mut event = message.get_mut("event")?.take();
+    let kind = event.as_object_mut()?.remove("type")?.as_str()?.to_string();
+    // Known events that failed to decode are malformed, not unrecognized.
+    if matches!(
+        kind.as_str(),
+        "show_message" | "log" | "publish" | "notify" | "append_output" | "publish_diagnostics"
+    ) {
+        return None;
+    }
+    Some((kind, event))
+}
+
+/// Run a process of a plugin in a terminal of the editor, so it can be used
+/// interactively. Only sent to editors setting the experimental
+/// `runInTerminal` client capability.
+#[derive(Debug)]
+pub enum RunInTerminalRequest {}
+
+impl Request for RunInTerminalRequest {
+    type Params = RunInTerminalParams;
+    type Result = RunInTerminalResult;
+    const METHOD: &'static str = "$/helix/runInTerminal";
+}
+
+/// Parameters of [`RunInTerminalRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunInTerminalParams {
+    /// Logical name of the plugin running the process.
+    pub plugin: String,
+    pub title: String,
+    /// Executable followed by its arguments.
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    /// Variables set in addition to those of the editor.
+    pub env: BTreeMap<String, String>,
+}
+
+/// Result of [`RunInTerminalRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunInTerminalResult {
+    pub process_id: Option<u32>,
+}
+
+/// Whether the editor answers [`RunInTerminalRequest`].
+pub fn supports_run_in_terminal(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .experimental
+        .as_ref()
+        .and_then(|experimental| experimental.get("runInTerminal"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Ask the user to enter text for a plugin, e.g. the value of a task
+/// parameter. Only sent to editors setting the experimental `input` client
+/// capability.
+#[derive(Debug)]
+pub enum InputRequest {}
+
+impl Request for InputRequest {
+    type Params = InputParams;
+    type Result = InputResult;
+    const METHOD: &'static str = "$/helix/input";
+}
+
+/// Parameters of [`InputRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputParams {
+    /// Logical name of the plugin asking.
+    pub plugin: String,
+    pub message: String,
+    /// Text the input starts with.
+    pub default: Option<String>,
+}
+
+/// Result of [`InputRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputResult {
+    /// Text entered, `None` when the user dismissed the input.
+    pub value: Option<String>,
+}
+
+/// Whether the editor answers [`InputRequest`].
+pub fn supports_input(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .experimental
+        .as_ref()
+        .and_then(|experimental| experimental.get("input"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Live status of every plugin, for editor-side plugin managers.
+#[derive(Debug)]
+pub enum PluginStatusRequest {}
+
+impl Request for PluginStatusRequest {
+    type Params = ();
+    type Result = PluginStatusResult;
+    const METHOD: &'static str = "$/helix/pluginStatus";
+}
+
+/// Result of [`PluginStatusRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginStatusResult {
+    pub plugins: Vec<PluginStatus>,
+}
+
+/// Lifecycle state of a plugin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PluginState {
+    Running,
+    /// Lazy plugin that has not been spawned yet.
+    Dormant,
+    /// Exited unexpectedly and waits for its restart.
+    Restarting,
+    /// Failed to start, or exited and is not restarted.
+    Crashed,
+    Disabled,
+}
+
+/// Status of a single plugin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginStatus {
+    pub name: String,
+    pub state: PluginState,
+    /// Process id of running native plugins.
+    pub pid: Option<u32>,
+    /// Version reported by running plugins.
+    pub version: Option<String>,
+    /// Seconds since the running process was started.
+    pub uptime_secs: Option<u64>,
+    /// Consecutive unexpected exits.
+    pub restarts: u32,
+    /// Most recent error of the plugin.
+    pub last_error: Option<String>,
+}
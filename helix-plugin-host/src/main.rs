@@ -1,11 +1,41 @@
+mod bus;
+mod cache;
+mod cli;
+mod decode;
+mod diagnostics;
+mod hints;
+mod install;
+mod limits;
+mod logfile;
+mod lsp_ext;
 mod manifest;
+mod metrics;
+mod output;
 mod plugin;
+mod profile;
+mod registry;
+mod results;
+mod runtime;
+mod sandbox;
+mod scaffold;
+mod secrets;
 mod server;
+mod settings;
+mod stderr;
+mod store;
+mod symbols;
+mod throttle;
+mod trace;
+mod transport;
+mod validate;
+#[cfg(feature = "wasm")]
+mod wasm;
 
 use anyhow::Result;
-use clap::Parser;
-use server::{HostOptions, PluginHost};
-use tower_lsp::{LspService, Server};
+use clap::{Parser, Subcommand};
+use server::HostOptions;
+use std::path::PathBuf;
+use transport::Endpoint;
 
 /// Command line arguments for the plugin host.
 #[derive(Debug, Parser, Clone)]
@@ -17,12 +47,137 @@
 )]
 struct Cli {
     /// Path to the plugin manifest. Defaults to the Helix config directory.
-    #[arg(long)]
-    manifest: Option<std::path::PathBuf>,
+    /// May be repeated to layer manifests; plugins declared in a later
+    /// manifest replace earlier ones with the same name.
+    #[arg(long, global = true)]
+    manifest: Vec<PathBuf>,
 
     /// Enable verbose logging for the plugin host.
-    #[arg(long)]
+    #[arg(long, global = true)]
     verbose: bool,
+
+    /// Serve the language server on a TCP address (`host:port`) or Unix
+    /// socket path instead of stdio. Each connection gets its own plugins.
+    /// Anyone who can connect may run plugin commands, so TCP addresses must
+    /// be loopback unless `--allow-remote` is passed.
+    #[arg(long, value_name = "ADDR|SOCKET")]
+    listen: Option<Endpoint>,
+
+    /// Allow `--listen` on addresses reachable from other machines. Requires
+    /// `--token-file`.
+    #[arg(long, requires = "listen")]
+    allow_remote: bool,
+
+    /// Require every `--listen` connection to send the contents of this file
+    /// as its first line before any language server message.
+    #[arg(long, value_name = "FILE", requires = "listen")]
+    token_file: Option<PathBuf>,
+
+    /// Record every message exchanged with plugins to a JSON lines file,
+    /// for use with `replay`.
+    #[arg(long, global = true, value_name = "FILE")]
+    trace: Option<PathBuf>,
+
+    /// Log how long the manifest took to load and every plugin took to
+    /// spawn and complete its handshake when the editor attaches.
+    #[arg(long)]
+    profile_startup: bool,
+
+    /// Also write the startup profile to a JSON file. Implies
+    /// `--profile-startup`.
+    #[arg(long, value_name = "FILE")]
+    profile_startup_json: Option<PathBuf>,
+
+    /// Run a one-off task instead of the language server.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Subcommands working on the manifest without an editor.
+#[derive(Debug, Subcommand, Clone)]
+enum Command {
+    /// Validate the manifest and handshake every plugin.
+    Check,
+    /// Handshake every plugin and list the commands they provide.
+    List {
+        /// Print the list as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Search the plugin registry.
+    Search {
+        /// Text matched against plugin names and descriptions.
+        term: String,
+        /// Registry index URL or path, overriding the manifest's `registry`.
+        #[arg(long)]
+        registry: Option<String>,
+    },
+    /// Build a plugin with `cargo install` and add it to the manifest.
+    Install {
+        /// Path to a Cargo project, git URL, registry plugin or crate name.
+        source: String,
+        /// Plugin name in the manifest. Defaults to the binary name.
+        #[arg(long)]
+        name: Option<String>,
+        /// Binary to install when the package provides several.
+        #[arg(long)]
+        bin: Option<String>,
+        /// Registry index URL or path, overriding the manifest's `registry`.
+        #[arg(long)]
+        registry: Option<String>,
+    },
+    /// Reinstall installed plugins that have an update available.
+    Upgrade {
+        /// Plugin to reinstall, even without a known update. Defaults to
+        /// every plugin with an update.
+        name: Option<String>,
+        /// Registry index URL or path, overriding the manifest's `registry`.
+        #[arg(long)]
+        registry: Option<String>,
+    },
+    /// Generate a plugin skeleton and print its manifest entry.
+    New {
+        /// Plugin name, used as package name and command prefix.
+        name: String,
+        /// Language of the generated plugin.
+        #[arg(long, value_enum, default_value_t = scaffold::Language::Rust)]
+        lang: scaffold::Language,
+        /// Directory to generate the plugin in. Defaults to `./<name>`.
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// Path to a local `helix-plugin-sdk` for Rust plugins, instead of
+        /// the git repository.
+        #[arg(long)]
+        sdk: Option<PathBuf>,
+    },
+    /// Run a single plugin command and print its JSON result.
+    Exec {
+        /// Identifier of the command to run.
+        command: String,
+        /// Command arguments as JSON. A value other than an array is passed
+        /// as the only argument.
+        arguments: Option<String>,
+        /// Language id reported as the active document's language.
+        #[arg(long)]
+        language: Option<String>,
+    },
+    /// Feed the requests of a session recorded with `--trace` into a plugin
+    /// and compare its responses with the recorded ones.
+    Replay {
+        /// Trace file written by `--trace`.
+        #[arg(value_name = "TRACE")]
+        file: PathBuf,
+        /// Plugin whose session is replayed. Required when the trace holds
+        /// several plugins.
+        #[arg(long)]
+        plugin: Option<String>,
+        /// Session to replay when the plugin was restarted, counting from 1.
+        #[arg(long, default_value_t = 1)]
+        session: usize,
+        /// Plugin executable and its arguments.
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
 }
 
 #[tokio::main]
@@ -33,17 +188,66 @@ async fn main() -> Result<()> {
         std::env::set_var("RUST_LOG", "info,helix_plugin_host=debug");
     }
 
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+    let env = || env_logger::Env::default().default_filter_or("warn");
+    let max_level = env_logger::Builder::from_env(env()).build().filter();
+    // The host's own records pass the filter so that `logLevel` can raise
+    // their verbosity at runtime; `log::max_level` gates them instead.
+    env_logger::Builder::from_env(env())
         .format_timestamp_millis()
+        .filter_module("helix_plugin_host", log::LevelFilter::Trace)
         .init();
+    let options = HostOptions::from_cli(
+        &cli.manifest,
+        cli.trace.as_deref(),
+        cli.profile_startup,
+        cli.profile_startup_json.as_deref(),
+    )?;
+    // The profile is logged at `info`.
+    if options.profile_startup() {
+        log::set_max_level(max_level.max(log::LevelFilter::Info));
+    } else {
+        log::set_max_level(max_level);
+    }
 
-    let options = HostOptions::from_cli(cli.manifest.as_deref())?;
-
-    let stdin = tokio::io::stdin();
-    let stdout = tokio::io::stdout();
-
-    let (service, socket) = LspService::new(|client| PluginHost::new(client, options.clone()));
-    Server::new(stdin, stdout, socket).serve(service).await;
+    match cli.command {
+        Some(Command::Check) => return cli::check(&options).await,
+        Some(Command::List { json }) => return cli::list(&options, json).await,
+        Some(Command::Search { term, registry }) => {
+            return cli::search(&options, &term, registry).await
+        }
+        Some(Command::Install {
+            source,
+            name,
+            bin,
+            registry,
+        }) => return cli::install(&options, &source, name, bin, registry).await,
+        Some(Command::Upgrade { name, registry }) => {
+            return cli::upgrade(&options, name, registry).await
+        }
+        Some(Command::New {
+            name,
+            lang,
+            path,
+            sdk,
+        }) => return cli::new(&name, lang, path, sdk.as_deref()),
+        Some(Command::Exec {
+            command,
+            arguments,
+            language,
+        }) => return cli::exec(&options, &command, arguments.as_deref(), language).await,
+        Some(Command::Replay {
+            file,
+            plugin,
+            session,
+            command,
+        }) => return cli::replay(&file, plugin.as_deref(), session, &command).await,
+        None => {}
+    }
 
+    if let Some(addr) = cli.listen {
+        let access = transport::Access::new(cli.allow_remote, cli.token_file.as_deref())?;
+        return transport::serve(addr, access, options).await;
+    }
+    transport::serve_connection(tokio::io::stdin(), tokio::io::stdout(), options).await;
     Ok(())
 }
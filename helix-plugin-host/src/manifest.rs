@@ -1,8 +1,10 @@
+use crate::runtime::ScriptRuntime;
 use anyhow::{Context, Result};
-use serde::Deserialize;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    fs,
+    collections::{HashMap, HashSet},
+    fmt, fs,
     path::{Path, PathBuf},
 };
 
@@ -10,19 +12,72 @@
 #[derive(Debug, Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct PluginManifest {
+    /// Additional manifest files merged into this one, relative to the file
+    /// including them. Plugins and settings of the including file take
+    /// precedence, then those of earlier includes.
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
+    /// How commands registered by more than one plugin are resolved.
+    #[serde(default)]
+    pub command_conflicts: Option<ConflictPolicy>,
+    /// URL or path of the plugin index queried by `search` and `install`.
+    #[serde(default)]
+    pub registry: Option<String>,
+    /// Whether the language server looks for updates of installed plugins
+    /// on startup. Defaults to `true`.
+    #[serde(default)]
+    pub check_updates: Option<bool>,
+    /// Interpreters used for plugins setting `runtime`, as a path or a name
+    /// looked up on `PATH`, overriding the automatic lookup.
+    #[serde(default)]
+    pub runtimes: HashMap<ScriptRuntime, PathBuf>,
     /// Declared plugin entries.
     #[serde(default)]
     pub plugins: Vec<PluginEntry>,
+    /// Canonical paths of every file pulled in through `include`.
+    #[serde(skip)]
+    pub included_files: Vec<PathBuf>,
+    /// Problems found while merging includes and drop-ins.
+    #[serde(skip)]
+    pub warnings: Vec<String>,
 }
 
 /// Individual plugin configuration entry.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct PluginEntry {
     /// Logical plugin name.
     pub name: String,
-    /// Command executed to spawn the plugin.
+    /// Command executed to spawn the plugin, the path to the component for
+    /// WebAssembly plugins, or the socket address (`host:port` or a Unix
+    /// socket path) of `connect` plugins.
     pub command: String,
+    /// Expected SHA-256 digest of the plugin executable or component, as a
+    /// hex string. The plugin is refused when the file does not match.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Runtime used to execute the plugin.
+    #[serde(default, rename = "type")]
+    pub kind: PluginKind,
+    /// Interpreter running the script named by `command`, which is then
+    /// relative to the manifest directory like other paths.
+    #[serde(default)]
+    pub runtime: Option<ScriptRuntime>,
+    /// Protocol versions the plugin must report during the handshake, e.g.
+    /// `">=1, <2"`. Plugins reporting another version are not registered.
+    #[serde(default)]
+    pub protocol: Option<String>,
+    /// Machine `ssh` plugins run on, as passed to `ssh` (e.g. `me@devbox`).
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Host environment variables passed on to the plugin. When set, the
+    /// plugin starts from an empty environment holding only these
+    /// variables, `env` and the `HELIX_*` variables set by the host. Names
+    /// ending in `*` match every variable with that prefix. The whole
+    /// environment is inherited when unset. `ssh` plugins inherit from the
+    /// remote environment and do not support prefixes.
+    #[serde(default)]
+    pub inherit_env: Option<Vec<String>>,
     /// Command line arguments passed to the plugin executable.
     #[serde(default)]
     pub args: Vec<String>,
@@ -32,11 +87,337 @@ pub struct PluginEntry {
     /// Optional working directory (relative to the manifest file if relative).
     #[serde(default)]
     pub cwd: Option<PathBuf>,
+    /// Maximum time in milliseconds to wait for the plugin to answer a
+    /// request. Requests wait indefinitely when unset.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Maximum time in milliseconds to wait for the initialization
+    /// handshake. Plugins not answering in time are marked failed.
+    #[serde(default)]
+    pub init_timeout_ms: Option<u64>,
+    /// Maximum number of command and event requests awaiting a response at
+    /// once. Further requests wait in FIFO order. Unlimited when unset.
+    #[serde(default)]
+    pub max_concurrent_requests: Option<usize>,
+    /// Maximum number of messages shown to the user per second. Further
+    /// messages, and messages repeated within ten seconds, are logged
+    /// instead. Defaults to 3.
+    #[serde(default)]
+    pub max_messages_per_second: Option<u32>,
+    /// Time in milliseconds the plugin gets to exit after the shutdown
+    /// request before it is sent `SIGTERM`. Defaults to 2000.
+    #[serde(default)]
+    pub shutdown_timeout_ms: Option<u64>,
+    /// Time in milliseconds the plugin gets to exit after `SIGTERM` before
+    /// it is killed. Defaults to 2000.
+    #[serde(default)]
+    pub terminate_timeout_ms: Option<u64>,
+    /// Level of records written to the plugin log file, e.g. `"debug"` to
+    /// include request and response traffic or `"off"` to disable the file.
+    /// Defaults to `"info"`.
+    #[serde(default)]
+    pub log_level: Option<String>,
+    /// What happens to stdout output that is not a protocol message, such
+    /// as a stray `print`. Defaults to `"log"`.
+    #[serde(default)]
+    pub stray_output: StrayOutput,
+    /// Memory budget in megabytes. Allocations beyond it fail, and plugins
+    /// whose resident memory exceeds it anyway are killed and restarted.
+    #[serde(default)]
+    pub max_memory_mb: Option<u64>,
+    /// Interval in milliseconds between the pings sent to the plugin while
+    /// requests are pending. Plugins missing `max_missed_pings` pings in a
+    /// row are killed and their pending requests fail. Disabled when unset.
+    #[serde(default)]
+    pub ping_interval_ms: Option<u64>,
+    /// Consecutive unanswered pings after which the plugin is considered
+    /// unresponsive. Defaults to 3.
+    #[serde(default)]
+    pub max_missed_pings: Option<u32>,
+    /// Whether plugins killed for being unresponsive are restarted like
+    /// crashed plugins. Defaults to `true`.
+    #[serde(default = "default_enabled")]
+    pub restart_unresponsive: bool,
+    /// Scheduling niceness applied to the plugin process.
+    #[serde(default)]
+    pub cpu_nice: Option<i32>,
+    /// Defer spawning the plugin until one of its commands is executed.
+    #[serde(default)]
+    pub lazy: bool,
+    /// Commands provided by a lazy plugin. When omitted, the commands the
+    /// plugin advertised the last time it ran are used.
+    #[serde(default)]
+    pub commands: Vec<String>,
+    /// Events spawning the plugin, e.g. `"onLanguage:rust"`,
+    /// `"onCommand:helix.task.run"` or `"workspaceContains:package.json"`.
+    /// The plugin stays dormant like a lazy plugin until one occurs.
+    #[serde(default)]
+    pub activation: Vec<ActivationEvent>,
+    /// Whether the plugin is started at all. Disabled plugins can be enabled
+    /// at runtime through the `helix.plugins.enable` command.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Plugins that must be initialized before this one.
+    #[serde(default)]
+    pub after: Vec<String>,
+    /// Languages the plugin serves, e.g. `["rust", "toml"]`. Its commands are
+    /// only offered, and bus events only delivered, while the active document
+    /// has one of them. The plugin serves every language when empty.
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Start one instance per workspace folder, named `<name>@<folder>` and
+    /// seeing its folder as the workspace root. Commands are routed to the
+    /// instance whose folder contains the active document.
+    #[serde(default)]
+    pub per_workspace: bool,
+    /// Confine the file system and network access of the plugin process.
+    /// Only applies to native plugins on Linux and macOS; elsewhere the
+    /// plugin is not started.
+    #[serde(default)]
+    pub sandbox: Option<SandboxConfig>,
+    /// Overrides applied when running on Windows.
+    #[serde(default)]
+    pub windows: Option<PlatformOverride>,
+    /// Overrides applied when running on macOS.
+    #[serde(default)]
+    pub macos: Option<PlatformOverride>,
+    /// Overrides applied when running on Linux.
+    #[serde(default)]
+    pub linux: Option<PlatformOverride>,
+    /// Directory relative paths are resolved against. Set for entries loaded
+    /// from a `plugins.d` subdirectory; the manifest directory is used
+    /// otherwise.
+    #[serde(skip)]
+    pub base_dir: Option<PathBuf>,
+    /// Plugin this entry is a per-workspace instance of.
+    #[serde(skip)]
+    pub instance_of: Option<String>,
+    /// Workspace folder served by a per-workspace instance.
+    #[serde(skip)]
+    pub workspace: Option<PathBuf>,
+    /// Interpreter configured for `runtime` in the manifest.
+    #[serde(skip)]
+    pub interpreter: Option<PathBuf>,
+}
+
+/// Resolution of a command id registered by several plugins.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// The plugin registering the command last takes it over.
+    #[default]
+    Override,
+    /// The plugin registering the command first keeps it.
+    FirstWins,
+    /// Later registrations are exposed as `<plugin>.<command>`.
+    Prefix,
+    /// Plugins registering taken commands are refused.
+    Error,
+}
+
+/// Event spawning a dormant plugin.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ActivationEvent {
+    /// `onLanguage:<language id>`: a document of the language was opened.
+    OnLanguage(String),
+    /// `onCommand:<command id>`: the command was executed.
+    OnCommand(String),
+    /// `workspaceContains:<path>`: a workspace folder contains the relative
+    /// path.
+    WorkspaceContains(PathBuf),
+}
+
+impl ActivationEvent {
+    /// Whether this is a `workspaceContains` event matched by one of
+    /// `folders`.
+    pub fn matches_workspace(&self, folders: &[PathBuf]) -> bool {
+        match self {
+            Self::WorkspaceContains(path) => {
+                folders.iter().any(|folder| folder.join(path).exists())
+            }
+            Self::OnLanguage(_) | Self::OnCommand(_) => false,
+        }
+    }
+}
+
+impl TryFrom<String> for ActivationEvent {
+    type Error = String;
+
+    fn try_from(event: String) -> Result<Self, Self::Error> {
+        let (kind, value) = event
+            .split_once(':')
+            .filter(|(_, value)| !value.is_empty())
+            .ok_or_else(|| {
+                format!("invalid activation event `{event}`, expected `<kind>:<value>`")
+            })?;
+        match kind {
+            "onLanguage" => Ok(Self::OnLanguage(value.to_string())),
+            "onCommand" => Ok(Self::OnCommand(value.to_string())),
+            "workspaceContains" => Ok(Self::WorkspaceContains(PathBuf::from(value))),
+            _ => Err(format!(
+                "unknown activation event `{kind}`, expected `onLanguage`, `onCommand` or `workspaceContains`"
+            )),
+        }
+    }
 }
 
+impl fmt::Display for ActivationEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OnLanguage(language) => write!(f, "onLanguage:{language}"),
+            Self::OnCommand(command) => write!(f, "onCommand:{command}"),
+            Self::WorkspaceContains(path) => write!(f, "workspaceContains:{}", path.display()),
+        }
+    }
+}
+
+/// Handling of plugin stdout output that is not a protocol message.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StrayOutput {
+    /// Forward it to the plugin log, like stderr.
+    #[default]
+    Log,
+    /// Drop it.
+    Discard,
+}
+
+/// Runtime used to execute a plugin.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginKind {
+    /// Native executable speaking the protocol over stdio.
+    #[default]
+    Native,
+    /// WebAssembly component implementing the `helix:plugin` world.
+    Wasm,
+    /// Externally managed plugin daemon speaking the protocol over a socket.
+    /// The host connects to it instead of spawning a process.
+    Connect,
+    /// Native executable started on `host` through `ssh`, speaking the
+    /// protocol over the SSH channel. `command`, `args`, `cwd` and `env`
+    /// apply on the remote machine; placeholders are expanded locally.
+    /// Only `HELIX_PLUGIN_NAME` is set remotely: `HELIX_PLUGIN_DATA_DIR` and
+    /// `HELIX_WORKSPACE_ROOT` name local paths and are not passed on.
+    Ssh,
+}
+
+/// Access granted to a sandboxed plugin. The workspace root and the
+/// temporary directory are always writable.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SandboxConfig {
+    /// Paths the plugin may read. Reads are unrestricted when empty.
+    #[serde(default)]
+    pub read: Vec<PathBuf>,
+    /// Additional paths the plugin may write to.
+    #[serde(default)]
+    pub write: Vec<PathBuf>,
+    /// Allow network access.
+    #[serde(default)]
+    pub network: bool,
+}
+
+/// Platform specific replacements for parts of a [`PluginEntry`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlatformOverride {
+    /// Replaces the entry's command.
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Replaces the entry's arguments.
+    #[serde(default)]
+    pub args: Option<Vec<String>>,
+    /// Merged into the entry's environment, overriding existing keys.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Name of the drop-in directory scanned next to the manifest file.
+const DROP_IN_DIR: &str = "plugins.d";
+
+/// Manifest file looked up inside `plugins.d` subdirectories.
+const DROP_IN_MANIFEST: &str = "plugin.toml";
+
 impl PluginManifest {
-    /// Load a manifest from disk. Missing manifests resolve to an empty set.
+    /// Load a manifest from disk and merge in its includes and the plugins
+    /// declared in the drop-in directory next to it. Missing manifests
+    /// resolve to an empty set.
     pub fn load(path: &Path) -> Result<Self> {
+        let mut manifest = Self::load_file(path)?;
+        let mut stack = vec![fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())];
+        manifest.merge_includes(path, &mut stack)?;
+        manifest.merge_drop_ins(&Self::drop_in_dir(path));
+        Ok(manifest)
+    }
+
+    /// Load several manifests and layer them in order: plugins declared in a
+    /// later manifest replace the earlier plugins with the same name.
+    pub fn load_layered(paths: &[PathBuf]) -> Result<Self> {
+        let mut layers = paths.iter();
+        let Some(first) = layers.next() else {
+            return Ok(Self::default());
+        };
+        let mut manifest = Self::load(first)?;
+        for path in layers {
+            let layer = Self::load(path)?;
+            manifest.overlay(layer, path);
+        }
+        for entry in &mut manifest.plugins {
+            entry.interpreter = entry
+                .runtime
+                .and_then(|runtime| manifest.runtimes.get(&runtime).cloned());
+        }
+        Ok(manifest)
+    }
+
+    /// Merge `layer`, loaded from `path`, on top of this manifest.
+    fn overlay(&mut self, mut layer: Self, path: &Path) {
+        let layer_dir = path.parent().map(Path::to_path_buf);
+        self.overlay_settings(&mut layer);
+        self.included_files.extend(layer.included_files);
+        self.warnings.extend(layer.warnings);
+        for mut entry in layer.plugins {
+            entry.base_dir = entry.base_dir.or_else(|| layer_dir.clone());
+            match self
+                .plugins
+                .iter_mut()
+                .find(|plugin| plugin.name == entry.name)
+            {
+                Some(plugin) => *plugin = entry,
+                None => self.plugins.push(entry),
+            }
+        }
+    }
+
+    /// Move the settings set in `layer` into this manifest, replacing its
+    /// own.
+    fn overlay_settings(&mut self, layer: &mut Self) {
+        if layer.command_conflicts.is_some() {
+            self.command_conflicts = layer.command_conflicts.take();
+        }
+        if layer.registry.is_some() {
+            self.registry = layer.registry.take();
+        }
+        if layer.check_updates.is_some() {
+            self.check_updates = layer.check_updates.take();
+        }
+        self.runtimes.extend(layer.runtimes.drain());
+    }
+
+    /// Drop-in directory belonging to the manifest at `path`.
+    pub fn drop_in_dir(path: &Path) -> PathBuf {
+        path.parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(DROP_IN_DIR)
+    }
+
+    fn load_file(path: &Path) -> Result<Self> {
         if !path.exists() {
             log::info!(
                 "plugin manifest `{}` not found ? plugin runtime will start without plugins",
@@ -44,10 +425,597 @@ pub fn load(path: &Path) -> Result<Self> {
             );
             return Ok(Self::default());
         }
+        Self::parse_file(path)
+    }
 
+    fn parse_file(path: &Path) -> Result<Self> {
         let contents = fs::read_to_string(path)
             .with_context(|| format!("failed to read plugin manifest `{}`", path.display()))?;
         toml::from_str(&contents)
             .with_context(|| format!("failed to parse plugin manifest `{}`", path.display()))
     }
+
+    /// Merge every file listed in `include`, recursively, keeping plugins
+    /// and settings already set. `stack` holds the files currently being included to detect cycles.
+    fn merge_includes(&mut self, path: &Path, stack: &mut Vec<PathBuf>) -> Result<()> {
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        for include in std::mem::take(&mut self.include) {
+            let file = base_dir.join(helix_stdx::path::expand_tilde(include.as_path()));
+            let canonical = fs::canonicalize(&file).with_context(|| {
+                format!(
+                    "failed to resolve `{}` included from `{}`",
+                    file.display(),
+                    path.display()
+                )
+            })?;
+            if stack.contains(&canonical) {
+                anyhow::bail!("plugin manifest `{}` includes itself", file.display());
+            }
+
+            let mut included = Self::parse_file(&file)?;
+            stack.push(canonical.clone());
+            included.merge_includes(&file, stack)?;
+            stack.pop();
+
+            // Settings set here win over the included ones.
+            included.overlay_settings(self);
+            self.overlay_settings(&mut included);
+            self.included_files.push(canonical);
+            self.included_files.append(&mut included.included_files);
+            self.warnings.append(&mut included.warnings);
+            let include_dir = file.parent().map(Path::to_path_buf);
+            for mut entry in included.plugins {
+                if self.plugins.iter().any(|plugin| plugin.name == entry.name) {
+                    let warning = format!(
+                        "plugin `{}` from `{}` is already declared; ignoring it",
+                        entry.name,
+                        file.display()
+                    );
+                    log::warn!("{warning}");
+                    self.warnings.push(warning);
+                    continue;
+                }
+                entry.base_dir = entry.base_dir.or_else(|| include_dir.clone());
+                self.plugins.push(entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// Append the plugins declared in `dir`. Each `*.toml` file, or each
+    /// subdirectory containing a `plugin.toml`, declares a single plugin.
+    /// Entries are merged in file name order; broken drop-ins and names
+    /// already declared are skipped so one bad file cannot take down the
+    /// other plugins.
+    fn merge_drop_ins(&mut self, dir: &Path) {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut paths: Vec<PathBuf> = read_dir
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let (file, base_dir) = if path.is_dir() {
+                let file = path.join(DROP_IN_MANIFEST);
+                if !file.is_file() {
+                    continue;
+                }
+                (file, Some(path))
+            } else if path.extension().is_some_and(|ext| ext == "toml") {
+                (path, None)
+            } else {
+                continue;
+            };
+
+            let mut entry = match load_drop_in(&file) {
+                Ok(entry) => entry,
+                Err(err) => {
+                    log::error!("skipping plugin drop-in: {err:?}");
+                    self.warnings
+                        .push(format!("skipping plugin drop-in: {err:#}"));
+                    continue;
+                }
+            };
+            if self.plugins.iter().any(|plugin| plugin.name == entry.name) {
+                let warning = format!(
+                    "plugin `{}` from `{}` is already declared; ignoring it",
+                    entry.name,
+                    file.display()
+                );
+                log::warn!("{warning}");
+                self.warnings.push(warning);
+                continue;
+            }
+            entry.base_dir = base_dir;
+            self.plugins.push(entry);
+        }
+    }
+}
+
+/// Replace `per_workspace` entries with one instance per folder in
+/// `folders`. Entries are kept as they are when there are no folders.
+pub fn workspace_instances(entries: Vec<PluginEntry>, folders: &[PathBuf]) -> Vec<PluginEntry> {
+    entries
+        .into_iter()
+        .flat_map(|entry| {
+            if !entry.per_workspace || folders.is_empty() {
+                return vec![entry];
+            }
+            let mut names = HashSet::new();
+            folders
+                .iter()
+                .map(|folder| {
+                    let label = folder
+                        .file_name()
+                        .map_or_else(|| folder.to_string_lossy(), |name| name.to_string_lossy());
+                    let mut name = format!("{}@{label}", entry.name);
+                    let mut suffix = 2;
+                    while !names.insert(name.clone()) {
+                        name = format!("{}@{label}-{suffix}", entry.name);
+                        suffix += 1;
+                    }
+                    PluginEntry {
+                        name,
+                        instance_of: Some(entry.name.clone()),
+                        workspace: Some(folder.clone()),
+                        ..entry.clone()
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Group `entries` into waves that can be started concurrently, each wave
+/// only depending on plugins of earlier waves. Dependencies outside `entries`
+/// are considered satisfied. Entries that are part of a dependency cycle, or
+/// depend on one, are returned separately.
+pub fn startup_waves(entries: Vec<PluginEntry>) -> (Vec<Vec<PluginEntry>>, Vec<PluginEntry>) {
+    let mut pending = entries;
+    let mut waves = Vec::new();
+    while !pending.is_empty() {
+        let waiting: HashSet<String> = pending.iter().map(|entry| entry.name.clone()).collect();
+        let (ready, blocked): (Vec<_>, Vec<_>) = pending
+            .into_iter()
+            .partition(|entry| !entry.after.iter().any(|name| waiting.contains(name)));
+        if ready.is_empty() {
+            return (waves, blocked);
+        }
+        waves.push(ready);
+        pending = blocked;
+    }
+    (waves, Vec::new())
+}
+
+/// Values substituted for placeholders in manifest entries.
+#[derive(Debug, Clone, Copy)]
+pub struct Placeholders<'a> {
+    /// Root of the workspace the host was started for.
+    pub workspace_root: Option<&'a Path>,
+    /// Directory containing the manifest file.
+    pub manifest_dir: &'a Path,
+}
+
+impl PluginEntry {
+    /// Whether spawning the plugin waits for one of its commands or
+    /// activation events.
+    pub fn is_deferred(&self) -> bool {
+        self.lazy || !self.activation.is_empty()
+    }
+
+    /// Whether the plugin serves documents of `language_id`. Every plugin
+    /// serves an unknown language.
+    pub fn serves_language(&self, language_id: Option<&str>) -> bool {
+        match language_id {
+            Some(language_id) if !self.languages.is_empty() => self
+                .languages
+                .iter()
+                .any(|language| language == language_id),
+            _ => true,
+        }
+    }
+
+    /// Directory relative paths of this entry are resolved against.
+    pub fn base_dir<'a>(&'a self, manifest_dir: &'a Path) -> &'a Path {
+        self.base_dir.as_deref().unwrap_or(manifest_dir)
+    }
+
+    /// Return a copy of the entry with the override for the current platform
+    /// applied.
+    pub fn for_current_platform(&self) -> Self {
+        let platform = if cfg!(windows) {
+            &self.windows
+        } else if cfg!(target_os = "macos") {
+            &self.macos
+        } else if cfg!(target_os = "linux") {
+            &self.linux
+        } else {
+            &None
+        };
+
+        let mut entry = self.clone();
+        if let Some(platform) = platform {
+            if let Some(command) = &platform.command {
+                entry.command = command.clone();
+            }
+            if let Some(args) = &platform.args {
+                entry.args = args.clone();
+            }
+            entry.env.extend(platform.env.clone());
+        }
+        entry
+    }
+
+    /// Check the protocol version reported by the plugin against the
+    /// `protocol` requirement of the entry.
+    pub fn check_protocol(&self, reported: Option<u32>) -> Result<(), String> {
+        let Some(requirement) = &self.protocol else {
+            return Ok(());
+        };
+        let name = &self.name;
+        let Some(version) = reported else {
+            return Err(format!(
+                "plugin `{name}` did not report a protocol version; its entry requires `{requirement}`"
+            ));
+        };
+        let requirement_met = parse_protocol(requirement)
+            .map_err(|err| format!("plugin `{name}`: {err}"))?
+            .matches(&Version::new(version.into(), 0, 0));
+        if requirement_met {
+            Ok(())
+        } else {
+            Err(format!(
+                "plugin `{name}` speaks protocol version {version}; its entry requires `{requirement}`"
+            ))
+        }
+    }
+
+    /// Return a copy of an `ssh` entry rewritten into a native entry running
+    /// `ssh`, with the command, arguments, working directory and environment
+    /// moved into the remote command line.
+    pub fn over_ssh(&self) -> Result<Self> {
+        let host = self
+            .host
+            .as_deref()
+            .with_context(|| format!("plugin `{}` has type `ssh` but no `host`", self.name))?;
+
+        let mut remote = String::new();
+        if let Some(cwd) = &self.cwd {
+            remote.push_str(&format!("cd {} && ", shell_quote(&cwd.to_string_lossy())));
+        }
+        remote.push_str("exec env");
+        if let Some(allowed) = &self.inherit_env {
+            // Only variables set on the remote machine are passed on.
+            remote.push_str(" -i");
+            for key in allowed.iter().filter(|key| is_variable_name(key)) {
+                remote.push_str(&format!(" ${{{key}+\"{key}=${key}\"}}"));
+            }
+        }
+        remote.push_str(" HELIX_PLUGIN_NAME=");
+        remote.push_str(&shell_quote(&self.name));
+        let mut env: Vec<_> = self.env.iter().collect();
+        env.sort();
+        for (key, value) in env {
+            if !is_variable_name(key) {
+                anyhow::bail!(
+                    "plugin `{}` sets `{key}`, which is not a valid variable name for `ssh`",
+                    self.name
+                );
+            }
+            remote.push_str(&format!(" {key}={}", shell_quote(value)));
+        }
+        for word in std::iter::once(&self.command).chain(&self.args) {
+            remote.push(' ');
+            remote.push_str(&shell_quote(word));
+        }
+
+        let mut entry = self.clone();
+        entry.kind = PluginKind::Native;
+        entry.command = "ssh".to_string();
+        entry.args = ["-T", "-o", "BatchMode=yes", host, "--", &remote]
+            .map(str::to_string)
+            .to_vec();
+        entry.cwd = None;
+        entry.env.clear();
+        entry.inherit_env = None;
+        Ok(entry)
+    }
+
+    /// Return a copy of the entry with `${env:VAR}`, `${workspaceRoot}`,
+    /// `${manifestDir}` and a leading `~` expanded in `command`, `args`,
+    /// `cwd`, `env` and sandbox path values.
+    pub fn expand(&self, placeholders: Placeholders<'_>) -> Result<Self> {
+        let placeholders = Placeholders {
+            manifest_dir: self.base_dir(placeholders.manifest_dir),
+            ..placeholders
+        };
+        let expand = |value: &str| {
+            expand_value(value, placeholders)
+                .with_context(|| format!("failed to expand `{value}` for plugin `{}`", self.name))
+        };
+
+        let mut entry = self.clone();
+        entry.command = expand(&self.command)?;
+        entry.args = self
+            .args
+            .iter()
+            .map(|arg| expand(arg))
+            .collect::<Result<_>>()?;
+        entry.cwd = self
+            .cwd
+            .as_ref()
+            .map(|cwd| expand(&cwd.to_string_lossy()).map(PathBuf::from))
+            .transpose()?;
+        entry.env = self
+            .env
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), expand(value)?)))
+            .collect::<Result<_>>()?;
+        if let Some(sandbox) = &mut entry.sandbox {
+            let base_dir = placeholders.manifest_dir;
+            let expand_paths = |paths: &[PathBuf]| {
+                paths
+                    .iter()
+                    .map(|path| Ok(base_dir.join(expand(&path.to_string_lossy())?)))
+                    .collect::<Result<Vec<_>>>()
+            };
+            sandbox.read = expand_paths(&sandbox.read)?;
+            sandbox.write = expand_paths(&sandbox.write)?;
+        }
+        Ok(entry)
+    }
+}
+
+/// Parse the `protocol` requirement of a manifest entry.
+pub fn parse_protocol(requirement: &str) -> Result<VersionReq, String> {
+    VersionReq::parse(requirement)
+        .map_err(|err| format!("invalid `protocol` requirement `{requirement}`: {err}"))
+}
+
+/// Whether `name` can be referenced as a POSIX shell variable.
+pub fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Quote `value` for a POSIX shell.
+fn shell_quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+    if plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+fn expand_value(value: &str, placeholders: Placeholders<'_>) -> Result<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .context("unterminated `${` placeholder")?;
+        let name = &rest[start + 2..end];
+        if let Some(var) = name.strip_prefix("env:") {
+            expanded.push_str(&std::env::var(var).unwrap_or_default());
+        } else if name == "workspaceRoot" {
+            let root = placeholders
+                .workspace_root
+                .context("`${workspaceRoot}` used without an open workspace")?;
+            expanded.push_str(&root.to_string_lossy());
+        } else if name == "manifestDir" {
+            expanded.push_str(&placeholders.manifest_dir.to_string_lossy());
+        } else {
+            anyhow::bail!("unknown placeholder `${{{name}}}`");
+        }
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+
+    if expanded == "~" || expanded.starts_with("~/") {
+        let path = helix_stdx::path::expand_tilde(Path::new(&expanded));
+        return Ok(path.to_string_lossy().into_owned());
+    }
+    Ok(expanded)
+}
+
+fn load_drop_in(path: &Path) -> Result<PluginEntry> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read plugin drop-in `{}`", path.display()))?;
+    toml::from_str(&contents)
+        .with_context(|| format!("failed to parse plugin drop-in `{}`", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(workspace_root: Option<&Path>) -> Placeholders<'_> {
+        Placeholders {
+            workspace_root,
+            manifest_dir: Path::new("/etc/helix"),
+        }
+    }
+
+    fn entry(name: &str, after: &[&str]) -> PluginEntry {
+        let after = after
+            .iter()
+            .map(|name| format!("{name:?}"))
+            .collect::<Vec<_>>();
+        toml::from_str(&format!(
+            "name = {name:?}\ncommand = \"true\"\nafter = [{}]",
+            after.join(", ")
+        ))
+        .unwrap()
+    }
+
+    fn names(entries: &[PluginEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn orders_startup_waves() {
+        let (waves, blocked) = startup_waves(vec![
+            entry("lint", &["format", "tasks"]),
+            entry("format", &["tasks"]),
+            entry("tasks", &[]),
+            entry("git", &["remote"]),
+        ]);
+        let waves: Vec<_> = waves.iter().map(|wave| names(wave)).collect();
+        assert_eq!(waves, [vec!["tasks", "git"], vec!["format"], vec!["lint"]]);
+        assert!(blocked.is_empty());
+    }
+
+    #[test]
+    fn separates_dependency_cycles() {
+        let (waves, blocked) = startup_waves(vec![
+            entry("a", &["b"]),
+            entry("b", &["a"]),
+            entry("c", &["a"]),
+            entry("d", &[]),
+            entry("e", &["d"]),
+        ]);
+        let waves: Vec<_> = waves.iter().map(|wave| names(wave)).collect();
+        assert_eq!(waves, [vec!["d"], vec!["e"]]);
+        assert_eq!(names(&blocked), ["a", "b", "c"]);
+
+        let (waves, blocked) = startup_waves(vec![entry("self", &["self"])]);
+        assert!(waves.is_empty());
+        assert_eq!(names(&blocked), ["self"]);
+    }
+
+    #[test]
+    fn merges_settings_of_includes() {
+        let dir = std::env::temp_dir().join(format!("helix-manifest-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("plugins.toml"),
+            "include = [\"first.toml\", \"second.toml\"]\nregistry = \"https://example.com\"\n\
+             [runtimes]\nnode = \"/opt/node\"",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("first.toml"),
+            "registry = \"first\"\ncommand_conflicts = \"prefix\"\n\
+             [runtimes]\nnode = \"first\"\npython = \"/usr/bin/python3\"",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("second.toml"),
+            "command_conflicts = \"first-wins\"\ncheck_updates = false",
+        )
+        .unwrap();
+
+        let manifest = PluginManifest::load(&dir.join("plugins.toml"));
+        fs::remove_dir_all(&dir).unwrap();
+        let manifest = manifest.unwrap();
+        assert_eq!(manifest.registry.as_deref(), Some("https://example.com"));
+        assert_eq!(manifest.command_conflicts, Some(ConflictPolicy::Prefix));
+        assert_eq!(manifest.check_updates, Some(false));
+        assert_eq!(
+            manifest.runtimes,
+            HashMap::from([
+                (ScriptRuntime::Node, PathBuf::from("/opt/node")),
+                (ScriptRuntime::Python, PathBuf::from("/usr/bin/python3")),
+            ])
+        );
+    }
+
+    #[test]
+    fn runs_ssh_entries_through_ssh() {
+        let entry: PluginEntry = toml::from_str(
+            "name = \"git\"\ntype = \"ssh\"\nhost = \"me@devbox\"\ncommand = \"helix-git\"\n\
+             args = [\"--log\", \"it's.log\"]\ncwd = \"/src\"\nenv = { RUST_LOG = \"debug info\" }",
+        )
+        .unwrap();
+        let entry = entry.over_ssh().unwrap();
+        assert_eq!(entry.kind, PluginKind::Native);
+        assert_eq!(entry.command, "ssh");
+        assert_eq!(
+            entry.args,
+            [
+                "-T",
+                "-o",
+                "BatchMode=yes",
+                "me@devbox",
+                "--",
+                "cd /src && exec env HELIX_PLUGIN_NAME=git RUST_LOG='debug info' helix-git --log 'it'\\''s.log'",
+            ]
+        );
+        assert!(entry.env.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_ssh_variables() {
+        let entry: PluginEntry = toml::from_str(
+            "name = \"git\"\ntype = \"ssh\"\nhost = \"devbox\"\ncommand = \"helix-git\"\n\
+             env = { \"A;rm -rf ~;B\" = \"1\" }",
+        )
+        .unwrap();
+        assert!(entry.over_ssh().is_err());
+    }
+
+    #[test]
+    fn expands_placeholders() {
+        let root = Path::new("/src/project");
+        assert_eq!(
+            expand_value(
+                "${workspaceRoot}/target:${manifestDir}",
+                placeholders(Some(root))
+            )
+            .unwrap(),
+            "/src/project/target:/etc/helix"
+        );
+        assert_eq!(
+            expand_value("--path=${env:PATH}", placeholders(None)).unwrap(),
+            format!("--path={}", std::env::var("PATH").unwrap())
+        );
+        assert_eq!(
+            expand_value("no placeholders", placeholders(None)).unwrap(),
+            "no placeholders"
+        );
+    }
+
+    #[test]
+    fn expands_unset_variables_to_nothing() {
+        assert_eq!(
+            expand_value("a${env:HELIX_PLUGIN_TEST_UNSET}b", placeholders(None)).unwrap(),
+            "ab"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        assert!(expand_value("${workspaceFolder}", placeholders(None)).is_err());
+        assert!(expand_value("${manifestDir", placeholders(None)).is_err());
+        assert!(expand_value("${workspaceRoot}", placeholders(None)).is_err());
+    }
+
+    #[test]
+    fn expands_a_leading_tilde() {
+        let home = helix_stdx::path::expand_tilde(Path::new("~"));
+        assert_eq!(
+            expand_value("~", placeholders(None)).unwrap(),
+            home.to_string_lossy()
+        );
+        assert_eq!(
+            expand_value("~/plugins", placeholders(None)).unwrap(),
+            home.join("plugins").to_string_lossy()
+        );
+        assert_eq!(expand_value("a/~/b", placeholders(None)).unwrap(), "a/~/b");
+        assert_eq!(
+            expand_value("~user/b", placeholders(None)).unwrap(),
+            "~user/b"
+        );
+    }
 }
@@ -0,0 +1,174 @@
+//! Request counts, error rates and latency percentiles of plugin commands.
+
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+/// Number of recent latency samples kept per command.
+const MAX_SAMPLES: usize = 256;
+
+/// Statistics of every command executed through the host, keyed by plugin
+/// and command id.
+#[derive(Debug, Default)]
+pub struct CommandMetrics {
+    commands: HashMap<(String, String), CommandStats>,
+}
+
+#[derive(Debug, Default)]
+struct CommandStats {
+    requests: u64,
+    errors: u64,
+    /// Latencies of the most recent requests, oldest first.
+    samples: VecDeque<Duration>,
+    /// Requests already covered by the last periodic log.
+    logged: u64,
+}
+
+impl CommandStats {
+    fn sorted_samples(&self) -> Vec<Duration> {
+        let mut samples: Vec<_> = self.samples.iter().copied().collect();
+        samples.sort_unstable();
+        samples
+    }
+}
+
+impl CommandMetrics {
+    /// Record a request to `command` of `plugin` that took `latency`.
+    pub fn record(&mut self, plugin: &str, command: &str, latency: Duration, failed: bool) {
+        let stats = self
+            .commands
+            .entry((plugin.to_string(), command.to_string()))
+            .or_default();
+        stats.requests += 1;
+        if failed {
+            stats.errors += 1;
+        }
+        if stats.samples.len() == MAX_SAMPLES {
+            stats.samples.pop_front();
+        }
+        stats.samples.push_back(latency);
+    }
+
+    /// Statistics of every command, slowest first by 95th percentile.
+    pub fn report(&self) -> Value {
+        let mut commands: Vec<_> = self
+            .commands
+            .iter()
+            .map(|((plugin, command), stats)| {
+                let sorted = stats.sorted_samples();
+                let p95 = percentile(&sorted, 95);
+                let report = json!({
+                    "plugin": plugin,
+                    "command": command,
+                    "requests": stats.requests,
+                    "errors": stats.errors,
+                    "error_rate": stats.errors as f64 / stats.requests as f64,
+                    "p50_ms": millis(percentile(&sorted, 50)),
+                    "p95_ms": millis(p95),
+                    "p99_ms": millis(percentile(&sorted, 99)),
+                    "max_ms": millis(sorted[sorted.len() - 1]),
+                });
+                (p95, report)
+            })
+            .collect();
+        commands.sort_by(|(a, _), (b, _)| b.cmp(a));
+        Value::Array(commands.into_iter().map(|(_, report)| report).collect())
+    }
+
+    /// Log the statistics of commands executed since the previous call.
+    pub fn log_summary(&mut self) {
+        for ((plugin, command), stats) in &mut self.commands {
+            if stats.requests == stats.logged {
+                continue;
+            }
+            stats.logged = stats.requests;
+            let sorted = stats.sorted_samples();
+            log::info!(
+                "command `{command}` of plugin `{plugin}`: {} requests, {} errors, p50 {:.1}ms, p95 {:.1}ms, p99 {:.1}ms",
+                stats.requests,
+                stats.errors,
+                millis(percentile(&sorted, 50)),
+                millis(percentile(&sorted, 95)),
+                millis(percentile(&sorted, 99)),
+            );
+        }
+    }
+
+    /// Drop all statistics.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+}
+
+/// Latency below which `percentile` percent of the `sorted` samples lie.
+fn percentile(sorted: &[Duration], percentile: usize) -> Duration {
+    let rank = (sorted.len() * percentile).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn takes_the_nearest_rank() {
+        let samples: Vec<_> = (1..=100).map(ms).collect();
+        assert_eq!(percentile(&samples, 50), ms(50));
+        assert_eq!(percentile(&samples, 95), ms(95));
+        assert_eq!(percentile(&samples, 99), ms(99));
+        assert_eq!(percentile(&samples, 100), ms(100));
+
+        let samples = [ms(10), ms(20), ms(30)];
+        assert_eq!(percentile(&samples, 50), ms(20));
+        assert_eq!(percentile(&samples, 95), ms(30));
+        assert_eq!(percentile(&samples, 1), ms(10));
+
+        assert_eq!(percentile(&[ms(7)], 50), ms(7));
+        assert_eq!(percentile(&[ms(7)], 99), ms(7));
+    }
+
+    #[test]
+    fn reports_slowest_commands_first() {
+        let mut metrics = CommandMetrics::default();
+        for latency in [30, 10, 20] {
+            metrics.record("tasks", "run", ms(latency), latency == 10);
+        }
+        metrics.record("tasks", "list", ms(5), false);
+
+        let report = metrics.report();
+        let report = report.as_array().unwrap();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0]["command"], "run");
+        assert_eq!(report[0]["requests"], 3);
+        assert_eq!(report[0]["errors"], 1);
+        assert_eq!(report[0]["error_rate"], 1.0 / 3.0);
+        assert_eq!(report[0]["p50_ms"], 20.0);
+        assert_eq!(report[0]["p99_ms"], 30.0);
+        assert_eq!(report[0]["max_ms"], 30.0);
+        assert_eq!(report[1]["command"], "list");
+        assert_eq!(report[1]["p50_ms"], 5.0);
+        assert_eq!(report[1]["max_ms"], 5.0);
+    }
+
+    #[test]
+    fn keeps_recent_samples_only() {
+        let mut metrics = CommandMetrics::default();
+        metrics.record("tasks", "run", ms(1000), false);
+        for _ in 0..MAX_SAMPLES {
+            metrics.record("tasks", "run", ms(1), false);
+        }
+        let report = metrics.report();
+        assert_eq!(report[0]["requests"], MAX_SAMPLES + 1);
+        assert_eq!(report[0]["max_ms"], 1.0);
+    }
+}
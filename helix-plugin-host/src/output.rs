@@ -0,0 +1,70 @@
+//! Named output channels of plugins.
+//!
+//! Plugins append text to channels, e.g. the log of a running build, instead
+//! of streaming it through messages. The host keeps the most recent
+//! [`MAX_CHANNEL_SIZE`] bytes of every channel for `helix.plugins.show_output`.
+
+use crate::logfile;
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Arc};
+
+/// Size in bytes after which the oldest lines of a channel are dropped.
+const MAX_CHANNEL_SIZE: usize = 1024 * 1024;
+
+/// Channels of every plugin, keyed by plugin and channel name. Cloning shares
+/// the channels.
+#[derive(Debug, Clone, Default)]
+pub struct OutputChannels(Arc<Mutex<BTreeMap<String, BTreeMap<String, String>>>>);
+
+impl OutputChannels {
+    /// Append `text` to `channel` of `plugin`.
+    pub fn append(&self, plugin: &str, channel: &str, text: &str) {
+        let mut plugins = self.0.lock();
+        let buffer = plugins
+            .entry(plugin.to_string())
+            .or_default()
+            .entry(channel.to_string())
+            .or_default();
+        buffer.push_str(text);
+        if buffer.len() > MAX_CHANNEL_SIZE {
+            let mut excess = buffer.len() - MAX_CHANNEL_SIZE;
+            while !buffer.is_char_boundary(excess) {
+                excess += 1;
+            }
+            let cut = buffer[excess..]
+                .find('\n')
+                .map_or(buffer.len(), |newline| excess + newline + 1);
+            buffer.drain(..cut);
+        }
+    }
+
+    /// Names of the channels of `plugin`.
+    pub fn channels(&self, plugin: &str) -> Vec<String> {
+        self.0
+            .lock()
+            .get(plugin)
+            .map(|channels| channels.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Contents of `channel` of `plugin`, if it was written to.
+    pub fn contents(&self, plugin: &str, channel: &str) -> Option<String> {
+        self.0.lock().get(plugin)?.get(channel).cloned()
+    }
+}
+
+/// Write `contents` of `channel` of `plugin` to a file the editor can open,
+/// returning its path.
+pub fn snapshot(plugin: &str, channel: &str, contents: &str) -> Result<PathBuf> {
+    let path = helix_loader::cache_dir()
+        .join("plugin-output")
+        .join(logfile::file_name(plugin))
+        .join(format!("{}.log", logfile::file_name(channel)));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create `{}`", parent.display()))?;
+    }
+    fs::write(&path, contents).with_context(|| format!("failed to write `{}`", path.display()))?;
+    Ok(path)
+}
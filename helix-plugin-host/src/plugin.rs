@@ -1,22 +1,181 @@
-use crate::manifest::PluginEntry;
+use crate::{
+    bus::{BusMessage, BusSender},
+    decode::Decoder,
+    diagnostics::PluginDiagnostics,
+    limits,
+    logfile::{self, PluginLog},
+    lsp_ext::{
+        self, InputRequest, PluginEventNotification, PluginEventParams, RunInTerminalRequest,
+    },
+    manifest::{Placeholders, PluginEntry, PluginKind, StrayOutput},
+    output::OutputChannels,
+    runtime,
+    sandbox::{self, SandboxPaths},
+    secrets::SecretStore,
+    stderr,
+    store::SharedStore,
+    throttle::{MessageThrottle, Verdict},
+    trace::{Direction, Tracer},
+    transport::Endpoint,
+};
 use anyhow::{anyhow, Context, Result};
 use helix_plugin_sdk::protocol::{
-    HostRequest, HostRequestPayload, MessageLevel, PluginEvent, PluginMessage, PluginResponse,
+    HostReply, HostRequest, HostRequestPayload, MessageLevel, PluginEvent, PluginMessage,
+    PluginRequest, PluginResponse,
 };
-use tokio::sync::Mutex as ParkingMutex;
+use serde_json::Value;
 use std::{
-    collections::HashMap,
-    ffi::OsString,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    ffi::{OsStr, OsString},
     path::{Path, PathBuf},
-    sync::atomic::{AtomicU64, Ordering},
-    sync::Arc,
+    process::ExitStatus,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    sync::{Arc, Weak},
+    time::Duration,
 };
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    process::{Child, ChildStderr, ChildStdout, Command},
-    sync::{oneshot, Mutex},
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    process::{Child, ChildStderr, Command},
+    sync::{mpsc, oneshot, watch, Mutex, Semaphore},
+    task::JoinHandle,
+};
+use tower_lsp::{
+    lsp_types::{MessageActionItem, MessageType, Url},
+    Client,
 };
-use tower_lsp::Client;
+use uuid::Uuid;
+
+/// Logged and traced in place of secret values.
+const REDACTED: &str = "<redacted>";
+
+/// Time a plugin gets to exit after the shutdown request, unless its entry
+/// sets `shutdown_timeout_ms`.
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Time a plugin gets to exit after `SIGTERM`, unless its entry sets
+/// `terminate_timeout_ms`.
+const DEFAULT_TERMINATE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Capacity of the pipe relaying messages from `connect` plugins.
+const CONNECTION_PIPE_CAPACITY: usize = 64 * 1024;
+
+/// Messages waiting to be written to a plugin's stdin before further
+/// requests fail.
+const WRITE_QUEUE_CAPACITY: usize = 256;
+
+/// Unanswered pings after which a plugin is unresponsive, unless its entry
+/// sets `max_missed_pings`.
+const DEFAULT_MAX_MISSED_PINGS: u32 = 3;
+
+/// Error returned when a plugin does not answer a request in time.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("plugin `{plugin}` did not respond within {}ms", timeout.as_millis())]
+pub struct RequestTimeout {
+    /// Logical plugin name.
+    pub plugin: String,
+    /// Timeout that elapsed.
+    pub timeout: Duration,
+}
+
+/// Error returned when a plugin binary does not match the digest declared in
+/// the manifest.
+#[derive(Debug, thiserror::Error)]
+#[error("refusing to run plugin `{plugin}`: `{}` has SHA-256 {actual} but the manifest expects {expected}", path.display())]
+pub struct IntegrityError {
+    /// Logical plugin name.
+    pub plugin: String,
+    /// Verified file.
+    pub path: PathBuf,
+    /// Digest declared in the manifest.
+    pub expected: String,
+    /// Digest of the file on disk.
+    pub actual: String,
+}
+
+/// Notification emitted when a plugin process exits without being asked to.
+#[derive(Debug)]
+pub struct PluginExit {
+    /// Logical plugin name.
+    pub name: String,
+    /// Instance of the process that exited.
+    pub instance: Uuid,
+    /// Exit status, if it could be collected.
+    pub status: Option<ExitStatus>,
+    /// Why the host killed the plugin, if it did.
+    pub reason: Option<String>,
+    /// Whether the plugin should be restarted.
+    pub restart: bool,
+}
+
+impl PluginExit {
+    /// Human readable description of how the plugin stopped, such as
+    /// `exit status: 1` or `signal: 9 (SIGKILL)`.
+    pub fn describe(&self) -> String {
+        match (&self.reason, self.status) {
+            (Some(reason), _) => reason.clone(),
+            (None, Some(status)) => status.to_string(),
+            (None, None) => "unknown status".to_string(),
+        }
+    }
+}
+
+/// Escalation step that stopped a plugin in [`PluginProcess::shutdown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownStage {
+    /// The plugin exited after the shutdown request.
+    Graceful,
+    /// The plugin exited after `SIGTERM`.
+    Terminated,
+    /// The plugin had to be killed.
+    Killed,
+}
+
+impl std::fmt::Display for ShutdownStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Graceful => "exited after the shutdown request",
+            Self::Terminated => "had to be terminated with SIGTERM",
+            Self::Killed => "had to be killed",
+        })
+    }
+}
+
+/// Sending half used by plugin processes to report unexpected exits.
+pub type ExitSender = mpsc::UnboundedSender<PluginExit>;
+
+/// Receiving half drained by the host restart supervisor.
+pub type ExitReceiver = mpsc::UnboundedReceiver<PluginExit>;
+
+/// Host side handles shared with every spawned plugin process.
+#[derive(Clone)]
+pub struct HostHandles {
+    /// LSP client used to surface plugin messages. Messages are logged
+    /// instead when the host runs without a language client.
+    pub client: Option<Client>,
+    /// Bus used to publish inter-plugin events.
+    pub bus: BusSender,
+    /// Channel receiving unexpected exit notifications.
+    pub exits: ExitSender,
+    /// Recorder of the messages exchanged with plugins, if tracing.
+    pub tracer: Option<Tracer>,
+    /// Key-value store shared between plugins.
+    pub store: SharedStore,
+    /// Secrets of every plugin.
+    pub secrets: SecretStore,
+    /// Output channels of every plugin.
+    pub output: OutputChannels,
+    /// Diagnostics of every plugin.
+    pub diagnostics: PluginDiagnostics,
+}
+
+/// Pings detecting unresponsive plugins, configured in the manifest entry.
+#[derive(Debug, Clone, Copy)]
+struct Watchdog {
+    interval: Duration,
+    max_missed: u32,
+    restart: bool,
+}
 
 /// Handle to a spawned plugin process.
 #[derive(Clone)]
@@ -26,12 +185,116 @@ pub struct PluginProcess {
 
 struct PluginProcessInner {
     name: String,
+    instance: Uuid,
+    pid: Option<u32>,
     display_command: String,
-    writer: Mutex<tokio::process::ChildStdin>,
+    /// Serialized messages waiting for the writer task, which stalls while
+    /// the plugin does not read its stdin.
+    outgoing: mpsc::Sender<Vec<u8>>,
     pending: Mutex<HashMap<u64, oneshot::Sender<PluginResponse>>>,
+    /// Identical execute requests awaiting the response to the one sent,
+    /// keyed by serialized payload.
+    in_flight: parking_lot::Mutex<HashMap<String, Vec<oneshot::Sender<Result<PluginResponse>>>>>,
+    /// Bounds the number of command and event requests sent at once.
+    permits: Option<Semaphore>,
     next_request_id: AtomicU64,
-    client: Client,
-    child: ParkingMutex<Option<Child>>,
+    timeout: Option<Duration>,
+    shutdown_timeout: Duration,
+    terminate_timeout: Duration,
+    client: Option<Client>,
+    bus: BusSender,
+    store: SharedStore,
+    secrets: SecretStore,
+    output: OutputChannels,
+    diagnostics: PluginDiagnostics,
+    /// Namespace of the plugin in the shared and secret stores, shared by
+    /// per-workspace instances.
+    namespace: String,
+    log: PluginLog,
+    tracer: Option<Tracer>,
+    stray_output: StrayOutput,
+    messages: parking_lot::Mutex<MessageThrottle>,
+    /// Whether the plugin wrote output that is not a protocol message.
+    stray_output_seen: AtomicBool,
+    shutting_down: AtomicBool,
+    watchdog: Option<Watchdog>,
+    /// Why the watchdog killed the plugin, if it did.
+    unresponsive: parking_lot::Mutex<Option<String>>,
+    /// Number of requests of the plugin the host has not answered yet, e.g.
+    /// prompts awaiting the user. Request timeouts and the watchdog are
+    /// suspended meanwhile, as the plugin waits for the host.
+    host_requests: watch::Sender<usize>,
+    /// Whether the plugin runs outside the host, which only owns the
+    /// connection to it.
+    attached: bool,
+    /// Whether the plugin may run processes in a terminal of the editor.
+    run_in_terminal: bool,
+    exited: watch::Receiver<bool>,
+    /// Firing or dropping this sender kills the process, tying its lifetime
+    /// to the last handle.
+    kill_switch: parking_lot::Mutex<Option<oneshot::Sender<()>>>,
+}
+
+/// Execute request awaiting its response, with identical requests sharing
+/// it. Dropping it without [`Self::finish`] cancels the shared requests.
+struct InFlight<'a> {
+    inner: &'a PluginProcessInner,
+    key: Option<String>,
+}
+
+impl InFlight<'_> {
+    /// Requests waiting for the response.
+    fn finish(mut self) -> Vec<oneshot::Sender<Result<PluginResponse>>> {
+        let key = self.key.take().expect("finished once");
+        self.inner.in_flight.lock().remove(&key).unwrap_or_default()
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = &self.key {
+            self.inner.in_flight.lock().remove(key);
+        }
+    }
+}
+
+impl PluginProcessInner {
+    fn trace(&self, direction: Direction, line: &str) {
+        if let Some(tracer) = &self.tracer {
+            tracer.record(&self.name, self.instance, direction, line);
+        }
+    }
+
+    /// Queue a request for the writer task. Fails right away instead of
+    /// waiting when the plugin stopped reading its stdin and the queue is
+    /// full.
+    async fn write_request(&self, request: &HostRequest) -> Result<()> {
+        self.write_logged(request, request).await
+    }
+
+    /// Queue `request` like [`Self::write_request`], logging and tracing
+    /// `logged` in its place.
+    async fn write_logged(&self, request: &HostRequest, logged: &HostRequest) -> Result<()> {
+        let mut serialized =
+            serde_json::to_vec(request).context("failed to serialize plugin request payload")?;
+        let log = &self.log;
+        if log.enabled(log::Level::Debug) || self.tracer.is_some() {
+            let line = serde_json::to_string(logged)
+                .context("failed to serialize plugin request payload")?;
+            log.write(log::Level::Debug, format_args!("--> {line}"));
+            self.trace(Direction::ToPlugin, &line);
+        }
+        serialized.push(b'\n');
+        self.outgoing.try_send(serialized).map_err(|err| match err {
+            mpsc::error::TrySendError::Full(_) => anyhow!(
+                "plugin `{}` is not reading its input; {WRITE_QUEUE_CAPACITY} messages are waiting to be written",
+                self.name
+            ),
+            mpsc::error::TrySendError::Closed(_) => {
+                anyhow!("plugin `{}` no longer accepts input", self.name)
+            }
+        })
+    }
 }
 
 impl PluginProcess {
@@ -39,12 +302,101 @@ impl PluginProcess {
     pub async fn spawn(
         manifest_dir: &Path,
         entry: &PluginEntry,
-        client: Client,
+        handles: HostHandles,
         workspace_root: Option<&Path>,
     ) -> Result<Self> {
-        let (cmd, display) = resolve_command(manifest_dir, &entry.command);
+        let entry = &entry.for_current_platform().expand(Placeholders {
+            workspace_root,
+            manifest_dir,
+        })?;
+        let base_dir = entry.base_dir(manifest_dir);
+
+        if let Some(expected) = &entry.sha256 {
+            let path = match entry.kind {
+                PluginKind::Native if entry.runtime.is_some() => {
+                    resolve_relative(base_dir, Path::new(&entry.command))
+                }
+                PluginKind::Native => {
+                    let (cmd, _) = resolve_command(base_dir, &entry.command);
+                    helix_stdx::env::which(&cmd).unwrap_or_else(|_| PathBuf::from(cmd))
+                }
+                PluginKind::Wasm => resolve_relative(base_dir, Path::new(&entry.command)),
+                PluginKind::Connect | PluginKind::Ssh => anyhow::bail!(
+                    "refusing to run plugin `{}`: `sha256` cannot be verified for remote plugins",
+                    entry.name
+                ),
+            };
+            verify_integrity(&entry.name, &path, expected).await?;
+        }
 
-        let mut command = Command::new(&cmd);
+        match entry.kind {
+            PluginKind::Native => {
+                let entry = &runtime::apply(entry, base_dir)?;
+                Self::spawn_native(base_dir, entry, handles, workspace_root)
+            }
+            PluginKind::Wasm => Self::spawn_wasm(base_dir, entry, handles, workspace_root).await,
+            PluginKind::Connect => Self::connect(base_dir, entry, handles).await,
+            PluginKind::Ssh => {
+                let entry = &runtime::apply(entry, base_dir)?.over_ssh()?;
+                Self::spawn_native(base_dir, entry, handles, workspace_root)
+            }
+        }
+    }
+
+    /// Connect to an externally managed plugin listening on the socket named
+    /// by the entry's command.
+    async fn connect(base_dir: &Path, entry: &PluginEntry, handles: HostHandles) -> Result<Self> {
+        let endpoint = endpoint(base_dir, &entry.command);
+        let (mut reader, writer) = endpoint
+            .connect()
+            .await
+            .with_context(|| format!("failed to connect to plugin `{}`", entry.name))?;
+
+        // Messages are relayed through a pipe so that the monitor task can
+        // observe the daemon closing the connection.
+        let (mut pipe, stdout) = tokio::io::duplex(CONNECTION_PIPE_CAPACITY);
+        let relay = tokio::spawn(async move {
+            let _ = tokio::io::copy(&mut reader, &mut pipe).await;
+        });
+
+        Ok(Self::start(
+            entry,
+            endpoint.to_string(),
+            None,
+            writer,
+            stdout,
+            Runtime::Connection(relay),
+            handles,
+        ))
+    }
+
+    fn spawn_native(
+        base_dir: &Path,
+        entry: &PluginEntry,
+        handles: HostHandles,
+        workspace_root: Option<&Path>,
+    ) -> Result<Self> {
+        let (cmd, display) = resolve_command(base_dir, &entry.command);
+        let data_dir = data_dir(&entry.name);
+        if let Err(err) = std::fs::create_dir_all(&data_dir) {
+            log::warn!(
+                "failed to create data directory `{}` of plugin `{}`: {err}",
+                data_dir.display(),
+                entry.name
+            );
+        }
+
+        let mut command = match &entry.sandbox {
+            Some(config) => {
+                let program = helix_stdx::env::which(&cmd).unwrap_or_else(|_| PathBuf::from(&cmd));
+                let paths = SandboxPaths::new(config, &program, workspace_root, &data_dir);
+                log::debug!("sandboxing plugin `{}`: {paths:?}", entry.name);
+                sandbox::command(program.as_os_str(), &paths)
+                    .with_context(|| format!("failed to sandbox plugin `{}`", entry.name))?
+            }
+            None => Command::new(&cmd),
+        };
+        limits::restrict(&mut command, entry.cpu_nice, entry.max_memory_mb);
         command.kill_on_drop(true);
         command.stdin(std::process::Stdio::piped());
         command.stdout(std::process::Stdio::piped());
@@ -52,14 +404,19 @@ pub async fn spawn(
         command.args(&entry.args);
 
         if let Some(cwd) = entry.cwd.as_ref() {
-            command.current_dir(resolve_relative(manifest_dir, cwd));
+            command.current_dir(resolve_relative(base_dir, cwd));
         }
 
+        if let Some(allowed) = &entry.inherit_env {
+            command.env_clear();
+            command.envs(std::env::vars_os().filter(|(key, _)| inherits(allowed, key)));
+        }
         for (key, value) in &entry.env {
             command.env(key, value);
         }
 
         command.env("HELIX_PLUGIN_NAME", &entry.name);
+        command.env("HELIX_PLUGIN_DATA_DIR", &data_dir);
         if let Some(root) = workspace_root {
             command.env("HELIX_WORKSPACE_ROOT", root);
         }
@@ -77,35 +434,266 @@ pub async fn spawn(
             .take()
             .ok_or_else(|| anyhow!("plugin `{}` stdout unavailable", entry.name))?;
         let stderr = child.stderr.take();
+        let pid = child.id();
+        let overrun = entry
+            .max_memory_mb
+            .zip(pid)
+            .map(|(max_memory_mb, pid)| limits::watch_memory(&entry.name, pid, max_memory_mb));
+
+        let process = Self::start(
+            entry,
+            display,
+            pid,
+            Box::new(stdin),
+            stdout,
+            Runtime::Native { child, overrun },
+            handles,
+        );
+        if let Some(stderr) = stderr {
+            process.spawn_stderr_task(stderr);
+        }
+        Ok(process)
+    }
+
+    #[cfg(feature = "wasm")]
+    async fn spawn_wasm(
+        base_dir: &Path,
+        entry: &PluginEntry,
+        handles: HostHandles,
+        workspace_root: Option<&Path>,
+    ) -> Result<Self> {
+        let path = resolve_relative(base_dir, Path::new(&entry.command));
+        let display = path.display().to_string();
+        let mut plugin = crate::wasm::WasmPlugin::spawn(path, entry, workspace_root)
+            .await
+            .with_context(|| format!("failed to spawn plugin `{}`", entry.name))?;
+
+        let stdin = std::mem::replace(&mut plugin.stdin, tokio::io::duplex(1).0);
+        let stdout = std::mem::replace(&mut plugin.stdout, tokio::io::duplex(1).0);
+        Ok(Self::start(
+            entry,
+            display,
+            None,
+            Box::new(stdin),
+            stdout,
+            Runtime::Wasm(plugin),
+            handles,
+        ))
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    async fn spawn_wasm(
+        _base_dir: &Path,
+        entry: &PluginEntry,
+        _handles: HostHandles,
+        _workspace_root: Option<&Path>,
+    ) -> Result<Self> {
+        anyhow::bail!(
+            "plugin `{}` is a WebAssembly plugin but the host was built without the `wasm` feature",
+            entry.name
+        )
+    }
+
+    /// Wire up a freshly spawned plugin and start its I/O and monitor tasks.
+    fn start(
+        entry: &PluginEntry,
+        display_command: String,
+        pid: Option<u32>,
+        writer: Box<dyn AsyncWrite + Send + Unpin>,
+        stdout: impl AsyncRead + Send + Unpin + 'static,
+        runtime: Runtime,
+        handles: HostHandles,
+    ) -> Self {
+        let (kill_tx, kill_rx) = oneshot::channel();
+        let (exited_tx, exited_rx) = watch::channel(false);
+        let (outgoing, queued) = mpsc::channel(WRITE_QUEUE_CAPACITY);
 
         let process = Self {
             inner: Arc::new(PluginProcessInner {
                 name: entry.name.clone(),
-                display_command: display,
-                writer: Mutex::new(stdin),
+                instance: Uuid::new_v4(),
+                pid,
+                display_command,
+                outgoing,
                 pending: Mutex::new(HashMap::new()),
+                in_flight: parking_lot::Mutex::new(HashMap::new()),
+                permits: entry
+                    .max_concurrent_requests
+                    .map(|max| Semaphore::new(max.max(1))),
                 next_request_id: AtomicU64::new(1),
-                client,
-                child: ParkingMutex::new(Some(child)),
+                timeout: entry.timeout_ms.map(Duration::from_millis),
+                shutdown_timeout: entry
+                    .shutdown_timeout_ms
+                    .map_or(DEFAULT_SHUTDOWN_TIMEOUT, Duration::from_millis),
+                terminate_timeout: entry
+                    .terminate_timeout_ms
+                    .map_or(DEFAULT_TERMINATE_TIMEOUT, Duration::from_millis),
+                client: handles.client,
+                bus: handles.bus,
+                store: handles.store,
+                secrets: handles.secrets,
+                output: handles.output,
+                diagnostics: handles.diagnostics,
+                namespace: entry
+                    .instance_of
+                    .clone()
+                    .unwrap_or_else(|| entry.name.clone()),
+                log: PluginLog::open(&entry.name, entry.log_level.as_deref()),
+                tracer: handles.tracer,
+                stray_output: entry.stray_output,
+                messages: parking_lot::Mutex::new(MessageThrottle::new(
+                    entry.max_messages_per_second,
+                )),
+                stray_output_seen: AtomicBool::new(false),
+                shutting_down: AtomicBool::new(false),
+                watchdog: entry.ping_interval_ms.map(|interval| Watchdog {
+                    interval: Duration::from_millis(interval.max(1)),
+                    max_missed: entry
+                        .max_missed_pings
+                        .unwrap_or(DEFAULT_MAX_MISSED_PINGS)
+                        .max(1),
+                    restart: entry.restart_unresponsive,
+                }),
+                unresponsive: parking_lot::Mutex::new(None),
+                host_requests: watch::Sender::new(0),
+                attached: matches!(runtime, Runtime::Connection(_)),
+                run_in_terminal: may_run_in_terminal(entry),
+                exited: exited_rx,
+                kill_switch: parking_lot::Mutex::new(Some(kill_tx)),
             }),
         };
 
+        process.spawn_writer_task(writer, queued);
         process.spawn_stdout_task(stdout);
-        if let Some(stderr) = stderr {
-            process.spawn_stderr_task(stderr);
+        process.spawn_monitor_task(runtime, kill_rx, exited_tx, handles.exits);
+        if let Some(watchdog) = process.inner.watchdog {
+            process.spawn_watchdog_task(watchdog);
         }
 
-        log::info!(
-            "spawned plugin `{}` using command `{}`",
-            entry.name,
-            process.inner.display_command
-        );
+        let log = &process.inner.log;
+        if process.inner.attached {
+            log.write(
+                log::Level::Info,
+                format_args!("connected to `{}`", process.inner.display_command),
+            );
+            log::info!(
+                "connected to plugin `{}` at `{}`",
+                entry.name,
+                process.inner.display_command
+            );
+        } else {
+            log.write(
+                log::Level::Info,
+                format_args!("spawned using command `{}`", process.inner.display_command),
+            );
+            log::info!(
+                "spawned plugin `{}` using command `{}`",
+                entry.name,
+                process.inner.display_command
+            );
+        }
 
-        Ok(process)
+        process
     }
 
-    /// Send a request to the plugin and await the response.
+    /// Send a request to the plugin and await the response, honouring the
+    /// timeout configured in the manifest entry.
     pub async fn send_request(&self, payload: HostRequestPayload) -> Result<PluginResponse> {
+        self.send_request_with_timeout(payload, self.inner.timeout)
+            .await
+    }
+
+    /// Send a request to the plugin, overriding the configured timeout.
+    ///
+    /// Requests that are not answered within `timeout` fail with a
+    /// [`RequestTimeout`] error. An execute request identical to one still
+    /// awaiting its response is not sent again; it shares that response.
+    pub async fn send_request_with_timeout(
+        &self,
+        payload: HostRequestPayload,
+        timeout: Option<Duration>,
+    ) -> Result<PluginResponse> {
+        let key = match &payload {
+            HostRequestPayload::Execute { .. } => serde_json::to_string(&payload).ok(),
+            _ => None,
+        };
+        let Some(key) = key else {
+            return self.send_queued(payload, timeout).await;
+        };
+
+        let joined = {
+            let mut in_flight = self.inner.in_flight.lock();
+            match in_flight.get_mut(&key) {
+                Some(waiters) => {
+                    let (tx, rx) = oneshot::channel();
+                    waiters.push(tx);
+                    Some(rx)
+                }
+                None => {
+                    in_flight.insert(key.clone(), Vec::new());
+                    None
+                }
+            }
+        };
+        if let Some(joined) = joined {
+            log::debug!(
+                "plugin `{}`: sharing the response to an identical request",
+                self.inner.name
+            );
+            return joined.await.unwrap_or_else(|_| {
+                Err(anyhow!(
+                    "identical request to plugin `{}` was cancelled",
+                    self.inner.name
+                ))
+            });
+        }
+
+        let in_flight = InFlight {
+            inner: &self.inner,
+            key: Some(key),
+        };
+        let response = self.send_queued(payload, timeout).await;
+        for waiter in in_flight.finish() {
+            let shared = match &response {
+                Ok(response) => Ok(response.clone()),
+                Err(err) => Err(match err.downcast_ref::<RequestTimeout>() {
+                    Some(timeout) => timeout.clone().into(),
+                    None => anyhow!("{err:#}"),
+                }),
+            };
+            let _ = waiter.send(shared);
+        }
+        response
+    }
+
+    /// Send a request once fewer than `max_concurrent_requests` command and
+    /// event requests are awaiting a response, in FIFO order. The timeout
+    /// starts when the request is sent. When it elapses while the plugin
+    /// waits for the host to answer a request of its own, it starts over
+    /// once that request was answered.
+    async fn send_queued(
+        &self,
+        payload: HostRequestPayload,
+        timeout: Option<Duration>,
+    ) -> Result<PluginResponse> {
+        let limited = matches!(
+            payload,
+            HostRequestPayload::Execute { .. }
+                | HostRequestPayload::BusEvent { .. }
+                | HostRequestPayload::WorkspaceSymbols { .. }
+                | HostRequestPayload::DocumentSymbols { .. }
+                | HostRequestPayload::InlayHints { .. }
+        );
+        let _permit = match self.inner.permits.as_ref().filter(|_| limited) {
+            Some(permits) => Some(
+                permits
+                    .acquire()
+                    .await
+                    .map_err(|_| anyhow!("plugin `{}` is shutting down", self.inner.name))?,
+            ),
+            None => None,
+        };
+
         let id = self.inner.next_request_id.fetch_add(1, Ordering::Relaxed);
         let request = HostRequest { id, payload };
 
@@ -115,114 +703,824 @@ pub async fn send_request(&self, payload: HostRequestPayload) -> Result<PluginRe
             pending.insert(id, tx);
         }
 
-        let mut writer = self.inner.writer.lock().await;
-        let serialized =
-            serde_json::to_vec(&request).context("failed to serialize plugin request payload")?;
-        writer
-            .write_all(&serialized)
-            .await
-            .context("failed to write plugin request")?;
-        writer
-            .write_all(b"\n")
-            .await
-            .context("failed to delimit plugin request")?;
-        writer
-            .flush()
+        if let Err(err) = self.inner.write_request(&request).await {
+            self.inner.pending.lock().await.remove(&id);
+            return Err(err);
+        }
+
+        let response = match timeout {
+            Some(timeout) => {
+                let mut rx = rx;
+                let mut deadline = tokio::time::Instant::now() + timeout;
+                loop {
+                    tokio::select! {
+                        response = &mut rx => break response,
+                        () = tokio::time::sleep_until(deadline) => {}
+                    }
+                    if *self.inner.host_requests.borrow() == 0 {
+                        self.inner.pending.lock().await.remove(&id);
+                        return Err(RequestTimeout {
+                            plugin: self.inner.name.clone(),
+                            timeout,
+                        }
+                        .into());
+                    }
+                    let mut host_requests = self.inner.host_requests.subscribe();
+                    tokio::select! {
+                        response = &mut rx => break response,
+                        _ = host_requests.wait_for(|outstanding| *outstanding == 0) => {
+                            deadline = tokio::time::Instant::now() + timeout;
+                        }
+                    }
+                }
+            }
+            None => rx.await,
+        };
+
+        response.map_err(|_| anyhow!("plugin `{}` terminated before responding", self.inner.name))
+    }
+
+    /// Request timeout configured in the manifest entry.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.inner.timeout
+    }
+
+    /// Logical plugin name from the manifest.
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// Unique identifier of this process instance. Restarted plugins receive
+    /// a fresh instance id.
+    pub fn instance(&self) -> Uuid {
+        self.inner.instance
+    }
+
+    /// Operating system process id, if the process was still running when
+    /// spawned.
+    pub fn pid(&self) -> Option<u32> {
+        self.inner.pid
+    }
+
+    /// Stop the plugin, escalating until it exits: the shutdown request is
+    /// followed by `SIGTERM` and finally a kill when the plugin does not exit
+    /// in time. The connection to `connect` plugins is closed after the
+    /// request instead, leaving the daemon running.
+    pub async fn shutdown(&self) -> ShutdownStage {
+        let name = &self.inner.name;
+        self.inner.shutting_down.store(true, Ordering::Release);
+
+        let request = async {
+            tokio::select! {
+                response = self.send_request_with_timeout(HostRequestPayload::Shutdown, None) => {
+                    match response {
+                        Ok(_) if self.inner.attached => self.kill(),
+                        Ok(_) => {}
+                        Err(err) => {
+                            log::debug!("plugin `{name}` did not acknowledge shutdown: {err}")
+                        }
+                    }
+                    self.wait_exited().await;
+                }
+                // A plugin that exited already cannot answer.
+                () = self.wait_exited() => {}
+            }
+        };
+        if tokio::time::timeout(self.inner.shutdown_timeout, request)
             .await
-            .context("failed to flush plugin request")?;
+            .is_ok()
+        {
+            return ShutdownStage::Graceful;
+        }
 
-        match rx.await {
-            Ok(response) => Ok(response),
-            Err(_) => Err(anyhow!(
-                "plugin `{}` terminated before responding",
-                self.inner.name
-            )),
+        if self.terminate() {
+            log::warn!(
+                "plugin `{name}` did not exit within {}ms of the shutdown request; sent SIGTERM",
+                self.inner.shutdown_timeout.as_millis()
+            );
+            if tokio::time::timeout(self.inner.terminate_timeout, self.wait_exited())
+                .await
+                .is_ok()
+            {
+                return ShutdownStage::Terminated;
+            }
         }
+
+        log::warn!("plugin `{name}` did not exit when asked to; killing it");
+        self.kill();
+        self.wait_exited().await;
+        ShutdownStage::Killed
+    }
+
+    async fn wait_exited(&self) {
+        let mut exited = self.inner.exited.clone();
+        let _ = exited.wait_for(|exited| *exited).await;
     }
 
-    /// Issue a shutdown request to the plugin and wait for process termination.
-    pub async fn shutdown(&self) -> Result<()> {
-        let _ = self.send_request(HostRequestPayload::Shutdown).await;
-        let mut child = self.inner.child.lock().await;
-        if let Some(mut child) = child.take() {
-            let _ = child.wait().await;
+    /// Send `SIGTERM` to the plugin process. Returns whether it was sent.
+    #[cfg(unix)]
+    fn terminate(&self) -> bool {
+        let Some(pid) = self
+            .inner
+            .pid
+            .and_then(|pid| libc::pid_t::try_from(pid).ok())
+        else {
+            return false;
+        };
+        if *self.inner.exited.borrow() {
+            return false;
         }
-        Ok(())
+        // SAFETY: `kill` has no memory safety preconditions.
+        unsafe { libc::kill(pid, libc::SIGTERM) == 0 }
+    }
+
+    /// Send `SIGTERM` to the plugin process. Not available on this platform.
+    #[cfg(not(unix))]
+    fn terminate(&self) -> bool {
+        false
     }
 
-    fn spawn_stdout_task(&self, stdout: ChildStdout) {
-        let inner = Arc::clone(&self.inner);
-        let mut reader = BufReader::new(stdout).lines();
+    /// Kill the plugin without asking it to shut down. `connect` plugins are
+    /// disconnected.
+    pub fn kill(&self) {
+        let kill_switch = self.inner.kill_switch.lock().take();
+        if let Some(kill_switch) = kill_switch {
+            let _ = kill_switch.send(());
+        }
+    }
 
+    /// Write queued messages to the plugin in order. Pending requests fail
+    /// once writing does.
+    fn spawn_writer_task(
+        &self,
+        mut writer: Box<dyn AsyncWrite + Send + Unpin>,
+        mut queued: mpsc::Receiver<Vec<u8>>,
+    ) {
+        let inner = Arc::downgrade(&self.inner);
         tokio::spawn(async move {
-            while let Ok(Some(line)) = reader.next_line().await {
-                match serde_json::from_str::<PluginMessage>(&line) {
-                    Ok(PluginMessage::Response { id, result }) => {
-                        let sender = inner.pending.lock().await.remove(&id);
-                        if let Some(sender) = sender {
-                            let _ = sender.send(result);
-                        } else {
-                            log::warn!(
-                                "plugin `{}` produced response for unknown request id {id}",
-                                inner.name
-                            );
-                        }
-                    }
-                    Ok(PluginMessage::Event { event }) => {
-                        handle_event(&inner, event).await;
-                    }
-                    Err(err) => {
-                        log::warn!(
-                            "failed to decode plugin `{}` message: {err}: {line}",
-                            inner.name
-                        );
+            while let Some(message) = queued.recv().await {
+                let written = async {
+                    writer.write_all(&message).await?;
+                    writer.flush().await
+                };
+                let Err(err) = written.await else {
+                    continue;
+                };
+                // Stop accepting messages before failing the requests
+                // awaiting a response to them.
+                queued.close();
+                if let Some(inner) = inner.upgrade() {
+                    if !inner.shutting_down.load(Ordering::Acquire) {
+                        log::debug!("failed to write to plugin `{}`: {err}", inner.name);
                     }
+                    drain_pending_with_failure(&inner, "failed to write plugin request").await;
+                }
+                return;
+            }
+        });
+    }
+
+    fn spawn_stdout_task(&self, stdout: impl AsyncRead + Send + Unpin + 'static) {
+        let inner = Arc::downgrade(&self.inner);
+        let mut reader = BufReader::new(stdout);
+
+        tokio::spawn(async move {
+            // Lines are read as bytes so that output which is not valid UTF-8
+            // does not end the connection. Both buffers are reused for every
+            // line.
+            let mut buf = Vec::new();
+            let mut decoder = Decoder::default();
+            while let Ok(read) = reader.read_until(b'\n', &mut buf).await {
+                if read == 0 {
+                    break;
                 }
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+                let line = String::from_utf8_lossy(&buf);
+                let line = line.trim_end_matches(['\n', '\r']);
+                if !line.is_empty() {
+                    let logged = redact_secret_set(line);
+                    inner
+                        .log
+                        .write(log::Level::Debug, format_args!("<-- {logged}"));
+                    inner.trace(Direction::FromPlugin, &logged);
+                    handle_line(&inner, &mut decoder, line).await;
+                }
+                buf.clear();
             }
 
-            drain_pending_with_failure(&inner, "plugin stdout closed").await;
+            if let Some(inner) = inner.upgrade() {
+                drain_pending_with_failure(&inner, "plugin stdout closed").await;
+            }
         });
     }
 
     fn spawn_stderr_task(&self, stderr: ChildStderr) {
         let name = self.inner.name.clone();
+        let log = self.inner.log.clone();
         let mut reader = BufReader::new(stderr).lines();
         tokio::spawn(async move {
             while let Ok(Some(line)) = reader.next_line().await {
-                log::warn!("plugin `{name}` stderr: {line}");
+                let (level, message) = stderr::parse_line(&line);
+                log.write(level, format_args!("stderr: {message}"));
+                log_plugin(&name, level, &message);
+            }
+        });
+    }
+
+    /// Wait for the plugin to exit, killing it once every handle is dropped,
+    /// and report exits that were not requested through [`Self::shutdown`].
+    fn spawn_monitor_task(
+        &self,
+        mut runtime: Runtime,
+        kill: oneshot::Receiver<()>,
+        exited: watch::Sender<bool>,
+        exits: ExitSender,
+    ) {
+        let inner: Weak<PluginProcessInner> = Arc::downgrade(&self.inner);
+        let name = self.inner.name.clone();
+        let instance = self.inner.instance;
+        let log = self.inner.log.clone();
+        let restart_unresponsive = self
+            .inner
+            .watchdog
+            .map_or(true, |watchdog| watchdog.restart);
+
+        tokio::spawn(async move {
+            let overrun = runtime.take_overrun();
+            let overrun = async move {
+                match overrun {
+                    Some(overrun) => overrun.await.ok(),
+                    None => None,
+                }
+            };
+
+            let mut reason = None;
+            let mut restart = true;
+            let status = tokio::select! {
+                status = runtime.wait(&name) => status,
+                Some(overrun) = overrun => {
+                    log::warn!("plugin `{name}` {overrun}; killing it");
+                    reason = Some(overrun);
+                    runtime.kill(&name);
+                    runtime.wait(&name).await
+                }
+                _ = kill => {
+                    reason = inner.upgrade().and_then(|inner| inner.unresponsive.lock().take());
+                    restart = reason.is_none() || restart_unresponsive;
+                    runtime.kill(&name);
+                    runtime.wait(&name).await
+                }
+            };
+            let _ = exited.send(true);
+
+            let requested = inner
+                .upgrade()
+                .map_or(true, |inner| inner.shutting_down.load(Ordering::Acquire));
+            if requested && reason.is_none() {
+                log.write(log::Level::Info, format_args!("exited"));
+                log::debug!("plugin `{name}` exited");
+                return;
+            }
+
+            let exit = PluginExit {
+                name,
+                instance,
+                status,
+                reason,
+                restart,
+            };
+            let status = exit.describe();
+            log.write(
+                log::Level::Error,
+                format_args!("exited unexpectedly ({status})"),
+            );
+            log::warn!("plugin `{}` exited unexpectedly ({status})", exit.name);
+            let _ = exits.send(exit);
+        });
+    }
+
+    /// Ping the plugin while requests are pending, killing it once it missed
+    /// `max_missed` pings in a row. Its pending requests fail instead of
+    /// waiting for a response that will not come.
+    fn spawn_watchdog_task(&self, watchdog: Watchdog) {
+        let inner = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(watchdog.interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticks.tick().await;
+            let mut missed = 0;
+            loop {
+                ticks.tick().await;
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+                if *inner.exited.borrow() || inner.shutting_down.load(Ordering::Acquire) {
+                    return;
+                }
+                // An unanswered ping counts as pending, so an unresponsive
+                // plugin keeps being pinged. Plugins waiting for the host,
+                // e.g. for the user to answer a prompt, are not pinged.
+                if inner.pending.lock().await.is_empty() || *inner.host_requests.borrow() > 0 {
+                    missed = 0;
+                    continue;
+                }
+
+                let process = PluginProcess { inner };
+                let ping = process
+                    .send_request_with_timeout(HostRequestPayload::Ping, Some(watchdog.interval));
+                match tokio::time::timeout(watchdog.interval, ping).await {
+                    Ok(Ok(_)) => {
+                        missed = 0;
+                        continue;
+                    }
+                    Ok(Err(err)) if err.downcast_ref::<RequestTimeout>().is_none() => {
+                        log::debug!("stopping watchdog of plugin `{}`: {err:#}", process.name());
+                        return;
+                    }
+                    Ok(Err(_)) | Err(_) => missed += 1,
+                }
+                let inner = &process.inner;
+                if missed < watchdog.max_missed {
+                    inner.log.write(
+                        log::Level::Warn,
+                        format_args!("missed {missed} of {} pings", watchdog.max_missed),
+                    );
+                    continue;
+                }
+
+                let reason = format!("missed {missed} pings in a row while requests were pending");
+                log::warn!("plugin `{}` {reason}; killing it", inner.name);
+                inner
+                    .log
+                    .write(log::Level::Error, format_args!("{reason}; killing it"));
+                *inner.unresponsive.lock() = Some(reason);
+                for (_, sender) in inner.pending.lock().await.drain() {
+                    let _ = sender.send(PluginResponse::CommandError {
+                        message: format!("plugin `{}` is unresponsive and was killed", inner.name),
+                    });
+                }
+                process.kill();
+                return;
             }
         });
     }
 }
 
-impl Drop for PluginProcessInner {
-    fn drop(&mut self) {
-        if let Ok(mut child_guard) = self.child.try_lock() {
-            if let Some(mut child) = child_guard.take() {
+/// Backend executing a plugin.
+enum Runtime {
+    /// Native child process, with the receiver reporting memory budget
+    /// overruns.
+    Native {
+        child: Child,
+        overrun: Option<oneshot::Receiver<String>>,
+    },
+    /// WebAssembly component running in-process.
+    #[cfg(feature = "wasm")]
+    Wasm(crate::wasm::WasmPlugin),
+    /// Externally managed plugin, with the task relaying its messages.
+    Connection(JoinHandle<()>),
+}
+
+impl Runtime {
+    fn take_overrun(&mut self) -> Option<oneshot::Receiver<String>> {
+        match self {
+            Self::Native { overrun, .. } => overrun.take(),
+            #[cfg(feature = "wasm")]
+            Self::Wasm(_) => None,
+            Self::Connection(_) => None,
+        }
+    }
+
+    /// Wait for the plugin to stop. WebAssembly and `connect` plugins have no
+    /// exit status; the latter stop when the connection closes.
+    #[cfg_attr(not(feature = "wasm"), allow(unused_variables))]
+    async fn wait(&mut self, name: &str) -> Option<ExitStatus> {
+        match self {
+            Self::Native { child, .. } => child.wait().await.ok(),
+            #[cfg(feature = "wasm")]
+            Self::Wasm(plugin) => {
+                if let Err(err) = plugin.wait().await {
+                    log::warn!("plugin `{name}` trapped: {err:?}");
+                }
+                None
+            }
+            Self::Connection(relay) => {
+                let _ = relay.await;
+                None
+            }
+        }
+    }
+
+    fn kill(&mut self, name: &str) {
+        match self {
+            Self::Native { child, .. } => {
                 if let Err(err) = child.start_kill() {
-                    log::warn!(
-                        "failed to terminate plugin `{}` during drop: {err}",
-                        self.name
-                    );
+                    log::warn!("failed to terminate plugin `{name}`: {err}");
                 }
             }
+            #[cfg(feature = "wasm")]
+            Self::Wasm(plugin) => plugin.interrupt(),
+            Self::Connection(relay) => relay.abort(),
         }
     }
 }
 
+/// Dispatch a line the plugin wrote to stdout. Output that is not part of
+/// the protocol, such as a stray `print`, is handed to [`stray_output`]; a
+/// message following such output on the same line is still decoded.
+async fn handle_line(inner: &Arc<PluginProcessInner>, decoder: &mut Decoder, line: &str) {
+    let err = match decoder.decode(line) {
+        Ok(message) => return handle_message(inner, message).await,
+        Err(err) => err,
+    };
+    if let Some((kind, payload)) = lsp_ext::unrecognized_event(line) {
+        inner.log.write(
+            log::Level::Debug,
+            format_args!("forwarded unrecognized `{kind}` event"),
+        );
+        return forward_event(inner, kind, payload).await;
+    }
+
+    let resynced = line
+        .match_indices('{')
+        .filter(|(start, _)| *start > 0)
+        .find_map(|(start, _)| Some((start, serde_json::from_str(&line[start..]).ok()?)));
+    if let Some((start, message)) = resynced {
+        stray_output(inner, &line[..start]);
+        handle_message(inner, message).await;
+    } else if line.trim_start().starts_with('{') {
+        inner.log.write(
+            log::Level::Warn,
+            format_args!("failed to decode message: {err}"),
+        );
+        log::warn!(
+            "failed to decode plugin `{}` message: {err}: {line}",
+            inner.name
+        );
+    } else {
+        stray_output(inner, line);
+    }
+}
+
+async fn handle_message(inner: &Arc<PluginProcessInner>, message: PluginMessage) {
+    match message {
+        PluginMessage::Response { id, result } => {
+            let sender = inner.pending.lock().await.remove(&id);
+            if let Some(sender) = sender {
+                let _ = sender.send(result);
+            } else {
+                log::warn!(
+                    "plugin `{}` produced response for unknown request id {id}",
+                    inner.name
+                );
+            }
+        }
+        PluginMessage::Event { event } => handle_event(inner, event).await,
+        // Requests are answered on their own task, as prompts wait for the
+        // user while responses and events of the plugin keep coming.
+        PluginMessage::Request { id, request } => {
+            inner
+                .host_requests
+                .send_modify(|outstanding| *outstanding += 1);
+            let inner = Arc::clone(inner);
+            tokio::spawn(async move {
+                handle_request(&inner, id, request).await;
+                inner
+                    .host_requests
+                    .send_modify(|outstanding| *outstanding -= 1);
+            });
+        }
+    }
+}
+
+/// Answer a service request of the plugin.
+async fn handle_request(inner: &PluginProcessInner, id: u64, request: PluginRequest) {
+    let namespace = &inner.namespace;
+    let secret = matches!(request, PluginRequest::SecretGet { .. });
+    let result = match request {
+        PluginRequest::StoreGet {
+            key,
+            namespace: owner,
+        } => inner.store.get(namespace, owner.as_deref(), &key),
+        PluginRequest::StoreSet { key, value, shared } => {
+            inner.store.set(namespace, key, value, shared);
+            Ok(Value::Null)
+        }
+        PluginRequest::StoreDelete { key } => {
+            inner.store.delete(namespace, &key);
+            Ok(Value::Null)
+        }
+        PluginRequest::SecretGet { key } => inner
+            .secrets
+            .get(namespace, &key)
+            .await
+            .map(|secret| secret.map_or(Value::Null, Value::String))
+            .map_err(|err| secret_error(inner, &key, err)),
+        PluginRequest::SecretSet { key, secret } => inner
+            .secrets
+            .set(namespace, &key, secret)
+            .await
+            .map(|()| Value::Null)
+            .map_err(|err| secret_error(inner, &key, err)),
+        PluginRequest::SecretDelete { key } => inner
+            .secrets
+            .delete(namespace, &key)
+            .await
+            .map(|()| Value::Null)
+            .map_err(|err| secret_error(inner, &key, err)),
+        PluginRequest::Prompt { message, choices } => prompt(inner, message, choices).await,
+        PluginRequest::Input { message, default } => input(inner, message, default).await,
+        PluginRequest::RunInTerminal {
+            title,
+            program,
+            args,
+            cwd,
+            env,
+        } => {
+            let args = std::iter::once(program).chain(args).collect();
+            run_in_terminal(inner, title, args, cwd, env).await
+        }
+    };
+    let result = match result {
+        Ok(value) => HostReply::Success { value },
+        Err(message) => {
+            inner.log.write(
+                log::Level::Debug,
+                format_args!("request {id} failed: {message}"),
+            );
+            HostReply::Error { message }
+        }
+    };
+    let reply = HostRequest {
+        id: inner.next_request_id.fetch_add(1, Ordering::Relaxed),
+        payload: HostRequestPayload::Reply {
+            request: id,
+            result,
+        },
+    };
+    let written = if secret {
+        inner.write_logged(&reply, &redact_reply(&reply)).await
+    } else {
+        inner.write_request(&reply).await
+    };
+    if let Err(err) = written {
+        log::warn!(
+            "failed to reply to request {id} of plugin `{}`: {err:#}",
+            inner.name
+        );
+    }
+}
+
+/// `reply` with the secret it carries replaced by [`REDACTED`].
+fn redact_reply(reply: &HostRequest) -> HostRequest {
+    let mut redacted = reply.clone();
+    if let HostRequestPayload::Reply {
+        result: HostReply::Success { value },
+        ..
+    } = &mut redacted.payload
+    {
+        if !value.is_null() {
+            *value = Value::from(REDACTED);
+        }
+    }
+    redacted
+}
+
+/// `line` read from a plugin with the value of a `secret_set` request
+/// replaced by [`REDACTED`]. Lines that mention it but are not valid JSON
+/// are redacted as a whole.
+fn redact_secret_set(line: &str) -> Cow<'_, str> {
+    if !line.contains("secret_set") {
+        return Cow::Borrowed(line);
+    }
+    match serde_json::from_str::<Value>(line) {
+        Ok(mut message) => {
+            if let Some(secret) = message.pointer_mut("/request/secret") {
+                *secret = Value::from(REDACTED);
+            }
+            Cow::Owned(message.to_string())
+        }
+        Err(_) => Cow::Borrowed(REDACTED),
+    }
+}
+
+/// Let the user pick one of `choices` through the language client.
+async fn prompt(
+    inner: &PluginProcessInner,
+    message: String,
+    choices: Vec<String>,
+) -> Result<Value, String> {
+    let Some(client) = &inner.client else {
+        return Ok(Value::Null);
+    };
+    let actions = choices
+        .into_iter()
+        .map(|title| MessageActionItem {
+            title,
+            properties: HashMap::new(),
+        })
+        .collect();
+    client
+        .show_message_request(MessageType::INFO, message, Some(actions))
+        .await
+        .map(|picked| picked.map_or(Value::Null, |item| Value::String(item.title)))
+        .map_err(|err| format!("failed to prompt the user: {err}"))
+}
+
+/// Let the user enter text through the editor.
+async fn input(
+    inner: &PluginProcessInner,
+    message: String,
+    default: Option<String>,
+) -> Result<Value, String> {
+    let Some(client) = &inner.client else {
+        return Err("no editor is attached".to_string());
+    };
+    let params = lsp_ext::InputParams {
+        plugin: inner.name.clone(),
+        message,
+        default,
+    };
+    client
+        .send_request::<InputRequest>(params)
+        .await
+        .map(|result| result.value.map_or(Value::Null, Value::String))
+        .map_err(|err| format!("failed to ask for input: {err}"))
+}
+
+/// Whether the plugin described by `entry` may run processes in a terminal
+/// of the editor. Sandboxed, WebAssembly and remote plugins may not, as the
+/// processes would run unconfined on the machine of the editor.
+pub fn may_run_in_terminal(entry: &PluginEntry) -> bool {
+    entry.kind == PluginKind::Native && entry.sandbox.is_none()
+}
+
+/// Run a process of the plugin in a terminal of the editor.
+async fn run_in_terminal(
+    inner: &PluginProcessInner,
+    title: String,
+    args: Vec<String>,
+    cwd: Option<String>,
+    env: BTreeMap<String, String>,
+) -> Result<Value, String> {
+    if !inner.run_in_terminal {
+        return Err("the plugin may not run processes in a terminal".to_string());
+    }
+    let Some(client) = &inner.client else {
+        return Err("no editor is attached".to_string());
+    };
+    let params = lsp_ext::RunInTerminalParams {
+        plugin: inner.name.clone(),
+        title,
+        args,
+        cwd,
+        env,
+    };
+    client
+        .send_request::<RunInTerminalRequest>(params)
+        .await
+        .map(|result| result.process_id.map_or(Value::Null, Value::from))
+        .map_err(|err| format!("failed to run in a terminal: {err}"))
+}
+
+fn secret_error(inner: &PluginProcessInner, key: &str, err: anyhow::Error) -> String {
+    log::warn!(
+        "failed to access secret `{key}` of plugin `{}`: {err:#}",
+        inner.name
+    );
+    format!("{err:#}")
+}
+
+/// Forward output the plugin wrote to stdout outside of protocol messages
+/// to its log like stderr, unless its entry discards it.
+fn stray_output(inner: &PluginProcessInner, output: &str) {
+    let output = output.trim();
+    if output.is_empty() {
+        return;
+    }
+    if !inner.stray_output_seen.swap(true, Ordering::Relaxed) {
+        let action = match inner.stray_output {
+            StrayOutput::Log => "forwarding it to the plugin log",
+            StrayOutput::Discard => "discarding it",
+        };
+        log::warn!(
+            "plugin `{}` wrote output that is not a protocol message to stdout; {action}",
+            inner.name
+        );
+    }
+    if inner.stray_output == StrayOutput::Log {
+        let (level, message) = stderr::parse_line(output);
+        inner.log.write(level, format_args!("stdout: {message}"));
+        log_plugin(&inner.name, level, &message);
+    }
+}
+
 async fn handle_event(inner: &PluginProcessInner, event: PluginEvent) {
+    match &event {
+        PluginEvent::ShowMessage { level, message } | PluginEvent::Log { level, message } => {
+            inner
+                .log
+                .write(log_level(*level), format_args!("{message}"));
+        }
+        PluginEvent::Publish { topic, .. } => {
+            inner
+                .log
+                .write(log::Level::Debug, format_args!("published `{topic}` event"));
+        }
+        PluginEvent::Notify { kind, .. } => {
+            inner
+                .log
+                .write(log::Level::Debug, format_args!("forwarded `{kind}` event"));
+        }
+        // Output is retrievable from its channel; it is not logged twice.
+        PluginEvent::AppendOutput { .. } => {}
+        PluginEvent::PublishDiagnostics { path, diagnostics } => {
+            inner.log.write(
+                log::Level::Debug,
+                format_args!("published {} diagnostics for `{path}`", diagnostics.len()),
+            );
+        }
+    }
     match event {
-        PluginEvent::ShowMessage { level, message } => {
-            let ty = map_message_level(level);
-            inner.client.show_message(ty, message).await;
+        PluginEvent::ShowMessage { level, message } => match &inner.client {
+            Some(client) => {
+                let level = map_message_level(level);
+                let verdict = inner.messages.lock().check(&message);
+                match verdict {
+                    Verdict::Show { suppressed: 0 } => client.show_message(level, message).await,
+                    Verdict::Show { suppressed } => {
+                        let message =
+                            format!("{message} ({suppressed} earlier messages were only logged)");
+                        client.show_message(level, message).await
+                    }
+                    Verdict::Suppress { first } => {
+                        if first {
+                            log::warn!(
+                                "plugin `{}` shows messages too often; logging them instead",
+                                inner.name
+                            );
+                        }
+                        client.log_message(level, message).await
+                    }
+                }
+            }
+            None => log_event(&inner.name, level, &message),
+        },
+        PluginEvent::Log { level, message } => match &inner.client {
+            Some(client) => client.log_message(map_message_level(level), message).await,
+            None => log_event(&inner.name, level, &message),
+        },
+        PluginEvent::Publish { topic, payload } => {
+            let message = BusMessage {
+                source: inner.name.clone(),
+                topic,
+                payload,
+            };
+            if inner.bus.send(message).is_err() {
+                log::warn!(
+                    "plugin `{}` published an event after the bus closed",
+                    inner.name
+                );
+            }
+        }
+        PluginEvent::Notify { kind, payload } => forward_event(inner, kind, payload).await,
+        PluginEvent::AppendOutput { channel, text } => {
+            inner.output.append(&inner.name, &channel, &text);
         }
-        PluginEvent::Log { level, message } => {
-            let ty = map_message_level(level);
-            inner.client.log_message(ty, message).await;
+        PluginEvent::PublishDiagnostics { path, diagnostics } => {
+            let Ok(uri) = Url::from_file_path(&path) else {
+                log::warn!(
+                    "plugin `{}` published diagnostics for `{path}`, which is not an absolute path",
+                    inner.name
+                );
+                return;
+            };
+            let diagnostics = inner.diagnostics.set(&inner.name, &uri, diagnostics);
+            if let Some(client) = &inner.client {
+                client.publish_diagnostics(uri, diagnostics, None).await;
+            }
         }
     }
 }
 
+/// Forward an event to the editor as a `$/helix/pluginEvent` notification.
+async fn forward_event(inner: &PluginProcessInner, kind: String, payload: Value) {
+    match &inner.client {
+        Some(client) => {
+            client
+                .send_notification::<PluginEventNotification>(PluginEventParams {
+                    plugin: inner.name.clone(),
+                    kind,
+                    payload,
+                })
+                .await
+        }
+        None => log::debug!("plugin `{}` emitted `{kind}` event: {payload}", inner.name),
+    }
+}
+
 async fn drain_pending_with_failure(inner: &PluginProcessInner, message: &str) {
     let mut pending = inner.pending.lock().await;
     if pending.is_empty() {
@@ -238,7 +1536,38 @@ async fn drain_pending_with_failure(inner: &PluginProcessInner, message: &str) {
     }
 }
 
-fn resolve_command(manifest_dir: &Path, command: &str) -> (OsString, String) {
+async fn verify_integrity(name: &str, path: &Path, expected: &str) -> Result<()> {
+    use sha2::{Digest, Sha256};
+    use std::fmt::Write;
+
+    let contents = tokio::fs::read(path).await.with_context(|| {
+        format!(
+            "failed to read `{}` to verify plugin `{name}`",
+            path.display()
+        )
+    })?;
+    let actual = Sha256::digest(&contents)
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        });
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(IntegrityError {
+            plugin: name.to_string(),
+            path: path.to_path_buf(),
+            expected: expected.trim().to_string(),
+            actual,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Resolve the program of a native plugin. Commands containing a path
+/// separator are relative to `manifest_dir`, bare names are looked up on
+/// `PATH` when spawning. Returns the program and its display form.
+pub fn resolve_command(manifest_dir: &Path, command: &str) -> (OsString, String) {
     let path = Path::new(command);
     if path.is_absolute() {
         (path.as_os_str().to_owned(), command.to_string())
@@ -251,7 +1580,16 @@ fn resolve_command(manifest_dir: &Path, command: &str) -> (OsString, String) {
     }
 }
 
-fn resolve_relative(base: &Path, path: &Path) -> PathBuf {
+/// Directory in which `plugin` keeps state across sessions, passed to native
+/// plugins as `HELIX_PLUGIN_DATA_DIR`.
+pub fn data_dir(plugin: &str) -> PathBuf {
+    helix_loader::cache_dir()
+        .join("plugin-data")
+        .join(logfile::file_name(plugin))
+}
+
+/// Resolve `path` against `base` unless it is absolute.
+pub fn resolve_relative(base: &Path, path: &Path) -> PathBuf {
     if path.is_absolute() {
         path.to_path_buf()
     } else {
@@ -259,8 +1597,48 @@ fn resolve_relative(base: &Path, path: &Path) -> PathBuf {
     }
 }
 
-fn map_message_level(level: MessageLevel) -> tower_lsp::lsp_types::MessageType {
-    use tower_lsp::lsp_types::MessageType;
+/// Whether the host variable `key` is passed to a plugin restricted to the
+/// `allowed` variables.
+fn inherits(allowed: &[String], key: &OsStr) -> bool {
+    let Some(key) = key.to_str() else {
+        return false;
+    };
+    allowed
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => key.starts_with(prefix),
+            None => key == pattern,
+        })
+}
+
+/// Socket of a `connect` plugin. Relative Unix socket paths are resolved
+/// against `base`.
+pub fn endpoint(base: &Path, address: &str) -> Endpoint {
+    match address.parse().unwrap_or_else(|never| match never {}) {
+        Endpoint::Unix(path) => Endpoint::Unix(resolve_relative(base, &path)),
+        endpoint => endpoint,
+    }
+}
+
+fn log_event(plugin: &str, level: MessageLevel, message: &str) {
+    log_plugin(plugin, log_level(level), message);
+}
+
+fn log_level(level: MessageLevel) -> log::Level {
+    match level {
+        MessageLevel::Error => log::Level::Error,
+        MessageLevel::Warning => log::Level::Warn,
+        MessageLevel::Info => log::Level::Info,
+        MessageLevel::Log => log::Level::Debug,
+    }
+}
+
+/// Log a message of `plugin`, tagged with its name.
+fn log_plugin(plugin: &str, level: log::Level, message: &str) {
+    log::log!(level, "[{plugin}] {message}");
+}
+
+fn map_message_level(level: MessageLevel) -> MessageType {
     match level {
         MessageLevel::Error => MessageType::ERROR,
         MessageLevel::Warning => MessageType::WARNING,
@@ -268,3 +1646,202 @@ fn map_message_level(level: MessageLevel) -> tower_lsp::lsp_types::MessageType {
         MessageLevel::Log => MessageType::LOG,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{DuplexStream, Lines};
+
+    /// Plugin end of a process connected over in-memory pipes.
+    struct FakePlugin {
+        requests: Lines<BufReader<DuplexStream>>,
+        responses: DuplexStream,
+    }
+
+    impl FakePlugin {
+        async fn next_request(&mut self) -> HostRequest {
+            let line = tokio::time::timeout(Duration::from_secs(5), self.requests.next_line())
+                .await
+                .expect("no request received")
+                .unwrap()
+                .expect("host closed the pipe");
+            serde_json::from_str(&line).unwrap()
+        }
+
+        async fn assert_idle(&mut self) {
+            let next = tokio::time::timeout(Duration::from_millis(100), self.requests.next_line());
+            assert!(next.await.is_err(), "unexpected request");
+        }
+
+        async fn respond(&mut self, id: u64, result: PluginResponse) {
+            let message = PluginMessage::Response { id, result };
+            let mut line = serde_json::to_vec(&message).unwrap();
+            line.push(b'\n');
+            self.responses.write_all(&line).await.unwrap();
+        }
+    }
+
+    fn connect(entry: &str) -> (PluginProcess, FakePlugin) {
+        let entry: PluginEntry = toml::from_str(entry).unwrap();
+        let (host_writer, plugin_reader) = tokio::io::duplex(CONNECTION_PIPE_CAPACITY);
+        let (plugin_writer, host_reader) = tokio::io::duplex(CONNECTION_PIPE_CAPACITY);
+        let (exits, _) = mpsc::unbounded_channel();
+        let handles = HostHandles {
+            client: None,
+            bus: crate::bus::channel().0,
+            exits,
+            tracer: None,
+            store: SharedStore::default(),
+            secrets: SecretStore::default(),
+            output: OutputChannels::default(),
+            diagnostics: PluginDiagnostics::default(),
+        };
+        let relay = tokio::spawn(std::future::pending());
+        let process = PluginProcess::start(
+            &entry,
+            "fake".to_string(),
+            None,
+            Box::new(host_writer),
+            host_reader,
+            Runtime::Connection(relay),
+            handles,
+        );
+        let plugin = FakePlugin {
+            requests: BufReader::new(plugin_reader).lines(),
+            responses: plugin_writer,
+        };
+        (process, plugin)
+    }
+
+    fn execute(argument: u64) -> HostRequestPayload {
+        HostRequestPayload::Execute {
+            command: "test.run".to_string(),
+            arguments: vec![Value::from(argument)],
+            language_id: None,
+        }
+    }
+
+    fn send(
+        process: &PluginProcess,
+        payload: HostRequestPayload,
+    ) -> JoinHandle<Result<PluginResponse>> {
+        let process = process.clone();
+        tokio::spawn(async move { process.send_request_with_timeout(payload, None).await })
+    }
+
+    fn result(value: impl Into<Value>) -> PluginResponse {
+        PluginResponse::CommandResult {
+            result: Some(value.into()),
+            cache_ttl_ms: None,
+        }
+    }
+
+    fn command_result(response: Result<PluginResponse>) -> Value {
+        match response.unwrap() {
+            PluginResponse::CommandResult { result, .. } => result.unwrap(),
+            other => panic!("unexpected response {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn identical_executes_share_one_request() {
+        let (process, mut plugin) = connect("name = \"test-dedup\"\ncommand = \"fake\"");
+
+        let first = send(&process, execute(1));
+        let second = send(&process, execute(1));
+        let request = plugin.next_request().await;
+        plugin.assert_idle().await;
+
+        // Requests differing in their arguments are sent on their own.
+        let other = send(&process, execute(2));
+        let other_request = plugin.next_request().await;
+        plugin.respond(other_request.id, result("two")).await;
+        plugin.respond(request.id, result("one")).await;
+
+        assert_eq!(command_result(first.await.unwrap()), "one");
+        assert_eq!(command_result(second.await.unwrap()), "one");
+        assert_eq!(command_result(other.await.unwrap()), "two");
+
+        // Once answered, the same request is sent again.
+        let again = send(&process, execute(1));
+        let request = plugin.next_request().await;
+        plugin.respond(request.id, result("again")).await;
+        assert_eq!(command_result(again.await.unwrap()), "again");
+    }
+
+    #[tokio::test]
+    async fn concurrency_limit_queues_requests_in_order() {
+        let (process, mut plugin) =
+            connect("name = \"test-queue\"\ncommand = \"fake\"\nmax_concurrent_requests = 1");
+
+        let mut sent = Vec::new();
+        for argument in 1..=3 {
+            sent.push(send(&process, execute(argument)));
+            // Let the request queue up before sending the next one.
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Pings are not limited.
+        let ping = send(&process, HostRequestPayload::Ping);
+        let request = plugin.next_request().await;
+        let ping_request = plugin.next_request().await;
+        assert!(matches!(ping_request.payload, HostRequestPayload::Ping));
+        plugin
+            .respond(ping_request.id, PluginResponse::Acknowledge)
+            .await;
+        assert!(matches!(
+            ping.await.unwrap().unwrap(),
+            PluginResponse::Acknowledge
+        ));
+
+        let mut request = request;
+        for argument in 1..=3u64 {
+            assert!(matches!(
+                &request.payload,
+                HostRequestPayload::Execute { arguments, .. } if arguments == &[Value::from(argument)]
+            ));
+            plugin.assert_idle().await;
+            plugin.respond(request.id, result(argument)).await;
+            if argument < 3 {
+                request = plugin.next_request().await;
+            }
+        }
+        for (argument, sent) in (1..=3u64).zip(sent) {
+            assert_eq!(command_result(sent.await.unwrap()), argument);
+        }
+    }
+
+    #[test]
+    fn secret_set_requests_are_redacted() {
+        let line = r#"{"type":"request","id":3,"request":{"type":"secret_set","key":"token","secret":"ghp_123"}}"#;
+        let redacted = redact_secret_set(line);
+        assert!(!redacted.contains("ghp_123"));
+        assert!(redacted.contains(r#""secret":"<redacted>""#));
+        assert!(redacted.contains(r#""key":"token""#));
+
+        assert_eq!(
+            redact_secret_set(r#"junk {"secret_set":"ghp_123""#),
+            REDACTED
+        );
+        let other = r#"{"type":"request","id":4,"request":{"type":"store_get","key":"a"}}"#;
+        assert!(matches!(redact_secret_set(other), Cow::Borrowed(line) if line == other));
+    }
+
+    #[test]
+    fn secret_replies_are_redacted() {
+        let reply = |value: Value| HostRequest {
+            id: 1,
+            payload: HostRequestPayload::Reply {
+                request: 3,
+                result: HostReply::Success { value },
+            },
+        };
+        let logged = serde_json::to_string(&redact_reply(&reply(Value::from("ghp_123")))).unwrap();
+        assert!(!logged.contains("ghp_123"));
+        assert!(logged.contains(REDACTED));
+
+        // Missing secrets stay recognizable.
+        let logged = serde_json::to_string(&redact_reply(&reply(Value::Null))).unwrap();
+        assert!(logged.contains(r#""value":null"#));
+    }
+}
@@ -0,0 +1,121 @@
+//! Startup profiling, enabled by `--profile-startup`.
+//!
+//! Measures how long the manifest takes to load and every plugin takes to
+//! spawn and answer the handshake, to find the plugin delaying the editor
+//! when it attaches.
+
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::{fs, path::Path, sync::Arc, time::Duration};
+
+/// Timings collected while the host starts. Cloning shares the timings.
+#[derive(Debug, Clone, Default)]
+pub struct StartupProfile(Arc<Mutex<Timings>>);
+
+#[derive(Debug, Default)]
+struct Timings {
+    manifest_load: Duration,
+    plugins: Vec<PluginTiming>,
+}
+
+#[derive(Debug)]
+struct PluginTiming {
+    name: String,
+    spawn: Duration,
+    /// Unset when the plugin failed to spawn.
+    handshake: Option<Duration>,
+    error: Option<String>,
+}
+
+impl PluginTiming {
+    fn total(&self) -> Duration {
+        self.spawn + self.handshake.unwrap_or_default()
+    }
+}
+
+impl StartupProfile {
+    pub fn record_manifest_load(&self, elapsed: Duration) {
+        self.0.lock().manifest_load = elapsed;
+    }
+
+    /// Record the launch of `plugin`, which failed with `error` if set.
+    pub fn record_plugin(
+        &self,
+        plugin: &str,
+        spawn: Duration,
+        handshake: Option<Duration>,
+        error: Option<&anyhow::Error>,
+    ) {
+        self.0.lock().plugins.push(PluginTiming {
+            name: plugin.to_string(),
+            spawn,
+            handshake,
+            error: error.map(|err| format!("{err:#}")),
+        });
+    }
+
+    /// Log the report, slowest plugin first, and write it to `json` if set.
+    pub fn finish(&self, total: Duration, json: Option<&Path>) {
+        let mut timings = self.0.lock();
+        timings
+            .plugins
+            .sort_by_key(|plugin| std::cmp::Reverse(plugin.total()));
+
+        log::info!(
+            "startup took {:.1}ms; manifest loaded in {:.1}ms",
+            millis(total),
+            millis(timings.manifest_load)
+        );
+        for plugin in &timings.plugins {
+            let name = &plugin.name;
+            let spawn = millis(plugin.spawn);
+            match (plugin.handshake, &plugin.error) {
+                (Some(handshake), None) => log::info!(
+                    "plugin `{name}` started in {:.1}ms: spawn {spawn:.1}ms, handshake {:.1}ms",
+                    millis(plugin.total()),
+                    millis(handshake)
+                ),
+                (_, error) => log::info!(
+                    "plugin `{name}` failed after {:.1}ms: {}",
+                    millis(plugin.total()),
+                    error.as_deref().unwrap_or("unknown error")
+                ),
+            }
+        }
+
+        if let Some(path) = json {
+            if let Err(err) = write_report(path, total, &timings) {
+                log::warn!("{err:#}");
+            }
+        }
+    }
+}
+
+fn write_report(path: &Path, total: Duration, timings: &Timings) -> Result<()> {
+    let plugins: Vec<Value> = timings
+        .plugins
+        .iter()
+        .map(|plugin| {
+            json!({
+                "name": plugin.name,
+                "total_ms": millis(plugin.total()),
+                "spawn_ms": millis(plugin.spawn),
+                "handshake_ms": plugin.handshake.map(millis),
+                "error": plugin.error,
+            })
+        })
+        .collect();
+    let report = json!({
+        "total_ms": millis(total),
+        "manifest_load_ms": millis(timings.manifest_load),
+        "plugins": plugins,
+    });
+    let contents = serde_json::to_string_pretty(&report)?;
+    fs::write(path, contents)
+        .with_context(|| format!("failed to write startup profile `{}`", path.display()))
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,84 @@
+//! Index of installable plugins.
+//!
+//! The index is a JSON or TOML document listing plugins under a `plugins`
+//! key, served over HTTP(S) or read from a local file. TOML is assumed when
+//! the location ends in `.toml`.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Plugin listed in the index.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryPlugin {
+    /// Name the plugin is installed under.
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Install source: a git URL, crate name or path.
+    pub source: String,
+    /// Latest published version.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Binary to install when the package provides several.
+    #[serde(default)]
+    pub bin: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Index {
+    #[serde(default)]
+    plugins: Vec<RegistryPlugin>,
+}
+
+/// Download and parse the index at `location`.
+pub async fn fetch(location: &str) -> Result<Vec<RegistryPlugin>> {
+    let contents = if location.starts_with("http://") || location.starts_with("https://") {
+        reqwest::get(location)
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .with_context(|| format!("failed to fetch plugin index `{location}`"))?
+            .text()
+            .await
+            .with_context(|| format!("failed to read plugin index `{location}`"))?
+    } else {
+        let path = location.strip_prefix("file://").unwrap_or(location);
+        tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("failed to read plugin index `{path}`"))?
+    };
+
+    let index: Index = if location.ends_with(".toml") {
+        toml::from_str(&contents).with_context(|| format!("invalid plugin index `{location}`"))?
+    } else {
+        serde_json::from_str(&contents)
+            .with_context(|| format!("invalid plugin index `{location}`"))?
+    };
+    Ok(index.plugins)
+}
+
+/// Fetch the index at `location` if one is configured. Failures are logged
+/// and yield an empty index.
+pub async fn fetch_optional(location: Option<&str>) -> Vec<RegistryPlugin> {
+    let Some(location) = location else {
+        return Vec::new();
+    };
+    fetch(location).await.unwrap_or_else(|err| {
+        log::warn!("{err:#}");
+        Vec::new()
+    })
+}
+
+/// Plugins whose name or description contains `term`, ignoring case.
+pub fn search<'a>(
+    plugins: &'a [RegistryPlugin],
+    term: &str,
+) -> impl Iterator<Item = &'a RegistryPlugin> {
+    let term = term.to_lowercase();
+    plugins.iter().filter(move |plugin| {
+        plugin.name.to_lowercase().contains(&term)
+            || plugin
+                .description
+                .as_ref()
+                .is_some_and(|description| description.to_lowercase().contains(&term))
+    })
+}
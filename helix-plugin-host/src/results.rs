@@ -0,0 +1,134 @@
+//! Command results plugins allowed the host to reuse.
+//!
+//! Plugins may tag a command result with `cache_ttl_ms`. Identical requests,
+//! with the same command, arguments and document language, are then
+//! answered from the cache until the result expires, sparing pickers that
+//! refresh constantly a round-trip to the plugin.
+
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Number of cached results above which expired ones are evicted.
+const PRUNE_THRESHOLD: usize = 256;
+
+/// Identity of a command request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestKey {
+    plugin: String,
+    command: String,
+    arguments: String,
+    language_id: Option<String>,
+}
+
+impl RequestKey {
+    /// Key of a request to `command` of `plugin`.
+    pub fn new(
+        plugin: &str,
+        command: &str,
+        arguments: &[Value],
+        language_id: Option<&str>,
+    ) -> Self {
+        Self {
+            plugin: plugin.to_string(),
+            command: command.to_string(),
+            arguments: Value::from(arguments).to_string(),
+            language_id: language_id.map(str::to_string),
+        }
+    }
+}
+
+/// Cached results with their expiry.
+#[derive(Debug, Default)]
+pub struct ResultCache {
+    results: HashMap<RequestKey, (Option<Value>, Instant)>,
+}
+
+impl ResultCache {
+    /// Result cached for `key`, unless it expired.
+    pub fn get(&self, key: &RequestKey) -> Option<Option<Value>> {
+        let (result, expires) = self.results.get(key)?;
+        (Instant::now() < *expires).then(|| result.clone())
+    }
+
+    /// Cache `result` for `ttl`.
+    pub fn insert(&mut self, key: RequestKey, result: Option<Value>, ttl: Duration) {
+        let now = Instant::now();
+        if self.results.len() >= PRUNE_THRESHOLD {
+            self.results.retain(|_, (_, expires)| now < *expires);
+        }
+        self.results.insert(key, (result, now + ttl));
+    }
+
+    /// Forget the results of `plugin`, e.g. because it was restarted.
+    pub fn remove_plugin(&mut self, plugin: &str) {
+        self.results.retain(|key, _| key.plugin != plugin);
+    }
+
+    /// Drop every cached result.
+    pub fn clear(&mut self) {
+        self.results.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key(plugin: &str, arguments: &[Value]) -> RequestKey {
+        RequestKey::new(plugin, "helix.task.list", arguments, Some("rust"))
+    }
+
+    #[test]
+    fn answers_identical_requests_until_expiry() {
+        let mut cache = ResultCache::default();
+        cache.insert(
+            key("tasks", &[json!({"all": true})]),
+            Some(json!(["build"])),
+            Duration::from_secs(60),
+        );
+        assert_eq!(
+            cache.get(&key("tasks", &[json!({"all": true})])),
+            Some(Some(json!(["build"])))
+        );
+        assert_eq!(cache.get(&key("tasks", &[json!({"all": false})])), None);
+        assert_eq!(
+            cache.get(&RequestKey::new(
+                "tasks",
+                "helix.task.list",
+                &[json!({"all": true})],
+                None
+            )),
+            None
+        );
+
+        cache.insert(key("tasks", &[]), None, Duration::ZERO);
+        assert_eq!(cache.get(&key("tasks", &[])), None);
+    }
+
+    #[test]
+    fn prunes_expired_results_once_full() {
+        let mut cache = ResultCache::default();
+        for n in 0..PRUNE_THRESHOLD {
+            cache.insert(key("tasks", &[json!(n)]), None, Duration::ZERO);
+        }
+        assert_eq!(cache.results.len(), PRUNE_THRESHOLD);
+        cache.insert(key("git", &[]), None, Duration::from_secs(60));
+        assert_eq!(cache.results.len(), 1);
+        assert_eq!(cache.get(&key("git", &[])), Some(None));
+    }
+
+    #[test]
+    fn forgets_the_results_of_a_plugin() {
+        let mut cache = ResultCache::default();
+        let ttl = Duration::from_secs(60);
+        cache.insert(key("tasks", &[]), Some(json!(1)), ttl);
+        cache.insert(key("git", &[]), Some(json!(2)), ttl);
+        cache.remove_plugin("tasks");
+        assert_eq!(cache.get(&key("tasks", &[])), None);
+        assert_eq!(cache.get(&key("git", &[])), Some(Some(json!(2))));
+    }
+}
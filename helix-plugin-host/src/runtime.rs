@@ -0,0 +1,149 @@
+//! Interpreters of script plugins.
+//!
+//! Entries setting `runtime` name a script as their `command`. The host
+//! locates the interpreter and runs the script with it, so script plugins
+//! need neither a shebang nor a wrapper, which Windows does not support.
+
+use crate::{
+    manifest::{PluginEntry, PluginKind},
+    plugin::resolve_relative,
+};
+use anyhow::{Context, Result};
+use helix_stdx::path::expand_tilde;
+use serde::Deserialize;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Interpreter running a script plugin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptRuntime {
+    Node,
+    Python,
+    Deno,
+}
+
+impl ScriptRuntime {
+    /// Executable names of the interpreter, in order of preference.
+    fn executables(self) -> &'static [&'static str] {
+        match self {
+            Self::Node => &["node"],
+            // `python3` is a store installer stub on Windows.
+            Self::Python if cfg!(windows) => &["python", "py"],
+            Self::Python => &["python3", "python"],
+            Self::Deno => &["deno"],
+        }
+    }
+
+    /// Arguments passed to the interpreter before the script.
+    fn script_args(self) -> &'static [&'static str] {
+        match self {
+            // Deno cannot prompt for permissions without a terminal. Native
+            // plugins are not restricted either; use `sandbox` to confine.
+            Self::Deno => &["run", "--allow-all"],
+            Self::Node | Self::Python => &[],
+        }
+    }
+}
+
+impl fmt::Display for ScriptRuntime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Node => "node",
+            Self::Python => "python",
+            Self::Deno => "deno",
+        })
+    }
+}
+
+/// Locate the interpreter of `runtime`. A `configured` path or name is used
+/// as is; otherwise `PATH` is searched, then the shims of the mise and asdf
+/// version managers.
+pub fn locate(runtime: ScriptRuntime, configured: Option<&Path>) -> Result<PathBuf> {
+    if let Some(configured) = configured {
+        let configured = expand_tilde(configured);
+        return helix_stdx::env::which(configured.as_os_str()).with_context(|| {
+            format!(
+                "`{runtime}` interpreter `{}` was not found",
+                configured.display()
+            )
+        });
+    }
+
+    let executables = runtime.executables();
+    if let Some(path) = executables
+        .iter()
+        .find_map(|name| helix_stdx::env::which(name).ok())
+    {
+        return Ok(path);
+    }
+    let cwd = helix_stdx::env::current_working_dir();
+    let shims = std::env::join_paths(shim_dirs()).ok();
+    executables
+        .iter()
+        .find_map(|name| which::which_in(name, shims.as_ref(), &cwd).ok())
+        .with_context(|| {
+            format!(
+                "no `{runtime}` interpreter was found on `PATH` or among mise and asdf shims; \
+                 set `runtimes.{runtime}` in the manifest"
+            )
+        })
+}
+
+/// Shim directories of the mise and asdf version managers.
+fn shim_dirs() -> Vec<PathBuf> {
+    let env_dir = |var| std::env::var_os(var).map(PathBuf::from);
+    let mise = env_dir("MISE_DATA_DIR").or_else(|| {
+        let data_dir = if cfg!(windows) {
+            env_dir("LOCALAPPDATA")?
+        } else {
+            env_dir("XDG_DATA_HOME")
+                .unwrap_or_else(|| expand_tilde(Path::new("~/.local/share")).into_owned())
+        };
+        Some(data_dir.join("mise"))
+    });
+    let asdf =
+        env_dir("ASDF_DATA_DIR").unwrap_or_else(|| expand_tilde(Path::new("~/.asdf")).into_owned());
+    mise.into_iter()
+        .chain([asdf])
+        .map(|dir| dir.join("shims"))
+        .collect()
+}
+
+/// Return a copy of `entry` running its script through the interpreter of
+/// its `runtime`. Scripts are relative to `base_dir`. `ssh` entries leave
+/// the lookup to the remote `PATH`.
+pub fn apply(entry: &PluginEntry, base_dir: &Path) -> Result<PluginEntry> {
+    let Some(runtime) = entry.runtime else {
+        return Ok(entry.clone());
+    };
+
+    let (interpreter, script) = if entry.kind == PluginKind::Ssh {
+        let interpreter = match &entry.interpreter {
+            Some(path) => path.to_string_lossy().into_owned(),
+            None => runtime.executables()[0].to_string(),
+        };
+        (interpreter, entry.command.clone())
+    } else {
+        let interpreter = locate(runtime, entry.interpreter.as_deref())
+            .with_context(|| format!("failed to start plugin `{}`", entry.name))?;
+        let script = resolve_relative(base_dir, Path::new(&entry.command));
+        (
+            interpreter.to_string_lossy().into_owned(),
+            script.to_string_lossy().into_owned(),
+        )
+    };
+
+    let mut resolved = entry.clone();
+    resolved.command = interpreter;
+    resolved.args = runtime
+        .script_args()
+        .iter()
+        .map(ToString::to_string)
+        .chain([script])
+        .chain(entry.args.iter().cloned())
+        .collect();
+    Ok(resolved)
+}
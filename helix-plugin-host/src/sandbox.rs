@@ -0,0 +1,181 @@
+//! Opt-in sandboxing for native plugin processes.
+//!
+//! The sandbox restricts file system and network access only. Linux
+//! processes are confined with Landlock, which needs kernel 6.7 or newer:
+//! older kernels cannot enforce every restriction, and sandboxed plugins fail
+//! to start there. macOS processes run under `sandbox-exec` with a generated
+//! profile. System calls are not filtered with seccomp, and Windows has no
+//! sandbox based on restricted tokens; like every other platform it refuses
+//! to start sandboxed plugins rather than silently running them unconfined.
+
+use crate::manifest::SandboxConfig;
+use anyhow::Result;
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+use tokio::process::Command;
+
+/// Locations a sandboxed plugin may access.
+#[derive(Debug)]
+pub struct SandboxPaths {
+    /// Paths readable by the plugin. Empty when reads are unrestricted.
+    pub read: Vec<PathBuf>,
+    /// Paths readable and writable by the plugin.
+    pub write: Vec<PathBuf>,
+    /// Whether outgoing and incoming network connections are allowed.
+    pub network: bool,
+}
+
+impl SandboxPaths {
+    /// Collect the paths granted by `config`, in addition to the workspace
+    /// root, the data directory of the plugin, the plugin executable and the
+    /// system locations every process needs to run.
+    pub fn new(
+        config: &SandboxConfig,
+        program: &Path,
+        workspace_root: Option<&Path>,
+        data_dir: &Path,
+    ) -> Self {
+        let mut write: Vec<PathBuf> = config.write.clone();
+        write.extend(workspace_root.map(Path::to_path_buf));
+        write.push(data_dir.to_path_buf());
+        write.push(std::env::temp_dir());
+        write.push(PathBuf::from("/dev/null"));
+
+        let read = if config.read.is_empty() {
+            Vec::new()
+        } else {
+            let mut read = config.read.clone();
+            read.extend(program.parent().map(Path::to_path_buf));
+            read.extend(SYSTEM_READ_PATHS.iter().map(PathBuf::from));
+            read
+        };
+
+        Self {
+            read,
+            write,
+            network: config.network,
+        }
+    }
+}
+
+/// System locations readable by every sandboxed plugin when reads are
+/// restricted.
+#[cfg(not(target_os = "macos"))]
+const SYSTEM_READ_PATHS: &[&str] = &["/bin", "/dev", "/etc", "/lib", "/lib64", "/proc", "/usr"];
+
+#[cfg(target_os = "macos")]
+const SYSTEM_READ_PATHS: &[&str] = &["/bin", "/dev", "/Library", "/System", "/private", "/usr"];
+
+/// Build the command running `program` inside the sandbox described by
+/// `paths`. Arguments, environment and working directory can be added to the
+/// returned command as usual.
+#[cfg(target_os = "linux")]
+pub fn command(program: &OsStr, paths: &SandboxPaths) -> Result<Command> {
+    use anyhow::Context;
+    use landlock::{
+        path_beneath_rules, Access, AccessFs, AccessNet, CompatLevel, Compatible, Ruleset,
+        RulesetAttr, RulesetCreatedAttr, RulesetStatus, ABI,
+    };
+
+    let abi = ABI::V4;
+    let handled = if paths.read.is_empty() {
+        AccessFs::from_write(abi)
+    } else {
+        AccessFs::from_all(abi)
+    };
+
+    // Kernels lacking part of the requested access control fail here rather
+    // than leaving the plugin partly unconfined.
+    let unsupported = "this kernel cannot enforce the plugin sandbox (Landlock ABI 4 required)";
+    let mut ruleset = Ruleset::default()
+        .set_compatibility(CompatLevel::HardRequirement)
+        .handle_access(handled)
+        .context(unsupported)?;
+    if !paths.network {
+        ruleset = ruleset
+            .handle_access(AccessNet::from_all(abi))
+            .context(unsupported)?;
+    }
+    let ruleset = ruleset
+        .create()
+        .context(unsupported)?
+        .add_rules(path_beneath_rules(
+            &paths.read,
+            AccessFs::from_read(abi) & handled,
+        ))?
+        .add_rules(path_beneath_rules(&paths.write, handled))
+        .context("failed to build sandbox ruleset")?;
+
+    // The ruleset is prepared before forking so that the child only has to
+    // issue the restriction syscalls.
+    let mut ruleset = Some(ruleset);
+    let mut command = Command::new(program);
+    // SAFETY: `restrict_self` only performs `prctl` and Landlock syscalls on
+    // file descriptors opened in the parent, which is async-signal-safe.
+    unsafe {
+        command.pre_exec(move || {
+            let Some(ruleset) = ruleset.take() else {
+                return Ok(());
+            };
+            let status = ruleset.restrict_self().map_err(std::io::Error::other)?;
+            if status.ruleset != RulesetStatus::FullyEnforced {
+                return Err(std::io::Error::other(
+                    "plugin sandbox could not be fully enforced",
+                ));
+            }
+            Ok(())
+        });
+    }
+    Ok(command)
+}
+
+/// Build the command running `program` inside the sandbox described by
+/// `paths`. Arguments, environment and working directory can be added to the
+/// returned command as usual.
+#[cfg(target_os = "macos")]
+pub fn command(program: &OsStr, paths: &SandboxPaths) -> Result<Command> {
+    let mut command = Command::new("/usr/bin/sandbox-exec");
+    command.arg("-p").arg(profile(paths)).arg(program);
+    Ok(command)
+}
+
+/// Generate a `sandbox-exec` profile granting `paths`.
+#[cfg(target_os = "macos")]
+fn profile(paths: &SandboxPaths) -> String {
+    fn subpaths(paths: &[PathBuf]) -> String {
+        paths
+            .iter()
+            .map(|path| {
+                let path = path
+                    .to_string_lossy()
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"");
+                format!(" (subpath \"{path}\")")
+            })
+            .collect()
+    }
+
+    let mut profile = String::from("(version 1)\n(allow default)\n");
+    if !paths.read.is_empty() {
+        profile.push_str("(deny file-read*)\n");
+        profile.push_str(&format!("(allow file-read*{})\n", subpaths(&paths.read)));
+    }
+    profile.push_str("(deny file-write*)\n");
+    profile.push_str(&format!(
+        "(allow file-read* file-write*{})\n",
+        subpaths(&paths.write)
+    ));
+    if !paths.network {
+        profile.push_str("(deny network*)\n");
+    }
+    profile
+}
+
+/// Build the command running `program` inside the sandbox described by
+/// `paths`. Sandboxing is not available on this platform.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn command(_program: &OsStr, _paths: &SandboxPaths) -> Result<Command> {
+    anyhow::bail!("plugin sandboxing is not supported on this platform")
+}
@@ -0,0 +1,159 @@
+//! Skeletons for new plugins.
+//!
+//! Rust plugins are generated as Cargo projects using the SDK; Node and
+//! Python plugins as a single script implementing the protocol by hand.
+
+use anyhow::{Context, Result};
+use helix_plugin_sdk::protocol;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Language of a generated plugin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Language {
+    Rust,
+    Node,
+    Python,
+}
+
+/// Generated plugin project.
+#[derive(Debug)]
+pub struct Scaffold {
+    /// Files that were written.
+    pub files: Vec<PathBuf>,
+    /// `[[plugins]]` entry running the plugin, ready to paste into a manifest.
+    pub manifest_entry: String,
+    /// Steps left before the plugin can be started.
+    pub next_steps: Option<String>,
+}
+
+/// Generate a plugin called `name` in `dir`, which must not exist or be
+/// empty. Rust projects depend on the SDK at `sdk` when given and on the
+/// SDK from the Helix repository otherwise.
+pub fn generate(
+    name: &str,
+    language: Language,
+    dir: &Path,
+    sdk: Option<&Path>,
+) -> Result<Scaffold> {
+    validate_name(name)?;
+    if fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some()) {
+        anyhow::bail!("`{}` already exists and is not empty", dir.display());
+    }
+    let dir = std::path::absolute(dir)
+        .with_context(|| format!("invalid plugin directory `{}`", dir.display()))?;
+
+    let render = |template: &str| {
+        template
+            .replace("{{name}}", name)
+            .replace("{{type_name}}", &type_name(name))
+            .replace("{{command}}", &format!("{name}.hello"))
+            .replace("{{protocol_version}}", &protocol::VERSION.to_string())
+    };
+
+    let (files, command, args, next_steps) = match language {
+        Language::Rust => {
+            let sdk = match sdk {
+                Some(path) => {
+                    let path = std::path::absolute(path)
+                        .with_context(|| format!("invalid SDK path `{}`", path.display()))?;
+                    format!("{{ path = {} }}", toml_string(&path.to_string_lossy()))
+                }
+                None => format!("{{ git = {} }}", toml_string(env!("CARGO_PKG_REPOSITORY"))),
+            };
+            let files = vec![
+                (
+                    dir.join("Cargo.toml"),
+                    render(include_str!("../templates/Cargo.toml.tmpl")).replace("{{sdk}}", &sdk),
+                ),
+                (
+                    dir.join("src/main.rs"),
+                    render(include_str!("../templates/main.rs.tmpl")),
+                ),
+            ];
+            let binary = dir.join("target/release").join(name);
+            (
+                files,
+                binary.to_string_lossy().into_owned(),
+                Vec::new(),
+                Some(format!("cd {} && cargo build --release", dir.display())),
+            )
+        }
+        Language::Node => {
+            let script = dir.join(format!("{name}.js"));
+            let files = vec![(
+                script.clone(),
+                render(include_str!("../templates/plugin.js.tmpl")),
+            )];
+            (files, "node".to_string(), vec![script], None)
+        }
+        Language::Python => {
+            let script = dir.join(format!("{name}.py"));
+            let files = vec![(
+                script.clone(),
+                render(include_str!("../templates/plugin.py.tmpl")),
+            )];
+            (files, "python3".to_string(), vec![script], None)
+        }
+    };
+
+    for (path, contents) in &files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create `{}`", parent.display()))?;
+        }
+        fs::write(path, contents)
+            .with_context(|| format!("failed to write `{}`", path.display()))?;
+    }
+
+    let mut manifest_entry = format!(
+        "[[plugins]]\nname = {}\ncommand = {}\n",
+        toml_string(name),
+        toml_string(&command)
+    );
+    if !args.is_empty() {
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| toml_string(&arg.to_string_lossy()))
+            .collect();
+        manifest_entry.push_str(&format!("args = [{}]\n", args.join(", ")));
+    }
+
+    Ok(Scaffold {
+        files: files.into_iter().map(|(path, _)| path).collect(),
+        manifest_entry,
+        next_steps,
+    })
+}
+
+/// Plugin names double as Cargo package names and command prefixes.
+fn validate_name(name: &str) -> Result<()> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    anyhow::ensure!(
+        valid,
+        "invalid plugin name `{name}`; use ASCII letters, digits, `-` and `_`, starting with a letter"
+    );
+    Ok(())
+}
+
+/// `my-plugin` -> `MyPlugin`.
+fn type_name(name: &str) -> String {
+    name.split(['-', '_'])
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn toml_string(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
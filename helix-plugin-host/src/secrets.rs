@@ -0,0 +1,272 @@
+//! Secrets plugins keep through the host, e.g. GitHub or Jira tokens.
+//!
+//! Secrets live in the keyring of the operating system: the Secret Service
+//! on Linux, the Keychain on macOS and the Credential Manager on Windows.
+//! Where no keyring is reachable, e.g. on headless machines, they are
+//! written to a file encrypted with a key kept beside it in the data
+//! directory, both readable by the user only. Either way tokens never sit in plaintext environment
+//! variables or configuration files.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Service secrets are filed under in the keyring.
+const KEYRING_SERVICE: &str = "helix-plugin-host";
+
+/// Encrypted file holding the secrets when no keyring is available.
+const SECRETS_FILE: &str = "plugin-secrets.enc";
+
+/// Key of [`SECRETS_FILE`].
+const KEY_FILE: &str = "plugin-secrets.key";
+
+const NONCE_LEN: usize = 12;
+
+/// Secrets of every plugin, namespaced by plugin name. Cloning shares the
+/// store.
+#[derive(Clone)]
+pub struct SecretStore(Arc<Backend>);
+
+struct Backend {
+    /// Directory of the encrypted fallback file.
+    dir: PathBuf,
+    /// Set once the keyring failed; the encrypted file is used from then on.
+    keyring_unavailable: AtomicBool,
+    /// Serializes updates of the encrypted file.
+    file: Mutex<()>,
+}
+
+impl Default for SecretStore {
+    fn default() -> Self {
+        Self(Arc::new(Backend {
+            dir: secrets_dir(),
+            keyring_unavailable: AtomicBool::new(false),
+            file: Mutex::new(()),
+        }))
+    }
+}
+
+impl SecretStore {
+    /// Secret `key` of the plugin owning `namespace`.
+    pub async fn get(&self, namespace: &str, key: &str) -> Result<Option<String>> {
+        let account = account(namespace, key);
+        self.blocking(move |backend| backend.get(&account)).await
+    }
+
+    /// Store `secret` as `key` of the plugin owning `namespace`.
+    pub async fn set(&self, namespace: &str, key: &str, secret: String) -> Result<()> {
+        let account = account(namespace, key);
+        self.blocking(move |backend| backend.set(&account, secret))
+            .await
+    }
+
+    /// Remove secret `key` of the plugin owning `namespace`.
+    pub async fn delete(&self, namespace: &str, key: &str) -> Result<()> {
+        let account = account(namespace, key);
+        self.blocking(move |backend| backend.delete(&account)).await
+    }
+
+    /// Run `f` on a blocking thread. Keyring calls block, and deadlock when
+    /// made from the runtime threads.
+    async fn blocking<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Backend) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let backend = Arc::clone(&self.0);
+        tokio::task::spawn_blocking(move || f(&backend))
+            .await
+            .context("secret store task panicked")?
+    }
+}
+
+/// Directory of the encrypted fallback file. It used to be the cache
+/// directory, which users clear and backups skip; files left there are
+/// moved over.
+fn secrets_dir() -> PathBuf {
+    let dir = helix_loader::data_dir();
+    let legacy = helix_loader::cache_dir();
+    if legacy.join(KEY_FILE).exists() && !dir.join(KEY_FILE).exists() {
+        if let Err(err) = move_secrets(&legacy, &dir) {
+            log::warn!(
+                "failed to move plugin secrets to `{}`: {err:#}",
+                dir.display()
+            );
+            return legacy;
+        }
+    }
+    dir
+}
+
+/// Move the encrypted file and its key from `from` to `to`. The key goes
+/// last, so an interrupted move is retried.
+fn move_secrets(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to).with_context(|| format!("failed to create `{}`", to.display()))?;
+    for file in [SECRETS_FILE, KEY_FILE] {
+        let source = from.join(file);
+        if source.exists() {
+            // Copying keeps the permissions and works across file systems.
+            fs::copy(&source, to.join(file))
+                .with_context(|| format!("failed to copy `{}`", source.display()))?;
+        }
+    }
+    for file in [SECRETS_FILE, KEY_FILE] {
+        let _ = fs::remove_file(from.join(file));
+    }
+    Ok(())
+}
+
+/// Keyring account of secret `key` of `namespace`.
+fn account(namespace: &str, key: &str) -> String {
+    format!("{namespace}/{key}")
+}
+
+impl Backend {
+    fn get(&self, account: &str) -> Result<Option<String>> {
+        if let Some(entry) = self.keyring_entry(account) {
+            match entry.get_password() {
+                Ok(secret) => return Ok(Some(secret)),
+                Err(keyring::Error::NoEntry) => {}
+                Err(err) => self.keyring_failed(err),
+            }
+        }
+        // Secrets stored while the keyring was unavailable stay in the file.
+        let _guard = self.file.lock();
+        Ok(self.read_file()?.remove(account))
+    }
+
+    fn set(&self, account: &str, secret: String) -> Result<()> {
+        if let Some(entry) = self.keyring_entry(account) {
+            match entry.set_password(&secret) {
+                Ok(()) => return self.remove_from_file(account),
+                Err(err) => self.keyring_failed(err),
+            }
+        }
+        let _guard = self.file.lock();
+        let mut secrets = self.read_file()?;
+        secrets.insert(account.to_string(), secret);
+        self.write_file(&secrets)
+    }
+
+    fn delete(&self, account: &str) -> Result<()> {
+        if let Some(entry) = self.keyring_entry(account) {
+            match entry.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => {}
+                Err(err) => self.keyring_failed(err),
+            }
+        }
+        self.remove_from_file(account)
+    }
+
+    fn keyring_entry(&self, account: &str) -> Option<keyring::Entry> {
+        if self.keyring_unavailable.load(Ordering::Relaxed) {
+            return None;
+        }
+        keyring::Entry::new(KEYRING_SERVICE, account)
+            .map_err(|err| self.keyring_failed(err))
+            .ok()
+    }
+
+    fn keyring_failed(&self, err: keyring::Error) {
+        if !self.keyring_unavailable.swap(true, Ordering::Relaxed) {
+            log::warn!(
+                "OS keyring unavailable ({err}); storing plugin secrets encrypted in `{}`",
+                self.dir.join(SECRETS_FILE).display()
+            );
+        }
+    }
+
+    fn remove_from_file(&self, account: &str) -> Result<()> {
+        if !self.dir.join(SECRETS_FILE).exists() {
+            return Ok(());
+        }
+        let _guard = self.file.lock();
+        let mut secrets = self.read_file()?;
+        if secrets.remove(account).is_some() {
+            self.write_file(&secrets)?;
+        }
+        Ok(())
+    }
+
+    fn read_file(&self) -> Result<HashMap<String, String>> {
+        let path = self.dir.join(SECRETS_FILE);
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read `{}`", path.display()))
+            }
+        };
+        if contents.len() < NONCE_LEN {
+            bail!("`{}` is truncated", path.display());
+        }
+        let (nonce, ciphertext) = contents.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("failed to decrypt `{}`", path.display()))?;
+        serde_json::from_slice(&plaintext)
+            .with_context(|| format!("failed to parse `{}`", path.display()))
+    }
+
+    fn write_file(&self, secrets: &HashMap<String, String>) -> Result<()> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = serde_json::to_vec(secrets)?;
+        let ciphertext = self
+            .cipher()?
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| anyhow!("failed to encrypt plugin secrets"))?;
+        let mut contents = nonce.to_vec();
+        contents.extend(ciphertext);
+        write_private(&self.dir.join(SECRETS_FILE), &contents)
+    }
+
+    /// Cipher of the encrypted file, generating its key on first use.
+    fn cipher(&self) -> Result<ChaCha20Poly1305> {
+        let path = self.dir.join(KEY_FILE);
+        let key = match fs::read(&path) {
+            Ok(key) if key.len() == 32 => *Key::from_slice(&key),
+            Ok(_) => bail!("`{}` is not a valid key", path.display()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                write_private(&path, &key)?;
+                key
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read `{}`", path.display()))
+            }
+        };
+        Ok(ChaCha20Poly1305::new(&key))
+    }
+}
+
+/// Write `contents` to `path`, readable by the current user only.
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create `{}`", parent.display()))?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents))
+        .with_context(|| format!("failed to write `{}`", path.display()))
+}
@@ -1,50 +1,211 @@
 use crate::{
-    manifest::{PluginEntry, PluginManifest},
-    plugin::PluginProcess,
+    bus::{self, BusMessage, BusReceiver, Subscriptions},
+    cache::CommandCache,
+    diagnostics::PluginDiagnostics,
+    hints,
+    install::{self, Installed},
+    logfile,
+    lsp_ext::{self, PluginState, PluginStatus, PluginStatusResult},
+    manifest::{self, ActivationEvent, ConflictPolicy, PluginEntry, PluginManifest},
+    metrics::CommandMetrics,
+    output::{self, OutputChannels},
+    plugin::{
+        self, ExitReceiver, HostHandles, IntegrityError, PluginExit, PluginProcess, RequestTimeout,
+        ShutdownStage,
+    },
+    profile::StartupProfile,
+    registry,
+    results::{RequestKey, ResultCache},
+    secrets::SecretStore,
+    settings::{self, HostSettings, CONFIGURATION_TOPIC},
+    store::SharedStore,
+    symbols,
+    trace::Tracer,
+    validate::validate,
 };
 use anyhow::{Context, Result};
-use helix_plugin_sdk::protocol::{HostRequestPayload, PluginResponse};
+use helix_plugin_sdk::protocol::{
+    self as protocol, HostCapabilities, HostRequestPayload, PluginResponse,
+};
+use serde_json::{json, Value};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
 };
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
 use tower_lsp::{
     jsonrpc::{Error as RpcError, ErrorCode},
     lsp_types::{self as lsp, InitializeParams, InitializeResult},
     Client, LanguageServer,
 };
 
+/// Delay used to coalesce bursts of manifest change events.
+const MANIFEST_RELOAD_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Delay before the first restart of a crashed plugin; doubled per attempt.
+const RESTART_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Upper bound for the restart backoff delay.
+const RESTART_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Longest an execute request waits for the plugin providing the command to
+/// restart.
+const RESTART_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Consecutive crashes tolerated before a plugin is disabled.
+const MAX_RESTART_ATTEMPTS: u32 = 5;
+
+/// Consecutive failed command requests tolerated before a plugin is
+/// disabled.
+const MAX_COMMAND_FAILURES: u32 = 5;
+
+/// Uptime after which a plugin is considered healthy again and its crash
+/// counter is reset.
+const RESTART_RESET_UPTIME: Duration = Duration::from_secs(60);
+
+/// Name reported as the owner of host-native commands.
+const HOST_PLUGIN_NAME: &str = "helix-plugin-host";
+
+/// Report the health of every plugin.
+const STATUS_COMMAND: &str = "helix.plugins.status";
+
+/// List every command exposed through the host.
+const COMMANDS_COMMAND: &str = "helix.plugins.commands";
+
+/// Restart a plugin by name.
+const RESTART_COMMAND: &str = "helix.plugins.restart";
+
+/// Start a disabled plugin by name.
+const ENABLE_COMMAND: &str = "helix.plugins.enable";
+
+/// Stop a plugin by name and keep it stopped.
+const DISABLE_COMMAND: &str = "helix.plugins.disable";
+
+/// List command ids registered by more than one plugin.
+const CONFLICTS_COMMAND: &str = "helix.plugins.conflicts";
+
+/// Report request counts, error rates and latencies of plugin commands.
+const METRICS_COMMAND: &str = "helix.plugins.metrics";
+
+/// Return the path of a plugin's log file by name.
+const OPEN_LOG_COMMAND: &str = "helix.plugins.open_log";
+
+/// Return the contents of a plugin's output channel, with the path of a copy
+/// to open in the editor.
+const SHOW_OUTPUT_COMMAND: &str = "helix.plugins.show_output";
+
+/// Commands implemented by the host itself rather than a plugin.
+const HOST_COMMANDS: &[&str] = &[
+    STATUS_COMMAND,
+    COMMANDS_COMMAND,
+    RESTART_COMMAND,
+    ENABLE_COMMAND,
+    DISABLE_COMMAND,
+    CONFLICTS_COMMAND,
+    OPEN_LOG_COMMAND,
+    SHOW_OUTPUT_COMMAND,
+    METRICS_COMMAND,
+];
+
+/// Interval between two logs of the command metrics.
+const METRICS_LOG_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Number of errors kept per plugin for introspection.
+const MAX_RECENT_ERRORS: usize = 10;
+
+/// Maximum time a plugin may take to answer the initialization handshake,
+/// unless its entry sets `init_timeout_ms`.
+const PLUGIN_INIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// LSP `RequestFailed` error code.
+const REQUEST_FAILED: i64 = -32803;
+
 /// Runtime configuration for the plugin host.
 #[derive(Debug, Clone)]
 pub struct HostOptions(Arc<HostOptionsInner>);
 
 #[derive(Debug)]
 struct HostOptionsInner {
-    manifest_path: PathBuf,
+    /// Manifest layers in the order they are merged; never empty.
+    manifest_paths: Vec<PathBuf>,
+    /// Recorder of plugin traffic, enabled by `--trace`.
+    tracer: Option<Tracer>,
+    /// Whether startup timings are reported, enabled by `--profile-startup`.
+    profile_startup: bool,
+    /// File the startup profile is written to as JSON.
+    profile_json: Option<PathBuf>,
 }
 
 impl HostOptions {
     /// Construct options from CLI arguments.
-    pub fn from_cli(manifest: Option<&Path>) -> Result<Self> {
-        let manifest_path = match manifest {
-            Some(path) => path.to_path_buf(),
-            None => helix_loader::config_dir().join("plugins.toml"),
+    pub fn from_cli(
+        manifests: &[PathBuf],
+        trace: Option<&Path>,
+        profile_startup: bool,
+        profile_json: Option<&Path>,
+    ) -> Result<Self> {
+        let manifest_paths = if manifests.is_empty() {
+            vec![helix_loader::config_dir().join("plugins.toml")]
+        } else {
+            manifests.to_vec()
         };
 
-        Ok(Self(Arc::new(HostOptionsInner { manifest_path })))
+        let tracer = trace.map(Tracer::create).transpose()?;
+
+        Ok(Self(Arc::new(HostOptionsInner {
+            manifest_paths,
+            tracer,
+            profile_startup: profile_startup || profile_json.is_some(),
+            profile_json: profile_json.map(Path::to_path_buf),
+        })))
+    }
+
+    /// Whether startup timings are reported.
+    pub fn profile_startup(&self) -> bool {
+        self.0.profile_startup
     }
 
-    /// Absolute path to the manifest file.
+    /// File the startup profile is written to as JSON, if any.
+    pub fn profile_json(&self) -> Option<&Path> {
+        self.0.profile_json.as_deref()
+    }
+
+    /// Recorder of plugin traffic, if tracing is enabled.
+    pub fn tracer(&self) -> Option<Tracer> {
+        self.0.tracer.clone()
+    }
+
+    /// Absolute path to the primary manifest file, the first layer.
     pub fn manifest_path(&self) -> &Path {
-        &self.0.manifest_path
+        &self.0.manifest_paths[0]
+    }
+
+    /// Every manifest layer, in merge order.
+    pub fn manifest_paths(&self) -> &[PathBuf] {
+        &self.0.manifest_paths
+    }
+
+    /// Manifest layers for display, separated by commas.
+    pub fn manifest_display(&self) -> String {
+        let paths: Vec<String> = self
+            .0
+            .manifest_paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        paths.join(", ")
     }
 
-    /// Directory containing the manifest file.
+    /// Load and layer every manifest.
+    pub fn load_manifest(&self) -> Result<PluginManifest> {
+        PluginManifest::load_layered(&self.0.manifest_paths)
+    }
+
+    /// Directory containing the primary manifest file.
     pub fn manifest_dir(&self) -> PathBuf {
-        self.0
-            .manifest_path
+        self.manifest_path()
             .parent()
             .map(|p| p.to_path_buf())
             .unwrap_or_else(|| std::env::current_dir().unwrap_or_default())
@@ -54,25 +215,288 @@ pub fn manifest_dir(&self) -> PathBuf {
 #[derive(Clone)]
 struct CommandBinding {
     plugin: PluginProcess,
-    #[allow(dead_code)]
+    /// Command id known to the plugin, which differs from the exposed id
+    /// when it was prefixed to resolve a conflict.
+    id: String,
     title: String,
-    #[allow(dead_code)]
     description: Option<String>,
+    /// Plugin the providing process is a per-workspace instance of.
+    instance_of: Option<String>,
+    /// Workspace folder served by the providing process.
+    workspace: Option<PathBuf>,
+}
+
+/// Command id claimed by a plugin while another plugin already provided it.
+struct CommandConflict {
+    command: String,
+    /// Plugin that registered the command first.
+    owner: String,
+    /// Plugin that registered the command again.
+    plugin: String,
+    policy: ConflictPolicy,
+    /// Id the second registration is exposed under, if any.
+    registered_as: Option<String>,
+}
+
+/// Command whose plugin exited unexpectedly and has not registered it again.
+struct UnavailableCommand {
+    plugin: String,
+    /// Why the command cannot be run, following the plugin name.
+    reason: String,
+}
+
+/// Error recorded against a plugin for introspection.
+struct PluginError {
+    message: String,
+    at: Instant,
+}
+
+struct LoadedPlugin {
+    entry: PluginEntry,
+    process: PluginProcess,
+    /// Version reported by the plugin during the handshake.
+    version: Option<String>,
+    /// Whether the plugin contributes workspace symbols.
+    workspace_symbols: bool,
+    /// Language ids the plugin outlines documents of.
+    document_symbols: Vec<String>,
+    /// Language ids the plugin shows inlay hints in.
+    inlay_hints: Vec<String>,
+    started_at: Instant,
+}
+
+/// Running plugin answering a language feature request.
+struct Provider {
+    process: PluginProcess,
+    /// Directory relative paths of the plugin are resolved against.
+    base: Option<PathBuf>,
+    timeout: Option<Duration>,
+}
+
+/// Everything required to spawn a plugin and complete its handshake,
+/// detached from the manager so launches can run concurrently.
+pub struct Launcher {
+    manifest_dir: PathBuf,
+    handles: HostHandles,
+    workspace_root: Option<PathBuf>,
+    workspace_folders: Vec<PathBuf>,
+    capabilities: HostCapabilities,
+    /// Collects launch timings while the host starts.
+    profile: Option<StartupProfile>,
+}
+
+impl Launcher {
+    /// Launcher for running plugins without a language client. Plugin
+    /// messages are logged, bus events and exits are dropped.
+    pub fn detached(options: &HostOptions, workspace_root: Option<PathBuf>) -> Self {
+        let (bus, _) = bus::channel();
+        let (exits, _) = tokio::sync::mpsc::unbounded_channel();
+        Self {
+            manifest_dir: options.manifest_dir(),
+            handles: HostHandles {
+                client: None,
+                bus,
+                exits,
+                tracer: options.tracer(),
+                store: SharedStore::default(),
+                secrets: SecretStore::default(),
+                output: OutputChannels::default(),
+                diagnostics: PluginDiagnostics::default(),
+            },
+            workspace_folders: workspace_root.iter().cloned().collect(),
+            workspace_root,
+            capabilities: HostCapabilities::default(),
+            profile: None,
+        }
+    }
+
+    /// Spawn the plugin declared by `entry` and complete its handshake.
+    pub async fn launch(&self, entry: &PluginEntry) -> Result<(PluginProcess, PluginResponse)> {
+        let started = Instant::now();
+        let spawned = self.spawn(entry).await;
+        let spawn_time = started.elapsed();
+        let (handshake_time, launched) = match spawned {
+            Ok(process) => {
+                let started = Instant::now();
+                let response = self.handshake(entry, &process).await;
+                let launched = response.map(|response| (process, response));
+                (Some(started.elapsed()), launched)
+            }
+            Err(err) => (None, Err(err)),
+        };
+        if let Some(profile) = &self.profile {
+            let error = launched.as_ref().err();
+            profile.record_plugin(&entry.name, spawn_time, handshake_time, error);
+        }
+        launched
+    }
+
+    /// Launch plugins that do not depend on each other concurrently.
+    async fn launch_wave(
+        &self,
+        entries: &[PluginEntry],
+    ) -> Vec<Result<(PluginProcess, PluginResponse)>> {
+        let launches = entries.iter().map(|entry| self.launch(entry));
+        futures::future::join_all(launches).await
+    }
+
+    fn workspace_root<'a>(&'a self, entry: &'a PluginEntry) -> Option<&'a PathBuf> {
+        // Per-workspace instances only see the folder they serve.
+        entry.workspace.as_ref().or(self.workspace_root.as_ref())
+    }
+
+    async fn spawn(&self, entry: &PluginEntry) -> Result<PluginProcess> {
+        PluginProcess::spawn(
+            &self.manifest_dir,
+            entry,
+            self.handles.clone(),
+            self.workspace_root(entry).map(PathBuf::as_path),
+        )
+        .await
+    }
+
+    async fn handshake(
+        &self,
+        entry: &PluginEntry,
+        process: &PluginProcess,
+    ) -> Result<PluginResponse> {
+        let workspace_root = self.workspace_root(entry);
+        let workspace_folders = match &entry.workspace {
+            Some(folder) => std::slice::from_ref(folder),
+            None => self.workspace_folders.as_slice(),
+        };
+        let workspace_root = workspace_root.map(|path| path.to_string_lossy().to_string());
+        let workspace_folders = workspace_folders
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+
+        let timeout = entry
+            .init_timeout_ms
+            .map_or(PLUGIN_INIT_TIMEOUT, Duration::from_millis);
+        // Dropping the process on failure kills it.
+        process
+            .send_request_with_timeout(
+                HostRequestPayload::Initialize {
+                    workspace_root,
+                    workspace_folders,
+                    capabilities: HostCapabilities {
+                        run_in_terminal: self.capabilities.run_in_terminal
+                            && plugin::may_run_in_terminal(entry),
+                        input: self.capabilities.input,
+                    },
+                },
+                Some(timeout),
+            )
+            .await
+            .with_context(|| format!("plugin `{}` failed initialization handshake", entry.name))
+    }
+}
+
+/// Plugins set aside by [`PluginManager::begin_launch`], to be spawned in
+/// dependency order.
+struct PendingLaunch {
+    launcher: Launcher,
+    waves: Vec<Vec<PluginEntry>>,
+}
+
+impl PendingLaunch {
+    /// Spawn and handshake the plugins without holding the lock of
+    /// `manager`, which is only taken to register each wave, so requests for
+    /// other plugins are not held up meanwhile.
+    async fn run(self, manager: &Mutex<PluginManager>) {
+        for wave in self.waves {
+            let results = self.launcher.launch_wave(&wave).await;
+            manager.lock().await.finish_launch(wave, results).await;
+        }
+    }
 }
 
 struct PluginManager {
     options: HostOptions,
-    plugins: Vec<(String, PluginProcess)>,
+    client: Client,
+    handles: HostHandles,
+    workspace_root: Option<PathBuf>,
+    workspace_folders: Vec<PathBuf>,
+    /// Features of the attached editor passed on to plugins.
+    capabilities: HostCapabilities,
+    plugins: Vec<LoadedPlugin>,
     commands: HashMap<String, CommandBinding>,
+    /// Bindings of every instance of per-workspace plugins, keyed by
+    /// command id. The first instance is also bound in `commands`.
+    routes: HashMap<String, Vec<CommandBinding>>,
+    /// Lazy plugins that have not been spawned yet.
+    dormant: Vec<PluginEntry>,
+    /// Commands of dormant plugins, mapped to the plugin providing them.
+    lazy_commands: HashMap<String, String>,
+    command_cache: CommandCache,
+    subscriptions: Subscriptions,
+    /// Commands of plugins that exited unexpectedly, keyed by command id.
+    unavailable: HashMap<String, UnavailableCommand>,
+    /// Consecutive crash counts keyed by plugin name.
+    restart_attempts: HashMap<String, u32>,
+    /// Latency and error statistics of executed plugin commands.
+    metrics: CommandMetrics,
+    /// Results plugins allowed the host to reuse.
+    results: ResultCache,
+    /// Consecutive failed command requests keyed by plugin name.
+    command_failures: HashMap<String, u32>,
+    /// Plugins that could not be started.
+    failed: Vec<PluginEntry>,
+    /// Plugins that exited unexpectedly and wait for their restart.
+    restarting: Vec<PluginEntry>,
+    /// Plugins being spawned without the manager lock held.
+    launching: Vec<PluginEntry>,
+    /// Bumped whenever a restart or launch completed, waking the execute
+    /// requests queued until then.
+    restarts: watch::Sender<u64>,
+    /// Plugins disabled in the manifest or at runtime.
+    disabled: Vec<PluginEntry>,
+    /// Most recent errors keyed by plugin name, oldest first.
+    recent_errors: HashMap<String, VecDeque<PluginError>>,
+    conflict_policy: ConflictPolicy,
+    conflicts: Vec<CommandConflict>,
+    /// Settings last received through `workspace/didChangeConfiguration`.
+    settings: HostSettings,
+    /// Log level in effect before any `logLevel` setting was applied.
+    default_log_level: log::LevelFilter,
+    /// Collects launch timings until the initial plugins started.
+    startup_profile: Option<StartupProfile>,
     initialized: bool,
 }
 
 impl PluginManager {
-    fn new(options: HostOptions) -> Self {
+    fn new(options: HostOptions, client: Client, handles: HostHandles) -> Self {
         Self {
             options,
+            client,
+            handles,
+            workspace_root: None,
+            workspace_folders: Vec::new(),
+            capabilities: HostCapabilities::default(),
             plugins: Vec::new(),
             commands: HashMap::new(),
+            routes: HashMap::new(),
+            dormant: Vec::new(),
+            lazy_commands: HashMap::new(),
+            command_cache: CommandCache::load(),
+            subscriptions: Subscriptions::default(),
+            unavailable: HashMap::new(),
+            restart_attempts: HashMap::new(),
+            metrics: CommandMetrics::default(),
+            results: ResultCache::default(),
+            command_failures: HashMap::new(),
+            failed: Vec::new(),
+            restarting: Vec::new(),
+            launching: Vec::new(),
+            restarts: watch::Sender::new(0),
+            disabled: Vec::new(),
+            recent_errors: HashMap::new(),
+            conflict_policy: ConflictPolicy::default(),
+            conflicts: Vec::new(),
+            settings: HostSettings::default(),
+            default_log_level: log::max_level(),
+            startup_profile: None,
             initialized: false,
         }
     }
@@ -81,122 +505,1550 @@ fn manifest_dir(&self) -> PathBuf {
         self.options.manifest_dir()
     }
 
+    /// Load the manifest with per-workspace plugins replaced by an instance
+    /// per workspace folder.
+    fn load_manifest(&self) -> Result<PluginManifest> {
+        let mut manifest = self.options.load_manifest()?;
+        manifest.plugins = manifest::workspace_instances(
+            std::mem::take(&mut manifest.plugins),
+            &self.workspace_folders,
+        );
+        Ok(manifest)
+    }
+
     async fn ensure_initialized(
         &mut self,
-        client: &Client,
         workspace_root: Option<&Path>,
+        workspace_folders: &[PathBuf],
+        capabilities: HostCapabilities,
     ) -> Result<()> {
         if self.initialized {
             return Ok(());
         }
 
-        let manifest_path = self.options.manifest_path().to_path_buf();
-        let manifest = PluginManifest::load(&manifest_path)?;
-        let manifest_dir = self.manifest_dir();
+        self.workspace_root = workspace_root.map(Path::to_path_buf);
+        self.workspace_folders = workspace_folders.to_vec();
+        self.capabilities = capabilities;
+        let started = Instant::now();
+        let manifest = self.load_manifest()?;
+        if self.options.profile_startup() {
+            let profile = StartupProfile::default();
+            profile.record_manifest_load(started.elapsed());
+            self.startup_profile = Some(profile);
+        }
 
         self.plugins.clear();
         self.commands.clear();
+        self.routes.clear();
+        self.dormant.clear();
+        self.lazy_commands.clear();
+        self.subscriptions.clear();
+        self.disabled.clear();
+        self.conflicts.clear();
+        self.conflict_policy = manifest.command_conflicts.unwrap_or_default();
+        self.report_problems(&manifest).await;
+
+        let eager = manifest
+            .plugins
+            .into_iter()
+            .filter_map(|entry| self.defer(entry))
+            .collect();
+        self.start_plugins(eager).await;
+        if let Some(profile) = self.startup_profile.take() {
+            profile.finish(started.elapsed(), self.options.profile_json());
+        }
+
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Re-read the manifest and reconcile running plugins with its entries:
+    /// new entries are started, removed ones shut down and modified ones
+    /// restarted. Unchanged plugins keep running untouched.
+    async fn reload(&mut self) -> Result<()> {
+        if !self.initialized {
+            return Ok(());
+        }
+
+        let manifest = self.load_manifest()?;
+        self.conflict_policy = manifest.command_conflicts.unwrap_or_default();
+        self.report_problems(&manifest).await;
+
+        let mut kept = Vec::new();
+        for plugin in std::mem::take(&mut self.plugins) {
+            if manifest.plugins.contains(&plugin.entry) {
+                kept.push(plugin);
+            } else {
+                log::info!(
+                    "stopping plugin `{}` after manifest change",
+                    plugin.entry.name
+                );
+                self.unload_plugin(plugin).await;
+            }
+        }
+        self.plugins = kept;
+
+        for entry in std::mem::take(&mut self.dormant) {
+            if manifest.plugins.contains(&entry) {
+                self.dormant.push(entry);
+            } else {
+                self.lazy_commands.retain(|_, plugin| *plugin != entry.name);
+            }
+        }
+        self.disabled
+            .retain(|entry| manifest.plugins.contains(entry));
+        self.failed.retain(|entry| manifest.plugins.contains(entry));
+        self.restarting
+            .retain(|entry| manifest.plugins.contains(entry));
+        // Launches of removed plugins are shut down once they complete.
+        self.launching
+            .retain(|entry| manifest.plugins.contains(entry));
+        self.unavailable.retain(|_, command| {
+            manifest
+                .plugins
+                .iter()
+                .any(|entry| entry.name == command.plugin)
+        });
+
+        // Plugins that failed are only started again on request and crashed
+        // ones once their restart is due.
+        let added = manifest
+            .plugins
+            .into_iter()
+            .filter(|entry| !self.is_known(&entry.name))
+            .collect::<Vec<_>>();
+        let eager = added
+            .into_iter()
+            .filter_map(|entry| self.defer(entry))
+            .collect();
+        self.start_plugins(eager).await;
+
+        Ok(())
+    }
+
+    /// Apply a change of the editor's workspace folders, starting and
+    /// stopping per-workspace instances accordingly.
+    async fn change_workspace_folders(
+        &mut self,
+        event: lsp::WorkspaceFoldersChangeEvent,
+    ) -> Result<()> {
+        let removed: Vec<PathBuf> = event
+            .removed
+            .iter()
+            .filter_map(|folder| folder.uri.to_file_path().ok())
+            .collect();
+        self.workspace_folders
+            .retain(|folder| !removed.contains(folder));
+        for folder in event.added {
+            if let Ok(path) = folder.uri.to_file_path() {
+                if !self.workspace_folders.contains(&path) {
+                    self.workspace_folders.push(path);
+                }
+            }
+        }
+        self.reload().await?;
+        self.activate_workspace().await;
+        Ok(())
+    }
+
+    fn is_loaded(&self, name: &str) -> bool {
+        self.plugins.iter().any(|plugin| plugin.entry.name == name)
+            || self.dormant.iter().any(|entry| entry.name == name)
+            || self.is_disabled(name)
+    }
+
+    /// Whether the named plugin is loaded, failed, launching or waits for
+    /// its restart.
+    fn is_known(&self, name: &str) -> bool {
+        self.is_loaded(name)
+            || self.failed.iter().any(|entry| entry.name == name)
+            || self.is_restarting(name)
+            || self.is_launching(name)
+    }
+
+    /// Whether the named plugin is being spawned outside the manager lock.
+    fn is_launching(&self, name: &str) -> bool {
+        self.launching.iter().any(|entry| entry.name == name)
+    }
+
+    fn is_disabled(&self, name: &str) -> bool {
+        self.disabled.iter().any(|entry| entry.name == name)
+    }
+
+    /// Set aside disabled and dormant lazy plugins. Returns the entry back
+    /// when it should be started right away.
+    fn defer(&mut self, entry: PluginEntry) -> Option<PluginEntry> {
+        let entry = self.defer_disabled(entry)?;
+        self.defer_lazy(entry)
+    }
+
+    /// Set aside a plugin disabled in the manifest. Returns the entry back
+    /// when it is enabled.
+    fn defer_disabled(&mut self, entry: PluginEntry) -> Option<PluginEntry> {
+        if entry.enabled {
+            return Some(entry);
+        }
+        log::info!("plugin `{}` is disabled", entry.name);
+        self.disabled.push(entry);
+        None
+    }
+
+    /// Shut the named plugin down and drop its commands until it is enabled
+    /// again. Other plugins are left untouched.
+    async fn disable_plugin(&mut self, name: &str) -> Result<()> {
+        if self.is_disabled(name) {
+            return Ok(());
+        }
+
+        let entry = if let Some(index) = self
+            .plugins
+            .iter()
+            .position(|plugin| plugin.entry.name == name)
+        {
+            let plugin = self.plugins.remove(index);
+            let entry = plugin.entry.clone();
+            self.unload_plugin(plugin).await;
+            entry
+        } else if let Some(index) = self.dormant.iter().position(|entry| entry.name == name) {
+            self.lazy_commands.retain(|_, plugin| plugin != name);
+            self.dormant.remove(index)
+        } else if let Some(index) = self.failed.iter().position(|entry| entry.name == name) {
+            self.failed.remove(index)
+        } else {
+            // The plugin may be waiting for a restart after a crash, in which
+            // case it is only known to the manifest.
+            self.load_manifest()?
+                .plugins
+                .into_iter()
+                .find(|entry| entry.name == name)
+                .with_context(|| format!("unknown plugin `{name}`"))?
+        };
+
+        log::info!("disabling plugin `{name}`");
+        self.restart_attempts.remove(name);
+        self.command_failures.remove(name);
+        self.disabled.push(entry);
+        Ok(())
+    }
+
+    /// Disable a misbehaving plugin and tell the user how to enable it
+    /// again.
+    async fn trip_breaker(&mut self, name: &str, reason: &str) {
+        if let Err(err) = self.disable_plugin(name).await {
+            log::warn!("failed to disable plugin `{name}`: {err:?}");
+            return;
+        }
+        let message = format!(
+            "plugin `{name}` {reason} and was disabled; run `{ENABLE_COMMAND}` to enable it again"
+        );
+        log::error!("{message}");
+        self.client
+            .show_message(lsp::MessageType::ERROR, message.clone())
+            .await;
+        self.record_error(name, message);
+    }
+
+    /// Track the outcome of a command request, disabling the plugin once
+    /// [`MAX_COMMAND_FAILURES`] requests failed in a row.
+    async fn record_command_outcome(&mut self, plugin: &str, succeeded: bool) {
+        if succeeded {
+            self.command_failures.remove(plugin);
+            return;
+        }
+        let failures = self.command_failures.entry(plugin.to_string()).or_default();
+        *failures += 1;
+        if *failures >= MAX_COMMAND_FAILURES {
+            self.trip_breaker(
+                plugin,
+                &format!("failed {MAX_COMMAND_FAILURES} commands in a row"),
+            )
+            .await;
+        }
+    }
+
+    /// Start a disabled plugin, honouring its `lazy` setting.
+    async fn enable_plugin(&mut self, name: &str) -> Result<()> {
+        let Some(index) = self.disabled.iter().position(|entry| entry.name == name) else {
+            if self.is_loaded(name) {
+                return Ok(());
+            }
+            anyhow::bail!("unknown plugin `{name}`");
+        };
+
+        log::info!("enabling plugin `{name}`");
+        let entry = self.disabled.remove(index);
+        if let Some(entry) = self.defer_lazy(entry) {
+            self.start_plugins(vec![entry]).await;
+        }
+        Ok(())
+    }
+
+    /// Park a lazy plugin until first use if its commands are known, or a
+    /// plugin with activation events until one occurs. Returns the entry
+    /// back when it should be started right away.
+    fn defer_lazy(&mut self, entry: PluginEntry) -> Option<PluginEntry> {
+        if !entry.is_deferred() {
+            return Some(entry);
+        }
+        if let Some(event) = self.workspace_activation(&entry) {
+            log::info!("starting plugin `{}` on `{event}`", entry.name);
+            return Some(entry);
+        }
+
+        let mut commands = if entry.commands.is_empty() {
+            self.command_cache.commands(&entry.name).to_vec()
+        } else {
+            entry.commands.clone()
+        };
+        commands.extend(entry.activation.iter().filter_map(|event| match event {
+            ActivationEvent::OnCommand(command) => Some(command.clone()),
+            _ => None,
+        }));
+        if commands.is_empty() && entry.activation.is_empty() {
+            log::info!(
+                "lazy plugin `{}` has no known commands, starting it to discover them",
+                entry.name
+            );
+            return Some(entry);
+        }
+
+        log::info!("deferring start of plugin `{}`", entry.name);
+        for command in commands {
+            self.lazy_commands.insert(command, entry.name.clone());
+        }
+        self.dormant.push(entry);
+        None
+    }
+
+    /// Spawn a dormant plugin because of `trigger`.
+    async fn wake_plugin(&mut self, name: &str, trigger: &str) {
+        if let Some(launch) = self.wake(name, trigger) {
+            self.start_pending(launch).await;
+        }
+    }
+
+    /// Prepare the launch of a dormant plugin because of `trigger`. Its lazy
+    /// commands stay bound to it until the launch completed.
+    fn wake(&mut self, name: &str, trigger: &str) -> Option<PendingLaunch> {
+        let index = self.dormant.iter().position(|entry| entry.name == name)?;
+        let entry = self.dormant.remove(index);
+        log::info!("starting plugin `{name}` on {trigger}");
+        self.begin_launch(vec![entry])
+    }
+
+    /// `workspaceContains` event of `entry` matched by its workspace folders.
+    fn workspace_activation<'a>(&self, entry: &'a PluginEntry) -> Option<&'a ActivationEvent> {
+        // Per-workspace instances only look at the folder they serve.
+        let folders = match &entry.workspace {
+            Some(folder) => std::slice::from_ref(folder),
+            None => self.workspace_folders.as_slice(),
+        };
+        entry
+            .activation
+            .iter()
+            .find(|event| event.matches_workspace(folders))
+    }
+
+    /// Spawn the dormant plugins activated by the current workspace folders.
+    async fn activate_workspace(&mut self) {
+        let activated: Vec<(String, String)> = self
+            .dormant
+            .iter()
+            .filter_map(|entry| {
+                let event = self.workspace_activation(entry)?;
+                Some((entry.name.clone(), format!("`{event}`")))
+            })
+            .collect();
+        for (name, trigger) in activated {
+            self.wake_plugin(&name, &trigger).await;
+        }
+    }
+
+    /// Prepare the launch of the dormant plugins waiting for a document of
+    /// `language_id`.
+    fn activate_language(&mut self, language_id: &str) -> Option<PendingLaunch> {
+        let event = ActivationEvent::OnLanguage(language_id.to_string());
+        let (activated, dormant) = std::mem::take(&mut self.dormant)
+            .into_iter()
+            .partition::<Vec<_>, _>(|entry| entry.activation.contains(&event));
+        self.dormant = dormant;
+        for entry in &activated {
+            log::info!("starting plugin `{}` on `{event}`", entry.name);
+        }
+        self.begin_launch(activated)
+    }
+
+    /// Show every problem found in `manifest` in a single message.
+    async fn report_problems(&self, manifest: &PluginManifest) {
+        let problems = validate(
+            manifest,
+            &self.manifest_dir(),
+            self.workspace_root.as_deref(),
+        );
+        if problems.is_empty() {
+            return;
+        }
+
+        for problem in &problems {
+            log::warn!("{problem}");
+        }
+        let list: Vec<String> = problems
+            .iter()
+            .map(|problem| format!("- {problem}"))
+            .collect();
+        let message = format!(
+            "plugin manifest `{}` has {} problem(s):\n{}",
+            self.options.manifest_display(),
+            problems.len(),
+            list.join("\n")
+        );
+        self.client
+            .show_message(lsp::MessageType::WARNING, message)
+            .await;
+    }
+
+    fn launcher(&self) -> Launcher {
+        Launcher {
+            manifest_dir: self.manifest_dir(),
+            handles: self.handles.clone(),
+            workspace_root: self.workspace_root.clone(),
+            workspace_folders: self.workspace_folders.clone(),
+            capabilities: self.capabilities.clone(),
+            profile: self.startup_profile.clone(),
+        }
+    }
+
+    /// Spawn and handshake `entries` concurrently, then register the plugins
+    /// that came up in manifest order so command overrides stay deterministic.
+    async fn start_plugins(&mut self, entries: Vec<PluginEntry>) {
+        if let Some(launch) = self.begin_launch(entries) {
+            self.start_pending(launch).await;
+        }
+    }
+
+    /// Launch plugins prepared by [`Self::begin_launch`] with the lock held.
+    async fn start_pending(&mut self, launch: PendingLaunch) {
+        for wave in launch.waves {
+            let results = launch.launcher.launch_wave(&wave).await;
+            self.finish_launch(wave, results).await;
+        }
+    }
+
+    /// Mark `entries` as launching and split them into startup waves, so
+    /// they can be spawned without holding the manager lock.
+    fn begin_launch(&mut self, entries: Vec<PluginEntry>) -> Option<PendingLaunch> {
+        if entries.is_empty() {
+            return None;
+        }
+
+        self.failed
+            .retain(|failed| !entries.iter().any(|entry| entry.name == failed.name));
+
+        let (waves, cyclic) = manifest::startup_waves(entries);
+        for entry in cyclic {
+            let message = format!(
+                "not started: dependency cycle through `after = [{}]`",
+                entry.after.join(", ")
+            );
+            log::error!("plugin `{}` {message}", entry.name);
+            self.record_error(&entry.name, message);
+            self.failed.push(entry);
+        }
+        self.launching.extend(waves.iter().flatten().cloned());
+        Some(PendingLaunch {
+            launcher: self.launcher(),
+            waves,
+        })
+    }
+
+    /// Register the plugins of a launched wave. Plugins removed from the
+    /// manifest or shut down while they were launching are stopped again.
+    async fn finish_launch(
+        &mut self,
+        entries: Vec<PluginEntry>,
+        results: Vec<Result<(PluginProcess, PluginResponse)>>,
+    ) {
+        for (entry, result) in entries.into_iter().zip(results) {
+            let Some(index) = self.launching.iter().position(|pending| *pending == entry) else {
+                if let Ok((process, _)) = result {
+                    process.shutdown().await;
+                }
+                continue;
+            };
+            self.launching.remove(index);
+            self.restarting.retain(|pending| *pending != entry);
+            if !self
+                .dormant
+                .iter()
+                .any(|dormant| dormant.name == entry.name)
+            {
+                self.lazy_commands.retain(|_, plugin| *plugin != entry.name);
+            }
+
+            let err = match result {
+                Ok((process, response)) => {
+                    self.register_plugin(entry, process, response).await;
+                    continue;
+                }
+                Err(err) => err,
+            };
+
+            log::error!("failed to start plugin `{}`: {err:?}", entry.name);
+            if let Some(integrity) = err.downcast_ref::<IntegrityError>() {
+                self.client
+                    .show_message(lsp::MessageType::ERROR, integrity.to_string())
+                    .await;
+            }
+            let message = match err.downcast_ref::<RequestTimeout>() {
+                Some(timeout) => format!(
+                    "did not initialize within {}ms",
+                    timeout.timeout.as_millis()
+                ),
+                None => format!("failed to start: {err}"),
+            };
+            self.record_error(&entry.name, message);
+            self.failed.push(entry);
+        }
+        self.restarts.send_modify(|restarts| *restarts += 1);
+    }
+
+    /// Handle an unexpected plugin exit by unbinding the dead process and
+    /// computing the backoff delay before it should be restarted. Returns
+    /// `None` when the exit is stale or the plugin exhausted its restarts.
+    async fn schedule_restart(&mut self, exit: &PluginExit) -> Option<(PluginEntry, Duration)> {
+        let index = self.plugins.iter().position(|plugin| {
+            plugin.entry.name == exit.name && plugin.process.instance() == exit.instance
+        })?;
+        let plugin = self.plugins.remove(index);
+
+        let commands: Vec<String> = self
+            .commands
+            .iter()
+            .filter(|(_, binding)| binding.plugin.name() == exit.name)
+            .map(|(id, _)| id.clone())
+            .collect();
+        self.unbind_plugin(&plugin.entry.name);
+        let status = exit.describe();
+        self.record_error(&exit.name, format!("exited unexpectedly ({status})"));
+
+        if !exit.restart {
+            let message = format!(
+                "plugin `{}` {status} and was stopped; run `{RESTART_COMMAND}` to start it again",
+                exit.name
+            );
+            log::warn!("{message}");
+            self.mark_unavailable(
+                &exit.name,
+                commands,
+                format!("{status}; run `{RESTART_COMMAND}` to start it again"),
+            );
+            self.client
+                .show_message(lsp::MessageType::WARNING, message)
+                .await;
+            self.failed.push(plugin.entry);
+            return None;
+        }
+
+        let attempts = self
+            .restart_attempts
+            .entry(plugin.entry.name.clone())
+            .or_default();
+        if plugin.started_at.elapsed() >= RESTART_RESET_UPTIME {
+            *attempts = 0;
+        }
+        *attempts += 1;
+
+        if *attempts > MAX_RESTART_ATTEMPTS {
+            self.trip_breaker(
+                &exit.name,
+                &format!("exited ({status}) and crashed {MAX_RESTART_ATTEMPTS} times in a row"),
+            )
+            .await;
+            self.mark_unavailable(
+                &exit.name,
+                commands,
+                format!("crashed repeatedly and was disabled; run `{ENABLE_COMMAND}` to enable it again"),
+            );
+            return None;
+        }
+
+        let delay = RESTART_BASE_DELAY
+            .saturating_mul(1 << (*attempts - 1))
+            .min(RESTART_MAX_DELAY);
+        let message = format!(
+            "plugin `{}` exited ({status}); restarting in {}ms",
+            exit.name,
+            delay.as_millis()
+        );
+        log::warn!("{message}");
+        self.mark_unavailable(
+            &exit.name,
+            commands,
+            format!("exited ({status}) and is being restarted; try again shortly"),
+        );
+        self.client
+            .show_message(lsp::MessageType::WARNING, message)
+            .await;
+
+        self.restarting.push(plugin.entry.clone());
+        Some((plugin.entry, delay))
+    }
+
+    /// Remember why the commands of a crashed plugin cannot be run until it
+    /// registers them again. Commands still served by a sibling instance
+    /// stay available.
+    fn mark_unavailable(&mut self, plugin: &str, commands: Vec<String>, reason: String) {
+        for id in commands {
+            if !self.commands.contains_key(&id) {
+                self.unavailable.insert(
+                    id,
+                    UnavailableCommand {
+                        plugin: plugin.to_string(),
+                        reason: reason.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Prepare the launch of a crashed plugin unless it was restarted or
+    /// removed in the meantime. The plugin counts as restarting until its
+    /// launch completed, so its commands stay queued.
+    fn begin_restart(&mut self, entry: PluginEntry) -> Option<PendingLaunch> {
+        // The plugin may have been removed from the manifest while waiting
+        // for the restart.
+        let index = self.restarting.iter().position(|pending| *pending == entry);
+        if index.is_some() && self.initialized && !self.is_loaded(&entry.name) {
+            return self.begin_launch(vec![entry]);
+        }
+        if let Some(index) = index {
+            self.restarting.remove(index);
+        }
+        self.restarts.send_modify(|restarts| *restarts += 1);
+        None
+    }
+
+    /// Whether `plugin` crashed and waits for its restart.
+    fn is_restarting(&self, plugin: &str) -> bool {
+        self.restarting.iter().any(|entry| entry.name == plugin)
+    }
+
+    /// Restart the named plugin on request, reviving it if it previously
+    /// failed to start.
+    async fn restart_by_name(&mut self, name: &str) -> Result<()> {
+        if self.is_disabled(name) {
+            anyhow::bail!("plugin `{name}` is disabled");
+        }
+        self.restart_attempts.remove(name);
+
+        if self.dormant.iter().any(|entry| entry.name == name) {
+            self.wake_plugin(name, "request").await;
+            return Ok(());
+        }
+
+        let entry = if let Some(index) = self.failed.iter().position(|entry| entry.name == name) {
+            self.failed.remove(index)
+        } else if let Some(index) = self
+            .plugins
+            .iter()
+            .position(|plugin| plugin.entry.name == name)
+        {
+            let plugin = self.plugins.remove(index);
+            let entry = plugin.entry.clone();
+            self.unload_plugin(plugin).await;
+            entry
+        } else {
+            anyhow::bail!("plugin `{name}` is not loaded");
+        };
+
+        log::info!("restarting plugin `{name}` on request");
+        self.start_plugins(vec![entry]).await;
+        Ok(())
+    }
+
+    fn record_error(&mut self, plugin: &str, message: String) {
+        let errors = self.recent_errors.entry(plugin.to_string()).or_default();
+        if errors.len() == MAX_RECENT_ERRORS {
+            errors.pop_front();
+        }
+        errors.push_back(PluginError {
+            message,
+            at: Instant::now(),
+        });
+    }
+
+    /// Health summary of every plugin known to the host.
+    fn status_report(&self) -> Value {
+        let running =
+            self.plugins.iter().map(|plugin| {
+                let mut commands: Vec<&str> =
+                    self.commands
+                        .iter()
+                        .chain(self.routes.iter().flat_map(|(id, routes)| {
+                            routes.iter().map(move |binding| (id, binding))
+                        }))
+                        .filter(|(_, binding)| binding.plugin.name() == plugin.entry.name)
+                        .map(|(id, _)| id.as_str())
+                        .collect();
+                commands.sort_unstable();
+                commands.dedup();
+                json!({
+                    "name": plugin.entry.name,
+                    "state": "running",
+                    "pid": plugin.process.pid(),
+                    "version": plugin.version,
+                    "uptime_secs": plugin.started_at.elapsed().as_secs(),
+                    "commands": commands,
+                })
+            });
+        let dormant = self.dormant.iter().map(|entry| {
+            let commands: Vec<&str> = self
+                .lazy_commands
+                .iter()
+                .filter(|(_, plugin)| **plugin == entry.name)
+                .map(|(id, _)| id.as_str())
+                .collect();
+            json!({ "name": entry.name, "state": "dormant", "commands": commands })
+        });
+        let failed = self
+            .failed
+            .iter()
+            .map(|entry| json!({ "name": entry.name, "state": "failed", "commands": [] }));
+        let restarting = self
+            .restarting
+            .iter()
+            .filter(|entry| !self.is_loaded(&entry.name))
+            .map(|entry| {
+                let commands = self.command_cache.commands(&entry.name);
+                json!({ "name": entry.name, "state": "restarting", "commands": commands })
+            });
+        let disabled = self
+            .disabled
+            .iter()
+            .map(|entry| json!({ "name": entry.name, "state": "disabled", "commands": [] }));
+
+        let mut report: Vec<Value> = running
+            .chain(dormant)
+            .chain(failed)
+            .chain(restarting)
+            .chain(disabled)
+            .collect();
+        for plugin in &mut report {
+            let name = plugin["name"].as_str().unwrap_or_default().to_string();
+            plugin["restarts"] = json!(self.restart_attempts.get(&name).copied().unwrap_or(0));
+            plugin["recent_errors"] = self
+                .recent_errors
+                .get(&name)
+                .into_iter()
+                .flatten()
+                .map(|error| {
+                    json!({
+                        "message": error.message,
+                        "age_secs": error.at.elapsed().as_secs(),
+                    })
+                })
+                .collect();
+        }
+        Value::Array(report)
+    }
+
+    /// Live status of every plugin, answering `$/helix/pluginStatus`.
+    fn plugin_statuses(&self) -> Vec<PluginStatus> {
+        let status = |name: &str, state| PluginStatus {
+            name: name.to_string(),
+            state,
+            pid: None,
+            version: None,
+            uptime_secs: None,
+            restarts: self.restart_attempts.get(name).copied().unwrap_or(0),
+            last_error: self
+                .recent_errors
+                .get(name)
+                .and_then(|errors| errors.back())
+                .map(|error| error.message.clone()),
+        };
+        let running = self.plugins.iter().map(|plugin| PluginStatus {
+            pid: plugin.process.pid(),
+            version: plugin.version.clone(),
+            uptime_secs: Some(plugin.started_at.elapsed().as_secs()),
+            ..status(&plugin.entry.name, PluginState::Running)
+        });
+        let dormant = self
+            .dormant
+            .iter()
+            .map(|entry| status(&entry.name, PluginState::Dormant));
+        let crashed = self
+            .failed
+            .iter()
+            .map(|entry| status(&entry.name, PluginState::Crashed));
+        let restarting = self
+            .restarting
+            .iter()
+            .filter(|entry| !self.is_loaded(&entry.name))
+            .map(|entry| status(&entry.name, PluginState::Restarting));
+        let disabled = self
+            .disabled
+            .iter()
+            .map(|entry| status(&entry.name, PluginState::Disabled));
+        running
+            .chain(dormant)
+            .chain(crashed)
+            .chain(restarting)
+            .chain(disabled)
+            .collect()
+    }
+
+    /// Every command exposed through the host for documents of
+    /// `language_id`, including host commands.
+    fn commands_report(&self, language_id: Option<&str>) -> Value {
+        let offered = |id: &str| self.offers_command(id, language_id);
+        let host = HOST_COMMANDS
+            .iter()
+            .map(|id| json!({ "id": id, "title": Value::Null, "plugin": HOST_PLUGIN_NAME }));
+        let bound = self
+            .commands
+            .iter()
+            .filter(|(id, _)| offered(id))
+            .map(|(id, binding)| {
+                json!({
+                    "id": id,
+                    "title": binding.title,
+                    "description": binding.description,
+                    "plugin": binding.plugin.name(),
+                })
+            });
+        let lazy = self
+            .lazy_commands
+            .iter()
+            .filter(|(id, _)| offered(id))
+            .map(|(id, plugin)| json!({ "id": id, "title": Value::Null, "plugin": plugin }));
+        let restarting = self
+            .unavailable
+            .iter()
+            .filter(|(id, command)| {
+                !self.commands.contains_key(*id)
+                    && self.restarting.iter().any(|entry| {
+                        entry.name == command.plugin && entry.serves_language(language_id)
+                    })
+            })
+            .map(
+                |(id, command)| json!({ "id": id, "title": Value::Null, "plugin": command.plugin }),
+            );
+        Value::Array(host.chain(bound).chain(lazy).chain(restarting).collect())
+    }
+
+    /// Whether `command` is offered while the active document is of
+    /// `language_id`, which depends on the languages its plugin serves.
+    fn offers_command(&self, command: &str, language_id: Option<&str>) -> bool {
+        let plugin = match self.commands.get(command) {
+            Some(binding) => binding.plugin.name(),
+            None => match self.lazy_commands.get(command) {
+                Some(plugin) => plugin.as_str(),
+                None => return true,
+            },
+        };
+        self.plugins
+            .iter()
+            .map(|plugin| &plugin.entry)
+            .chain(&self.dormant)
+            .find(|entry| entry.name == plugin)
+            .map_or(true, |entry| entry.serves_language(language_id))
+    }
+
+    /// Command ids registered by more than one plugin and how they were
+    /// resolved.
+    fn conflicts_report(&self) -> Value {
+        self.conflicts
+            .iter()
+            .map(|conflict| {
+                json!({
+                    "command": conflict.command,
+                    "owner": conflict.owner,
+                    "plugin": conflict.plugin,
+                    "policy": conflict.policy,
+                    "registered_as": conflict.registered_as,
+                })
+            })
+            .collect()
+    }
 
-        let workspace_string = workspace_root
-            .map(|path| path.to_path_buf())
-            .map(|path| path.to_string_lossy().to_string());
+    /// Plugin other than `plugin` currently providing `command`.
+    fn command_owner(&self, command: &str, plugin: &str) -> Option<String> {
+        self.commands
+            .get(command)
+            .map(|binding| binding.plugin.name())
+            .filter(|owner| *owner != plugin)
+            .map(str::to_string)
+    }
 
-        for entry in manifest.plugins {
-            match PluginProcess::spawn(&manifest_dir, &entry, client.clone(), workspace_root).await
-            {
-                Ok(process) => {
-                    self.register_plugin(entry, process, workspace_string.clone())
-                        .await?;
-                }
-                Err(err) => {
-                    log::error!("failed to start plugin `{}`: {err:?}", entry.name);
+    fn unbind_plugin(&mut self, name: &str) {
+        for routes in self.routes.values_mut() {
+            routes.retain(|binding| binding.plugin.name() != name);
+        }
+        self.routes.retain(|_, routes| !routes.is_empty());
+        // Commands of a removed per-workspace instance fall back to another
+        // instance of the same plugin.
+        let routes = &self.routes;
+        self.commands.retain(|id, binding| {
+            if binding.plugin.name() != name {
+                return true;
+            }
+            match routes.get(id).and_then(|routes| routes.first()) {
+                Some(route) => {
+                    *binding = route.clone();
+                    true
                 }
+                None => false,
             }
-        }
+        });
+        self.subscriptions.remove_plugin(name);
+        self.conflicts.retain(|conflict| conflict.plugin != name);
+        self.results.remove_plugin(name);
+    }
 
-        self.initialized = true;
-        Ok(())
+    async fn unload_plugin(&mut self, plugin: LoadedPlugin) {
+        let name = plugin.entry.name.as_str();
+        self.unbind_plugin(name);
+        for (uri, diagnostics) in self.handles.diagnostics.remove_plugin(name) {
+            self.client
+                .publish_diagnostics(uri, diagnostics, None)
+                .await;
+        }
+        let stage = plugin.process.shutdown().await;
+        if stage != ShutdownStage::Graceful {
+            let message = format!("{stage} while shutting down");
+            self.record_error(&plugin.entry.name, message);
+        }
     }
 
     async fn register_plugin(
         &mut self,
         entry: PluginEntry,
         process: PluginProcess,
-        workspace: Option<String>,
-    ) -> Result<()> {
-        let response = process
-            .send_request(HostRequestPayload::Initialize {
-                workspace_root: workspace,
-            })
-            .await
-            .with_context(|| format!("plugin `{}` failed initialization handshake", entry.name))?;
+        response: PluginResponse,
+    ) {
+        let PluginResponse::Initialized {
+            commands,
+            languages,
+            protocol_version,
+            subscriptions,
+            version,
+            workspace_symbols,
+            document_symbols,
+            inlay_hints,
+        } = response
+        else {
+            // Left out of `failed`, the plugin would be started again on
+            // every reload.
+            let message = match response {
+                PluginResponse::CommandError { message } => {
+                    format!("failed to initialize: {message}")
+                }
+                _ => "responded with unexpected payload during initialization".to_string(),
+            };
+            log::warn!("plugin `{}` {message}", entry.name);
+            self.record_error(&entry.name, message);
+            process.shutdown().await;
+            self.failed.push(entry);
+            return;
+        };
 
-        let PluginResponse::Initialized { commands } = response else {
-            log::warn!(
-                "plugin `{}` responded with unexpected payload during initialization",
-                entry.name
+        if let Err(reason) = entry.check_protocol(protocol_version) {
+            let message = format!("{reason}; it was not registered");
+            log::error!("{message}");
+            self.client
+                .show_message(lsp::MessageType::ERROR, message.clone())
+                .await;
+            self.record_error(&entry.name, message);
+            process.shutdown().await;
+            self.failed.push(entry);
+            return;
+        }
+
+        if self.conflict_policy == ConflictPolicy::Error {
+            let taken: Vec<(String, String)> = commands
+                .iter()
+                .filter_map(|command| {
+                    let owner = self.command_owner(&command.id, &entry.name)?;
+                    Some((command.id.clone(), owner))
+                })
+                .collect();
+            if !taken.is_empty() {
+                self.refuse_plugin(entry, process, taken).await;
+                return;
+            }
+        }
+
+        // A `protocol` requirement was checked above; the versions it
+        // accepts are not worth a warning.
+        if entry.protocol.is_none() && protocol_version != Some(protocol::VERSION) {
+            let reported = protocol_version
+                .map(|version| version.to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            let message = format!(
+                "plugin `{}` speaks protocol version {reported} but the host expects {}; consider updating it",
+                entry.name,
+                protocol::VERSION
             );
-            return Ok(());
-        };
+            log::warn!("{message}");
+            self.client
+                .show_message(lsp::MessageType::WARNING, message)
+                .await;
+        }
+
+        if !languages.is_empty() {
+            log::info!(
+                "plugin `{}` restricted to languages: {}",
+                entry.name,
+                languages.join(", ")
+            );
+        }
+
+        for topic in &subscriptions {
+            self.subscriptions.subscribe(&entry.name, topic);
+        }
+        if self.settings.plugins.contains_key(settings_key(&entry)) {
+            self.push_settings(&entry, &process);
+        }
 
+        self.conflicts
+            .retain(|conflict| conflict.plugin != entry.name);
+        self.unavailable
+            .retain(|_, command| command.plugin != entry.name);
+        let mut registered = Vec::new();
         for command in commands {
+            let mut id = command.id.clone();
             let binding = CommandBinding {
                 plugin: process.clone(),
-                title: command.title.clone(),
-                description: command.description.clone(),
+                id: command.id.clone(),
+                title: command.title,
+                description: command.description,
+                instance_of: entry.instance_of.clone(),
+                workspace: entry.workspace.clone(),
             };
-
-            if self.commands.insert(command.id.clone(), binding).is_some() {
-                log::warn!(
-                    "command `{}` already registered ? overriding with plugin `{}`",
-                    command.id,
-                    entry.name
-                );
+            if entry.instance_of.is_some() {
+                self.routes
+                    .entry(id.clone())
+                    .or_default()
+                    .push(binding.clone());
+                let sibling = self
+                    .commands
+                    .get(&id)
+                    .is_some_and(|bound| bound.instance_of == entry.instance_of);
+                if sibling {
+                    registered.push(id);
+                    continue;
+                }
+            }
+            if let Some(owner) = self.command_owner(&id, &entry.name) {
+                match self.conflict_policy {
+                    ConflictPolicy::FirstWins => {
+                        log::warn!(
+                            "command `{id}` already registered by plugin `{owner}`; ignoring the one of plugin `{}`",
+                            entry.name
+                        );
+                    }
+                    ConflictPolicy::Prefix => {
+                        id = format!("{}.{}", entry.name, command.id);
+                        log::warn!(
+                            "command `{}` already registered by plugin `{owner}`; exposing the one of plugin `{}` as `{id}`",
+                            command.id,
+                            entry.name
+                        );
+                    }
+                    ConflictPolicy::Override | ConflictPolicy::Error => {
+                        log::warn!(
+                            "command `{id}` already registered ? overriding with plugin `{}`",
+                            entry.name
+                        );
+                    }
+                }
+                let ignored = self.conflict_policy == ConflictPolicy::FirstWins;
+                self.conflicts.push(CommandConflict {
+                    command: command.id.clone(),
+                    owner,
+                    plugin: entry.name.clone(),
+                    policy: self.conflict_policy,
+                    registered_as: (!ignored).then(|| id.clone()),
+                });
+                if ignored {
+                    continue;
+                }
             }
+
+            self.commands.insert(id.clone(), binding);
+            registered.push(id);
         }
 
-        self.plugins.push((entry.name, process));
-        Ok(())
+        self.unavailable
+            .retain(|_, command| command.plugin != entry.name);
+        if let Err(err) = self.command_cache.update(&entry.name, registered) {
+            log::warn!(
+                "failed to cache commands of plugin `{}`: {err:?}",
+                entry.name
+            );
+        }
+
+        self.plugins.push(LoadedPlugin {
+            entry,
+            process,
+            version,
+            workspace_symbols,
+            document_symbols,
+            inlay_hints,
+            started_at: Instant::now(),
+        });
     }
 
     fn command_names(&self) -> Vec<String> {
-        self.commands.keys().cloned().collect()
+        HOST_COMMANDS
+            .iter()
+            .map(|id| id.to_string())
+            .chain(self.commands.keys().cloned())
+            .chain(self.lazy_commands.keys().cloned())
+            .collect()
+    }
+
+    /// Stop a plugin registering commands that are already `taken` by other
+    /// plugins and keep it stopped.
+    async fn refuse_plugin(
+        &mut self,
+        entry: PluginEntry,
+        process: PluginProcess,
+        taken: Vec<(String, String)>,
+    ) {
+        let ids: Vec<&str> = taken.iter().map(|(id, _)| id.as_str()).collect();
+        let message = format!(
+            "plugin `{}` was not started: commands {} are already registered by other plugins",
+            entry.name,
+            ids.join(", ")
+        );
+        log::error!("{message}");
+        self.client
+            .show_message(lsp::MessageType::ERROR, message.clone())
+            .await;
+        self.record_error(&entry.name, message);
+
+        for (command, owner) in taken {
+            self.conflicts.push(CommandConflict {
+                command,
+                owner,
+                plugin: entry.name.clone(),
+                policy: ConflictPolicy::Error,
+                registered_as: None,
+            });
+        }
+        process.shutdown().await;
+        self.failed.push(entry);
     }
 
-    fn lookup_command(&self, name: &str) -> Option<CommandBinding> {
+    /// Look up the binding for `name`. Commands of per-workspace plugins
+    /// resolve to the instance whose folder contains `document`, or the first
+    /// instance otherwise.
+    fn resolve_command(&self, name: &str, document: Option<&Path>) -> Option<CommandBinding> {
+        let routed = document.and_then(|document| {
+            self.routes
+                .get(name)?
+                .iter()
+                .filter(|binding| {
+                    binding
+                        .workspace
+                        .as_ref()
+                        .is_some_and(|folder| document.starts_with(folder))
+                })
+                .max_by_key(|binding| {
+                    binding
+                        .workspace
+                        .as_ref()
+                        .map(|folder| folder.as_os_str().len())
+                })
+        });
+        if let Some(binding) = routed {
+            return Some(binding.clone());
+        }
         self.commands.get(name).cloned()
     }
 
-    async fn shutdown_all(&mut self) {
-        for (_, plugin) in &self.plugins {
-            if let Err(err) = plugin.shutdown().await {
-                log::warn!("failed to gracefully shutdown plugin: {err:?}");
+    /// Apply settings received from the editor and push the changed plugin
+    /// settings to running plugins.
+    fn apply_settings(&mut self, settings: HostSettings) {
+        log::set_max_level(settings.log_level().unwrap_or(self.default_log_level));
+
+        let previous = std::mem::replace(&mut self.settings, settings);
+        for plugin in &self.plugins {
+            let key = settings_key(&plugin.entry);
+            if self.settings.plugins.get(key) != previous.plugins.get(key) {
+                self.push_settings(&plugin.entry, &plugin.process);
             }
         }
+    }
+
+    /// Send the settings of `process` if it subscribed to them.
+    fn push_settings(&self, entry: &PluginEntry, process: &PluginProcess) {
+        if !self
+            .subscriptions
+            .is_subscribed(process.name(), CONFIGURATION_TOPIC)
+        {
+            return;
+        }
+        let payload = self
+            .settings
+            .plugins
+            .get(settings_key(entry))
+            .cloned()
+            .unwrap_or(Value::Null);
+        deliver_event(
+            process.clone(),
+            BusMessage {
+                source: HOST_PLUGIN_NAME.to_string(),
+                topic: CONFIGURATION_TOPIC.to_string(),
+                payload,
+            },
+        );
+    }
+
+    /// Running plugins for which `provides` holds.
+    fn providers(&self, provides: impl Fn(&LoadedPlugin) -> bool) -> Vec<Provider> {
+        self.plugins
+            .iter()
+            .filter(|plugin| provides(plugin))
+            .map(|plugin| {
+                let base = plugin
+                    .entry
+                    .workspace
+                    .clone()
+                    .or_else(|| self.workspace_root.clone());
+                let timeout = plugin
+                    .process
+                    .timeout()
+                    .or_else(|| self.settings.request_timeout());
+                Provider {
+                    process: plugin.process.clone(),
+                    base,
+                    timeout,
+                }
+            })
+            .collect()
+    }
+
+    /// Subscribers of `message` serving documents of `language_id`.
+    fn bus_recipients(
+        &self,
+        message: &bus::BusMessage,
+        language_id: Option<&str>,
+    ) -> Vec<PluginProcess> {
+        self.subscriptions
+            .recipients(message)
+            .filter_map(|name| {
+                self.plugins
+                    .iter()
+                    .find(|plugin| plugin.entry.name == name)
+                    .filter(|plugin| plugin.entry.serves_language(language_id))
+                    .map(|plugin| plugin.process.clone())
+            })
+            .collect()
+    }
+
+    async fn shutdown_all(&mut self) {
+        let shutdowns = self.plugins.iter().map(|plugin| plugin.process.shutdown());
+        futures::future::join_all(shutdowns).await;
         self.plugins.clear();
         self.commands.clear();
+        self.routes.clear();
+        self.dormant.clear();
+        self.lazy_commands.clear();
+        self.subscriptions.clear();
+        self.unavailable.clear();
+        self.restart_attempts.clear();
+        self.metrics.clear();
+        self.results.clear();
+        self.command_failures.clear();
+        self.failed.clear();
+        self.restarting.clear();
+        self.launching.clear();
+        self.disabled.clear();
         self.initialized = false;
     }
 }
 
+/// Name plugin settings are looked up under; per-workspace instances share
+/// the settings of their plugin.
+fn settings_key(entry: &PluginEntry) -> &str {
+    entry.instance_of.as_deref().unwrap_or(&entry.name)
+}
+
+/// Documents opened in the editor, used to resolve the active language and
+/// workspace folder.
+#[derive(Default)]
+struct DocumentTracker {
+    languages: HashMap<lsp::Url, String>,
+    active: Option<lsp::Url>,
+}
+
+impl DocumentTracker {
+    fn open(&mut self, uri: lsp::Url, language_id: String) {
+        self.languages.insert(uri.clone(), language_id);
+        self.active = Some(uri);
+    }
+
+    fn focus(&mut self, uri: &lsp::Url) {
+        if self.languages.contains_key(uri) {
+            self.active = Some(uri.clone());
+        }
+    }
+
+    fn close(&mut self, uri: &lsp::Url) {
+        self.languages.remove(uri);
+        if self.active.as_ref() == Some(uri) {
+            self.active = None;
+        }
+    }
+
+    fn language(&self, uri: &lsp::Url) -> Option<String> {
+        self.languages.get(uri).cloned()
+    }
+
+    fn active_path(&self) -> Option<PathBuf> {
+        self.active.as_ref()?.to_file_path().ok()
+    }
+
+    fn active_language(&self) -> Option<String> {
+        self.active
+            .as_ref()
+            .and_then(|uri| self.languages.get(uri))
+            .cloned()
+    }
+}
+
 #[derive(Clone)]
 pub struct PluginHost {
-    client: Client,
     options: HostOptions,
     manager: Arc<Mutex<PluginManager>>,
+    documents: Arc<parking_lot::Mutex<DocumentTracker>>,
+}
+
+/// Stops the plugins of a [`PluginHost`] from outside the language server.
+#[derive(Clone)]
+pub struct ShutdownHandle(Arc<Mutex<PluginManager>>);
+
+impl ShutdownHandle {
+    pub async fn shutdown(&self) {
+        self.0.lock().await.shutdown_all().await;
+    }
 }
 
 impl PluginHost {
     pub fn new(client: Client, options: HostOptions) -> Self {
-        let manager = PluginManager::new(options.clone());
-        Self {
+        let (bus_tx, bus_rx) = bus::channel();
+        let (exits_tx, exits_rx) = tokio::sync::mpsc::unbounded_channel();
+        let handles = HostHandles {
+            client: Some(client.clone()),
+            bus: bus_tx,
+            exits: exits_tx,
+            tracer: options.tracer(),
+            store: SharedStore::default(),
+            secrets: SecretStore::default(),
+            output: OutputChannels::default(),
+            diagnostics: PluginDiagnostics::default(),
+        };
+        let manager = Arc::new(Mutex::new(PluginManager::new(
+            options.clone(),
             client,
+            handles,
+        )));
+        let documents = Arc::new(parking_lot::Mutex::new(DocumentTracker::default()));
+        spawn_bus_dispatcher(Arc::clone(&manager), Arc::clone(&documents), bus_rx);
+        spawn_restart_supervisor(Arc::clone(&manager), exits_rx);
+        spawn_metrics_logger(Arc::downgrade(&manager));
+        Self {
             options,
-            manager: Arc::new(Mutex::new(manager)),
+            manager,
+            documents,
+        }
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle(Arc::clone(&self.manager))
+    }
+
+    /// Handler of the `$/helix/pluginStatus` request.
+    pub async fn plugin_status(&self) -> tower_lsp::jsonrpc::Result<PluginStatusResult> {
+        let plugins = self.manager.lock().await.plugin_statuses();
+        Ok(PluginStatusResult { plugins })
+    }
+
+    /// Send `payload` to every provider concurrently and collect the
+    /// responses `accept` takes. A failing plugin is logged and only loses
+    /// its own contribution to the `feature`.
+    async fn query_providers<T>(
+        &self,
+        providers: Vec<Provider>,
+        payload: HostRequestPayload,
+        feature: &str,
+        accept: impl Fn(PluginResponse) -> Result<T, PluginResponse>,
+    ) -> Vec<(Provider, T)> {
+        let requests = providers.into_iter().map(|provider| {
+            let payload = payload.clone();
+            async move {
+                let response = provider
+                    .process
+                    .send_request_with_timeout(payload, provider.timeout)
+                    .await;
+                (provider, response)
+            }
+        });
+
+        let mut accepted = Vec::new();
+        let mut failures = Vec::new();
+        for (provider, response) in futures::future::join_all(requests).await {
+            let name = provider.process.name().to_string();
+            match response.map(&accept) {
+                Ok(Ok(value)) => accepted.push((provider, value)),
+                Ok(Err(PluginResponse::CommandError { message })) => failures.push((name, message)),
+                Ok(Err(other)) => {
+                    failures.push((name, format!("unexpected response: {other:?}")));
+                }
+                Err(err) => failures.push((name, format!("{err:#}"))),
+            }
+        }
+
+        if !failures.is_empty() {
+            let mut manager = self.manager.lock().await;
+            for (plugin, message) in failures {
+                log::warn!("plugin `{plugin}` failed to answer {feature}: {message}");
+                manager.record_error(&plugin, format!("{feature} failed: {message}"));
+            }
+        }
+        accepted
+    }
+
+    async fn execute_host_command(
+        &self,
+        command: &str,
+        arguments: &[Value],
+    ) -> Result<Option<Value>, RpcError> {
+        let mut manager = self.manager.lock().await;
+        match command {
+            STATUS_COMMAND => Ok(Some(manager.status_report())),
+            COMMANDS_COMMAND => {
+                let language_id = self.documents.lock().active_language();
+                Ok(Some(manager.commands_report(language_id.as_deref())))
+            }
+            CONFLICTS_COMMAND => Ok(Some(manager.conflicts_report())),
+            METRICS_COMMAND => Ok(Some(manager.metrics.report())),
+            RESTART_COMMAND => {
+                let name = plugin_name_argument(arguments)?;
+                manager
+                    .restart_by_name(name)
+                    .await
+                    .map_err(internal_error)?;
+                Ok(None)
+            }
+            ENABLE_COMMAND => {
+                let name = plugin_name_argument(arguments)?;
+                manager.enable_plugin(name).await.map_err(internal_error)?;
+                Ok(None)
+            }
+            DISABLE_COMMAND => {
+                let name = plugin_name_argument(arguments)?;
+                manager.disable_plugin(name).await.map_err(internal_error)?;
+                Ok(None)
+            }
+            OPEN_LOG_COMMAND => {
+                let name = plugin_name_argument(arguments)?;
+                let path = logfile::path(name);
+                if !path.is_file() {
+                    return Err(internal_error(format!("plugin `{name}` has no log file")));
+                }
+                Ok(Some(json!(path)))
+            }
+            SHOW_OUTPUT_COMMAND => {
+                let name = plugin_name_argument(arguments)?;
+                let output = &manager.handles.output;
+                let Some(channel) = channel_argument(arguments) else {
+                    let channels = output.channels(name);
+                    return Ok(Some(json!({ "plugin": name, "channels": channels })));
+                };
+                let text = output.contents(name, channel).ok_or_else(|| {
+                    internal_error(format!("plugin `{name}` has no output channel `{channel}`"))
+                })?;
+                let path = output::snapshot(name, channel, &text).map_err(internal_error)?;
+                Ok(Some(json!({
+                    "plugin": name,
+                    "channel": channel,
+                    "text": text,
+                    "path": path,
+                })))
+            }
+            _ => Err(method_not_found(command)),
         }
     }
 }
 
+/// Extract the plugin name from `["name"]` or `[{ "name": "name" }]`.
+fn plugin_name_argument(arguments: &[Value]) -> Result<&str, RpcError> {
+    arguments
+        .first()
+        .and_then(|argument| argument.as_str().or_else(|| argument["name"].as_str()))
+        .ok_or_else(|| RpcError::invalid_params("expected a plugin name argument"))
+}
+
+/// Extract the channel name from `["name", "channel"]` or
+/// `[{ "name": "name", "channel": "channel" }]`.
+fn channel_argument(arguments: &[Value]) -> Option<&str> {
+    arguments
+        .get(1)
+        .and_then(Value::as_str)
+        .or_else(|| arguments.first()?["channel"].as_str())
+}
+
+/// Forward events published on the bus to every subscribed plugin serving
+/// the language of the active document.
+fn spawn_bus_dispatcher(
+    manager: Arc<Mutex<PluginManager>>,
+    documents: Arc<parking_lot::Mutex<DocumentTracker>>,
+    mut receiver: BusReceiver,
+) {
+    tokio::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            let language_id = documents.lock().active_language();
+            let recipients = manager
+                .lock()
+                .await
+                .bus_recipients(&message, language_id.as_deref());
+            for plugin in recipients {
+                deliver_event(plugin, message.clone());
+            }
+        }
+    });
+}
+
+/// Deliver `message` to `plugin` in the background.
+fn deliver_event(plugin: PluginProcess, message: BusMessage) {
+    tokio::spawn(async move {
+        let response = plugin
+            .send_request(HostRequestPayload::BusEvent {
+                topic: message.topic.clone(),
+                source: message.source,
+                payload: message.payload,
+            })
+            .await;
+        match response {
+            Ok(PluginResponse::CommandError { message: err }) => log::warn!(
+                "plugin `{}` failed to handle `{}` event: {err}",
+                plugin.name(),
+                message.topic
+            ),
+            Err(err) => log::warn!(
+                "failed to deliver `{}` event to plugin `{}`: {err:?}",
+                message.topic,
+                plugin.name()
+            ),
+            Ok(_) => {}
+        }
+    });
+}
+
 #[tower_lsp::async_trait]
 impl LanguageServer for PluginHost {
     async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult, RpcError> {
@@ -205,10 +2057,25 @@ async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult,
             .as_ref()
             .and_then(|uri| uri.to_file_path().ok());
 
+        let mut workspace_folders: Vec<PathBuf> = params
+            .workspace_folders
+            .iter()
+            .flatten()
+            .filter_map(|folder| folder.uri.to_file_path().ok())
+            .collect();
+        if workspace_folders.is_empty() {
+            workspace_folders.extend(workspace_root.clone());
+        }
+        let workspace_root = workspace_root.or_else(|| workspace_folders.first().cloned());
+        let capabilities = HostCapabilities {
+            run_in_terminal: lsp_ext::supports_run_in_terminal(&params.capabilities),
+            input: lsp_ext::supports_input(&params.capabilities),
+        };
+
         {
             let mut manager = self.manager.lock().await;
             manager
-                .ensure_initialized(&self.client, workspace_root.as_deref())
+                .ensure_initialized(workspace_root.as_deref(), &workspace_folders, capabilities)
                 .await
                 .map_err(internal_error)?;
         }
@@ -219,10 +2086,27 @@ async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult,
         };
 
         let capabilities = lsp::ServerCapabilities {
+            text_document_sync: Some(lsp::TextDocumentSyncCapability::Options(
+                lsp::TextDocumentSyncOptions {
+                    open_close: Some(true),
+                    change: Some(lsp::TextDocumentSyncKind::NONE),
+                    ..Default::default()
+                },
+            )),
             execute_command_provider: Some(lsp::ExecuteCommandOptions {
                 commands: command_names,
                 ..Default::default()
             }),
+            document_symbol_provider: Some(lsp::OneOf::Left(true)),
+            inlay_hint_provider: Some(lsp::OneOf::Left(true)),
+            workspace_symbol_provider: Some(lsp::OneOf::Left(true)),
+            workspace: Some(lsp::WorkspaceServerCapabilities {
+                workspace_folders: Some(lsp::WorkspaceFoldersServerCapabilities {
+                    supported: Some(true),
+                    change_notifications: Some(lsp::OneOf::Left(true)),
+                }),
+                file_operations: None,
+            }),
             ..Default::default()
         };
 
@@ -238,8 +2122,22 @@ async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult,
     async fn initialized(&self, _: lsp::InitializedParams) {
         log::info!(
             "Helix plugin host initialized (manifest: {})",
-            self.options.manifest_path().display()
+            self.options.manifest_display()
         );
+
+        for manifest_path in self.options.manifest_paths() {
+            if let Err(err) =
+                spawn_manifest_watcher(manifest_path.clone(), Arc::clone(&self.manager))
+            {
+                log::warn!(
+                    "failed to watch plugin manifest `{}` for changes: {err:?}",
+                    manifest_path.display()
+                );
+            }
+        }
+
+        let client = self.manager.lock().await.client.clone();
+        spawn_update_check(self.options.clone(), client);
     }
 
     async fn shutdown(&self) -> Result<(), RpcError> {
@@ -248,6 +2146,47 @@ async fn shutdown(&self) -> Result<(), RpcError> {
         Ok(())
     }
 
+    async fn did_change_configuration(&self, params: lsp::DidChangeConfigurationParams) {
+        match HostSettings::from_lsp(params.settings) {
+            Ok(settings) => self.manager.lock().await.apply_settings(settings),
+            Err(err) => log::warn!("ignoring invalid `{}` settings: {err}", settings::SECTION),
+        }
+    }
+
+    async fn did_change_workspace_folders(&self, params: lsp::DidChangeWorkspaceFoldersParams) {
+        let mut manager = self.manager.lock().await;
+        if let Err(err) = manager.change_workspace_folders(params.event).await {
+            log::error!("failed to apply workspace folder change: {err:?}");
+        }
+    }
+
+    async fn did_open(&self, params: lsp::DidOpenTextDocumentParams) {
+        let document = params.text_document;
+        self.documents
+            .lock()
+            .open(document.uri, document.language_id.clone());
+        // Activated plugins are launched without the lock held, so requests
+        // for other plugins are not held up meanwhile.
+        let launch = {
+            let mut manager = self.manager.lock().await;
+            if !manager.initialized {
+                return;
+            }
+            manager.activate_language(&document.language_id)
+        };
+        if let Some(launch) = launch {
+            launch.run(&self.manager).await;
+        }
+    }
+
+    async fn did_change(&self, params: lsp::DidChangeTextDocumentParams) {
+        self.documents.lock().focus(&params.text_document.uri);
+    }
+
+    async fn did_close(&self, params: lsp::DidCloseTextDocumentParams) {
+        self.documents.lock().close(&params.text_document.uri);
+    }
+
     async fn execute_command(
         &self,
         params: lsp::ExecuteCommandParams,
@@ -256,29 +2195,401 @@ async fn execute_command(
             command, arguments, ..
         } = params;
 
-        let binding = {
-            let manager = self.manager.lock().await;
-            manager.lookup_command(&command)
+        if HOST_COMMANDS.contains(&command.as_str()) {
+            return self.execute_host_command(&command, &arguments).await;
         }
-        .ok_or_else(|| method_not_found(&command))?;
 
+        let document = self.documents.lock().active_path();
+        let language_id = self.documents.lock().active_language();
+        let (binding, timeout, key) = {
+            let mut manager = self.manager.lock().await;
+            // Commands of crashed or launching plugins are queued until the
+            // plugin started and registered its commands.
+            if !manager.offers_command(&command, language_id.as_deref()) {
+                return Err(command_not_offered(&command, language_id.as_deref()));
+            }
+            let deadline = tokio::time::Instant::now() + RESTART_WAIT_TIMEOUT;
+            let binding = loop {
+                if let Some(binding) = manager.resolve_command(&command, document.as_deref()) {
+                    break binding;
+                }
+                // Lazy plugins are launched without the lock held, so
+                // requests for other plugins are not held up meanwhile.
+                let lazy = manager.lazy_commands.get(&command).cloned();
+                let woken = lazy
+                    .as_ref()
+                    .and_then(|plugin| manager.wake(plugin, &format!("first use of `{command}`")));
+                if let Some(launch) = woken {
+                    drop(manager);
+                    launch.run(&self.manager).await;
+                    manager = self.manager.lock().await;
+                    continue;
+                }
+                let plugin = match lazy {
+                    Some(plugin)
+                        if manager.is_launching(&plugin)
+                            && tokio::time::Instant::now() < deadline =>
+                    {
+                        plugin
+                    }
+                    _ => {
+                        let unavailable = manager
+                            .unavailable
+                            .get(&command)
+                            .ok_or_else(|| method_not_found(&command))?;
+                        if !manager.is_restarting(&unavailable.plugin)
+                            || tokio::time::Instant::now() >= deadline
+                        {
+                            return Err(command_unavailable(&command, unavailable));
+                        }
+                        unavailable.plugin.clone()
+                    }
+                };
+                log::debug!("queueing `{command}` until plugin `{plugin}` started");
+                let mut restarts = manager.restarts.subscribe();
+                drop(manager);
+                let _ = tokio::time::timeout_at(deadline, restarts.changed()).await;
+                manager = self.manager.lock().await;
+            };
+            let key = RequestKey::new(
+                binding.plugin.name(),
+                &binding.id,
+                &arguments,
+                language_id.as_deref(),
+            );
+            if let Some(result) = manager.results.get(&key) {
+                return Ok(result);
+            }
+            let timeout = binding
+                .plugin
+                .timeout()
+                .or_else(|| manager.settings.request_timeout());
+            (binding, timeout, key)
+        };
+
+        let started = Instant::now();
         let response = binding
             .plugin
-            .send_request(HostRequestPayload::Execute {
-                command: command.clone(),
-                arguments,
-            })
+            .send_request_with_timeout(
+                HostRequestPayload::Execute {
+                    command: binding.id.clone(),
+                    arguments,
+                    language_id,
+                },
+                timeout,
+            )
+            .await;
+
+        // Errors reported by the plugin itself do not count against it; only
+        // timeouts, crashes and protocol violations trip the breaker.
+        let mut cache_ttl = None;
+        let (result, misbehaved) = match response {
+            Ok(PluginResponse::CommandResult {
+                result,
+                cache_ttl_ms,
+            }) => {
+                cache_ttl = cache_ttl_ms.map(Duration::from_millis);
+                (Ok(result), false)
+            }
+            Ok(PluginResponse::CommandError { message }) => (Err(internal_error(message)), false),
+            Ok(other) => (
+                Err(internal_error(format!(
+                    "plugin returned unexpected response for executeCommand: {other:?}"
+                ))),
+                true,
+            ),
+            Err(err) => (Err(request_error(err)), true),
+        };
+
+        let mut manager = self.manager.lock().await;
+        let plugin = binding.plugin.name();
+        if let (Ok(result), Some(ttl)) = (&result, cache_ttl) {
+            manager.results.insert(key, result.clone(), ttl);
+        }
+        if let Err(error) = &result {
+            manager.record_error(plugin, format!("`{command}` failed: {}", error.message));
+        }
+        manager
+            .metrics
+            .record(plugin, &command, started.elapsed(), result.is_err());
+        manager.record_command_outcome(plugin, !misbehaved).await;
+        result
+    }
+
+    async fn symbol(
+        &self,
+        params: lsp::WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<lsp::SymbolInformation>>, RpcError> {
+        let providers = self
+            .manager
+            .lock()
             .await
-            .map_err(internal_error)?;
+            .providers(|plugin| plugin.workspace_symbols);
+        let found = self
+            .query_providers(
+                providers,
+                HostRequestPayload::WorkspaceSymbols {
+                    query: params.query,
+                },
+                "workspace symbol search",
+                |response| match response {
+                    PluginResponse::WorkspaceSymbols { symbols } => Ok(symbols),
+                    other => Err(other),
+                },
+            )
+            .await;
 
-        match response {
-            PluginResponse::CommandResult { result } => Ok(result),
-            PluginResponse::CommandError { message } => Err(internal_error(message)),
-            other => Err(internal_error(format!(
-                "plugin returned unexpected response for executeCommand: {other:?}"
-            ))),
+        let symbols = found
+            .into_iter()
+            .flat_map(|(provider, found)| {
+                let name = provider.process.name().to_string();
+                found.into_iter().filter_map(move |symbol| {
+                    symbols::workspace_to_lsp(symbol, &name, provider.base.as_deref())
+                })
+            })
+            .collect();
+        Ok(Some(symbols))
+    }
+
+    async fn document_symbol(
+        &self,
+        params: lsp::DocumentSymbolParams,
+    ) -> Result<Option<lsp::DocumentSymbolResponse>, RpcError> {
+        let uri = params.text_document.uri;
+        let Some(language_id) = self.documents.lock().language(&uri) else {
+            return Ok(None);
+        };
+        let Ok(path) = uri.to_file_path() else {
+            return Ok(None);
+        };
+        let providers = self.manager.lock().await.providers(|plugin| {
+            plugin
+                .document_symbols
+                .iter()
+                .any(|language| *language == language_id)
+        });
+        if providers.is_empty() {
+            return Ok(None);
+        }
+
+        let outlines = self
+            .query_providers(
+                providers,
+                HostRequestPayload::DocumentSymbols {
+                    path: path.to_string_lossy().into_owned(),
+                    language_id,
+                },
+                "document symbol request",
+                |response| match response {
+                    PluginResponse::DocumentSymbols { symbols } => Ok(symbols),
+                    other => Err(other),
+                },
+            )
+            .await;
+        let symbols = outlines
+            .into_iter()
+            .flat_map(|(_, symbols)| symbols)
+            .map(symbols::document_to_lsp)
+            .collect();
+        Ok(Some(lsp::DocumentSymbolResponse::Nested(symbols)))
+    }
+
+    async fn inlay_hint(
+        &self,
+        params: lsp::InlayHintParams,
+    ) -> Result<Option<Vec<lsp::InlayHint>>, RpcError> {
+        let uri = params.text_document.uri;
+        let Some(language_id) = self.documents.lock().language(&uri) else {
+            return Ok(None);
+        };
+        let Ok(path) = uri.to_file_path() else {
+            return Ok(None);
+        };
+        let providers = self.manager.lock().await.providers(|plugin| {
+            plugin
+                .inlay_hints
+                .iter()
+                .any(|language| *language == language_id)
+        });
+        if providers.is_empty() {
+            return Ok(None);
+        }
+
+        let found = self
+            .query_providers(
+                providers,
+                HostRequestPayload::InlayHints {
+                    path: path.to_string_lossy().into_owned(),
+                    language_id,
+                    range: symbols::range_from_lsp(params.range),
+                },
+                "inlay hint request",
+                |response| match response {
+                    PluginResponse::InlayHints { hints } => Ok(hints),
+                    other => Err(other),
+                },
+            )
+            .await;
+        let hints = found
+            .into_iter()
+            .flat_map(|(_, hints)| hints)
+            .map(hints::to_lsp)
+            .collect();
+        Ok(Some(hints))
+    }
+}
+
+/// Periodically log the statistics of commands executed since the last time,
+/// until the host is dropped.
+fn spawn_metrics_logger(manager: Weak<Mutex<PluginManager>>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(METRICS_LOG_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let Some(manager) = manager.upgrade() else {
+                return;
+            };
+            manager.lock().await.metrics.log_summary();
+        }
+    });
+}
+
+/// Restart plugins that exit unexpectedly, backing off exponentially.
+fn spawn_restart_supervisor(manager: Arc<Mutex<PluginManager>>, mut exits: ExitReceiver) {
+    tokio::spawn(async move {
+        while let Some(exit) = exits.recv().await {
+            let manager = Arc::clone(&manager);
+            tokio::spawn(async move {
+                let Some((entry, delay)) = manager.lock().await.schedule_restart(&exit).await
+                else {
+                    return;
+                };
+                tokio::time::sleep(delay).await;
+                let launch = manager.lock().await.begin_restart(entry);
+                if let Some(launch) = launch {
+                    launch.run(&manager).await;
+                }
+            });
+        }
+    });
+}
+
+/// Tell the user about installed plugins with a newer release, unless the
+/// manifest sets `check_updates = false`.
+fn spawn_update_check(options: HostOptions, client: Client) {
+    tokio::spawn(async move {
+        let Ok(manifest) = options.load_manifest() else {
+            return;
+        };
+        if !manifest.check_updates.unwrap_or(true) {
+            return;
+        }
+        let installed = match Installed::load(&options) {
+            Ok(installed) => installed,
+            Err(err) => {
+                log::warn!("{err:#}");
+                return;
+            }
+        };
+        if installed.plugins.is_empty() {
+            return;
+        }
+
+        let index = registry::fetch_optional(manifest.registry.as_deref()).await;
+        for (name, update) in install::updates(&installed, &index).await {
+            client
+                .show_message(
+                    lsp::MessageType::INFO,
+                    format!(
+                        "plugin `{name}` has an update ({update}); run `helix-plugin-host upgrade {name}`"
+                    ),
+                )
+                .await;
+        }
+    });
+}
+
+/// Reload plugins whenever the manifest file changes on disk.
+///
+/// The parent directory is watched rather than the file itself so that editors
+/// which save by replacing the file are picked up as well.
+fn spawn_manifest_watcher(
+    manifest_path: PathBuf,
+    manager: Arc<Mutex<PluginManager>>,
+) -> Result<()> {
+    use notify::{RecursiveMode, Watcher};
+
+    let watch_dir = manifest_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let file_name = manifest_path.file_name().map(|name| name.to_os_string());
+    let drop_in_dir = PluginManifest::drop_in_dir(&manifest_path);
+    let drop_in_prefix = drop_in_dir.clone();
+    let included_files = PluginManifest::load(&manifest_path)
+        .map(|manifest| manifest.included_files)
+        .unwrap_or_default();
+    let included = included_files.clone();
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else { return };
+        if event.kind.is_access() {
+            return;
+        }
+        let touches_manifest = event.paths.iter().any(|path| {
+            path.file_name().map(|name| name.to_os_string()) == file_name
+                || path.starts_with(&drop_in_prefix)
+                || included.contains(path)
+        });
+        if touches_manifest {
+            let _ = tx.send(());
         }
+    })?;
+    watcher
+        .watch(&watch_dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("failed to watch `{}`", watch_dir.display()))?;
+    // Only drop-in directories and includes present at startup are watched;
+    // creating a drop-in directory later still triggers a reload through the
+    // manifest directory watch.
+    let mut include_dirs: Vec<&Path> = included_files
+        .iter()
+        .filter_map(|file| file.parent())
+        .filter(|dir| *dir != watch_dir)
+        .collect();
+    include_dirs.sort();
+    include_dirs.dedup();
+    for dir in include_dirs {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("failed to watch `{}`", dir.display()))?;
     }
+    if drop_in_dir.is_dir() {
+        watcher
+            .watch(&drop_in_dir, RecursiveMode::Recursive)
+            .with_context(|| format!("failed to watch `{}`", drop_in_dir.display()))?;
+    }
+
+    tokio::spawn(async move {
+        // Keep the watcher alive for as long as the task runs.
+        let _watcher = watcher;
+        while rx.recv().await.is_some() {
+            // Editors frequently emit several events per save; coalesce them.
+            tokio::time::sleep(MANIFEST_RELOAD_DEBOUNCE).await;
+            while rx.try_recv().is_ok() {}
+
+            log::info!(
+                "plugin manifest `{}` changed, reloading",
+                manifest_path.display()
+            );
+            if let Err(err) = manager.lock().await.reload().await {
+                log::error!("failed to reload plugin manifest: {err:?}");
+            }
+        }
+    });
+
+    Ok(())
 }
 
 fn internal_error(err: impl ToString) -> RpcError {
@@ -289,6 +2600,47 @@ fn internal_error(err: impl ToString) -> RpcError {
     }
 }
 
+/// Map a failed plugin request to an RPC error, reporting timeouts with
+/// structured data so clients can tell them apart from plugin failures.
+fn request_error(err: anyhow::Error) -> RpcError {
+    match err.downcast_ref::<RequestTimeout>() {
+        Some(timeout) => RpcError {
+            code: ErrorCode::ServerError(REQUEST_FAILED),
+            message: timeout.to_string().into(),
+            data: Some(serde_json::json!({
+                "kind": "timeout",
+                "plugin": timeout.plugin,
+                "timeout_ms": timeout.timeout.as_millis() as u64,
+            })),
+        },
+        None => internal_error(err),
+    }
+}
+
+fn command_unavailable(command: &str, unavailable: &UnavailableCommand) -> RpcError {
+    RpcError {
+        code: ErrorCode::ServerError(REQUEST_FAILED),
+        message: format!(
+            "command `{command}` is unavailable: plugin `{}` {}",
+            unavailable.plugin, unavailable.reason
+        )
+        .into(),
+        data: None,
+    }
+}
+
+fn command_not_offered(command: &str, language_id: Option<&str>) -> RpcError {
+    RpcError {
+        code: ErrorCode::ServerError(REQUEST_FAILED),
+        message: format!(
+            "command `{command}` is not available in {} documents",
+            language_id.unwrap_or("these")
+        )
+        .into(),
+        data: None,
+    }
+}
+
 fn method_not_found(command: &str) -> RpcError {
     RpcError {
         code: ErrorCode::MethodNotFound,
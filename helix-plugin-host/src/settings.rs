@@ -0,0 +1,57 @@
+//! Host settings pushed by the editor through
+//! `workspace/didChangeConfiguration`.
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::{collections::HashMap, time::Duration};
+
+/// Section of the editor configuration read by the host.
+pub const SECTION: &str = "helix-plugin-host";
+
+/// Bus topic on which plugins receive their settings. Plugins subscribing to
+/// it get their entry of [`HostSettings::plugins`] whenever it changes, and
+/// `null` once it is removed.
+pub const CONFIGURATION_TOPIC: &str = "helix.configuration";
+
+/// Settings of the `helix-plugin-host` section.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HostSettings {
+    /// Maximum level of host log records, e.g. `"debug"`.
+    pub log_level: Option<String>,
+    /// Timeout applied to command requests of plugins without `timeout_ms`.
+    pub request_timeout_ms: Option<u64>,
+    /// Arbitrary settings forwarded to plugins, keyed by plugin name.
+    pub plugins: HashMap<String, Value>,
+}
+
+impl HostSettings {
+    /// Parse the settings sent by the editor. Both the whole configuration
+    /// object and the bare `helix-plugin-host` section are accepted.
+    pub fn from_lsp(mut settings: Value) -> serde_json::Result<Self> {
+        if let Some(section) = settings.get_mut(SECTION) {
+            settings = section.take();
+        }
+        if settings.is_null() {
+            return Ok(Self::default());
+        }
+        serde_json::from_value(settings)
+    }
+
+    /// Host-wide request timeout.
+    pub fn request_timeout(&self) -> Option<Duration> {
+        self.request_timeout_ms.map(Duration::from_millis)
+    }
+
+    /// Parsed log level, `None` when unset or invalid.
+    pub fn log_level(&self) -> Option<log::LevelFilter> {
+        let level = self.log_level.as_deref()?;
+        match level.parse() {
+            Ok(level) => Some(level),
+            Err(_) => {
+                log::warn!("ignoring invalid `logLevel` setting `{level}`");
+                None
+            }
+        }
+    }
+}
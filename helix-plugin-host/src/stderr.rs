@@ -0,0 +1,207 @@
+//! Log level detection for lines plugins write to stderr.
+//!
+//! Plugins commonly log through `env_logger`, Python's `logging` or a JSON
+//! logger such as `pino`. Recognised lines are logged at their own level with
+//! the prefix stripped; anything else is logged as info, except for panics
+//! and tracebacks which are logged as errors.
+
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// Level and message of a stderr line.
+pub fn parse_line(line: &str) -> (log::Level, Cow<'_, str>) {
+    let line = line.trim_end();
+    if line.starts_with('{') {
+        if let Some((level, message)) = parse_json(line) {
+            return (level, Cow::Owned(message));
+        }
+    }
+    let (level, message) = parse_env_logger(line)
+        .or_else(|| parse_prefix(line))
+        .unwrap_or_else(|| (fallback_level(line), line));
+    (level, Cow::Borrowed(message))
+}
+
+/// JSON log records with a `level`, `severity` or `lvl` field, either named or
+/// numeric as used by `pino` and `bunyan`.
+fn parse_json(line: &str) -> Option<(log::Level, String)> {
+    let record: Value = serde_json::from_str(line).ok()?;
+    let record = record.as_object()?;
+    let level = ["level", "severity", "lvl"]
+        .iter()
+        .find_map(|key| record.get(*key))?;
+    let level = match level {
+        Value::String(level) => level_from_name(level)?,
+        Value::Number(level) => match level.as_u64()? {
+            0..=10 => log::Level::Trace,
+            11..=20 => log::Level::Debug,
+            21..=30 => log::Level::Info,
+            31..=40 => log::Level::Warn,
+            _ => log::Level::Error,
+        },
+        _ => return None,
+    };
+    let message = ["message", "msg"]
+        .iter()
+        .find_map(|key| record.get(*key)?.as_str())
+        .or_else(|| record.get("fields")?.get("message")?.as_str())
+        .unwrap_or(line);
+    Some((level, message.to_string()))
+}
+
+/// `env_logger` lines such as `[2024-01-01T00:00:00Z WARN  my_plugin] message`.
+fn parse_env_logger(line: &str) -> Option<(log::Level, &str)> {
+    let (header, message) = line.strip_prefix('[')?.split_once(']')?;
+    let level = header.split_whitespace().find_map(level_from_name)?;
+    Some((level, message.trim_start()))
+}
+
+/// Lines starting with a level, such as `error: message`, `WARN message` or
+/// Python's `WARNING:root:message`. The level is followed by a colon or
+/// written in capitals so that sentences like `Error handling disabled` are
+/// not taken for log records.
+fn parse_prefix(line: &str) -> Option<(log::Level, &str)> {
+    let end = line.find(|c: char| c == ':' || c.is_whitespace())?;
+    let word = &line[..end];
+    if !line[end..].starts_with(':') && word.chars().any(|c| !c.is_ascii_uppercase()) {
+        return None;
+    }
+    let level = level_from_name(word)?;
+    let message = line[end..].trim_start_matches(|c: char| c == ':' || c.is_whitespace());
+    Some((level, message))
+}
+
+fn fallback_level(line: &str) -> log::Level {
+    let panicked = line.starts_with("thread '") && line.contains("panicked at");
+    if panicked || line.starts_with("Traceback (most recent call last)") {
+        log::Level::Error
+    } else {
+        log::Level::Info
+    }
+}
+
+fn level_from_name(name: &str) -> Option<log::Level> {
+    let level = match name.to_ascii_lowercase().as_str() {
+        "error" | "err" | "fatal" | "critical" | "crit" | "panic" => log::Level::Error,
+        "warn" | "warning" => log::Level::Warn,
+        "info" | "notice" => log::Level::Info,
+        "debug" => log::Level::Debug,
+        "trace" => log::Level::Trace,
+        _ => return None,
+    };
+    Some(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    fn parsed(line: &str) -> (Level, String) {
+        let (level, message) = parse_line(line);
+        (level, message.into_owned())
+    }
+
+    #[test]
+    fn parses_env_logger_lines() {
+        assert_eq!(
+            parsed("[2024-01-01T00:00:00Z WARN  my_plugin] disk is full"),
+            (Level::Warn, "disk is full".to_string())
+        );
+        assert_eq!(
+            parsed("[DEBUG my_plugin::server] listening"),
+            (Level::Debug, "listening".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_json_lines() {
+        assert_eq!(
+            parsed(r#"{"level":"error","msg":"connection lost"}"#),
+            (Level::Error, "connection lost".to_string())
+        );
+        assert_eq!(
+            parsed(r#"{"level":40,"time":1,"msg":"slow response"}"#),
+            (Level::Warn, "slow response".to_string())
+        );
+        assert_eq!(
+            parsed(r#"{"severity":"INFO","fields":{"message":"ready"}}"#),
+            (Level::Info, "ready".to_string())
+        );
+        assert_eq!(
+            parsed(r#"{"level":"debug"}"#),
+            (Level::Debug, r#"{"level":"debug"}"#.to_string())
+        );
+        // Not a log record.
+        assert_eq!(
+            parsed(r#"{"result": 1}"#),
+            (Level::Info, r#"{"result": 1}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn parses_python_lines() {
+        assert_eq!(
+            parsed("WARNING:root:deprecated option"),
+            (Level::Warn, "root:deprecated option".to_string())
+        );
+        assert_eq!(
+            parsed("CRITICAL:plugin:out of memory"),
+            (Level::Error, "plugin:out of memory".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_level_prefixes() {
+        assert_eq!(
+            parsed("error: no such file"),
+            (Level::Error, "no such file".to_string())
+        );
+        assert_eq!(
+            parsed("WARN cache is stale"),
+            (Level::Warn, "cache is stale".to_string())
+        );
+        assert_eq!(
+            parsed("Info:  started  "),
+            (Level::Info, "started".to_string())
+        );
+    }
+
+    #[test]
+    fn leaves_sentences_starting_with_a_level_alone() {
+        assert_eq!(
+            parsed("Error handling disabled"),
+            (Level::Info, "Error handling disabled".to_string())
+        );
+        assert_eq!(
+            parsed("Critical path: 3 tasks"),
+            (Level::Info, "Critical path: 3 tasks".to_string())
+        );
+        assert_eq!(
+            parsed("debug mode enabled"),
+            (Level::Info, "debug mode enabled".to_string())
+        );
+    }
+
+    #[test]
+    fn falls_back_to_info_and_errors_for_crashes() {
+        assert_eq!(
+            parsed("plain output"),
+            (Level::Info, "plain output".to_string())
+        );
+        assert_eq!(
+            parsed("thread 'main' panicked at src/main.rs:3:5:"),
+            (
+                Level::Error,
+                "thread 'main' panicked at src/main.rs:3:5:".to_string()
+            )
+        );
+        assert_eq!(
+            parsed("Traceback (most recent call last):"),
+            (
+                Level::Error,
+                "Traceback (most recent call last):".to_string()
+            )
+        );
+    }
+}
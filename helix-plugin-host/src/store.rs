@@ -0,0 +1,54 @@
+//! Key-value store the host shares between plugins.
+//!
+//! Cooperating plugins, e.g. a git plugin and a pull request plugin, share
+//! what they detected instead of each shelling out for it. Every plugin
+//! writes to its own namespace; values it marks as shared are readable by
+//! the other plugins. The store lives as long as the host, so values survive
+//! plugin restarts.
+
+use parking_lot::Mutex;
+use serde_json::Value;
+use std::{collections::HashMap, sync::Arc};
+
+/// Store shared by every plugin of the host. Cloning shares the values.
+#[derive(Debug, Clone, Default)]
+pub struct SharedStore(Arc<Mutex<HashMap<String, HashMap<String, StoredValue>>>>);
+
+#[derive(Debug)]
+struct StoredValue {
+    value: Value,
+    /// Whether plugins other than the owner may read the value.
+    shared: bool,
+}
+
+impl SharedStore {
+    /// Value `key` of the `owner` namespace, read by the plugin owning
+    /// `namespace`. Missing values read as `null`.
+    pub fn get(&self, namespace: &str, owner: Option<&str>, key: &str) -> Result<Value, String> {
+        let owner = owner.unwrap_or(namespace);
+        let namespaces = self.0.lock();
+        let Some(stored) = namespaces.get(owner).and_then(|values| values.get(key)) else {
+            return Ok(Value::Null);
+        };
+        if owner != namespace && !stored.shared {
+            return Err(format!("`{key}` of plugin `{owner}` is not shared"));
+        }
+        Ok(stored.value.clone())
+    }
+
+    /// Store `value` as `key` of `namespace`.
+    pub fn set(&self, namespace: &str, key: String, value: Value, shared: bool) {
+        self.0
+            .lock()
+            .entry(namespace.to_string())
+            .or_default()
+            .insert(key, StoredValue { value, shared });
+    }
+
+    /// Remove `key` of `namespace`.
+    pub fn delete(&self, namespace: &str, key: &str) {
+        if let Some(values) = self.0.lock().get_mut(namespace) {
+            values.remove(key);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Symbols plugins contribute to the editor: workspace symbols, e.g. tasks
+//! or TODO items, show up in its symbol picker, and document symbols outline
+//! file types Helix cannot parse, e.g. Makefile targets.
+
+use crate::plugin::resolve_relative;
+use helix_plugin_sdk::protocol::{DocumentSymbol, Position, Range, SymbolKind, WorkspaceSymbol};
+use std::path::Path;
+use tower_lsp::lsp_types as lsp;
+
+/// Convert a symbol of `plugin` to its LSP form. Relative paths are resolved
+/// against `base`; symbols with a relative path and no base are dropped.
+/// Symbols without a container are shown as contained in the plugin.
+pub fn workspace_to_lsp(
+    symbol: WorkspaceSymbol,
+    plugin: &str,
+    base: Option<&Path>,
+) -> Option<lsp::SymbolInformation> {
+    let path = Path::new(&symbol.path);
+    let path = match base {
+        Some(base) => resolve_relative(base, path),
+        None if path.is_absolute() => path.to_path_buf(),
+        None => {
+            log::debug!(
+                "dropping symbol `{}` of plugin `{plugin}`: relative path `{}` without a workspace root",
+                symbol.name,
+                symbol.path
+            );
+            return None;
+        }
+    };
+    let uri = lsp::Url::from_file_path(&path).ok()?;
+    let position = lsp::Position::new(symbol.line, symbol.character);
+
+    #[allow(deprecated)]
+    Some(lsp::SymbolInformation {
+        name: symbol.name,
+        kind: kind_to_lsp(symbol.kind),
+        tags: None,
+        deprecated: None,
+        location: lsp::Location::new(uri, lsp::Range::new(position, position)),
+        container_name: Some(symbol.container.unwrap_or_else(|| plugin.to_string())),
+    })
+}
+
+/// Convert a document symbol and its children to their LSP form.
+pub fn document_to_lsp(symbol: DocumentSymbol) -> lsp::DocumentSymbol {
+    let range = range_to_lsp(symbol.range);
+    let children: Vec<_> = symbol.children.into_iter().map(document_to_lsp).collect();
+
+    #[allow(deprecated)]
+    lsp::DocumentSymbol {
+        name: symbol.name,
+        detail: symbol.detail,
+        kind: kind_to_lsp(symbol.kind),
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range: range,
+        children: (!children.is_empty()).then_some(children),
+    }
+}
+
+pub fn range_to_lsp(range: Range) -> lsp::Range {
+    lsp::Range::new(position_to_lsp(range.start), position_to_lsp(range.end))
+}
+
+pub fn range_from_lsp(range: lsp::Range) -> Range {
+    Range::new(
+        Position::new(range.start.line, range.start.character),
+        Position::new(range.end.line, range.end.character),
+    )
+}
+
+pub fn position_to_lsp(position: Position) -> lsp::Position {
+    lsp::Position::new(position.line, position.character)
+}
+
+fn kind_to_lsp(kind: SymbolKind) -> lsp::SymbolKind {
+    match kind {
+        SymbolKind::File => lsp::SymbolKind::FILE,
+        SymbolKind::Module => lsp::SymbolKind::MODULE,
+        SymbolKind::Namespace => lsp::SymbolKind::NAMESPACE,
+        SymbolKind::Package => lsp::SymbolKind::PACKAGE,
+        SymbolKind::Class => lsp::SymbolKind::CLASS,
+        SymbolKind::Method => lsp::SymbolKind::METHOD,
+        SymbolKind::Property => lsp::SymbolKind::PROPERTY,
+        SymbolKind::Field => lsp::SymbolKind::FIELD,
+        SymbolKind::Constructor => lsp::SymbolKind::CONSTRUCTOR,
+        SymbolKind::Enum => lsp::SymbolKind::ENUM,
+        SymbolKind::Interface => lsp::SymbolKind::INTERFACE,
+        SymbolKind::Function => lsp::SymbolKind::FUNCTION,
+        SymbolKind::Variable => lsp::SymbolKind::VARIABLE,
+        SymbolKind::Constant => lsp::SymbolKind::CONSTANT,
+        SymbolKind::String => lsp::SymbolKind::STRING,
+        SymbolKind::Number => lsp::SymbolKind::NUMBER,
+        SymbolKind::Boolean => lsp::SymbolKind::BOOLEAN,
+        SymbolKind::Array => lsp::SymbolKind::ARRAY,
+        SymbolKind::Object => lsp::SymbolKind::OBJECT,
+        SymbolKind::Key => lsp::SymbolKind::KEY,
+        SymbolKind::Null => lsp::SymbolKind::NULL,
+        SymbolKind::EnumMember => lsp::SymbolKind::ENUM_MEMBER,
+        SymbolKind::Struct => lsp::SymbolKind::STRUCT,
+        SymbolKind::Event => lsp::SymbolKind::EVENT,
+        SymbolKind::Operator => lsp::SymbolKind::OPERATOR,
+        SymbolKind::TypeParameter => lsp::SymbolKind::TYPE_PARAMETER,
+    }
+}
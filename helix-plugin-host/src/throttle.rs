@@ -0,0 +1,150 @@
+//! Rate limiting of the messages plugins show to the user.
+//!
+//! A plugin stuck in a loop could otherwise bury the editor in popups. An
+//! identical message is shown once per [`DUPLICATE_WINDOW`], and at most
+//! `max_messages_per_second` messages are shown per second. Suppressed
+//! messages are logged instead.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Messages shown per second when the entry does not set
+/// `max_messages_per_second`.
+const DEFAULT_MAX_PER_SECOND: u32 = 3;
+
+/// Time during which a message is not shown again.
+const DUPLICATE_WINDOW: Duration = Duration::from_secs(10);
+
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Whether a message is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Show the message, mentioning the messages suppressed since the
+    /// previous one.
+    Show { suppressed: u32 },
+    /// Log the message instead. `first` is set for the first message of a
+    /// burst.
+    Suppress { first: bool },
+}
+
+/// Messages recently shown by a plugin.
+#[derive(Debug)]
+pub struct MessageThrottle {
+    max_per_second: u32,
+    window_start: Instant,
+    shown_in_window: u32,
+    /// Messages shown within [`DUPLICATE_WINDOW`], with the time they were
+    /// shown.
+    recent: HashMap<String, Instant>,
+    suppressed: u32,
+}
+
+impl MessageThrottle {
+    pub fn new(max_per_second: Option<u32>) -> Self {
+        Self {
+            max_per_second: max_per_second.unwrap_or(DEFAULT_MAX_PER_SECOND).max(1),
+            window_start: Instant::now(),
+            shown_in_window: 0,
+            recent: HashMap::new(),
+            suppressed: 0,
+        }
+    }
+
+    /// Decide whether `message` is shown, counting it if so.
+    pub fn check(&mut self, message: &str) -> Verdict {
+        self.check_at(message, Instant::now())
+    }
+
+    fn check_at(&mut self, message: &str, now: Instant) -> Verdict {
+        if now.duration_since(self.window_start) >= RATE_WINDOW {
+            self.window_start = now;
+            self.shown_in_window = 0;
+        }
+        self.recent
+            .retain(|_, shown| now.duration_since(*shown) < DUPLICATE_WINDOW);
+
+        if self.recent.contains_key(message) || self.shown_in_window >= self.max_per_second {
+            self.suppressed += 1;
+            return Verdict::Suppress {
+                first: self.suppressed == 1,
+            };
+        }
+        self.shown_in_window += 1;
+        self.recent.insert(message.to_string(), now);
+        Verdict::Show {
+            suppressed: std::mem::take(&mut self.suppressed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suppresses_duplicates_within_the_window() {
+        let mut throttle = MessageThrottle::new(Some(10));
+        let start = Instant::now();
+        assert_eq!(
+            throttle.check_at("build failed", start),
+            Verdict::Show { suppressed: 0 }
+        );
+        let later = start + Duration::from_secs(5);
+        assert_eq!(
+            throttle.check_at("build failed", later),
+            Verdict::Suppress { first: true }
+        );
+        assert_eq!(
+            throttle.check_at("build passed", later),
+            Verdict::Show { suppressed: 1 }
+        );
+        let expired = start + DUPLICATE_WINDOW;
+        assert_eq!(
+            throttle.check_at("build failed", expired),
+            Verdict::Show { suppressed: 0 }
+        );
+    }
+
+    #[test]
+    fn limits_messages_per_second() {
+        let mut throttle = MessageThrottle::new(Some(2));
+        let start = Instant::now();
+        assert_eq!(
+            throttle.check_at("a", start),
+            Verdict::Show { suppressed: 0 }
+        );
+        assert_eq!(
+            throttle.check_at("b", start),
+            Verdict::Show { suppressed: 0 }
+        );
+        assert_eq!(
+            throttle.check_at("c", start),
+            Verdict::Suppress { first: true }
+        );
+        assert_eq!(
+            throttle.check_at("d", start + Duration::from_millis(500)),
+            Verdict::Suppress { first: false }
+        );
+        assert_eq!(
+            throttle.check_at("e", start + RATE_WINDOW),
+            Verdict::Show { suppressed: 2 }
+        );
+    }
+
+    #[test]
+    fn shows_at_least_one_message_per_second() {
+        let mut throttle = MessageThrottle::new(Some(0));
+        let start = Instant::now();
+        assert_eq!(
+            throttle.check_at("a", start),
+            Verdict::Show { suppressed: 0 }
+        );
+        assert_eq!(
+            throttle.check_at("b", start),
+            Verdict::Suppress { first: true }
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Recording of the messages exchanged with plugins.
+//!
+//! With `--trace <file>` every protocol line written to or read from a plugin
+//! is appended to the file as a JSON record. `helix-plugin-host replay` feeds
+//! the requests of a recorded session back into a plugin binary and compares
+//! its responses with the recorded ones.
+
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+    time::Instant,
+};
+use uuid::Uuid;
+
+/// Direction of a traced message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Request written by the host.
+    ToPlugin,
+    /// Response or event read from the plugin.
+    FromPlugin,
+}
+
+/// Single line of a trace file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TraceRecord {
+    /// Wall clock time the message was observed at.
+    pub time: String,
+    /// Milliseconds since the trace was started.
+    pub elapsed_ms: f64,
+    /// Logical name of the plugin.
+    pub plugin: String,
+    /// Process instance, distinguishing restarts of the same plugin.
+    pub instance: Uuid,
+    /// Whether the host sent or received the message.
+    pub direction: Direction,
+    /// The protocol message, or the raw line if it is not valid JSON.
+    pub message: Value,
+}
+
+/// Writer shared by every plugin process of the host. Cloning shares the
+/// file.
+#[derive(Clone)]
+pub struct Tracer {
+    file: Arc<Mutex<BufWriter<File>>>,
+    started: Instant,
+}
+
+impl std::fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tracer").finish_non_exhaustive()
+    }
+}
+
+impl Tracer {
+    /// Create or truncate the trace file at `path`, readable by the current
+    /// user only since plugin messages may carry tokens and file contents.
+    pub fn create(path: &Path) -> Result<Self> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options
+            .open(path)
+            .with_context(|| format!("failed to create trace file `{}`", path.display()))?;
+        // The mode only applies to new files.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))
+                .with_context(|| format!("failed to restrict trace file `{}`", path.display()))?;
+        }
+        Ok(Self {
+            file: Arc::new(Mutex::new(BufWriter::new(file))),
+            started: Instant::now(),
+        })
+    }
+
+    /// Append a message exchanged with `plugin`.
+    pub fn record(&self, plugin: &str, instance: Uuid, direction: Direction, line: &str) {
+        let record = TraceRecord {
+            time: chrono::Utc::now()
+                .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+                .to_string(),
+            elapsed_ms: self.started.elapsed().as_secs_f64() * 1000.0,
+            plugin: plugin.to_string(),
+            instance,
+            direction,
+            message: serde_json::from_str(line).unwrap_or_else(|_| Value::from(line)),
+        };
+        let mut file = self.file.lock();
+        let written = serde_json::to_writer(&mut *file, &record)
+            .map_err(std::io::Error::from)
+            .and_then(|()| file.write_all(b"\n"))
+            .and_then(|()| file.flush());
+        if let Err(err) = written {
+            log::warn!("failed to write trace record: {err}");
+        }
+    }
+}
+
+/// Read every record of the trace file at `path`.
+pub fn load(path: &Path) -> Result<Vec<TraceRecord>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read trace file `{}`", path.display()))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("invalid trace record on line {}", index + 1))
+        })
+        .collect()
+}
@@ -0,0 +1,346 @@
+//! Socket transports: the language server is served over stdio by default,
+//! or a TCP or Unix socket with `--listen`, and `connect` plugins are reached
+//! over a socket as well.
+//!
+//! Every socket connection gets its own [`PluginHost`] with its own plugin
+//! processes, so a single host process can serve several editors.
+//!
+//! Anyone able to connect can execute plugin commands, which commonly run
+//! arbitrary programs. TCP addresses are therefore restricted to loopback
+//! unless `--allow-remote` is passed, which in turn requires a `--token-file`
+//! whose contents every connection has to send as its first line.
+
+use crate::{
+    lsp_ext::PluginStatusRequest,
+    server::{HostOptions, PluginHost},
+};
+use anyhow::{Context, Result};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+use tower_lsp::{lsp_types::request::Request, LspService, Server};
+
+/// Socket address passed to `--listen` or declared by a `connect` plugin.
+#[derive(Debug, Clone)]
+pub enum Endpoint {
+    /// `host:port`.
+    Tcp(String),
+    /// Path of a Unix socket, optionally prefixed with `unix:`.
+    Unix(PathBuf),
+}
+
+impl FromStr for Endpoint {
+    type Err = std::convert::Infallible;
+
+    fn from_str(addr: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = addr.strip_prefix("unix:") {
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+        let is_tcp = !addr.contains(std::path::is_separator)
+            && addr
+                .rsplit_once(':')
+                .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+        Ok(if is_tcp {
+            Self::Tcp(addr.to_string())
+        } else {
+            Self::Unix(PathBuf::from(addr))
+        })
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Boxed halves of a socket connection.
+pub type Connection = (
+    Box<dyn AsyncRead + Send + Unpin>,
+    Box<dyn AsyncWrite + Send + Unpin>,
+);
+
+impl Endpoint {
+    /// Open a connection to the endpoint.
+    pub async fn connect(&self) -> Result<Connection> {
+        match self {
+            Self::Tcp(addr) => {
+                let stream = tokio::net::TcpStream::connect(addr.as_str())
+                    .await
+                    .with_context(|| format!("failed to connect to `{addr}`"))?;
+                let (read, write) = stream.into_split();
+                Ok((Box::new(read), Box::new(write)))
+            }
+            #[cfg(unix)]
+            Self::Unix(path) => {
+                let stream = tokio::net::UnixStream::connect(path)
+                    .await
+                    .with_context(|| format!("failed to connect to `{}`", path.display()))?;
+                let (read, write) = stream.into_split();
+                Ok((Box::new(read), Box::new(write)))
+            }
+            #[cfg(not(unix))]
+            Self::Unix(path) => anyhow::bail!(
+                "cannot connect to `{}`: Unix sockets are not supported on this platform",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Time a client has to send the access token after connecting.
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest token line accepted from a client.
+const MAX_TOKEN_LEN: usize = 4096;
+
+/// Who may connect to a `--listen` socket.
+#[derive(Debug, Clone, Default)]
+pub struct Access {
+    /// Accept TCP connections on addresses reachable from other machines.
+    allow_remote: bool,
+    /// Secret every connection has to send as its first line.
+    token: Option<String>,
+}
+
+impl Access {
+    /// Access settings from the command line. The token is read from
+    /// `token_file`, ignoring surrounding whitespace.
+    pub fn new(allow_remote: bool, token_file: Option<&Path>) -> Result<Self> {
+        let token = token_file
+            .map(|path| {
+                let token = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read token file `{}`", path.display()))?;
+                let token = token.trim().to_string();
+                anyhow::ensure!(
+                    !token.is_empty(),
+                    "token file `{}` is empty",
+                    path.display()
+                );
+                Ok(token)
+            })
+            .transpose()?;
+        anyhow::ensure!(
+            !allow_remote || token.is_some(),
+            "`--allow-remote` requires `--token-file`"
+        );
+        Ok(Self {
+            allow_remote,
+            token,
+        })
+    }
+
+    /// Check the first line sent over a new connection against the token.
+    async fn authenticate<R: AsyncRead + Unpin>(&self, read: &mut R) -> Result<()> {
+        let Some(token) = &self.token else {
+            return Ok(());
+        };
+        let line = tokio::time::timeout(TOKEN_TIMEOUT, read_token_line(read))
+            .await
+            .context("no token received")??;
+        anyhow::ensure!(constant_time_eq(&line, token.as_bytes()), "invalid token");
+        Ok(())
+    }
+}
+
+/// Read a line byte by byte, so nothing sent after it is consumed.
+async fn read_token_line<R: AsyncRead + Unpin>(read: &mut R) -> Result<Vec<u8>> {
+    let mut line = Vec::new();
+    loop {
+        match read.read_u8().await? {
+            b'\n' => break,
+            byte => line.push(byte),
+        }
+        anyhow::ensure!(line.len() <= MAX_TOKEN_LEN, "token line too long");
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(line)
+}
+
+/// Compare without returning early, so the time taken does not reveal how
+/// much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Refuse TCP addresses reachable from other machines unless remote access
+/// was allowed explicitly.
+async fn check_address(addr: &str, access: &Access) -> Result<()> {
+    if access.allow_remote {
+        return Ok(());
+    }
+    let mut resolved = tokio::net::lookup_host(addr)
+        .await
+        .with_context(|| format!("failed to resolve `{addr}`"))?;
+    anyhow::ensure!(
+        resolved.all(|addr| addr.ip().is_loopback()),
+        "refusing to listen on `{addr}`: anyone who can connect may run plugin commands; \
+         listen on a loopback address or pass `--allow-remote` with `--token-file`"
+    );
+    Ok(())
+}
+
+/// Accept connections on `addr` until the process is stopped.
+pub async fn serve(addr: Endpoint, access: Access, options: HostOptions) -> Result<()> {
+    match addr {
+        Endpoint::Tcp(addr) => {
+            check_address(&addr, &access).await?;
+            let listener = tokio::net::TcpListener::bind(addr.as_str())
+                .await
+                .with_context(|| format!("failed to listen on `{addr}`"))?;
+            log::info!("listening on {}", listener.local_addr()?);
+            loop {
+                let (stream, peer) = listener.accept().await?;
+                log::info!("accepted connection from {peer}");
+                let (read, write) = stream.into_split();
+                tokio::spawn(accept(
+                    read,
+                    write,
+                    peer.to_string(),
+                    access.clone(),
+                    options.clone(),
+                ));
+            }
+        }
+        Endpoint::Unix(path) => serve_unix(path, access, options).await,
+    }
+}
+
+/// Serve a connection once the client presented the access token.
+async fn accept<R, W>(mut read: R, write: W, peer: String, access: Access, options: HostOptions)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite,
+{
+    if let Err(err) = access.authenticate(&mut read).await {
+        log::warn!("rejected connection from {peer}: {err}");
+        return;
+    }
+    serve_connection(read, write, options).await;
+}
+
+#[cfg(unix)]
+async fn serve_unix(path: PathBuf, access: Access, options: HostOptions) -> Result<()> {
+    // A socket left behind by a host that did not exit cleanly can be
+    // replaced; one that still accepts connections belongs to a running host.
+    if path.exists() {
+        anyhow::ensure!(
+            tokio::net::UnixStream::connect(&path).await.is_err(),
+            "another plugin host is listening on `{}`",
+            path.display()
+        );
+        std::fs::remove_file(&path)
+            .with_context(|| format!("failed to remove stale socket `{}`", path.display()))?;
+    }
+    let listener = tokio::net::UnixListener::bind(&path)
+        .with_context(|| format!("failed to listen on `{}`", path.display()))?;
+    log::info!("listening on {}", path.display());
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let peer = path.display().to_string();
+        log::info!("accepted connection on {peer}");
+        let (read, write) = stream.into_split();
+        tokio::spawn(accept(read, write, peer, access.clone(), options.clone()));
+    }
+}
+
+#[cfg(not(unix))]
+async fn serve_unix(path: PathBuf, _access: Access, _options: HostOptions) -> Result<()> {
+    anyhow::bail!(
+        "cannot listen on `{}`: Unix sockets are not supported on this platform",
+        path.display()
+    )
+}
+
+/// Run a language server session over one connection. Plugins are stopped
+/// once the connection closes, since editors may disconnect without shutting
+/// the server down.
+pub async fn serve_connection<R, W>(read: R, write: W, options: HostOptions)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite,
+{
+    let mut shutdown = None;
+    let (service, socket) = LspService::build(|client| {
+        let host = PluginHost::new(client, options);
+        shutdown = Some(host.shutdown_handle());
+        host
+    })
+    .custom_method(PluginStatusRequest::METHOD, PluginHost::plugin_status)
+    .finish();
+    Server::new(read, write, socket).serve(service).await;
+    if let Some(shutdown) = shutdown {
+        shutdown.shutdown().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    fn access(token: Option<&str>) -> Access {
+        Access {
+            allow_remote: false,
+            token: token.map(str::to_string),
+        }
+    }
+
+    async fn authenticate(access: &Access, sent: &[u8]) -> (Result<()>, Vec<u8>) {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(sent).await.unwrap();
+        drop(client);
+        let result = access.authenticate(&mut server).await;
+        let mut rest = Vec::new();
+        server.read_to_end(&mut rest).await.unwrap();
+        (result, rest)
+    }
+
+    #[tokio::test]
+    async fn token_line_is_consumed_before_messages() {
+        let (result, rest) =
+            authenticate(&access(Some("secret")), b"secret\r\nContent-Length").await;
+        assert!(result.is_ok());
+        assert_eq!(rest, b"Content-Length");
+    }
+
+    #[tokio::test]
+    async fn wrong_or_missing_token_is_rejected() {
+        let access = access(Some("secret"));
+        assert!(authenticate(&access, b"secreT\n").await.0.is_err());
+        assert!(authenticate(&access, b"secret2\n").await.0.is_err());
+        assert!(authenticate(&access, b"Content-Length: 2\r\n")
+            .await
+            .0
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn nothing_is_read_without_a_token() {
+        let (result, rest) = authenticate(&access(None), b"Content-Length").await;
+        assert!(result.is_ok());
+        assert_eq!(rest, b"Content-Length");
+    }
+
+    #[tokio::test]
+    async fn only_loopback_addresses_are_allowed_by_default() {
+        assert!(check_address("127.0.0.1:0", &access(None)).await.is_ok());
+        assert!(check_address("[::1]:0", &access(None)).await.is_ok());
+        assert!(check_address("0.0.0.0:0", &access(None)).await.is_err());
+
+        let remote = Access {
+            allow_remote: true,
+            token: Some("secret".to_string()),
+        };
+        assert!(check_address("0.0.0.0:0", &remote).await.is_ok());
+    }
+}
@@ -0,0 +1,193 @@
+//! Checks run over a loaded manifest so that every problem can be reported
+//! at once instead of surfacing one spawn failure at a time.
+
+use crate::{
+    logfile,
+    manifest::{self, Placeholders, PluginEntry, PluginKind, PluginManifest},
+    plugin::{endpoint, resolve_command, resolve_relative},
+    runtime,
+    transport::Endpoint,
+};
+use std::{collections::HashSet, path::Path};
+
+/// Describe every problem found in `manifest`, in declaration order.
+pub fn validate(
+    manifest: &PluginManifest,
+    manifest_dir: &Path,
+    workspace_root: Option<&Path>,
+) -> Vec<String> {
+    let mut problems = manifest.warnings.clone();
+
+    let mut seen = HashSet::new();
+    for entry in &manifest.plugins {
+        if !seen.insert(entry.name.as_str()) {
+            problems.push(format!(
+                "plugin `{}` is declared more than once",
+                entry.name
+            ));
+        }
+    }
+
+    let enabled = manifest.plugins.iter().filter(|entry| entry.enabled);
+    let (_, cyclic) = manifest::startup_waves(enabled.clone().cloned().collect());
+    for entry in cyclic {
+        problems.push(format!(
+            "plugin `{}` is part of or depends on a dependency cycle",
+            entry.name
+        ));
+    }
+
+    for entry in enabled {
+        for name in &entry.after {
+            if !manifest.plugins.iter().any(|plugin| plugin.name == *name) {
+                problems.push(format!(
+                    "plugin `{}` starts after unknown plugin `{name}`",
+                    entry.name
+                ));
+            }
+        }
+
+        let placeholders = Placeholders {
+            workspace_root,
+            manifest_dir,
+        };
+        match entry.for_current_platform().expand(placeholders) {
+            Ok(expanded) => check_entry(&expanded, manifest_dir, &mut problems),
+            Err(err) => problems.push(format!("{err:#}")),
+        }
+    }
+    problems
+}
+
+fn check_entry(entry: &PluginEntry, manifest_dir: &Path, problems: &mut Vec<String>) {
+    let name = &entry.name;
+    let base_dir = entry.base_dir(manifest_dir);
+
+    match entry.kind {
+        PluginKind::Native if entry.runtime.is_some() => {
+            let script = resolve_relative(base_dir, Path::new(&entry.command));
+            if !script.is_file() {
+                problems.push(format!(
+                    "plugin `{name}`: script `{}` was not found",
+                    script.display()
+                ));
+            }
+        }
+        PluginKind::Native => {
+            let (program, display) = resolve_command(base_dir, &entry.command);
+            if helix_stdx::env::which(&program).is_err() {
+                problems.push(format!(
+                    "plugin `{name}`: executable `{display}` was not found"
+                ));
+            }
+        }
+        PluginKind::Wasm => {
+            let path = resolve_relative(base_dir, Path::new(&entry.command));
+            if !path.is_file() {
+                problems.push(format!(
+                    "plugin `{name}`: component `{}` was not found",
+                    path.display()
+                ));
+            }
+        }
+        PluginKind::Ssh => {
+            if entry.host.is_none() {
+                problems.push(format!("plugin `{name}`: `ssh` plugins require a `host`"));
+            }
+            if helix_stdx::env::which("ssh").is_err() {
+                problems.push(format!("plugin `{name}`: `ssh` was not found"));
+            }
+        }
+        PluginKind::Connect => match endpoint(base_dir, &entry.command) {
+            Endpoint::Tcp(_) => {}
+            Endpoint::Unix(path) => {
+                if !path.exists() {
+                    problems.push(format!(
+                        "plugin `{name}`: socket `{}` does not exist",
+                        path.display()
+                    ));
+                }
+            }
+        },
+    }
+    if let Some(runtime) = entry.runtime {
+        match entry.kind {
+            PluginKind::Native => {
+                if let Err(err) = runtime::locate(runtime, entry.interpreter.as_deref()) {
+                    problems.push(format!("plugin `{name}`: {err:#}"));
+                }
+            }
+            PluginKind::Ssh => {}
+            PluginKind::Wasm | PluginKind::Connect => problems.push(format!(
+                "plugin `{name}`: `runtime` only applies to native and `ssh` plugins"
+            )),
+        }
+    }
+    if matches!(entry.kind, PluginKind::Connect | PluginKind::Ssh) && entry.sha256.is_some() {
+        problems.push(format!(
+            "plugin `{name}`: `sha256` cannot be verified for remote plugins"
+        ));
+    }
+
+    if let Some(Err(err)) = entry.protocol.as_deref().map(manifest::parse_protocol) {
+        problems.push(format!("plugin `{name}`: {err}"));
+    }
+    if entry.max_concurrent_requests == Some(0) {
+        problems.push(format!(
+            "plugin `{name}`: `max_concurrent_requests` must be at least 1"
+        ));
+    }
+    if entry.max_messages_per_second == Some(0) {
+        problems.push(format!(
+            "plugin `{name}`: `max_messages_per_second` must be at least 1"
+        ));
+    }
+
+    if let Err(err) = logfile::parse_level(entry.log_level.as_deref()) {
+        problems.push(format!("plugin `{name}`: {err}"));
+    }
+
+    // The working directory of `ssh` plugins is on the remote machine.
+    if let Some(cwd) = entry.cwd.as_ref().filter(|_| entry.kind != PluginKind::Ssh) {
+        let cwd = resolve_relative(base_dir, cwd);
+        if let Err(err) = std::fs::read_dir(&cwd) {
+            problems.push(format!(
+                "plugin `{name}`: working directory `{}` is not readable: {err}",
+                cwd.display()
+            ));
+        }
+    }
+
+    for pattern in entry.inherit_env.iter().flatten() {
+        let valid = match entry.kind {
+            // Remote variables are referenced from the shell command line.
+            PluginKind::Ssh => manifest::is_variable_name(pattern),
+            _ => !pattern.is_empty() && !pattern.contains(['=', '\0']),
+        };
+        if !valid {
+            problems.push(format!(
+                "plugin `{name}`: invalid `inherit_env` variable `{pattern}`"
+            ));
+        }
+    }
+
+    for (key, value) in &entry.env {
+        if key.is_empty() || key.contains(['=', '\0']) || key.contains(char::is_whitespace) {
+            problems.push(format!(
+                "plugin `{name}`: invalid environment variable name `{key}`"
+            ));
+        } else if value.contains('\0') {
+            problems.push(format!(
+                "plugin `{name}`: environment variable `{key}` contains a NUL byte"
+            ));
+        } else if value.is_empty() {
+            problems.push(format!(
+                "plugin `{name}`: environment variable `{key}` is empty; is a referenced variable unset?"
+            ));
+        } else if value.trim() != value {
+            problems.push(format!(
+                "plugin `{name}`: environment variable `{key}` has leading or trailing whitespace"
+            ));
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! WebAssembly plugin runtime.
+//!
+//! Components implementing the `helix:plugin` world (see `wit/plugin.wit`)
+//! are driven on a blocking thread and exposed through in-memory pipes that
+//! carry the same JSON lines protocol as native plugins, so the rest of the
+//! host treats them exactly like child processes.
+
+use crate::manifest::PluginEntry;
+use anyhow::{Context, Result};
+use helix_plugin_sdk::protocol::{HostRequest, HostRequestPayload};
+use std::path::{Path, PathBuf};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream},
+    runtime::Handle,
+    task::JoinHandle,
+};
+use wasmtime::{
+    component::{Component, Linker, ResourceTable},
+    Config, Engine, Store,
+};
+use wasmtime_wasi::{DirPerms, FilePerms, IoView, WasiCtx, WasiCtxBuilder, WasiView};
+
+wasmtime::component::bindgen!({
+    world: "plugin",
+    path: "wit",
+});
+
+/// Capacity of the in-memory pipes connecting the host to a component.
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// A running WebAssembly plugin.
+pub struct WasmPlugin {
+    /// Receives JSON encoded host requests, one per line.
+    pub stdin: DuplexStream,
+    /// Yields JSON encoded plugin messages, one per line.
+    pub stdout: DuplexStream,
+    engine: Engine,
+    task: JoinHandle<Result<()>>,
+}
+
+struct State {
+    wasi: WasiCtx,
+    table: ResourceTable,
+    /// Messages sent by the guest during the current call.
+    outbox: Vec<String>,
+}
+
+impl IoView for State {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
+impl WasiView for State {
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
+}
+
+impl helix::plugin::host::Host for State {
+    fn send(&mut self, message: String) {
+        self.outbox.push(message);
+    }
+}
+
+impl WasmPlugin {
+    /// Compile and instantiate the component at `path`.
+    ///
+    /// The guest only gets access to the workspace root, the arguments and
+    /// the environment declared in the manifest entry.
+    pub async fn spawn(
+        path: PathBuf,
+        entry: &PluginEntry,
+        workspace_root: Option<&Path>,
+    ) -> Result<Self> {
+        let mut wasi = WasiCtxBuilder::new();
+        wasi.inherit_stderr()
+            .arg(&entry.name)
+            .args(&entry.args)
+            .env("HELIX_PLUGIN_NAME", &entry.name);
+        for (key, value) in &entry.env {
+            wasi.env(key, value);
+        }
+        if let Some(root) = workspace_root {
+            let root = root.to_string_lossy();
+            wasi.env("HELIX_WORKSPACE_ROOT", &root);
+            wasi.preopened_dir(
+                root.as_ref(),
+                root.as_ref(),
+                DirPerms::all(),
+                FilePerms::all(),
+            )
+            .with_context(|| format!("failed to expose `{root}` to plugin `{}`", entry.name))?;
+        }
+        let state = State {
+            wasi: wasi.build(),
+            table: ResourceTable::new(),
+            outbox: Vec::new(),
+        };
+
+        let mut config = Config::new();
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config)?;
+
+        let (store, plugin) = tokio::task::spawn_blocking({
+            let engine = engine.clone();
+            move || instantiate(&engine, &path, state)
+        })
+        .await??;
+
+        let (stdin, requests) = tokio::io::duplex(PIPE_CAPACITY);
+        let (responses, stdout) = tokio::io::duplex(PIPE_CAPACITY);
+        let runtime = Handle::current();
+        let task =
+            tokio::task::spawn_blocking(move || drive(runtime, store, plugin, requests, responses));
+
+        Ok(Self {
+            stdin,
+            stdout,
+            engine,
+            task,
+        })
+    }
+
+    /// Wait for the component to stop, either after a shutdown request or
+    /// because it trapped.
+    pub async fn wait(&mut self) -> Result<()> {
+        (&mut self.task).await?
+    }
+
+    /// Interrupt the guest if it is currently executing.
+    pub fn interrupt(&self) {
+        self.engine.increment_epoch();
+    }
+}
+
+fn instantiate(engine: &Engine, path: &Path, state: State) -> Result<(Store<State>, Plugin)> {
+    let component = Component::from_file(engine, path)
+        .with_context(|| format!("failed to load component `{}`", path.display()))?;
+
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::add_to_linker_sync(&mut linker)?;
+    Plugin::add_to_linker(&mut linker, |state: &mut State| state)?;
+
+    let mut store = Store::new(engine, state);
+    // Any epoch increment traps the guest; it is only bumped on kill.
+    store.set_epoch_deadline(1);
+    let plugin = Plugin::instantiate(&mut store, &component, &linker)?;
+    Ok((store, plugin))
+}
+
+/// Feed requests into the guest one at a time and forward its messages until
+/// the request pipe closes or the plugin acknowledged a shutdown.
+fn drive(
+    runtime: Handle,
+    mut store: Store<State>,
+    plugin: Plugin,
+    requests: DuplexStream,
+    mut responses: DuplexStream,
+) -> Result<()> {
+    let mut requests = BufReader::new(requests).lines();
+    while let Some(line) = runtime.block_on(requests.next_line())? {
+        let shutdown = matches!(
+            serde_json::from_str::<HostRequest>(&line),
+            Ok(HostRequest {
+                payload: HostRequestPayload::Shutdown,
+                ..
+            })
+        );
+
+        plugin.call_handle(&mut store, &line)?;
+
+        let outbox = std::mem::take(&mut store.data_mut().outbox);
+        runtime.block_on(async {
+            for message in outbox {
+                responses.write_all(message.as_bytes()).await?;
+                responses.write_all(b"\n").await?;
+            }
+            responses.flush().await
+        })?;
+
+        if shutdown {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,11 @@
+//! Print the JSON Schema of the plugin protocol to stdout.
+//!
+//! ```sh
+//! cargo run -p helix-plugin-sdk --features schema --example export-schema > protocol.schema.json
+//! ```
+
+fn main() -> anyhow::Result<()> {
+    let schema = helix_plugin_sdk::protocol::json_schema();
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
@@ -0,0 +1,397 @@
+//! Protocol conformance harness for plugin executables.
+//!
+//! The harness spawns an arbitrary plugin binary, drives it through the
+//! initialize, execute and shutdown flows and records every deviation from the
+//! protocol it observes. It only relies on the wire format, which makes it
+//! suitable for validating plugins written in languages other than Rust.
+
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fmt,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::protocol::{
+    HostCapabilities, HostRequest, HostRequestPayload, PluginCommand, PluginEvent, PluginMessage,
+    PluginResponse,
+};
+
+/// Command id used to probe how a plugin handles unknown commands.
+const UNKNOWN_COMMAND: &str = "helix.conformance.__unknown__";
+
+/// Describes the plugin under test and the flows to exercise.
+#[derive(Debug, Clone)]
+pub struct ConformanceConfig {
+    program: OsString,
+    args: Vec<OsString>,
+    workspace_root: Option<PathBuf>,
+    executions: Vec<(String, Vec<Value>)>,
+    timeout: Duration,
+}
+
+impl ConformanceConfig {
+    /// Create a configuration for the given plugin executable.
+    pub fn new(program: impl Into<OsString>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            workspace_root: None,
+            executions: Vec::new(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Append a command line argument passed to the plugin executable.
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Workspace root announced during initialization.
+    pub fn workspace_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.workspace_root = Some(root.into());
+        self
+    }
+
+    /// Execute `command` with `arguments` after a successful handshake.
+    ///
+    /// Commands are only executed when requested explicitly since they may
+    /// have side effects.
+    pub fn execute(mut self, command: impl Into<String>, arguments: Vec<Value>) -> Self {
+        self.executions.push((command.into(), arguments));
+        self
+    }
+
+    /// Maximum time to wait for each individual response.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// A deviation from the plugin protocol observed by the harness.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// A line written to stdout could not be decoded as a [`PluginMessage`].
+    MalformedMessage {
+        /// The offending line.
+        line: String,
+    },
+    /// The plugin did not answer a request within the configured timeout.
+    Timeout {
+        /// Request that went unanswered.
+        request: String,
+    },
+    /// The plugin closed stdout before answering a request.
+    Disconnected {
+        /// Request that went unanswered.
+        request: String,
+    },
+    /// A response referenced an id that was never issued or already answered.
+    UnexpectedResponseId {
+        /// The unknown id.
+        id: u64,
+    },
+    /// A response variant did not match the request it answered.
+    UnexpectedResponse {
+        /// Request that was answered.
+        request: String,
+        /// Debug rendering of the received response.
+        response: String,
+    },
+    /// The same command id was advertised more than once.
+    DuplicateCommand {
+        /// The duplicated command id.
+        id: String,
+    },
+    /// The process kept running after acknowledging shutdown.
+    NoExitAfterShutdown,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MalformedMessage { line } => write!(f, "malformed message on stdout: {line}"),
+            Self::Timeout { request } => write!(f, "`{request}` request timed out"),
+            Self::Disconnected { request } => {
+                write!(f, "stdout closed before `{request}` was answered")
+            }
+            Self::UnexpectedResponseId { id } => write!(f, "response for unknown request id {id}"),
+            Self::UnexpectedResponse { request, response } => {
+                write!(f, "unexpected response to `{request}`: {response}")
+            }
+            Self::DuplicateCommand { id } => write!(f, "command `{id}` advertised multiple times"),
+            Self::NoExitAfterShutdown => write!(f, "process did not exit after shutdown"),
+        }
+    }
+}
+
+/// Outcome of a conformance run.
+#[derive(Debug, Clone, Default)]
+pub struct ConformanceReport {
+    /// Commands advertised during the handshake.
+    pub commands: Vec<PluginCommand>,
+    /// Events emitted by the plugin over the whole session.
+    pub events: Vec<PluginEvent>,
+    /// Protocol violations in the order they were observed.
+    pub violations: Vec<Violation>,
+}
+
+impl ConformanceReport {
+    /// Returns `true` when no violations were recorded.
+    pub fn is_conformant(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// Spawn the plugin described by `config` and run every conformance flow.
+///
+/// Errors are only returned when the harness itself fails (for example when
+/// the executable cannot be spawned); protocol problems are reported through
+/// [`ConformanceReport::violations`].
+pub fn check(config: &ConformanceConfig) -> Result<ConformanceReport> {
+    let mut command = Command::new(&config.program);
+    command
+        .args(&config.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit());
+    if let Some(root) = &config.workspace_root {
+        command.env("HELIX_WORKSPACE_ROOT", root);
+    }
+
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("failed to spawn `{}`", config.program.to_string_lossy()))?;
+    let stdin = child.stdin.take().context("plugin stdin unavailable")?;
+    let stdout = child.stdout.take().context("plugin stdout unavailable")?;
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut session = Session {
+        child,
+        stdin,
+        lines: rx,
+        timeout: config.timeout,
+        next_id: 1,
+        report: ConformanceReport::default(),
+    };
+    session.run(config)?;
+    Ok(session.report)
+}
+
+struct Session {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    timeout: Duration,
+    next_id: u64,
+    report: ConformanceReport,
+}
+
+impl Session {
+    fn run(&mut self, config: &ConformanceConfig) -> Result<()> {
+        let workspace_root = config
+            .workspace_root
+            .as_ref()
+            .map(|root| root.to_string_lossy().to_string());
+        let initialize = HostRequestPayload::Initialize {
+            workspace_folders: workspace_root.iter().cloned().collect(),
+            workspace_root,
+            capabilities: HostCapabilities::default(),
+        };
+
+        match self.request("initialize", initialize)? {
+            Some(PluginResponse::Initialized { commands, .. }) => {
+                let mut seen = HashSet::new();
+                for command in &commands {
+                    if !seen.insert(command.id.clone()) {
+                        self.report.violations.push(Violation::DuplicateCommand {
+                            id: command.id.clone(),
+                        });
+                    }
+                }
+                self.report.commands = commands;
+            }
+            Some(other) => {
+                self.unexpected("initialize", &other);
+                return self.terminate();
+            }
+            None => return self.terminate(),
+        }
+
+        for (command, arguments) in &config.executions {
+            let payload = HostRequestPayload::Execute {
+                command: command.clone(),
+                arguments: arguments.clone(),
+                language_id: None,
+            };
+            match self.request(command, payload)? {
+                Some(
+                    PluginResponse::CommandResult { .. } | PluginResponse::CommandError { .. },
+                ) => {}
+                Some(other) => self.unexpected(command, &other),
+                None => return self.terminate(),
+            }
+        }
+
+        let probe = HostRequestPayload::Execute {
+            command: UNKNOWN_COMMAND.to_string(),
+            arguments: Vec::new(),
+            language_id: None,
+        };
+        match self.request(UNKNOWN_COMMAND, probe)? {
+            Some(PluginResponse::CommandError { .. }) => {}
+            Some(other) => self.unexpected(UNKNOWN_COMMAND, &other),
+            None => return self.terminate(),
+        }
+
+        match self.request("shutdown", HostRequestPayload::Shutdown)? {
+            Some(PluginResponse::Acknowledge) => {}
+            Some(other) => self.unexpected("shutdown", &other),
+            None => return self.terminate(),
+        }
+
+        self.drain_until_exit()
+    }
+
+    /// Send a request and wait for its response, recording violations on the
+    /// way. Returns `None` when the plugin timed out or disconnected.
+    fn request(
+        &mut self,
+        label: &str,
+        payload: HostRequestPayload,
+    ) -> Result<Option<PluginResponse>> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut serialized = serde_json::to_vec(&HostRequest { id, payload })
+            .context("failed to serialize host request")?;
+        serialized.push(b'\n');
+        if self
+            .stdin
+            .write_all(&serialized)
+            .and_then(|_| self.stdin.flush())
+            .is_err()
+        {
+            self.report.violations.push(Violation::Disconnected {
+                request: label.to_string(),
+            });
+            return Ok(None);
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let line = match self.lines.recv_timeout(remaining) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    self.report.violations.push(Violation::Timeout {
+                        request: label.to_string(),
+                    });
+                    return Ok(None);
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.report.violations.push(Violation::Disconnected {
+                        request: label.to_string(),
+                    });
+                    return Ok(None);
+                }
+            };
+
+            match self.decode(line) {
+                Some((response_id, result)) if response_id == id => return Ok(Some(result)),
+                Some((response_id, _)) => {
+                    self.report
+                        .violations
+                        .push(Violation::UnexpectedResponseId { id: response_id });
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Decode a line, collecting events and malformed output. Returns the
+    /// response contained in the line, if any.
+    fn decode(&mut self, line: String) -> Option<(u64, PluginResponse)> {
+        if line.trim().is_empty() {
+            return None;
+        }
+        match serde_json::from_str::<PluginMessage>(&line) {
+            Ok(PluginMessage::Response { id, result }) => Some((id, result)),
+            Ok(PluginMessage::Event { event }) => {
+                self.report.events.push(event);
+                None
+            }
+            // The harness offers no host services; the request times out.
+            Ok(PluginMessage::Request { .. }) => None,
+            Err(_) => {
+                self.report
+                    .violations
+                    .push(Violation::MalformedMessage { line });
+                None
+            }
+        }
+    }
+
+    fn unexpected(&mut self, request: &str, response: &PluginResponse) {
+        self.report.violations.push(Violation::UnexpectedResponse {
+            request: request.to_string(),
+            response: format!("{response:?}"),
+        });
+    }
+
+    /// Collect trailing output and wait for the process to exit on its own.
+    fn drain_until_exit(&mut self) -> Result<()> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) => {
+                    if let Some((id, _)) = self.decode(line) {
+                        self.report
+                            .violations
+                            .push(Violation::UnexpectedResponseId { id });
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {
+                    self.report.violations.push(Violation::NoExitAfterShutdown);
+                    return self.terminate();
+                }
+            }
+        }
+
+        while Instant::now() < deadline {
+            if self.child.try_wait()?.is_some() {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.report.violations.push(Violation::NoExitAfterShutdown);
+        self.terminate()
+    }
+
+    fn terminate(&mut self) -> Result<()> {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        Ok(())
+    }
+}
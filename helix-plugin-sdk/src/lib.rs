@@ -7,14 +7,24 @@
 //! plugin host and plugin processes as well as a small runtime that plugin
 //! authors can embed in their binaries.
 
+pub mod conformance;
+
 pub mod protocol {
     //! Shared protocol definitions between the host and plugin processes.
 
     use serde::{Deserialize, Serialize};
     use serde_json::Value;
+    use std::collections::BTreeMap;
+
+    /// Version of the wire protocol implemented by this SDK.
+    ///
+    /// Bumped whenever a change is made that older hosts or plugins cannot
+    /// safely ignore.
+    pub const VERSION: u32 = 1;
 
     /// A command exported by a plugin.
     #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
     pub struct PluginCommand {
         /// Fully qualified command identifier.
         pub id: String,
@@ -44,6 +54,7 @@ pub fn with_description(mut self, description: impl Into<String>) -> Self {
 
     /// Severity levels understood by the host for logging and UI messages.
     #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
     #[serde(rename_all = "snake_case")]
     pub enum MessageLevel {
         /// Error severity.
@@ -56,8 +67,296 @@ pub enum MessageLevel {
         Log,
     }
 
+    /// Kind of a symbol contributed by a plugin, mirroring the LSP symbol
+    /// kinds.
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    #[serde(rename_all = "snake_case")]
+    #[allow(missing_docs)]
+    pub enum SymbolKind {
+        File,
+        Module,
+        Namespace,
+        Package,
+        Class,
+        Method,
+        Property,
+        Field,
+        Constructor,
+        Enum,
+        Interface,
+        Function,
+        Variable,
+        Constant,
+        String,
+        Number,
+        Boolean,
+        Array,
+        Object,
+        Key,
+        Null,
+        EnumMember,
+        Struct,
+        Event,
+        Operator,
+        TypeParameter,
+    }
+
+    /// A symbol contributed to the workspace symbol search of the editor,
+    /// e.g. a task or a TODO item.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    pub struct WorkspaceSymbol {
+        /// Name shown in the symbol picker.
+        pub name: String,
+        /// Kind of the symbol.
+        pub kind: SymbolKind,
+        /// File defining the symbol, absolute or relative to the workspace
+        /// root.
+        pub path: String,
+        /// Zero based line of the symbol.
+        #[serde(default)]
+        pub line: u32,
+        /// Zero based character offset of the symbol within its line.
+        #[serde(default)]
+        pub character: u32,
+        /// Name of the symbol containing this one, shown next to it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub container: Option<String>,
+    }
+
+    impl WorkspaceSymbol {
+        /// Create a symbol at the start of `path`.
+        pub fn new(name: impl Into<String>, kind: SymbolKind, path: impl Into<String>) -> Self {
+            Self {
+                name: name.into(),
+                kind,
+                path: path.into(),
+                line: 0,
+                character: 0,
+                container: None,
+            }
+        }
+
+        /// Place the symbol at the zero based `line` and `character`.
+        pub fn at(mut self, line: u32, character: u32) -> Self {
+            self.line = line;
+            self.character = character;
+            self
+        }
+
+        /// Attach the name of the containing symbol.
+        pub fn with_container(mut self, container: impl Into<String>) -> Self {
+            self.container = Some(container.into());
+            self
+        }
+    }
+
+    /// Zero based position within a document.
+    #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    pub struct Position {
+        /// Zero based line.
+        pub line: u32,
+        /// Zero based character offset within the line.
+        pub character: u32,
+    }
+
+    impl Position {
+        /// Create a position.
+        pub fn new(line: u32, character: u32) -> Self {
+            Self { line, character }
+        }
+    }
+
+    /// Range within a document, `end` exclusive.
+    #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    pub struct Range {
+        /// Start of the range.
+        pub start: Position,
+        /// End of the range.
+        pub end: Position,
+    }
+
+    impl Range {
+        /// Create a range.
+        pub fn new(start: Position, end: Position) -> Self {
+            Self { start, end }
+        }
+
+        /// Range spanning the whole of `line`, up to `length` characters.
+        pub fn line(line: u32, length: u32) -> Self {
+            Self::new(Position::new(line, 0), Position::new(line, length))
+        }
+    }
+
+    /// A symbol of the outline of a document, e.g. a Makefile target.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    pub struct DocumentSymbol {
+        /// Name shown in the outline.
+        pub name: String,
+        /// Kind of the symbol.
+        pub kind: SymbolKind,
+        /// Additional details shown next to the name, e.g. a signature.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub detail: Option<String>,
+        /// Range covering the symbol.
+        pub range: Range,
+        /// Symbols nested in this one.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub children: Vec<DocumentSymbol>,
+    }
+
+    impl DocumentSymbol {
+        /// Create a symbol covering `range`.
+        pub fn new(name: impl Into<String>, kind: SymbolKind, range: Range) -> Self {
+            Self {
+                name: name.into(),
+                kind,
+                detail: None,
+                range,
+                children: Vec::new(),
+            }
+        }
+
+        /// Attach details shown next to the name.
+        pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+            self.detail = Some(detail.into());
+            self
+        }
+
+        /// Nest `child` in the symbol.
+        pub fn with_child(mut self, child: DocumentSymbol) -> Self {
+            self.children.push(child);
+            self
+        }
+    }
+
+    /// Kind of an inlay hint, which editors may style differently.
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    #[serde(rename_all = "snake_case")]
+    pub enum InlayHintKind {
+        /// Annotates a type.
+        Type,
+        /// Annotates a parameter.
+        Parameter,
+    }
+
+    /// Text shown inline in a document, e.g. `1.4.0 available` after a
+    /// dependency version.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    pub struct InlayHint {
+        /// Position the hint is shown at.
+        pub position: Position,
+        /// Text of the hint.
+        pub label: String,
+        /// Kind of the hint.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub kind: Option<InlayHintKind>,
+        /// Text shown when hovering the hint.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub tooltip: Option<String>,
+        /// Whether to pad the hint with a space before it.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pub padding_left: bool,
+        /// Whether to pad the hint with a space after it.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pub padding_right: bool,
+    }
+
+    impl InlayHint {
+        /// Create a hint showing `label` at `position`.
+        pub fn new(position: Position, label: impl Into<String>) -> Self {
+            Self {
+                position,
+                label: label.into(),
+                kind: None,
+                tooltip: None,
+                padding_left: false,
+                padding_right: false,
+            }
+        }
+
+        /// Set the kind of the hint.
+        pub fn with_kind(mut self, kind: InlayHintKind) -> Self {
+            self.kind = Some(kind);
+            self
+        }
+
+        /// Attach text shown when hovering the hint.
+        pub fn with_tooltip(mut self, tooltip: impl Into<String>) -> Self {
+            self.tooltip = Some(tooltip.into());
+            self
+        }
+
+        /// Pad the hint with a space on either side.
+        pub fn padded(mut self, left: bool, right: bool) -> Self {
+            self.padding_left = left;
+            self.padding_right = right;
+            self
+        }
+    }
+
+    /// Severity of a diagnostic, mirroring the LSP severities.
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    #[serde(rename_all = "snake_case")]
+    #[allow(missing_docs)]
+    pub enum DiagnosticSeverity {
+        Error,
+        Warning,
+        Information,
+        Hint,
+    }
+
+    /// Message attached to a range of a file, e.g. a review comment on a
+    /// line.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    pub struct Diagnostic {
+        /// Range the message applies to.
+        pub range: Range,
+        /// Text of the diagnostic.
+        pub message: String,
+        /// Severity; editors treat diagnostics without one as errors.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub severity: Option<DiagnosticSeverity>,
+        /// What produced the diagnostic, shown next to it, e.g. `github`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub source: Option<String>,
+    }
+
+    impl Diagnostic {
+        /// Create a diagnostic showing `message` at `range`.
+        pub fn new(range: Range, message: impl Into<String>) -> Self {
+            Self {
+                range,
+                message: message.into(),
+                severity: None,
+                source: None,
+            }
+        }
+
+        /// Set the severity of the diagnostic.
+        pub fn with_severity(mut self, severity: DiagnosticSeverity) -> Self {
+            self.severity = Some(severity);
+            self
+        }
+
+        /// Name what produced the diagnostic.
+        pub fn with_source(mut self, source: impl Into<String>) -> Self {
+            self.source = Some(source.into());
+            self
+        }
+    }
+
     /// Request message sent from the host to a plugin.
     #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
     pub struct HostRequest {
         /// Unique request identifier.
         pub id: u64,
@@ -67,12 +366,19 @@ pub struct HostRequest {
 
     /// Host -> plugin request payload variants.
     #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
     #[serde(tag = "type", rename_all = "snake_case")]
     pub enum HostRequestPayload {
         /// Initial handshake providing workspace information.
         Initialize {
             /// Optional workspace root resolved by the host.
             workspace_root: Option<String>,
+            /// All workspace folders opened in the editor, including the root.
+            #[serde(default)]
+            workspace_folders: Vec<String>,
+            /// Optional features the host supports.
+            #[serde(default)]
+            capabilities: HostCapabilities,
         },
         /// Execute a previously registered command.
         Execute {
@@ -81,13 +387,179 @@ pub enum HostRequestPayload {
             /// Command arguments forwarded from Helix.
             #[serde(default)]
             arguments: Vec<Value>,
+            /// Language id of the active document, if known to the host.
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            language_id: Option<String>,
         },
+        /// Deliver an event published by another plugin on a subscribed topic.
+        BusEvent {
+            /// Topic the event was published on.
+            topic: String,
+            /// Name of the publishing plugin.
+            source: String,
+            /// Event payload supplied by the publisher.
+            #[serde(default)]
+            payload: Value,
+        },
+        /// Search the symbols the plugin contributes to the workspace.
+        WorkspaceSymbols {
+            /// Query typed by the user; empty to list every symbol.
+            #[serde(default)]
+            query: String,
+        },
+        /// Outline a document of a language the plugin provides document
+        /// symbols for.
+        DocumentSymbols {
+            /// Absolute path of the document.
+            path: String,
+            /// Language id of the document.
+            language_id: String,
+        },
+        /// Compute the inlay hints of the visible part of a document of a
+        /// language the plugin provides inlay hints for.
+        InlayHints {
+            /// Absolute path of the document.
+            path: String,
+            /// Language id of the document.
+            language_id: String,
+            /// Part of the document hints are requested for.
+            range: Range,
+        },
+        /// Answer a [`PluginRequest`]. Plugins do not respond to it.
+        Reply {
+            /// Identifier of the answered plugin request.
+            request: u64,
+            /// Outcome of the plugin request.
+            result: HostReply,
+        },
+        /// Check that the plugin is responsive. Plugins acknowledge it.
+        Ping,
         /// Terminate the plugin process gracefully.
         Shutdown,
     }
 
+    /// Optional features of the host, which may depend on the editor
+    /// attached to it.
+    #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    pub struct HostCapabilities {
+        /// Whether the host answers [`PluginRequest::RunInTerminal`].
+        #[serde(default)]
+        pub run_in_terminal: bool,
+        /// Whether the host answers [`PluginRequest::Input`].
+        #[serde(default)]
+        pub input: bool,
+    }
+
+    /// Outcome of a [`PluginRequest`].
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    pub enum HostReply {
+        /// The request succeeded.
+        Success {
+            /// Requested value; `null` for requests returning nothing.
+            #[serde(default)]
+            value: Value,
+        },
+        /// The request failed.
+        Error {
+            /// Human readable error string.
+            message: String,
+        },
+    }
+
+    /// Services a plugin requests from the host.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    pub enum PluginRequest {
+        /// Read a value of the store the host shares between plugins. Missing
+        /// keys read as `null`.
+        StoreGet {
+            /// Key of the value.
+            key: String,
+            /// Plugin whose namespace is read, if not the requesting one. Only
+            /// values it shared are readable.
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            namespace: Option<String>,
+        },
+        /// Write a value to the namespace of the requesting plugin.
+        StoreSet {
+            /// Key of the value.
+            key: String,
+            /// Stored value.
+            value: Value,
+            /// Whether other plugins may read the value.
+            #[serde(default)]
+            shared: bool,
+        },
+        /// Remove a value from the namespace of the requesting plugin.
+        StoreDelete {
+            /// Key of the value.
+            key: String,
+        },
+        /// Read a secret of the requesting plugin from the OS keyring.
+        /// Missing secrets read as `null`.
+        SecretGet {
+            /// Name of the secret.
+            key: String,
+        },
+        /// Store a secret of the requesting plugin in the OS keyring.
+        SecretSet {
+            /// Name of the secret.
+            key: String,
+            /// Secret value, e.g. an access token.
+            secret: String,
+        },
+        /// Remove a secret of the requesting plugin.
+        SecretDelete {
+            /// Name of the secret.
+            key: String,
+        },
+        /// Ask the user to pick one of `choices`. Resolves to the picked
+        /// choice, or `null` when the user dismissed the prompt or no editor
+        /// is attached.
+        Prompt {
+            /// Question shown to the user.
+            message: String,
+            /// Answers the user picks from.
+            choices: Vec<String>,
+        },
+        /// Ask the user to enter text. Resolves to the text, or `null` when
+        /// the user dismissed the prompt. Only available when
+        /// [`HostCapabilities::input`] is set.
+        Input {
+            /// Question shown to the user.
+            message: String,
+            /// Text the input starts with.
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            default: Option<String>,
+        },
+        /// Run a process in a terminal of the editor, where it can be used
+        /// interactively. Resolves to the process id, or `null` when the
+        /// editor does not report it. Only available when
+        /// [`HostCapabilities::run_in_terminal`] is set.
+        RunInTerminal {
+            /// Title of the terminal.
+            title: String,
+            /// Executable to run.
+            program: String,
+            /// Arguments passed to the executable.
+            #[serde(default)]
+            args: Vec<String>,
+            /// Working directory of the process.
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            cwd: Option<String>,
+            /// Variables set in addition to those of the editor.
+            #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+            env: BTreeMap<String, String>,
+        },
+    }
+
     /// Message emitted by the plugin process towards the host.
     #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
     #[serde(tag = "type", rename_all = "snake_case")]
     pub enum PluginMessage {
         /// Response to a host request.
@@ -102,22 +574,74 @@ pub enum PluginMessage {
             /// Event payload.
             event: PluginEvent,
         },
+        /// Request a service of the host, answered with
+        /// [`HostRequestPayload::Reply`].
+        Request {
+            /// Correlates with the reply of the host.
+            id: u64,
+            /// Requested service.
+            request: PluginRequest,
+        },
     }
 
     /// Response kinds emitted by a plugin.
     #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
     #[serde(tag = "type", rename_all = "snake_case")]
     pub enum PluginResponse {
         /// Successful initialization containing command metadata.
         Initialized {
             /// Commands exposed by the plugin.
             commands: Vec<PluginCommand>,
+            /// Language ids the plugin operates on. Empty means every language.
+            #[serde(default, skip_serializing_if = "Vec::is_empty")]
+            languages: Vec<String>,
+            /// Protocol [`VERSION`] the plugin was built against. Absent for
+            /// plugins that predate protocol versioning.
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            protocol_version: Option<u32>,
+            /// Bus topics the plugin wants to receive events for.
+            #[serde(default, skip_serializing_if = "Vec::is_empty")]
+            subscriptions: Vec<String>,
+            /// Version of the plugin itself, if it reports one.
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            version: Option<String>,
+            /// Whether the plugin answers [`HostRequestPayload::WorkspaceSymbols`].
+            #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+            workspace_symbols: bool,
+            /// Language ids the plugin answers
+            /// [`HostRequestPayload::DocumentSymbols`] for.
+            #[serde(default, skip_serializing_if = "Vec::is_empty")]
+            document_symbols: Vec<String>,
+            /// Language ids the plugin answers [`HostRequestPayload::InlayHints`]
+            /// for.
+            #[serde(default, skip_serializing_if = "Vec::is_empty")]
+            inlay_hints: Vec<String>,
         },
         /// Command executed successfully.
         CommandResult {
             /// Optional command return value.
             #[serde(default)]
             result: Option<Value>,
+            /// Milliseconds during which the host may answer identical
+            /// requests with this result instead of asking the plugin again.
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            cache_ttl_ms: Option<u64>,
+        },
+        /// Symbols matching a workspace symbol query.
+        WorkspaceSymbols {
+            /// Matching symbols.
+            symbols: Vec<WorkspaceSymbol>,
+        },
+        /// Outline of a document.
+        DocumentSymbols {
+            /// Top level symbols of the document.
+            symbols: Vec<DocumentSymbol>,
+        },
+        /// Inlay hints of a document.
+        InlayHints {
+            /// Hints within the requested range.
+            hints: Vec<InlayHint>,
         },
         /// Command execution failed with an error message.
         CommandError {
@@ -130,6 +654,7 @@ pub enum PluginResponse {
 
     /// Out-of-band events emitted by a plugin.
     #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
     #[serde(tag = "type", rename_all = "snake_case")]
     pub enum PluginEvent {
         /// Request the host to surface a status / error message to the user.
@@ -146,25 +671,83 @@ pub enum PluginEvent {
             /// Log message.
             message: String,
         },
+        /// Publish an event on the host bus for subscribed plugins.
+        Publish {
+            /// Topic name, e.g. `tasks/finished`.
+            topic: String,
+            /// Arbitrary event payload.
+            #[serde(default)]
+            payload: Value,
+        },
+        /// Forward an event to the editor for integrations that build their
+        /// own UI, e.g. progress of a long running task.
+        Notify {
+            /// Event kind, e.g. `progress`.
+            kind: String,
+            /// Arbitrary event payload.
+            #[serde(default)]
+            payload: Value,
+        },
+        /// Append text to a named output channel of the plugin, e.g. the log
+        /// of a build, which users open with `helix.plugins.show_output`.
+        AppendOutput {
+            /// Channel name, e.g. `build`.
+            channel: String,
+            /// Text appended as is; include line breaks.
+            text: String,
+        },
+        /// Replace the diagnostics the plugin shows in a file. An empty list
+        /// clears them.
+        PublishDiagnostics {
+            /// Absolute path of the file.
+            path: String,
+            /// Diagnostics of the file.
+            #[serde(default)]
+            diagnostics: Vec<Diagnostic>,
+        },
+    }
+
+    /// Build a JSON Schema document describing the plugin protocol.
+    ///
+    /// The document contains definitions for every protocol type and accepts
+    /// either a [`HostRequest`] or a [`PluginMessage`] at the top level, which
+    /// makes it suitable as input for code generators in other languages.
+    #[cfg(feature = "schema")]
+    pub fn json_schema() -> Value {
+        let mut generator = schemars::gen::SchemaSettings::draft07().into_generator();
+        let host_request = generator.subschema_for::<HostRequest>();
+        let plugin_message = generator.subschema_for::<PluginMessage>();
+
+        serde_json::json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "title": "Helix plugin protocol",
+            "anyOf": [host_request, plugin_message],
+            "definitions": generator.take_definitions(),
+        })
     }
 }
 
 pub mod runtime {
     //! Minimal runtime for authoring Helix plugins.
 
-    use anyhow::{anyhow, Context, Result};
+    use anyhow::{anyhow, bail, Context, Result};
     use log::{debug, error, trace};
     use serde_json::Value;
     use std::{
-        collections::HashSet,
+        collections::{BTreeMap, HashSet, VecDeque},
         io::{self, BufRead, Write},
         path::{Path, PathBuf},
-        sync::{Arc, Mutex},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
     };
 
     use crate::protocol::{
-        HostRequest, HostRequestPayload, MessageLevel, PluginCommand, PluginEvent, PluginMessage,
-        PluginResponse,
+        Diagnostic, DocumentSymbol, HostCapabilities, HostReply, HostRequest, HostRequestPayload,
+        InlayHint, MessageLevel, PluginCommand, PluginEvent, PluginMessage, PluginRequest,
+        PluginResponse, Range, WorkspaceSymbol,
     };
 
     /// Plugins implement this trait to participate in the runtime.
@@ -172,6 +755,11 @@ pub trait Plugin: Send {
         /// Name of the plugin used for diagnostics.
         fn name(&self) -> &'static str;
 
+        /// Version reported to the host during initialization.
+        fn version(&self) -> Option<&'static str> {
+            None
+        }
+
         /// Called once when the host sends the initialization message.
         fn initialize(
             &mut self,
@@ -186,18 +774,126 @@ fn execute(
             arguments: Vec<Value>,
             ctx: &mut CommandContext<'_>,
         ) -> Result<Option<Value>>;
+
+        /// Called once the host shut the plugin down or closed the
+        /// connection, before [`run`] returns. Plugins stop the processes
+        /// they started here; the host terminates plugins that do not exit
+        /// shortly after.
+        fn shutdown(&mut self) {}
+
+        /// Handle an event published by another plugin on a subscribed topic.
+        fn on_event(
+            &mut self,
+            _topic: &str,
+            _source: &str,
+            _payload: Value,
+            _ctx: &mut CommandContext<'_>,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        /// Return the symbols matching `query` that the plugin contributes to
+        /// workspace symbol searches. Only called for plugins that
+        /// [`Registrar::provide_workspace_symbols`].
+        fn workspace_symbols(
+            &mut self,
+            _query: &str,
+            _ctx: &mut CommandContext<'_>,
+        ) -> Result<Vec<WorkspaceSymbol>> {
+            Ok(Vec::new())
+        }
+
+        /// Return the outline of the document at `path`. Only called for
+        /// languages the plugin [`Registrar::provide_document_symbols`] for.
+        fn document_symbols(
+            &mut self,
+            _path: &Path,
+            _language_id: &str,
+            _ctx: &mut CommandContext<'_>,
+        ) -> Result<Vec<DocumentSymbol>> {
+            Ok(Vec::new())
+        }
+
+        /// Return the inlay hints within `range` of the document at `path`.
+        /// Only called for languages the plugin
+        /// [`Registrar::provide_inlay_hints`] for.
+        fn inlay_hints(
+            &mut self,
+            _path: &Path,
+            _language_id: &str,
+            _range: Range,
+            _ctx: &mut CommandContext<'_>,
+        ) -> Result<Vec<InlayHint>> {
+            Ok(Vec::new())
+        }
     }
 
     /// Registrar passed to [`Plugin::initialize`] allowing command registration.
     pub trait Registrar {
         /// Register a command with the runtime.
         fn register_command(&mut self, command: PluginCommand) -> Result<()>;
+
+        /// Restrict the plugin to documents of the given language id.
+        ///
+        /// Plugins that never declare a language run for every document. The
+        /// default implementation ignores the language.
+        fn register_language(&mut self, language_id: &str) -> Result<()> {
+            let _ = language_id;
+            Ok(())
+        }
+
+        /// Receive events published on `topic` by other plugins through
+        /// [`Plugin::on_event`]. The default implementation receives no
+        /// events.
+        fn subscribe(&mut self, topic: &str) -> Result<()> {
+            let _ = topic;
+            Ok(())
+        }
+
+        /// Contribute symbols to workspace symbol searches through
+        /// [`Plugin::workspace_symbols`]. The default implementation
+        /// contributes none.
+        fn provide_workspace_symbols(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        /// Outline documents of the given language id through
+        /// [`Plugin::document_symbols`], e.g. for file types Helix cannot
+        /// parse. The default implementation outlines nothing.
+        fn provide_document_symbols(&mut self, language_id: &str) -> Result<()> {
+            let _ = language_id;
+            Ok(())
+        }
+
+        /// Show hints inline in documents of the given language id through
+        /// [`Plugin::inlay_hints`]. The default implementation shows none.
+        fn provide_inlay_hints(&mut self, language_id: &str) -> Result<()> {
+            let _ = language_id;
+            Ok(())
+        }
     }
 
     #[derive(Default)]
     struct CommandRegistry {
         commands: Vec<PluginCommand>,
         seen: HashSet<String>,
+        languages: Vec<String>,
+        subscriptions: Vec<String>,
+        workspace_symbols: bool,
+        document_symbols: Vec<String>,
+        inlay_hints: Vec<String>,
+    }
+
+    impl CommandRegistry {
+        fn accepts_language(&self, language_id: Option<&str>) -> bool {
+            match language_id {
+                Some(language_id) if !self.languages.is_empty() => self
+                    .languages
+                    .iter()
+                    .any(|language| language == language_id),
+                _ => true,
+            }
+        }
     }
 
     impl Registrar for CommandRegistry {
@@ -211,15 +907,138 @@ fn register_command(&mut self, command: PluginCommand) -> Result<()> {
             self.commands.push(command);
             Ok(())
         }
+
+        fn register_language(&mut self, language_id: &str) -> Result<()> {
+            if !self
+                .languages
+                .iter()
+                .any(|language| language == language_id)
+            {
+                self.languages.push(language_id.to_string());
+            }
+            Ok(())
+        }
+
+        fn subscribe(&mut self, topic: &str) -> Result<()> {
+            if !self.subscriptions.iter().any(|existing| existing == topic) {
+                self.subscriptions.push(topic.to_string());
+            }
+            Ok(())
+        }
+
+        fn provide_workspace_symbols(&mut self) -> Result<()> {
+            self.workspace_symbols = true;
+            Ok(())
+        }
+
+        fn provide_document_symbols(&mut self, language_id: &str) -> Result<()> {
+            if !self
+                .document_symbols
+                .iter()
+                .any(|language| language == language_id)
+            {
+                self.document_symbols.push(language_id.to_string());
+            }
+            Ok(())
+        }
+
+        fn provide_inlay_hints(&mut self, language_id: &str) -> Result<()> {
+            if !self
+                .inlay_hints
+                .iter()
+                .any(|language| language == language_id)
+            {
+                self.inlay_hints.push(language_id.to_string());
+            }
+            Ok(())
+        }
+    }
+
+    /// Messages read from the host. Requests arriving while the plugin
+    /// awaits the reply to one of its own requests are queued for the event
+    /// loop, except pings, which are answered right away.
+    struct HostReader {
+        lines: Box<dyn BufRead + Send>,
+        queued: VecDeque<HostRequest>,
+    }
+
+    impl HostReader {
+        /// Read the next message of the host, or `None` once stdin closes.
+        fn read(&mut self) -> Result<Option<HostRequest>> {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                let read = self
+                    .lines
+                    .read_line(&mut line)
+                    .context("failed to read plugin request")?;
+                if read == 0 {
+                    return Ok(None);
+                }
+                if !line.trim().is_empty() {
+                    return serde_json::from_str(&line)
+                        .map(Some)
+                        .context("failed to parse plugin request payload");
+                }
+            }
+        }
     }
 
     /// Connection handle for emitting events back to the host.
     #[derive(Clone)]
     struct HostConnection {
         writer: Arc<Mutex<io::Stdout>>,
+        reader: Arc<Mutex<HostReader>>,
+        next_request_id: Arc<AtomicU64>,
     }
 
     impl HostConnection {
+        /// Next request for the event loop, or `None` once stdin closes.
+        fn next_request(&self) -> Result<Option<HostRequest>> {
+            let mut reader = self
+                .reader
+                .lock()
+                .map_err(|_| anyhow!("failed to lock stdin for reading"))?;
+            match reader.queued.pop_front() {
+                Some(request) => Ok(Some(request)),
+                None => reader.read(),
+            }
+        }
+
+        /// Send `request` to the host and wait for its reply.
+        fn request(&self, request: PluginRequest) -> Result<Value> {
+            let id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+            self.send_message(&PluginMessage::Request { id, request })?;
+
+            let mut reader = self
+                .reader
+                .lock()
+                .map_err(|_| anyhow!("failed to lock stdin for reading"))?;
+            loop {
+                let Some(message) = reader.read()? else {
+                    return Err(anyhow!("host disconnected before replying"));
+                };
+                match message.payload {
+                    HostRequestPayload::Reply { request, result } if request == id => {
+                        return match result {
+                            HostReply::Success { value } => Ok(value),
+                            HostReply::Error { message } => Err(anyhow!(message)),
+                        };
+                    }
+                    // The plugin is responsive while it waits, e.g. for the
+                    // user to answer a prompt.
+                    HostRequestPayload::Ping => self.send_message(&PluginMessage::Response {
+                        id: message.id,
+                        result: PluginResponse::Acknowledge,
+                    })?,
+                    payload => reader.queued.push_back(HostRequest {
+                        id: message.id,
+                        payload,
+                    }),
+                }
+            }
+        }
+
         fn send_message(&self, message: &PluginMessage) -> Result<()> {
             let mut writer = self
                 .writer
@@ -236,17 +1055,121 @@ fn send_message(&self, message: &PluginMessage) -> Result<()> {
         }
     }
 
+    /// Key-value store the host shares between plugins, e.g. for a git
+    /// plugin to share the detected repository with a pull request plugin.
+    ///
+    /// Every plugin writes to its own namespace. Values it shares are
+    /// readable by the other plugins; the rest stay private. Values live as
+    /// long as the host, surviving plugin restarts.
+    pub struct SharedStore<'a> {
+        connection: &'a HostConnection,
+    }
+
+    impl SharedStore<'_> {
+        /// Read the value this plugin stored under `key`.
+        pub fn get(&self, key: &str) -> Result<Option<Value>> {
+            self.read(key, None)
+        }
+
+        /// Read the value `plugin` shared under `key`.
+        pub fn get_from(&self, plugin: &str, key: &str) -> Result<Option<Value>> {
+            self.read(key, Some(plugin))
+        }
+
+        /// Store `value` under `key`, readable by this plugin only.
+        pub fn set(&self, key: &str, value: Value) -> Result<()> {
+            self.write(key, value, false)
+        }
+
+        /// Store `value` under `key`, readable by every plugin.
+        pub fn share(&self, key: &str, value: Value) -> Result<()> {
+            self.write(key, value, true)
+        }
+
+        /// Remove the value stored under `key`.
+        pub fn delete(&self, key: &str) -> Result<()> {
+            self.connection.request(PluginRequest::StoreDelete {
+                key: key.to_string(),
+            })?;
+            Ok(())
+        }
+
+        fn read(&self, key: &str, namespace: Option<&str>) -> Result<Option<Value>> {
+            let value = self.connection.request(PluginRequest::StoreGet {
+                key: key.to_string(),
+                namespace: namespace.map(str::to_string),
+            })?;
+            Ok(Some(value).filter(|value| !value.is_null()))
+        }
+
+        fn write(&self, key: &str, value: Value, shared: bool) -> Result<()> {
+            self.connection.request(PluginRequest::StoreSet {
+                key: key.to_string(),
+                value,
+                shared,
+            })?;
+            Ok(())
+        }
+    }
+
+    /// Secrets of the plugin, e.g. access tokens, kept by the host in the
+    /// keyring of the operating system or, where there is none, in an
+    /// encrypted file.
+    pub struct Secrets<'a> {
+        connection: &'a HostConnection,
+    }
+
+    impl Secrets<'_> {
+        /// Read the secret stored as `key`.
+        pub fn get(&self, key: &str) -> Result<Option<String>> {
+            let secret = self.connection.request(PluginRequest::SecretGet {
+                key: key.to_string(),
+            })?;
+            match secret {
+                Value::Null => Ok(None),
+                Value::String(secret) => Ok(Some(secret)),
+                other => Err(anyhow!("host returned a malformed secret: {other}")),
+            }
+        }
+
+        /// Store `secret` as `key`.
+        pub fn set(&self, key: &str, secret: &str) -> Result<()> {
+            self.connection.request(PluginRequest::SecretSet {
+                key: key.to_string(),
+                secret: secret.to_string(),
+            })?;
+            Ok(())
+        }
+
+        /// Remove the secret stored as `key`.
+        pub fn delete(&self, key: &str) -> Result<()> {
+            self.connection.request(PluginRequest::SecretDelete {
+                key: key.to_string(),
+            })?;
+            Ok(())
+        }
+    }
+
     /// Context available during plugin initialization.
     pub struct InitializeContext {
         connection: HostConnection,
         workspace_root: Option<PathBuf>,
+        workspace_folders: Vec<PathBuf>,
+        capabilities: HostCapabilities,
     }
 
     impl InitializeContext {
-        fn new(connection: HostConnection, workspace_root: Option<PathBuf>) -> Self {
+        fn new(
+            connection: HostConnection,
+            workspace_root: Option<PathBuf>,
+            workspace_folders: Vec<PathBuf>,
+            capabilities: HostCapabilities,
+        ) -> Self {
             Self {
                 connection,
                 workspace_root,
+                workspace_folders,
+                capabilities,
             }
         }
 
@@ -255,6 +1178,20 @@ pub fn workspace_root(&self) -> Option<&Path> {
             self.workspace_root.as_deref()
         }
 
+        /// Returns every workspace folder provided by the host.
+        ///
+        /// Multi-root workspaces list each folder here. Hosts that only know a
+        /// single root report it as the sole entry.
+        pub fn workspace_folders(&self) -> &[PathBuf] {
+            &self.workspace_folders
+        }
+
+        /// Optional features the host supports, e.g. to register commands
+        /// relying on them only when they are available.
+        pub fn capabilities(&self) -> &HostCapabilities {
+            &self.capabilities
+        }
+
         /// Emit a user facing message through the host.
         pub fn show_message(&self, level: MessageLevel, message: impl Into<String>) -> Result<()> {
             self.connection.send_message(&PluginMessage::Event {
@@ -274,22 +1211,109 @@ pub fn log(&self, level: MessageLevel, message: impl Into<String>) -> Result<()>
                 },
             })
         }
+
+        /// Publish an event on the host bus.
+        pub fn publish(&self, topic: impl Into<String>, payload: Value) -> Result<()> {
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::Publish {
+                    topic: topic.into(),
+                    payload,
+                },
+            })
+        }
+
+        /// Forward an event to editor-side integrations.
+        pub fn notify(&self, kind: impl Into<String>, payload: Value) -> Result<()> {
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::Notify {
+                    kind: kind.into(),
+                    payload,
+                },
+            })
+        }
+
+        /// Append `text` to the output channel named `channel`.
+        pub fn append_output(
+            &self,
+            channel: impl Into<String>,
+            text: impl Into<String>,
+        ) -> Result<()> {
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::AppendOutput {
+                    channel: channel.into(),
+                    text: text.into(),
+                },
+            })
+        }
+
+        /// Replace the diagnostics of the file at the absolute `path`.
+        pub fn publish_diagnostics(
+            &self,
+            path: impl Into<String>,
+            diagnostics: Vec<Diagnostic>,
+        ) -> Result<()> {
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::PublishDiagnostics {
+                    path: path.into(),
+                    diagnostics,
+                },
+            })
+        }
+
+        /// Store shared between plugins.
+        pub fn store(&self) -> SharedStore<'_> {
+            SharedStore {
+                connection: &self.connection,
+            }
+        }
+
+        /// Secrets of the plugin.
+        pub fn secrets(&self) -> Secrets<'_> {
+            Secrets {
+                connection: &self.connection,
+            }
+        }
     }
 
     /// Execution context made available to command handlers.
     pub struct CommandContext<'a> {
         connection: &'a HostConnection,
         plugin_name: &'a str,
+        language_id: Option<&'a str>,
+        cache_ttl: Option<Duration>,
+        /// Id of the command request, which handlers may answer later.
+        /// `None` for other requests.
+        command_id: Option<u64>,
+        deferred: bool,
     }
 
     impl<'a> CommandContext<'a> {
-        fn new(connection: &'a HostConnection, plugin_name: &'a str) -> Self {
+        fn new(
+            connection: &'a HostConnection,
+            plugin_name: &'a str,
+            language_id: Option<&'a str>,
+        ) -> Self {
             Self {
                 connection,
                 plugin_name,
+                language_id,
+                cache_ttl: None,
+                command_id: None,
+                deferred: false,
             }
         }
 
+        /// Language id of the document active when the command was invoked.
+        pub fn language_id(&self) -> Option<&str> {
+            self.language_id
+        }
+
+        /// Allow the host to answer identical invocations of the command with
+        /// its successful result for `ttl`, without calling the plugin again.
+        pub fn cache_result_for(&mut self, ttl: Duration) {
+            self.cache_ttl = Some(ttl);
+        }
+
         /// Emit a user facing message via the host.
         pub fn show_message(&self, level: MessageLevel, message: impl Into<String>) -> Result<()> {
             trace!("{}: show_message({level:?})", self.plugin_name);
@@ -311,34 +1335,342 @@ pub fn log(&self, level: MessageLevel, message: impl Into<String>) -> Result<()>
                 },
             })
         }
+
+        /// Publish an event on the host bus. Plugins subscribed to `topic`
+        /// receive it through [`Plugin::on_event`].
+        pub fn publish(&self, topic: impl Into<String>, payload: Value) -> Result<()> {
+            let topic = topic.into();
+            trace!("{}: publish({topic})", self.plugin_name);
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::Publish { topic, payload },
+            })
+        }
+
+        /// Forward an event to editor-side integrations, which receive it as
+        /// a `$/helix/pluginEvent` notification.
+        pub fn notify(&self, kind: impl Into<String>, payload: Value) -> Result<()> {
+            let kind = kind.into();
+            trace!("{}: notify({kind})", self.plugin_name);
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::Notify { kind, payload },
+            })
+        }
+
+        /// Append `text` to the output channel named `channel`, e.g. a line
+        /// of build output. Users open the channel with
+        /// `helix.plugins.show_output`.
+        pub fn append_output(
+            &self,
+            channel: impl Into<String>,
+            text: impl Into<String>,
+        ) -> Result<()> {
+            let channel = channel.into();
+            trace!("{}: append_output({channel})", self.plugin_name);
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::AppendOutput {
+                    channel,
+                    text: text.into(),
+                },
+            })
+        }
+
+        /// Replace the diagnostics the plugin shows in the file at the
+        /// absolute `path`, e.g. review comments on its lines. An empty list
+        /// clears them.
+        pub fn publish_diagnostics(
+            &self,
+            path: impl Into<String>,
+            diagnostics: Vec<Diagnostic>,
+        ) -> Result<()> {
+            let path = path.into();
+            trace!("{}: publish_diagnostics({path})", self.plugin_name);
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::PublishDiagnostics { path, diagnostics },
+            })
+        }
+
+        /// Ask the user to pick one of `choices`. Returns `None` when the
+        /// user dismissed the prompt.
+        pub fn prompt(
+            &self,
+            message: impl Into<String>,
+            choices: &[String],
+        ) -> Result<Option<String>> {
+            let answer = self.connection.request(PluginRequest::Prompt {
+                message: message.into(),
+                choices: choices.to_vec(),
+            })?;
+            Ok(answer.as_str().map(str::to_string))
+        }
+
+        /// Ask the user to enter text, starting from `default`. Returns
+        /// `None` when the user dismissed the prompt. Fails unless the host
+        /// has the [`HostCapabilities::input`] capability.
+        pub fn input(
+            &self,
+            message: impl Into<String>,
+            default: Option<&str>,
+        ) -> Result<Option<String>> {
+            let answer = self.connection.request(PluginRequest::Input {
+                message: message.into(),
+                default: default.map(str::to_string),
+            })?;
+            Ok(answer.as_str().map(str::to_string))
+        }
+
+        /// Run `program` in a terminal of the editor titled `title`. Returns
+        /// the id of the process, if the editor reports it. Fails unless the
+        /// host has the [`HostCapabilities::run_in_terminal`] capability.
+        pub fn run_in_terminal(
+            &self,
+            title: impl Into<String>,
+            program: impl Into<String>,
+            args: &[String],
+            cwd: Option<&Path>,
+            env: &BTreeMap<String, String>,
+        ) -> Result<Option<u32>> {
+            let process_id = self.connection.request(PluginRequest::RunInTerminal {
+                title: title.into(),
+                program: program.into(),
+                args: args.to_vec(),
+                cwd: cwd.map(|cwd| cwd.to_string_lossy().into_owned()),
+                env: env.clone(),
+            })?;
+            Ok(process_id.as_u64().and_then(|id| u32::try_from(id).ok()))
+        }
+
+        /// Answer the command from any thread through the returned
+        /// [`Responder`], so that slow commands do not hold up the requests
+        /// of the host meanwhile. The value [`Plugin::execute`] returns is
+        /// ignored then. Only commands can be answered later.
+        pub fn respond_later(&mut self) -> Result<Responder> {
+            let id = self
+                .command_id
+                .ok_or_else(|| anyhow!("only commands can be answered later"))?;
+            if self.deferred {
+                bail!("the command is answered later already");
+            }
+            self.deferred = true;
+            Ok(Responder {
+                connection: self.connection.clone(),
+                plugin_name: self.plugin_name.to_string(),
+                id: Some(id),
+                cache_ttl: None,
+            })
+        }
+
+        /// Handle emitting events once the command returned, e.g. the
+        /// output of a process the command started.
+        pub fn events(&self) -> EventSender {
+            EventSender {
+                connection: self.connection.clone(),
+                plugin_name: self.plugin_name.to_string(),
+            }
+        }
+
+        /// Store shared between plugins.
+        pub fn store(&self) -> SharedStore<'_> {
+            SharedStore {
+                connection: self.connection,
+            }
+        }
+
+        /// Secrets of the plugin.
+        pub fn secrets(&self) -> Secrets<'_> {
+            Secrets {
+                connection: self.connection,
+            }
+        }
+    }
+
+    /// Emits events from any thread. Requests to the host, such as store
+    /// access, are only possible from handlers.
+    #[derive(Clone)]
+    pub struct EventSender {
+        connection: HostConnection,
+        plugin_name: String,
+    }
+
+    impl EventSender {
+        /// Emit a user facing message via the host.
+        pub fn show_message(&self, level: MessageLevel, message: impl Into<String>) -> Result<()> {
+            trace!("{}: show_message({level:?})", self.plugin_name);
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::ShowMessage {
+                    level,
+                    message: message.into(),
+                },
+            })
+        }
+
+        /// Emit a log message.
+        pub fn log(&self, level: MessageLevel, message: impl Into<String>) -> Result<()> {
+            trace!("{}: log({level:?})", self.plugin_name);
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::Log {
+                    level,
+                    message: message.into(),
+                },
+            })
+        }
+
+        /// Publish an event on the host bus.
+        pub fn publish(&self, topic: impl Into<String>, payload: Value) -> Result<()> {
+            let topic = topic.into();
+            trace!("{}: publish({topic})", self.plugin_name);
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::Publish { topic, payload },
+            })
+        }
+
+        /// Forward an event to editor-side integrations.
+        pub fn notify(&self, kind: impl Into<String>, payload: Value) -> Result<()> {
+            let kind = kind.into();
+            trace!("{}: notify({kind})", self.plugin_name);
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::Notify { kind, payload },
+            })
+        }
+
+        /// Append `text` to the output channel named `channel`.
+        pub fn append_output(
+            &self,
+            channel: impl Into<String>,
+            text: impl Into<String>,
+        ) -> Result<()> {
+            let channel = channel.into();
+            trace!("{}: append_output({channel})", self.plugin_name);
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::AppendOutput {
+                    channel,
+                    text: text.into(),
+                },
+            })
+        }
+
+        /// Replace the diagnostics of the file at the absolute `path`.
+        pub fn publish_diagnostics(
+            &self,
+            path: impl Into<String>,
+            diagnostics: Vec<Diagnostic>,
+        ) -> Result<()> {
+            let path = path.into();
+            trace!("{}: publish_diagnostics({path})", self.plugin_name);
+            self.connection.send_message(&PluginMessage::Event {
+                event: PluginEvent::PublishDiagnostics { path, diagnostics },
+            })
+        }
+    }
+
+    /// Answers a command once its result is ready, see
+    /// [`CommandContext::respond_later`]. A responder dropped without
+    /// answering fails the command.
+    pub struct Responder {
+        connection: HostConnection,
+        plugin_name: String,
+        id: Option<u64>,
+        cache_ttl: Option<Duration>,
+    }
+
+    impl Responder {
+        /// Allow the host to answer identical invocations of the command with
+        /// its successful result for `ttl`, without calling the plugin again.
+        pub fn cache_result_for(&mut self, ttl: Duration) {
+            self.cache_ttl = Some(ttl);
+        }
+
+        /// Answer the command with `result`.
+        pub fn respond(mut self, result: Result<Option<Value>>) -> Result<()> {
+            let id = self.id.take().expect("commands are answered once");
+            if let Err(err) = &result {
+                error!("{} command failed: {err:?}", self.plugin_name);
+            }
+            self.connection.send_message(&PluginMessage::Response {
+                id,
+                result: command_response(result, self.cache_ttl),
+            })
+        }
+    }
+
+    impl Drop for Responder {
+        fn drop(&mut self) {
+            if let Some(id) = self.id {
+                let _ = self.connection.send_message(&PluginMessage::Response {
+                    id,
+                    result: PluginResponse::CommandError {
+                        message: "the command finished without a result".to_string(),
+                    },
+                });
+            }
+        }
+    }
+
+    /// Response to a command that returned `result`.
+    fn command_response(
+        result: Result<Option<Value>>,
+        cache_ttl: Option<Duration>,
+    ) -> PluginResponse {
+        match result {
+            Ok(result) => PluginResponse::CommandResult {
+                result,
+                cache_ttl_ms: cache_ttl
+                    .map(|ttl| u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX)),
+            },
+            Err(err) => PluginResponse::CommandError {
+                message: err.to_string(),
+            },
+        }
     }
 
     /// Run the plugin event loop.
     pub fn run<P: Plugin>(mut plugin: P) -> Result<()> {
-        let stdout = io::stdout();
+        let result = serve(&mut plugin);
+        plugin.shutdown();
+        result
+    }
+
+    /// Handle the requests of the host until it shuts the plugin down or
+    /// closes the connection.
+    fn serve<P: Plugin>(plugin: &mut P) -> Result<()> {
         let connection = HostConnection {
-            writer: Arc::new(Mutex::new(stdout)),
+            writer: Arc::new(Mutex::new(io::stdout())),
+            reader: Arc::new(Mutex::new(HostReader {
+                lines: Box::new(io::BufReader::new(io::stdin())),
+                queued: VecDeque::new(),
+            })),
+            next_request_id: Arc::new(AtomicU64::new(1)),
         };
 
-        let stdin = io::stdin();
-        let reader = io::BufReader::new(stdin.lock());
-
         let mut initialized = false;
         let mut registry = CommandRegistry::default();
 
-        for line in reader.lines() {
-            let line = line.context("failed to read plugin request")?;
-            if line.trim().is_empty() {
+        while let Some(request) = connection.next_request()? {
+            trace!("plugin received request: {:?}", request.payload);
+
+            let needs_initialize = !matches!(
+                request.payload,
+                HostRequestPayload::Initialize { .. }
+                    | HostRequestPayload::Reply { .. }
+                    | HostRequestPayload::Ping
+                    | HostRequestPayload::Shutdown
+            );
+            if needs_initialize && !initialized {
+                error!("plugin received request {} before initialize", request.id);
+                connection.send_message(&PluginMessage::Response {
+                    id: request.id,
+                    result: PluginResponse::CommandError {
+                        message: "plugin not initialized".to_string(),
+                    },
+                })?;
                 continue;
             }
 
-            let request: HostRequest =
-                serde_json::from_str(&line).context("failed to parse plugin request payload")?;
-
-            trace!("plugin received request: {:?}", request.payload);
-
             match request.payload {
-                HostRequestPayload::Initialize { workspace_root } => {
+                HostRequestPayload::Initialize {
+                    workspace_root,
+                    workspace_folders,
+                    capabilities,
+                } => {
                     if initialized {
                         error!("plugin received duplicate initialize request");
                         connection.send_message(&PluginMessage::Response {
@@ -351,7 +1683,17 @@ pub fn run<P: Plugin>(mut plugin: P) -> Result<()> {
                     }
 
                     let workspace_root = workspace_root.map(PathBuf::from);
-                    let mut init_ctx = InitializeContext::new(connection.clone(), workspace_root);
+                    let mut workspace_folders: Vec<PathBuf> =
+                        workspace_folders.into_iter().map(PathBuf::from).collect();
+                    if workspace_folders.is_empty() {
+                        workspace_folders.extend(workspace_root.clone());
+                    }
+                    let mut init_ctx = InitializeContext::new(
+                        connection.clone(),
+                        workspace_root,
+                        workspace_folders,
+                        capabilities,
+                    );
                     plugin
                         .initialize(&mut init_ctx, &mut registry)
                         .with_context(|| format!("{} failed to initialize", plugin.name()))?;
@@ -360,41 +1702,153 @@ pub fn run<P: Plugin>(mut plugin: P) -> Result<()> {
                         id: request.id,
                         result: PluginResponse::Initialized {
                             commands: registry.commands.clone(),
+                            languages: registry.languages.clone(),
+                            protocol_version: Some(crate::protocol::VERSION),
+                            subscriptions: registry.subscriptions.clone(),
+                            version: plugin.version().map(str::to_string),
+                            workspace_symbols: registry.workspace_symbols,
+                            document_symbols: registry.document_symbols.clone(),
+                            inlay_hints: registry.inlay_hints.clone(),
                         },
                     })?;
                     initialized = true;
                 }
-                HostRequestPayload::Execute { command, arguments } => {
-                    if !initialized {
-                        error!("plugin received execute before initialize");
+                HostRequestPayload::Execute {
+                    command,
+                    arguments,
+                    language_id,
+                } => {
+                    if !registry.accepts_language(language_id.as_deref()) {
+                        debug!(
+                            "{} skipping `{command}` for unsupported language {language_id:?}",
+                            plugin.name()
+                        );
                         connection.send_message(&PluginMessage::Response {
                             id: request.id,
                             result: PluginResponse::CommandError {
-                                message: "plugin not initialized".to_string(),
+                                message: format!(
+                                    "{} does not handle `{}` documents",
+                                    plugin.name(),
+                                    language_id.unwrap_or_default()
+                                ),
                             },
                         })?;
                         continue;
                     }
 
-                    let mut ctx = CommandContext::new(&connection, plugin.name());
+                    let mut ctx =
+                        CommandContext::new(&connection, plugin.name(), language_id.as_deref());
+                    ctx.command_id = Some(request.id);
 
-                    match plugin.execute(&command, arguments, &mut ctx) {
-                        Ok(result) => {
-                            connection.send_message(&PluginMessage::Response {
-                                id: request.id,
-                                result: PluginResponse::CommandResult { result },
-                            })?;
+                    let result = plugin.execute(&command, arguments, &mut ctx);
+                    if let Err(err) = &result {
+                        error!("{} command `{command}` failed: {err:?}", plugin.name());
+                    }
+                    // Handlers answering later may still fail before handing
+                    // the command off; the responder they dropped reports it.
+                    if !ctx.deferred {
+                        connection.send_message(&PluginMessage::Response {
+                            id: request.id,
+                            result: command_response(result, ctx.cache_ttl),
+                        })?;
+                    }
+                }
+                HostRequestPayload::BusEvent {
+                    topic,
+                    source,
+                    payload,
+                } => {
+                    let mut ctx = CommandContext::new(&connection, plugin.name(), None);
+                    let result = match plugin.on_event(&topic, &source, payload, &mut ctx) {
+                        Ok(()) => PluginResponse::Acknowledge,
+                        Err(err) => {
+                            error!(
+                                "{} failed to handle `{topic}` event: {err:?}",
+                                plugin.name()
+                            );
+                            PluginResponse::CommandError {
+                                message: err.to_string(),
+                            }
                         }
+                    };
+                    connection.send_message(&PluginMessage::Response {
+                        id: request.id,
+                        result,
+                    })?;
+                }
+                HostRequestPayload::WorkspaceSymbols { query } => {
+                    let mut ctx = CommandContext::new(&connection, plugin.name(), None);
+                    let result = match plugin.workspace_symbols(&query, &mut ctx) {
+                        Ok(symbols) => PluginResponse::WorkspaceSymbols { symbols },
                         Err(err) => {
-                            error!("{} command `{command}` failed: {err:?}", plugin.name());
-                            connection.send_message(&PluginMessage::Response {
-                                id: request.id,
-                                result: PluginResponse::CommandError {
-                                    message: err.to_string(),
-                                },
-                            })?;
+                            error!(
+                                "{} failed to search workspace symbols: {err:?}",
+                                plugin.name()
+                            );
+                            PluginResponse::CommandError {
+                                message: err.to_string(),
+                            }
                         }
-                    }
+                    };
+                    connection.send_message(&PluginMessage::Response {
+                        id: request.id,
+                        result,
+                    })?;
+                }
+                HostRequestPayload::DocumentSymbols { path, language_id } => {
+                    let mut ctx =
+                        CommandContext::new(&connection, plugin.name(), Some(&language_id));
+                    let result =
+                        match plugin.document_symbols(Path::new(&path), &language_id, &mut ctx) {
+                            Ok(symbols) => PluginResponse::DocumentSymbols { symbols },
+                            Err(err) => {
+                                error!("{} failed to outline `{path}`: {err:?}", plugin.name());
+                                PluginResponse::CommandError {
+                                    message: err.to_string(),
+                                }
+                            }
+                        };
+                    connection.send_message(&PluginMessage::Response {
+                        id: request.id,
+                        result,
+                    })?;
+                }
+                HostRequestPayload::InlayHints {
+                    path,
+                    language_id,
+                    range,
+                } => {
+                    let mut ctx =
+                        CommandContext::new(&connection, plugin.name(), Some(&language_id));
+                    let result =
+                        match plugin.inlay_hints(Path::new(&path), &language_id, range, &mut ctx) {
+                            Ok(hints) => PluginResponse::InlayHints { hints },
+                            Err(err) => {
+                                error!(
+                                    "{} failed to compute inlay hints of `{path}`: {err:?}",
+                                    plugin.name()
+                                );
+                                PluginResponse::CommandError {
+                                    message: err.to_string(),
+                                }
+                            }
+                        };
+                    connection.send_message(&PluginMessage::Response {
+                        id: request.id,
+                        result,
+                    })?;
+                }
+                HostRequestPayload::Reply { request, .. } => {
+                    debug!(
+                        "{} ignoring reply to unknown request {request}",
+                        plugin.name()
+                    );
+                }
+                HostRequestPayload::Ping => {
+                    connection.send_message(&PluginMessage::Response {
+                        id: request.id,
+                        result: PluginResponse::Acknowledge,
+                    })?;
                 }
                 HostRequestPayload::Shutdown => {
                     debug!("{} shutting down", plugin.name());
@@ -411,5 +1865,35 @@ pub fn run<P: Plugin>(mut plugin: P) -> Result<()> {
     }
 }
 
-pub use protocol::{MessageLevel, PluginCommand};
-pub use runtime::{run, CommandContext, InitializeContext, Plugin, Registrar};
+/// Assert at compile time that the plugin targets the SDK protocol version.
+///
+/// Invoked without arguments the macro only refers to [`protocol::VERSION`]
+/// and checks nothing further. Passing a version fails the build when the SDK
+/// speaks a different protocol version than the one the plugin was written
+/// against:
+///
+/// ```
+/// helix_plugin_sdk::assert_protocol_compat!();
+/// helix_plugin_sdk::assert_protocol_compat!(1);
+/// ```
+#[macro_export]
+macro_rules! assert_protocol_compat {
+    () => {
+        const _: u32 = $crate::protocol::VERSION;
+    };
+    ($version:expr) => {
+        const _: () = assert!(
+            $version == $crate::protocol::VERSION,
+            "plugin targets a different helix plugin protocol version than the SDK"
+        );
+    };
+}
+
+pub use protocol::{
+    Diagnostic, DiagnosticSeverity, DocumentSymbol, HostCapabilities, InlayHint, InlayHintKind,
+    MessageLevel, PluginCommand, Position, Range, SymbolKind, WorkspaceSymbol,
+};
+pub use runtime::{
+    run, CommandContext, EventSender, InitializeContext, Plugin, Registrar, Responder, Secrets,
+    SharedStore,
+};
@@ -0,0 +1,209 @@
+//! Requests to the GitHub REST API.
+
+use crate::{PluginError, Repository};
+use anyhow::Result;
+use reqwest::{header::LINK, Client, Method, RequestBuilder, Response};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::Duration;
+use url::Url;
+
+pub const API_URL: &str = "https://api.github.com";
+
+/// Items requested per page, the most GitHub returns.
+const PER_PAGE: usize = 100;
+
+/// Page of a listing. Searches wrap their items in an object.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Page<T> {
+    List(Vec<T>),
+    Search { items: Vec<T> },
+}
+
+/// Body of error responses.
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    message: String,
+    /// Details of validation failures, as text or objects with a message.
+    #[serde(default)]
+    errors: Vec<serde_json::Value>,
+}
+
+/// `response` unless it reports a failure, which is turned into an error
+/// with the explanation GitHub gave for it.
+async fn check(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let Ok(body) = response.json::<ErrorBody>().await else {
+        return Err(PluginError::ApiStatus(status).into());
+    };
+    let mut message = body.message;
+    for error in body.errors {
+        let detail = match error.get("message").unwrap_or(&error) {
+            serde_json::Value::String(detail) => detail.clone(),
+            _ => error.to_string(),
+        };
+        message = format!("{message}; {detail}");
+    }
+    Err(PluginError::Api { status, message }.into())
+}
+
+/// Client authenticated as the user, if a token is set.
+#[derive(Debug, Clone)]
+pub struct Api {
+    client: Client,
+    token: Option<String>,
+    /// Items a listing returns at most.
+    max_results: usize,
+}
+
+impl Api {
+    pub fn new(token: Option<String>, max_results: usize) -> Result<Self> {
+        let client = Client::builder()
+            .user_agent("helix-plugin-github-pr-dashboard/0.1")
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(Self {
+            client,
+            token,
+            max_results,
+        })
+    }
+
+    /// Url of `path` below the API of `repo`.
+    pub fn repo_url(repo: &Repository, path: &str) -> Result<Url> {
+        let url = format!(
+            "{API_URL}/repos/{owner}/{name}/{path}",
+            owner = repo.owner,
+            name = repo.name
+        );
+        Ok(Url::parse(&url)?)
+    }
+
+    fn authenticate(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Resource at `url`.
+    pub async fn get<T: DeserializeOwned>(&self, url: Url) -> Result<T> {
+        let response = self.authenticate(self.client.get(url)).send().await?;
+        Ok(check(response).await?.json().await?)
+    }
+
+    /// Response to the `method` request of `url` with the JSON `body`.
+    pub async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: Url,
+        body: &impl Serialize,
+    ) -> Result<T> {
+        let request = self.client.request(method, url).json(body);
+        let response = self.authenticate(request).send().await?;
+        Ok(check(response).await?.json().await?)
+    }
+
+    /// Delete the resource at `url`.
+    pub async fn delete(&self, url: Url) -> Result<()> {
+        let response = self.authenticate(self.client.delete(url)).send().await?;
+        check(response).await?;
+        Ok(())
+    }
+
+    /// Items of the listing at `url`, following its pages until the maximum
+    /// number of items was fetched. Also returns whether items were left
+    /// out.
+    pub async fn list<T: DeserializeOwned>(&self, mut url: Url) -> Result<(Vec<T>, bool)> {
+        url.query_pairs_mut()
+            .append_pair("per_page", &PER_PAGE.to_string());
+        let mut items = Vec::new();
+        let mut next = Some(url);
+        while let Some(url) = next.take().filter(|_| items.len() < self.max_results) {
+            let response = check(self.authenticate(self.client.get(url)).send().await?).await?;
+            next = response
+                .headers()
+                .get(LINK)
+                .and_then(|link| link.to_str().ok())
+                .and_then(next_page);
+
+            match response.json().await? {
+                Page::List(page) | Page::Search { items: page } => items.extend(page),
+            }
+        }
+        let truncated = truncate(&mut items, self.max_results, next.is_some());
+        Ok((items, truncated))
+    }
+}
+
+/// Keep the first `max_results` of `items`, returning whether items were left
+/// out, including those of `more_pages` not fetched.
+fn truncate<T>(items: &mut Vec<T>, max_results: usize, more_pages: bool) -> bool {
+    let truncated = more_pages || items.len() > max_results;
+    items.truncate(max_results);
+    truncated
+}
+
+/// Url of the next page in a `Link` header such as
+/// `<https://api.github.com/...&page=2>; rel="next", <...>; rel="last"`.
+fn next_page(link: &str) -> Option<Url> {
+    link.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        if !params
+            .split(';')
+            .any(|param| param.trim() == r#"rel="next""#)
+        {
+            return None;
+        }
+        Url::parse(url.trim().trim_start_matches('<').trim_end_matches('>')).ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_next_page() {
+        let link = concat!(
+            r#"<https://api.github.com/repositories/1/pulls?per_page=100&page=1>; rel="prev", "#,
+            r#"<https://api.github.com/repositories/1/pulls?per_page=100&page=3>; rel="next", "#,
+            r#"<https://api.github.com/repositories/1/pulls?per_page=100&page=9>; rel="last""#,
+        );
+        assert_eq!(
+            next_page(link).map(String::from),
+            Some("https://api.github.com/repositories/1/pulls?per_page=100&page=3".to_string())
+        );
+    }
+
+    #[test]
+    fn ends_at_the_last_page() {
+        let link = concat!(
+            r#"<https://api.github.com/repositories/1/pulls?page=1>; rel="first", "#,
+            r#"<https://api.github.com/repositories/1/pulls?page=8>; rel="prev""#,
+        );
+        assert_eq!(next_page(link), None);
+        assert_eq!(next_page(""), None);
+    }
+
+    #[test]
+    fn truncates_listings() {
+        // The maximum is reached in the middle of the last fetched page.
+        let mut items: Vec<u32> = (0..150).collect();
+        assert!(truncate(&mut items, 120, false));
+        assert_eq!(items.len(), 120);
+
+        // Pages are left unfetched once a page ends at the maximum.
+        let mut items: Vec<u32> = (0..100).collect();
+        assert!(truncate(&mut items, 100, true));
+        assert_eq!(items.len(), 100);
+
+        let mut items: Vec<u32> = (0..100).collect();
+        assert!(!truncate(&mut items, 100, false));
+        assert!(!truncate(&mut items, 120, false));
+        assert_eq!(items.len(), 100);
+    }
+}
@@ -0,0 +1,119 @@
+//! Checking out the branch of a pull request, as `helix.github.checkout_pr`
+//! does.
+//!
+//! Branches of the repository itself are fetched from `origin` and tracked
+//! there. Branches of forks are fetched through `pull/<number>/head` of
+//! `origin` into a local branch prefixed with the owner of the fork, or
+//! `pr/<number>` once the fork was deleted. The branch pulls from the fork
+//! when maintainers may push to it, and from `pull/<number>/head`
+//! otherwise. Branches checked out before are fast-forwarded to the head of
+//! the pull request.
+
+use crate::{
+    api::Api,
+    git::{git, has_branch},
+    Repository,
+};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Deserialize)]
+struct PullRequest {
+    head: Head,
+    #[serde(default)]
+    maintainer_can_modify: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Head {
+    #[serde(rename = "ref")]
+    name: String,
+    /// `None` once the fork was deleted.
+    repo: Option<HeadRepository>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HeadRepository {
+    full_name: String,
+    clone_url: String,
+    owner: Owner,
+}
+
+#[derive(Debug, Deserialize)]
+struct Owner {
+    login: String,
+}
+
+/// Result of `helix.github.checkout_pr`.
+#[derive(Debug, Serialize)]
+pub struct Checkout {
+    branch: String,
+    /// Commit checked out.
+    head: String,
+    /// Whether the branch was created rather than updated.
+    created: bool,
+}
+
+/// Check out the branch of the pull request `number` of `repo` in `root`.
+pub async fn checkout(api: Api, repo: Repository, root: &Path, number: u64) -> Result<Checkout> {
+    let pr: PullRequest = api
+        .get(Api::repo_url(&repo, &format!("pulls/{number}"))?)
+        .await?;
+    let full_name = format!("{}/{}", repo.owner, repo.name);
+    let fork = pr
+        .head
+        .repo
+        .as_ref()
+        .filter(|head| !head.full_name.eq_ignore_ascii_case(&full_name));
+
+    let same_repository = pr.head.repo.is_some() && fork.is_none();
+
+    let pull_ref = format!("refs/pull/{number}/head");
+    let head_ref = format!("refs/heads/{}", pr.head.name);
+    let (branch, fetched) = if same_repository {
+        let remote_ref = format!("refs/remotes/origin/{}", pr.head.name);
+        git(
+            root,
+            &["fetch", "origin", &format!("+{head_ref}:{remote_ref}")],
+        )
+        .await?;
+        (pr.head.name.clone(), remote_ref)
+    } else {
+        let branch = match fork {
+            Some(fork) => format!("{}/{}", fork.owner.login, pr.head.name),
+            None => format!("pr/{number}"),
+        };
+        git(root, &["fetch", "origin", &pull_ref]).await?;
+        (branch, "FETCH_HEAD".to_string())
+    };
+
+    let created = !has_branch(root, &branch).await;
+    if created {
+        git(root, &["checkout", "-b", &branch, &fetched]).await?;
+    } else {
+        git(root, &["checkout", &branch]).await?;
+        if let Err(err) = git(root, &["merge", "--ff-only", &fetched]).await {
+            bail!("`{branch}` was checked out but could not be fast-forwarded: {err}");
+        }
+    }
+
+    let (remote, merge) = match fork {
+        Some(fork) if pr.maintainer_can_modify => (fork.clone_url.as_str(), head_ref),
+        _ if same_repository => ("origin", head_ref),
+        _ => ("origin", pull_ref),
+    };
+    git(
+        root,
+        &["config", &format!("branch.{branch}.remote"), remote],
+    )
+    .await?;
+    git(root, &["config", &format!("branch.{branch}.merge"), &merge]).await?;
+
+    let head = git(root, &["rev-parse", "HEAD"]).await?;
+    Ok(Checkout {
+        branch,
+        head,
+        created,
+    })
+}
@@ -0,0 +1,463 @@
+//! Review comments of the pull request of the checked out branch, as
+//! `helix.github.review_comments` shows them in the working tree.
+//!
+//! The pull request is found through the branch `helix.github.checkout_pr`
+//! or `git push` made the branch track, falling back to a branch of the same
+//! name in the repository. Comments are grouped into threads, each shown as
+//! one diagnostic on the lines its first comment is on.
+//!
+//! GitHub reports those lines in the commit the comment was made on, or
+//! through the diff hunk once the code changed. They are carried over to the
+//! working tree through `git diff` from that commit; threads whose lines
+//! changed since are marked outdated. Lines are taken as they are when the
+//! commit is not available locally.
+
+use crate::{api::Api, git::git, parse_remote, Repository, User};
+use anyhow::{bail, Result};
+use helix_plugin_sdk::{Diagnostic, DiagnosticSeverity, EventSender, Position, Range};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+#[derive(Debug, Deserialize)]
+struct PullRequest {
+    number: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReviewComment {
+    id: u64,
+    in_reply_to_id: Option<u64>,
+    path: String,
+    user: Option<User>,
+    body: String,
+    created_at: String,
+    html_url: String,
+    diff_hunk: String,
+    commit_id: String,
+    original_commit_id: String,
+    /// Last line commented on in `commit_id`, `None` once outdated.
+    line: Option<u32>,
+    start_line: Option<u32>,
+    /// Last line commented on in `original_commit_id`.
+    original_line: Option<u32>,
+    original_start_line: Option<u32>,
+    /// `LEFT` for comments on removed lines.
+    side: Option<String>,
+}
+
+/// One based lines a comment is on in the version of its file at `commit`.
+#[derive(Debug)]
+struct Anchor<'a> {
+    commit: &'a str,
+    start: u32,
+    end: u32,
+}
+
+impl ReviewComment {
+    fn anchor(&self) -> Option<Anchor<'_>> {
+        if self.side.as_deref() != Some("LEFT") {
+            if let Some(end) = self.line {
+                return Some(Anchor {
+                    commit: &self.commit_id,
+                    start: self.start_line.unwrap_or(end),
+                    end,
+                });
+            }
+            if let Some(end) = self.original_line {
+                return Some(Anchor {
+                    commit: &self.original_commit_id,
+                    start: self.original_start_line.unwrap_or(end),
+                    end,
+                });
+            }
+        }
+        // Removed lines no longer exist; the comment goes on the line
+        // following them.
+        let end = hunk_line(&self.diff_hunk)?;
+        Some(Anchor {
+            commit: &self.original_commit_id,
+            start: end,
+            end,
+        })
+    }
+}
+
+/// Result of `helix.github.review_comments`.
+#[derive(Debug, Serialize)]
+pub struct ReviewComments {
+    number: u64,
+    threads: Vec<Thread>,
+}
+
+/// Comment and its replies.
+#[derive(Debug, Serialize)]
+struct Thread {
+    /// File relative to the root of the repository.
+    path: String,
+    /// One based lines in the working tree, unless the file is gone.
+    start_line: Option<u32>,
+    line: Option<u32>,
+    /// Whether the lines changed since the thread was started.
+    outdated: bool,
+    url: String,
+    comments: Vec<CommentSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct CommentSummary {
+    author: Option<String>,
+    body: String,
+    created_at: String,
+}
+
+impl Thread {
+    fn message(&self) -> String {
+        let mut message = String::new();
+        if self.outdated {
+            message.push_str("(outdated) ");
+        }
+        for (index, comment) in self.comments.iter().enumerate() {
+            if index > 0 {
+                message.push_str("\n  ");
+            }
+            let author = comment.author.as_deref().unwrap_or("ghost");
+            message.push_str(&format!("{author}: {}", comment.body.trim()));
+        }
+        message
+    }
+}
+
+/// Hunk of `git diff -U0`: `old_len` lines from `old_start` were replaced by
+/// `new_len` lines from `new_start`.
+#[derive(Debug, PartialEq, Eq)]
+struct Hunk {
+    old_start: u32,
+    old_len: u32,
+    new_start: u32,
+    new_len: u32,
+}
+
+/// Start and length of a hunk range such as `12,3`, or `12` for one line.
+fn hunk_range(range: &str) -> Option<(u32, u32)> {
+    match range.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Old and new range of a hunk header such as `@@ -12,3 +14,4 @@ fn main`.
+fn hunk_header(header: &str) -> Option<Hunk> {
+    let mut ranges = header.strip_prefix("@@ ")?.split(' ');
+    let (old_start, old_len) = hunk_range(ranges.next()?.strip_prefix('-')?)?;
+    let (new_start, new_len) = hunk_range(ranges.next()?.strip_prefix('+')?)?;
+    Some(Hunk {
+        old_start,
+        old_len,
+        new_start,
+        new_len,
+    })
+}
+
+/// Line on the new side of the last line of `diff_hunk`, which is the line
+/// a comment was made on.
+fn hunk_line(diff_hunk: &str) -> Option<u32> {
+    let mut lines = diff_hunk.lines();
+    let mut line = hunk_header(lines.next()?)?.new_start;
+    let body: Vec<&str> = lines.filter(|line| !line.starts_with('\\')).collect();
+    let (_, before) = body.split_last()?;
+    for text in before {
+        if !text.starts_with('-') {
+            line += 1;
+        }
+    }
+    Some(line)
+}
+
+/// Line in the working tree of `line` of the old side of `hunks`, and
+/// whether it was changed.
+fn map_line(hunks: &[Hunk], line: u32) -> (u32, bool) {
+    let mut offset = 0i64;
+    for hunk in hunks {
+        // Lines added without removing any follow `old_start`.
+        if hunk.old_len == 0 {
+            if line <= hunk.old_start {
+                break;
+            }
+        } else if line < hunk.old_start {
+            break;
+        } else if line < hunk.old_start + hunk.old_len {
+            let start = if hunk.new_len == 0 {
+                hunk.new_start + 1
+            } else {
+                hunk.new_start
+            };
+            return (start, true);
+        }
+        offset += i64::from(hunk.new_len) - i64::from(hunk.old_len);
+    }
+    let line = (i64::from(line) + offset).max(1);
+    (u32::try_from(line).unwrap_or(u32::MAX), false)
+}
+
+/// Hunks turning `path` at `commit` into the file in the working tree, or
+/// `None` when the commit is not available locally.
+async fn local_changes(root: &Path, commit: &str, path: &str) -> Option<Vec<Hunk>> {
+    let diff = git(
+        root,
+        &[
+            "diff",
+            "-U0",
+            "--no-color",
+            "--no-ext-diff",
+            commit,
+            "--",
+            path,
+        ],
+    )
+    .await
+    .ok()?;
+    Some(diff.lines().filter_map(hunk_header).collect())
+}
+
+/// Number of the open pull request of the branch checked out in `root`.
+async fn current_pull_request(api: &Api, repo: &Repository, root: &Path) -> Result<u64> {
+    let branch = git(root, &["rev-parse", "--abbrev-ref", "HEAD"]).await?;
+    if branch == "HEAD" {
+        bail!("no branch is checked out");
+    }
+
+    let merge = git(root, &["config", &format!("branch.{branch}.merge")])
+        .await
+        .ok();
+    if let Some(number) = merge
+        .as_deref()
+        .and_then(|merge| merge.strip_prefix("refs/pull/"))
+        .and_then(|merge| merge.strip_suffix("/head"))
+        .and_then(|number| number.parse().ok())
+    {
+        return Ok(number);
+    }
+    let head = merge
+        .as_deref()
+        .and_then(|merge| merge.strip_prefix("refs/heads/"))
+        .unwrap_or(&branch);
+
+    // Branches of forks track the url of the fork or a remote named after
+    // it.
+    let mut owner = repo.owner.clone();
+    if let Ok(remote) = git(root, &["config", &format!("branch.{branch}.remote")]).await {
+        let url = match git(root, &["config", &format!("remote.{remote}.url")]).await {
+            Ok(url) => url,
+            Err(_) => remote,
+        };
+        if let Ok(remote) = parse_remote(&url) {
+            owner = remote.owner;
+        }
+    }
+
+    let mut url = Api::repo_url(repo, "pulls")?;
+    url.query_pairs_mut()
+        .append_pair("head", &format!("{owner}:{head}"));
+    let prs: Vec<PullRequest> = api.get(url).await?;
+    match prs.first() {
+        Some(pr) => Ok(pr.number),
+        None => bail!("`{branch}` has no open pull request"),
+    }
+}
+
+/// Review comments of the pull request of the branch checked out in `root`.
+pub async fn fetch(api: Api, repo: Repository, root: &Path) -> Result<ReviewComments> {
+    let number = current_pull_request(&api, &repo, root).await?;
+    let url = Api::repo_url(&repo, &format!("pulls/{number}/comments"))?;
+    let (comments, _) = api.list::<ReviewComment>(url).await?;
+
+    let mut roots: Vec<(ReviewComment, Vec<ReviewComment>)> = Vec::new();
+    let mut replies: BTreeMap<u64, Vec<ReviewComment>> = BTreeMap::new();
+    for comment in comments {
+        match comment.in_reply_to_id {
+            Some(id) => replies.entry(id).or_default().push(comment),
+            None => roots.push((comment, Vec::new())),
+        }
+    }
+    for (comment, thread) in &mut roots {
+        *thread = replies.remove(&comment.id).unwrap_or_default();
+    }
+
+    let mut changes: BTreeMap<(String, String), Option<Vec<Hunk>>> = BTreeMap::new();
+    let mut threads = Vec::new();
+    for (comment, replies) in roots {
+        let mut lines = None;
+        let mut outdated = comment.line.is_none();
+        if root.join(&comment.path).is_file() {
+            if let Some(anchor) = comment.anchor() {
+                let key = (anchor.commit.to_string(), comment.path.clone());
+                if !changes.contains_key(&key) {
+                    let hunks = local_changes(root, anchor.commit, &comment.path).await;
+                    changes.insert(key.clone(), hunks);
+                }
+                let (start, end) = match &changes[&key] {
+                    Some(hunks) => {
+                        let (start, start_changed) = map_line(hunks, anchor.start);
+                        let (end, end_changed) = map_line(hunks, anchor.end);
+                        outdated |= start_changed || end_changed;
+                        (start, end.max(start))
+                    }
+                    None => (anchor.start, anchor.end),
+                };
+                lines = Some((start, end));
+            }
+        }
+
+        threads.push(Thread {
+            path: comment.path.clone(),
+            start_line: lines.map(|(start, _)| start),
+            line: lines.map(|(_, end)| end),
+            outdated,
+            url: comment.html_url.clone(),
+            comments: std::iter::once(comment)
+                .chain(replies)
+                .map(|comment| CommentSummary {
+                    author: comment.user.map(|user| user.login),
+                    body: comment.body,
+                    created_at: comment.created_at,
+                })
+                .collect(),
+        });
+    }
+    Ok(ReviewComments { number, threads })
+}
+
+impl ReviewComments {
+    /// Show the threads as diagnostics of the files in `root`, clearing the
+    /// diagnostics of files in `published` that no longer have threads.
+    pub fn publish(
+        &self,
+        root: &Path,
+        events: &EventSender,
+        published: &Mutex<BTreeSet<PathBuf>>,
+    ) -> Result<()> {
+        let mut files: BTreeMap<PathBuf, Vec<Diagnostic>> = BTreeMap::new();
+        let mut contents: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for thread in &self.threads {
+            let (Some(start), Some(end)) = (thread.start_line, thread.line) else {
+                continue;
+            };
+            let lines = contents.entry(&thread.path).or_insert_with(|| {
+                std::fs::read_to_string(root.join(&thread.path))
+                    .map(|text| text.lines().map(str::to_string).collect())
+                    .unwrap_or_default()
+            });
+            let last = u32::try_from(lines.len().saturating_sub(1)).unwrap_or(u32::MAX);
+            let end = (end - 1).min(last);
+            let length = lines
+                .get(end as usize)
+                .map_or(0, |line| line.encode_utf16().count());
+            let range = Range::new(
+                Position::new((start - 1).min(end), 0),
+                Position::new(end, u32::try_from(length).unwrap_or(u32::MAX)),
+            );
+            files.entry(root.join(&thread.path)).or_default().push(
+                Diagnostic::new(range, thread.message())
+                    .with_severity(DiagnosticSeverity::Information)
+                    .with_source("github"),
+            );
+        }
+
+        let mut published = published.lock().unwrap_or_else(|err| err.into_inner());
+        for stale in published.iter().filter(|path| !files.contains_key(*path)) {
+            events.publish_diagnostics(stale.to_string_lossy(), Vec::new())?;
+        }
+        published.clear();
+        for (path, diagnostics) in files {
+            events.publish_diagnostics(path.to_string_lossy(), diagnostics)?;
+            published.insert(path);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(old_start: u32, old_len: u32, new_start: u32, new_len: u32) -> Hunk {
+        Hunk {
+            old_start,
+            old_len,
+            new_start,
+            new_len,
+        }
+    }
+
+    #[test]
+    fn parses_hunk_headers() {
+        assert_eq!(
+            hunk_header("@@ -12,3 +14,4 @@ fn main()"),
+            Some(hunk(12, 3, 14, 4))
+        );
+        assert_eq!(hunk_header("@@ -5 +5,0 @@"), Some(hunk(5, 1, 5, 0)));
+        assert_eq!(hunk_header("@@ -0,0 +1,2 @@"), Some(hunk(0, 0, 1, 2)));
+        assert_eq!(hunk_header("@@ -a +1 @@"), None);
+        assert_eq!(hunk_header("diff --git a/src b/src"), None);
+    }
+
+    #[test]
+    fn finds_the_commented_line_of_a_hunk() {
+        assert_eq!(
+            hunk_line("@@ -10,3 +10,4 @@\n ctx\n-old\n+new\n+newer"),
+            Some(12)
+        );
+        assert_eq!(hunk_line("@@ -1,3 +1,3 @@\n a\n b\n c"), Some(3));
+        assert_eq!(
+            hunk_line(
+                "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+c\n\\ No newline at end of file"
+            ),
+            Some(2)
+        );
+        assert_eq!(hunk_line("@@ -1 +1 @@"), None);
+        assert_eq!(hunk_line("no header\n+a"), None);
+    }
+
+    #[test]
+    fn maps_lines_around_insertions() {
+        // Two lines added after line 3.
+        let hunks = [hunk(3, 0, 4, 2)];
+        assert_eq!(map_line(&hunks, 3), (3, false));
+        assert_eq!(map_line(&hunks, 4), (6, false));
+    }
+
+    #[test]
+    fn maps_lines_around_deletions() {
+        // Lines 5 and 6 removed.
+        let hunks = [hunk(5, 2, 4, 0)];
+        assert_eq!(map_line(&hunks, 4), (4, false));
+        assert_eq!(map_line(&hunks, 5), (5, true));
+        assert_eq!(map_line(&hunks, 6), (5, true));
+        assert_eq!(map_line(&hunks, 7), (5, false));
+
+        let hunks = [hunk(1, 2, 0, 0)];
+        assert_eq!(map_line(&hunks, 2), (1, true));
+        assert_eq!(map_line(&hunks, 3), (1, false));
+    }
+
+    #[test]
+    fn maps_lines_inside_changed_hunks() {
+        let hunks = [hunk(10, 3, 10, 1)];
+        assert_eq!(map_line(&hunks, 11), (10, true));
+        assert_eq!(map_line(&hunks, 13), (11, false));
+    }
+
+    #[test]
+    fn maps_lines_after_several_hunks() {
+        let hunks = [hunk(3, 0, 4, 2), hunk(10, 2, 12, 5), hunk(20, 4, 24, 0)];
+        assert_eq!(map_line(&hunks, 1), (1, false));
+        assert_eq!(map_line(&hunks, 11), (12, true));
+        assert_eq!(map_line(&hunks, 15), (20, false));
+        assert_eq!(map_line(&hunks, 21), (25, true));
+        assert_eq!(map_line(&hunks, 30), (31, false));
+    }
+}
@@ -0,0 +1,349 @@
+//! Details of one pull request, as returned by `helix.github.pr_detail`.
+//!
+//! The pull request, its reviews, the check runs of its head commit, its
+//! changed files and its comments are fetched concurrently. Only the most
+//! recent comments are kept, and the markdown preview is rendered when asked
+//! for.
+
+use crate::{
+    api::Api,
+    review::{self, ReviewSummary},
+    Repository, User,
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Comments returned, the most recent ones.
+const RECENT_COMMENTS: usize = 10;
+
+/// Arguments of `helix.github.pr_detail`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DetailOptions {
+    pub number: u64,
+    /// Also render the details as markdown.
+    #[serde(default)]
+    pub markdown: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequest {
+    number: u64,
+    title: String,
+    html_url: String,
+    state: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    merged: bool,
+    user: Option<User>,
+    body: Option<String>,
+    created_at: String,
+    updated_at: String,
+    #[serde(default)]
+    labels: Vec<Label>,
+    head: Branch,
+    base: Branch,
+    mergeable: Option<bool>,
+    mergeable_state: Option<String>,
+    #[serde(default)]
+    requested_reviewers: Vec<User>,
+    #[serde(default)]
+    requested_teams: Vec<Team>,
+    #[serde(default)]
+    commits: u64,
+    #[serde(default)]
+    additions: u64,
+    #[serde(default)]
+    deletions: u64,
+    #[serde(default)]
+    changed_files: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Label {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Team {
+    slug: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Branch {
+    #[serde(rename = "ref")]
+    name: String,
+    sha: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CheckRuns {
+    check_runs: Vec<CheckRun>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CheckRun {
+    name: String,
+    status: String,
+    conclusion: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct File {
+    filename: String,
+    status: String,
+    additions: u64,
+    deletions: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Comment {
+    user: Option<User>,
+    body: String,
+    created_at: String,
+    html_url: String,
+}
+
+/// Payload of `helix.github.pr_detail`.
+#[derive(Debug, Serialize)]
+pub struct Detail {
+    number: u64,
+    title: String,
+    url: String,
+    state: String,
+    draft: bool,
+    merged: bool,
+    author: Option<String>,
+    body: Option<String>,
+    created_at: String,
+    updated_at: String,
+    labels: Vec<String>,
+    head: String,
+    base: String,
+    mergeable: Option<bool>,
+    mergeable_state: Option<String>,
+    /// Logins of the reviewers and slugs of the teams asked for a review.
+    requested_reviewers: Vec<String>,
+    reviews: Vec<ReviewSummary>,
+    checks: Checks,
+    changes: Changes,
+    comments: Vec<CommentSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    markdown: Option<String>,
+}
+
+/// Check runs of the head commit, counted by outcome.
+#[derive(Debug, Default, Serialize)]
+struct Checks {
+    passed: usize,
+    failed: usize,
+    pending: usize,
+    skipped: usize,
+    runs: Vec<CheckRun>,
+}
+
+#[derive(Debug, Serialize)]
+struct Changes {
+    commits: u64,
+    additions: u64,
+    deletions: u64,
+    changed_files: u64,
+    files: Vec<FileSummary>,
+    /// Whether files were left out of `files`.
+    truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct FileSummary {
+    path: String,
+    status: String,
+    additions: u64,
+    deletions: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct CommentSummary {
+    author: Option<String>,
+    body: String,
+    created_at: String,
+    url: String,
+}
+
+fn login(user: Option<User>) -> Option<String> {
+    user.map(|user| user.login)
+}
+
+impl Checks {
+    fn new(runs: Vec<CheckRun>) -> Self {
+        let mut checks = Self::default();
+        for run in &runs {
+            match (run.status.as_str(), run.conclusion.as_deref()) {
+                ("completed", Some("success")) => checks.passed += 1,
+                ("completed", Some("neutral" | "skipped" | "stale")) => checks.skipped += 1,
+                ("completed", _) => checks.failed += 1,
+                _ => checks.pending += 1,
+            }
+        }
+        checks.runs = runs;
+        checks
+    }
+}
+
+/// Details of the pull request `number` of `repo`.
+pub async fn fetch(api: Api, repo: Repository, options: DetailOptions) -> Result<Detail> {
+    let path = format!("pulls/{}", options.number);
+    let pr: PullRequest = api.get(Api::repo_url(&repo, &path)?).await?;
+
+    let reviews = review::list(&api, &repo, options.number);
+    let files = api.list::<File>(Api::repo_url(&repo, &format!("{path}/files"))?);
+    let comments = api.list::<Comment>(Api::repo_url(
+        &repo,
+        &format!("issues/{}/comments", options.number),
+    )?);
+    let mut checks_url = Api::repo_url(&repo, &format!("commits/{}/check-runs", pr.head.sha))?;
+    checks_url.query_pairs_mut().append_pair("per_page", "100");
+    let checks = api.get::<CheckRuns>(checks_url);
+    let (reviews, (files, truncated), (mut comments, _), checks) =
+        tokio::try_join!(reviews, files, comments, checks)?;
+    let recent = comments.len().saturating_sub(RECENT_COMMENTS);
+
+    let mut detail = Detail {
+        number: pr.number,
+        title: pr.title,
+        url: pr.html_url,
+        state: pr.state,
+        draft: pr.draft,
+        merged: pr.merged,
+        author: login(pr.user),
+        body: pr.body.filter(|body| !body.trim().is_empty()),
+        created_at: pr.created_at,
+        updated_at: pr.updated_at,
+        labels: pr.labels.into_iter().map(|label| label.name).collect(),
+        head: pr.head.name,
+        base: pr.base.name,
+        mergeable: pr.mergeable,
+        mergeable_state: pr.mergeable_state,
+        requested_reviewers: pr
+            .requested_reviewers
+            .into_iter()
+            .map(|user| user.login)
+            .chain(pr.requested_teams.into_iter().map(|team| team.slug))
+            .collect(),
+        reviews,
+        checks: Checks::new(checks.check_runs),
+        changes: Changes {
+            commits: pr.commits,
+            additions: pr.additions,
+            deletions: pr.deletions,
+            changed_files: pr.changed_files,
+            files: files
+                .into_iter()
+                .map(|file| FileSummary {
+                    path: file.filename,
+                    status: file.status,
+                    additions: file.additions,
+                    deletions: file.deletions,
+                })
+                .collect(),
+            truncated,
+        },
+        comments: comments
+            .drain(recent..)
+            .map(|comment| CommentSummary {
+                author: login(comment.user),
+                body: comment.body,
+                created_at: comment.created_at,
+                url: comment.html_url,
+            })
+            .collect(),
+        markdown: None,
+    };
+    if options.markdown {
+        detail.markdown = Some(render(&detail));
+    }
+    Ok(detail)
+}
+
+/// Markdown preview of `detail`.
+fn render(detail: &Detail) -> String {
+    let mut out = String::new();
+    let author = detail.author.as_deref().unwrap_or("ghost");
+    let state = if detail.merged {
+        "merged"
+    } else if detail.draft {
+        "draft"
+    } else {
+        detail.state.as_str()
+    };
+    let _ = writeln!(out, "# {} (#{})\n", detail.title, detail.number);
+    let _ = writeln!(
+        out,
+        "**{author}** wants to merge `{}` into `{}` · {state}",
+        detail.head, detail.base
+    );
+    if !detail.labels.is_empty() {
+        let _ = writeln!(out, "\nLabels: {}", detail.labels.join(", "));
+    }
+    let changes = &detail.changes;
+    let _ = writeln!(
+        out,
+        "\n+{} −{} in {} files over {} commits",
+        changes.additions, changes.deletions, changes.changed_files, changes.commits
+    );
+
+    if let Some(body) = &detail.body {
+        let _ = writeln!(out, "\n## Description\n\n{}", body.trim());
+    }
+
+    let checks = &detail.checks;
+    if !checks.runs.is_empty() {
+        let _ = writeln!(
+            out,
+            "\n## Checks\n\n{} passed, {} failed, {} pending, {} skipped",
+            checks.passed, checks.failed, checks.pending, checks.skipped
+        );
+        for run in &checks.runs {
+            let outcome = run.conclusion.as_deref().unwrap_or(&run.status);
+            let _ = writeln!(out, "- {}: {outcome}", run.name);
+        }
+    }
+
+    if !detail.reviews.is_empty() || !detail.requested_reviewers.is_empty() {
+        let _ = writeln!(out, "\n## Reviews\n");
+        for review in &detail.reviews {
+            let state = review.state.to_lowercase().replace('_', " ");
+            let _ = writeln!(out, "- **{}** {state}", review.author());
+        }
+        if !detail.requested_reviewers.is_empty() {
+            let _ = writeln!(out, "- Awaiting {}", detail.requested_reviewers.join(", "));
+        }
+    }
+
+    if !changes.files.is_empty() {
+        let _ = writeln!(out, "\n## Files\n");
+        for file in &changes.files {
+            let _ = writeln!(
+                out,
+                "- `{}` ({}, +{} −{})",
+                file.path, file.status, file.additions, file.deletions
+            );
+        }
+        if changes.truncated {
+            let _ = writeln!(out, "- …");
+        }
+    }
+
+    if !detail.comments.is_empty() {
+        let _ = writeln!(out, "\n## Recent comments");
+        for comment in &detail.comments {
+            let commenter = comment.author.as_deref().unwrap_or("ghost");
+            let _ = writeln!(out, "\n**{commenter}** on {}:\n", comment.created_at);
+            for line in comment.body.trim().lines() {
+                let _ = writeln!(out, "> {line}");
+            }
+        }
+    }
+    out
+}
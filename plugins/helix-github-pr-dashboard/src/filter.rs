@@ -0,0 +1,157 @@
+//! Filters and sort order of `helix.github.list_prs`.
+//!
+//! Open pull requests are listed through the pulls endpoint while at most the
+//! base branch and the sort order are given. Filtering by author, assignee,
+//! label, draft state or requested reviewer takes the search endpoint
+//! instead, which has qualifiers for them but does not report the mergeable
+//! state of pull requests.
+
+use crate::{
+    api::{Api, API_URL},
+    Repository,
+};
+use anyhow::Result;
+use serde::Deserialize;
+use url::Url;
+
+/// Arguments of `helix.github.list_prs`, all optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Filter {
+    /// Login of the author, or `me`.
+    author: Option<String>,
+    /// Login of an assignee, or `me`.
+    assignee: Option<String>,
+    /// Labels pull requests carry all of.
+    #[serde(default)]
+    labels: Vec<String>,
+    /// Branch pull requests merge into.
+    base: Option<String>,
+    /// Only drafts when `true`, only pull requests ready for review when
+    /// `false`.
+    draft: Option<bool>,
+    /// Login of a reviewer whose review is requested, or `me`.
+    review_requested: Option<String>,
+    sort: Option<Sort>,
+    direction: Option<Direction>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Sort {
+    Created,
+    Updated,
+    /// Most commented first.
+    Popularity,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Direction {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Asc => "asc",
+            Self::Desc => "desc",
+        }
+    }
+}
+
+/// Search qualifier value for `login`, where `me` is the authenticated user.
+fn user(login: &str) -> &str {
+    if login == "me" {
+        "@me"
+    } else {
+        login
+    }
+}
+
+/// `value` as one word of a search query.
+fn quote(value: &str) -> String {
+    if value.contains(char::is_whitespace) {
+        format!("\"{}\"", value.replace('"', ""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl Filter {
+    fn needs_search(&self) -> bool {
+        self.author.is_some()
+            || self.assignee.is_some()
+            || !self.labels.is_empty()
+            || self.draft.is_some()
+            || self.review_requested.is_some()
+    }
+
+    /// Url listing the open pull requests of `repo` matching the filter.
+    pub fn url(&self, repo: &Repository) -> Result<Url> {
+        let direction = self.direction.unwrap_or_default().as_str();
+        if !self.needs_search() {
+            let mut url = Api::repo_url(repo, "pulls")?;
+            {
+                let mut query = url.query_pairs_mut();
+                if let Some(base) = &self.base {
+                    query.append_pair("base", base);
+                }
+                if let Some(sort) = self.sort {
+                    let sort = match sort {
+                        Sort::Created => "created",
+                        Sort::Updated => "updated",
+                        Sort::Popularity => "popularity",
+                    };
+                    query.append_pair("sort", sort);
+                    query.append_pair("direction", direction);
+                }
+            }
+            return Ok(url);
+        }
+
+        let mut qualifiers = vec![
+            format!("repo:{}/{}", repo.owner, repo.name),
+            "is:pr".to_string(),
+            "is:open".to_string(),
+        ];
+        if let Some(author) = &self.author {
+            qualifiers.push(format!("author:{}", user(author)));
+        }
+        if let Some(assignee) = &self.assignee {
+            qualifiers.push(format!("assignee:{}", user(assignee)));
+        }
+        qualifiers.extend(
+            self.labels
+                .iter()
+                .map(|label| format!("label:{}", quote(label))),
+        );
+        if let Some(base) = &self.base {
+            qualifiers.push(format!("base:{}", quote(base)));
+        }
+        if let Some(draft) = self.draft {
+            qualifiers.push(format!("draft:{draft}"));
+        }
+        if let Some(reviewer) = &self.review_requested {
+            qualifiers.push(format!("review-requested:{}", user(reviewer)));
+        }
+
+        let mut url = Url::parse(&format!("{API_URL}/search/issues"))?;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("q", &qualifiers.join(" "));
+            if let Some(sort) = self.sort {
+                let sort = match sort {
+                    Sort::Created => "created",
+                    Sort::Updated => "updated",
+                    Sort::Popularity => "comments",
+                };
+                query.append_pair("sort", sort);
+                query.append_pair("order", direction);
+            }
+        }
+        Ok(url)
+    }
+}
@@ -0,0 +1,33 @@
+//! Running git in the repository of the workspace.
+
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use tokio::process::Command;
+
+/// Output of `git args` run in `root`, trimmed. Fails with the error git
+/// reported unless it succeeded.
+pub async fn git(root: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .await
+        .context("failed to spawn `git`")?;
+    if !output.status.success() {
+        bail!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Whether the branch `name` exists locally.
+pub async fn has_branch(root: &Path, name: &str) -> bool {
+    let reference = format!("refs/heads/{name}");
+    git(root, &["show-ref", "--verify", "--quiet", &reference])
+        .await
+        .is_ok()
+}
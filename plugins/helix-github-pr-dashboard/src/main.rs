@@ -1,19 +1,58 @@
+mod api;
+mod checkout;
+mod comments;
+mod detail;
+mod filter;
+mod git;
+mod merge;
+mod review;
+
 use anyhow::{anyhow, Result};
+use api::Api;
+use detail::DetailOptions;
+use filter::Filter;
 use helix_plugin_sdk::{
     run, CommandContext, InitializeContext, MessageLevel, Plugin, PluginCommand, Registrar,
 };
-use reqwest::blocking::Client;
+use merge::MergeOptions;
+use review::ReviewOptions;
 use serde::Deserialize;
 use serde_json::Value;
-use std::{env, process::Command, time::Duration};
+use std::{
+    collections::BTreeSet,
+    env,
+    future::Future,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use thiserror::Error;
+use tokio::runtime::Runtime;
 use url::Url;
 
-#[derive(Default)]
+/// Time the host may reuse a pull request listing for, sparing the GitHub
+/// rate limit.
+const PR_LIST_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Time the host may reuse the details of a pull request for. Shorter than
+/// for listings, as details are looked at while a review progresses.
+const PR_DETAIL_CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// Items a listing returns at most unless `HELIX_GITHUB_MAX_RESULTS` is set,
+/// bounding the requests made for very busy repositories.
+const DEFAULT_MAX_RESULTS: usize = 1000;
+
 struct GithubPrPlugin {
+    /// Root of the workspace, in the clone of the repository.
+    root: PathBuf,
     repo: Option<Repository>,
-    token: Option<String>,
-    client: Client,
+    api: Api,
+    /// Runtime the requests to GitHub run on, so that the plugin keeps
+    /// answering the host while GitHub is slow to respond.
+    runtime: Runtime,
+    /// Files review comments were published as diagnostics for.
+    commented_files: Arc<Mutex<BTreeSet<PathBuf>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -49,44 +88,66 @@ enum PluginError {
     MissingRepository,
     #[error("GitHub API responded with status {0}")]
     ApiStatus(reqwest::StatusCode),
+    #[error("pull request #{number} cannot be merged: {reason}")]
+    MergeBlocked { number: u64, reason: String },
+    /// Error GitHub explained, e.g. the branch protection rule a merge
+    /// violates.
+    #[error("GitHub API responded with status {status}: {message}")]
+    Api {
+        status: reqwest::StatusCode,
+        message: String,
+    },
 }
 
 impl GithubPrPlugin {
     fn new() -> Result<Self> {
         let token = env::var("GITHUB_TOKEN").ok();
-        let client = Client::builder()
-            .user_agent("helix-plugin-github-pr-dashboard/0.1")
-            .timeout(Duration::from_secs(10))
+        let max_results = match env::var("HELIX_GITHUB_MAX_RESULTS") {
+            Ok(max) => max
+                .parse()
+                .map_err(|_| anyhow!("HELIX_GITHUB_MAX_RESULTS must be a number, not `{max}`"))?,
+            Err(_) => DEFAULT_MAX_RESULTS,
+        };
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
             .build()?;
 
+        let root = env::var_os("HELIX_WORKSPACE_ROOT")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| env::current_dir().unwrap_or_default());
+
         Ok(Self {
-            repo: detect_repository()?,
-            token,
-            client,
+            repo: detect_repository(&root)?,
+            root,
+            api: Api::new(token, max_results)?,
+            runtime,
+            commented_files: Arc::default(),
         })
     }
 
-    fn list_pull_requests(&self) -> Result<Vec<PullRequest>> {
-        let repo = self.repo.clone().ok_or(PluginError::MissingRepository)?;
-
-        let url = format!(
-            "https://api.github.com/repos/{owner}/{repo}/pulls",
-            owner = repo.owner,
-            repo = repo.name
-        );
-
-        let mut request = self.client.get(url);
-        if let Some(token) = &self.token {
-            request = request.bearer_auth(token);
-        }
-
-        let response = request.send()?;
-        if !response.status().is_success() {
-            return Err(PluginError::ApiStatus(response.status()).into());
-        }
+    fn repo(&self) -> Result<Repository> {
+        Ok(self.repo.clone().ok_or(PluginError::MissingRepository)?)
+    }
 
-        let prs: Vec<PullRequest> = response.json()?;
-        Ok(prs)
+    /// Answer the command once `request` finished, running it on the
+    /// runtime of the plugin meanwhile. Successful results may be reused by
+    /// the host for `cache_ttl`.
+    fn respond_later(
+        &self,
+        ctx: &mut CommandContext<'_>,
+        cache_ttl: Option<Duration>,
+        request: impl Future<Output = Result<Value>> + Send + 'static,
+    ) -> Result<Option<Value>> {
+        let mut responder = ctx.respond_later()?;
+        self.runtime.spawn(async move {
+            let result = request.await;
+            if let (Ok(_), Some(ttl)) = (&result, cache_ttl) {
+                responder.cache_result_for(ttl);
+            }
+            let _ = responder.respond(result.map(Some));
+        });
+        Ok(None)
     }
 }
 
@@ -95,6 +156,10 @@ fn name(&self) -> &'static str {
         "github-pr-dashboard"
     }
 
+    fn version(&self) -> Option<&'static str> {
+        Some(env!("CARGO_PKG_VERSION"))
+    }
+
     fn initialize(
         &mut self,
         ctx: &mut InitializeContext,
@@ -102,7 +167,61 @@ fn initialize(
     ) -> Result<()> {
         registrar.register_command(
             PluginCommand::new("helix.github.list_prs", "List GitHub pull requests")
-                .with_description("Fetch open pull requests for the current repository"),
+                .with_description(
+                    "Fetch open pull requests for the current repository, optionally filtered by \
+                     `author`, `assignee`, `labels`, `base`, `draft` or `review_requested` and \
+                     sorted by `created`, `updated` or `popularity`",
+                ),
+        )?;
+        registrar.register_command(
+            PluginCommand::new("helix.github.pr_detail", "Show GitHub pull request")
+                .with_description(
+                    "Fetch the pull request `number` with its reviews, checks, changed files and \
+                     recent comments; with `markdown`, also render them as markdown",
+                ),
+        )?;
+        registrar.register_command(
+            PluginCommand::new("helix.github.checkout_pr", "Check out GitHub pull request")
+                .with_description(
+                    "Fetch the branch of the pull request `number`, including branches of forks, \
+                     and check it out as a local branch tracking it",
+                ),
+        )?;
+        registrar.register_command(
+            PluginCommand::new("helix.github.merge_pr", "Merge GitHub pull request")
+                .with_description(
+                    "Merge the pull request `number` with the `method` merge, squash or rebase, \
+                     optionally with a commit `title` and `message`; with `delete_branch`, \
+                     delete its branch afterwards",
+                ),
+        )?;
+        registrar.register_command(
+            PluginCommand::new("helix.github.close_pr", "Close GitHub pull request")
+                .with_description("Close the pull request `number` without merging it"),
+        )?;
+        registrar.register_command(
+            PluginCommand::new("helix.github.review", "Review GitHub pull request")
+                .with_description(
+                    "Submit a review of the pull request `number` with the `verdict` approve, \
+                     request_changes or comment and its `body`",
+                ),
+        )?;
+        registrar.register_command(
+            PluginCommand::new(
+                "helix.github.list_reviews",
+                "List GitHub pull request reviews",
+            )
+            .with_description("Fetch the reviews of the pull request `number`"),
+        )?;
+        registrar.register_command(
+            PluginCommand::new(
+                "helix.github.review_comments",
+                "Show GitHub review comments",
+            )
+            .with_description(
+                "Fetch the review comments of the pull request of the checked out branch and \
+                 show each thread as a diagnostic on the lines it is about",
+            ),
         )?;
 
         if self.repo.is_none() {
@@ -118,43 +237,174 @@ fn initialize(
     fn execute(
         &mut self,
         command: &str,
-        _arguments: Vec<Value>,
-        _ctx: &mut CommandContext<'_>,
+        arguments: Vec<Value>,
+        ctx: &mut CommandContext<'_>,
     ) -> Result<Option<Value>> {
         match command {
             "helix.github.list_prs" => {
-                let prs = self.list_pull_requests()?;
-                let result = serde_json::to_value(
-                    prs.iter()
-                        .map(|pr| {
-                            serde_json::json!({
-                                "number": pr.number,
-                                "title": pr.title,
-                                "url": pr.html_url,
-                                "state": pr.state,
-                                "draft": pr.draft,
-                                "author": pr.user.as_ref().map(|user| user.login.clone()),
-                                "mergeable_state": pr.mergeable_state,
+                let filter: Filter = match arguments.into_iter().next() {
+                    Some(filter) => serde_json::from_value(filter)
+                        .map_err(|err| anyhow!("invalid pull request filter: {err}"))?,
+                    None => Filter::default(),
+                };
+                let url = filter.url(&self.repo()?)?;
+                let api = self.api.clone();
+                let events = ctx.events();
+                self.respond_later(ctx, Some(PR_LIST_CACHE_TTL), async move {
+                    let (prs, truncated) = api.list::<PullRequest>(url).await?;
+                    if truncated {
+                        let _ = events.log(
+                            MessageLevel::Warning,
+                            format!(
+                                "Listing the first {} pull requests only; raise \
+                                 HELIX_GITHUB_MAX_RESULTS to list more.",
+                                prs.len()
+                            ),
+                        );
+                    }
+                    Ok(serde_json::to_value(
+                        prs.iter()
+                            .map(|pr| {
+                                serde_json::json!({
+                                    "number": pr.number,
+                                    "title": pr.title,
+                                    "url": pr.html_url,
+                                    "state": pr.state,
+                                    "draft": pr.draft,
+                                    "author": pr.user.as_ref().map(|user| user.login.clone()),
+                                    "mergeable_state": pr.mergeable_state,
+                                })
                             })
-                        })
-                        .collect::<Vec<_>>(),
-                )?;
-                Ok(Some(result))
+                            .collect::<Vec<_>>(),
+                    )?)
+                })
+            }
+            "helix.github.pr_detail" => {
+                let options: DetailOptions = arguments
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow!("pass the `number` of the pull request"))
+                    .and_then(|options| {
+                        serde_json::from_value(options)
+                            .map_err(|err| anyhow!("invalid pull request detail options: {err}"))
+                    })?;
+                let repo = self.repo()?;
+                let api = self.api.clone();
+                self.respond_later(ctx, Some(PR_DETAIL_CACHE_TTL), async move {
+                    Ok(serde_json::to_value(
+                        detail::fetch(api, repo, options).await?,
+                    )?)
+                })
+            }
+            "helix.github.checkout_pr" => {
+                let number = pull_request_number(&arguments)?;
+                let repo = self.repo()?;
+                let (api, root) = (self.api.clone(), self.root.clone());
+                let events = ctx.events();
+                self.respond_later(ctx, None, async move {
+                    let checkout = checkout::checkout(api, repo, &root, number).await?;
+                    let _ = events.show_message(
+                        MessageLevel::Info,
+                        format!("Checked out pull request #{number}"),
+                    );
+                    Ok(serde_json::to_value(checkout)?)
+                })
+            }
+            "helix.github.merge_pr" => {
+                let options: MergeOptions = arguments
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow!("pass the `number` of the pull request"))
+                    .and_then(|options| {
+                        serde_json::from_value(options)
+                            .map_err(|err| anyhow!("invalid merge options: {err}"))
+                    })?;
+                let repo = self.repo()?;
+                let api = self.api.clone();
+                let events = ctx.events();
+                self.respond_later(ctx, None, async move {
+                    let merged = merge::merge(api, repo, options).await?;
+                    let _ = events.show_message(MessageLevel::Info, merged.summary());
+                    Ok(serde_json::to_value(merged)?)
+                })
+            }
+            "helix.github.close_pr" => {
+                let number = pull_request_number(&arguments)?;
+                let repo = self.repo()?;
+                let api = self.api.clone();
+                let events = ctx.events();
+                self.respond_later(ctx, None, async move {
+                    let closed = merge::close(api, repo, number).await?;
+                    let _ = events
+                        .show_message(MessageLevel::Info, format!("Closed pull request #{number}"));
+                    Ok(serde_json::to_value(closed)?)
+                })
+            }
+            "helix.github.review" => {
+                let options: ReviewOptions = arguments
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow!("pass the `number` of the pull request"))
+                    .and_then(|options| {
+                        serde_json::from_value(options)
+                            .map_err(|err| anyhow!("invalid review: {err}"))
+                    })?;
+                let number = options.number;
+                let repo = self.repo()?;
+                let api = self.api.clone();
+                let events = ctx.events();
+                self.respond_later(ctx, None, async move {
+                    let review = review::submit(api, repo, options).await?;
+                    let state = review.state.to_lowercase().replace('_', " ");
+                    let _ = events.show_message(
+                        MessageLevel::Info,
+                        format!("Submitted review of pull request #{number}: {state}"),
+                    );
+                    Ok(serde_json::to_value(review)?)
+                })
+            }
+            "helix.github.list_reviews" => {
+                let number = pull_request_number(&arguments)?;
+                let repo = self.repo()?;
+                let api = self.api.clone();
+                self.respond_later(ctx, None, async move {
+                    Ok(serde_json::to_value(
+                        review::list(&api, &repo, number).await?,
+                    )?)
+                })
+            }
+            "helix.github.review_comments" => {
+                let repo = self.repo()?;
+                let (api, root) = (self.api.clone(), self.root.clone());
+                let commented_files = Arc::clone(&self.commented_files);
+                let events = ctx.events();
+                self.respond_later(ctx, None, async move {
+                    let comments = comments::fetch(api, repo, &root).await?;
+                    comments.publish(&root, &events, &commented_files)?;
+                    Ok(serde_json::to_value(comments)?)
+                })
             }
             _ => Err(anyhow!("unknown command `{command}`")),
         }
     }
 }
 
-fn detect_repository() -> Result<Option<Repository>> {
-    let workspace = env::var("HELIX_WORKSPACE_ROOT").ok();
-    let repo_root = workspace
-        .map(|path| path.into())
-        .unwrap_or_else(|| env::current_dir().unwrap_or_default());
+/// Number of the pull request a command is invoked for, given as the
+/// number or as `{"number": ...}`.
+fn pull_request_number(arguments: &[Value]) -> Result<u64> {
+    let number = match arguments.first() {
+        Some(Value::Object(options)) => options.get("number"),
+        argument => argument,
+    };
+    number
+        .and_then(Value::as_u64)
+        .ok_or_else(|| anyhow!("pass the `number` of the pull request"))
+}
 
+fn detect_repository(repo_root: &Path) -> Result<Option<Repository>> {
     let output = Command::new("git")
         .arg("-C")
-        .arg(&repo_root)
+        .arg(repo_root)
         .arg("config")
         .arg("--get")
         .arg("remote.origin.url")
@@ -211,4 +461,4 @@ fn parse_remote(remote: &str) -> Result<Repository> {
 
 fn main() -> Result<()> {
     run(GithubPrPlugin::new()?)
-}
\ No newline at end of file
+}
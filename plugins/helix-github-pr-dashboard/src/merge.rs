@@ -0,0 +1,185 @@
+//! Merging and closing pull requests, as `helix.github.merge_pr` and
+//! `helix.github.close_pr` do.
+//!
+//! Merges GitHub refuses, e.g. because branch protection requires reviews or
+//! checks that are missing, fail with the reason GitHub gave, completed by
+//! the mergeable state of the pull request. Branches are only deleted after a
+//! merge, and only when they belong to the repository itself rather than to
+//! a fork.
+
+use crate::{api::Api, PluginError, Repository};
+use anyhow::{anyhow, Result};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Arguments of `helix.github.merge_pr`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MergeOptions {
+    pub number: u64,
+    #[serde(default)]
+    method: MergeMethod,
+    /// Delete the branch of the pull request once it was merged.
+    #[serde(default)]
+    delete_branch: bool,
+    /// Title of the merge or squashed commit, GitHub's default when unset.
+    title: Option<String>,
+    /// Message of the merge or squashed commit, GitHub's default when unset.
+    message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum MergeMethod {
+    #[default]
+    Merge,
+    Squash,
+    Rebase,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequest {
+    head: Head,
+    mergeable_state: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Head {
+    #[serde(rename = "ref")]
+    name: String,
+    /// `None` once the fork was deleted.
+    repo: Option<HeadRepository>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HeadRepository {
+    full_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MergeResponse {
+    sha: String,
+    message: String,
+}
+
+/// Result of `helix.github.merge_pr`.
+#[derive(Debug, Serialize)]
+pub struct Merge {
+    number: u64,
+    /// Commit the pull request was merged as.
+    sha: String,
+    message: String,
+    branch: String,
+    branch_deleted: bool,
+    /// Why the branch was not deleted although asked to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch_error: Option<String>,
+}
+
+impl Merge {
+    /// Message telling the user the pull request was merged.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("Merged pull request #{}", self.number);
+        if self.branch_deleted {
+            summary.push_str(&format!(" and deleted `{}`", self.branch));
+        }
+        if let Some(error) = &self.branch_error {
+            summary.push_str(&format!("; `{}` was kept: {error}", self.branch));
+        }
+        summary
+    }
+}
+
+/// Why a pull request in the mergeable `state` cannot be merged.
+fn blocked_by(state: &str) -> Option<&'static str> {
+    match state {
+        "blocked" => Some("branch protection rules are not satisfied"),
+        "dirty" => Some("it conflicts with the base branch"),
+        "behind" => Some("its branch is behind the base branch"),
+        "draft" => Some("it is a draft"),
+        _ => None,
+    }
+}
+
+/// Merge the pull request of `options` in `repo`.
+pub async fn merge(api: Api, repo: Repository, options: MergeOptions) -> Result<Merge> {
+    let number = options.number;
+    let path = format!("pulls/{number}");
+    let pr: PullRequest = api.get(Api::repo_url(&repo, &path)?).await?;
+
+    let mut body = json!({ "merge_method": options.method });
+    if let Some(title) = &options.title {
+        body["commit_title"] = json!(title);
+    }
+    if let Some(message) = &options.message {
+        body["commit_message"] = json!(message);
+    }
+    let url = Api::repo_url(&repo, &format!("{path}/merge"))?;
+    let merged: MergeResponse = match api.send(Method::PUT, url, &body).await {
+        Ok(merged) => merged,
+        Err(err) => {
+            return Err(match err.downcast::<PluginError>() {
+                // GitHub answers 405 to merges it refuses and 409 when the
+                // head changed meanwhile.
+                Ok(PluginError::Api {
+                    status: StatusCode::METHOD_NOT_ALLOWED | StatusCode::CONFLICT,
+                    message,
+                }) => {
+                    let reason = match pr.mergeable_state.as_deref().and_then(blocked_by) {
+                        Some(state) => format!("{message} ({state})"),
+                        None => message,
+                    };
+                    PluginError::MergeBlocked { number, reason }.into()
+                }
+                Ok(err) => err.into(),
+                Err(err) => err,
+            });
+        }
+    };
+
+    let mut result = Merge {
+        number,
+        sha: merged.sha,
+        message: merged.message,
+        branch: pr.head.name,
+        branch_deleted: false,
+        branch_error: None,
+    };
+    if options.delete_branch {
+        let full_name = format!("{}/{}", repo.owner, repo.name);
+        let own_branch = pr
+            .head
+            .repo
+            .is_some_and(|head| head.full_name.eq_ignore_ascii_case(&full_name));
+        if own_branch {
+            // Branch names may contain `#` or `%`, which must not end up
+            // in the url as they are.
+            let mut url = Api::repo_url(&repo, "git/refs/heads")?;
+            url.path_segments_mut()
+                .map_err(|()| anyhow!("the GitHub API url cannot have a path"))?
+                .extend(result.branch.split('/'));
+            match api.delete(url).await {
+                Ok(()) => result.branch_deleted = true,
+                Err(err) => result.branch_error = Some(err.to_string()),
+            }
+        } else {
+            result.branch_error = Some("it belongs to a fork".to_string());
+        }
+    }
+    Ok(result)
+}
+
+/// Result of `helix.github.close_pr`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Closed {
+    number: u64,
+    state: String,
+}
+
+/// Close the pull request `number` of `repo` without merging it.
+pub async fn close(api: Api, repo: Repository, number: u64) -> Result<Closed> {
+    let url = Api::repo_url(&repo, &format!("pulls/{number}"))?;
+    api.send(Method::PATCH, url, &json!({ "state": "closed" }))
+        .await
+}
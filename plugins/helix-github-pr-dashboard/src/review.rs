@@ -0,0 +1,101 @@
+//! Reviews of pull requests, as submitted by `helix.github.review` and
+//! listed by `helix.github.list_reviews`.
+
+use crate::{api::Api, Repository, User};
+use anyhow::{bail, Result};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Arguments of `helix.github.review`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReviewOptions {
+    pub number: u64,
+    verdict: Verdict,
+    /// Text of the review, required unless approving.
+    #[serde(default)]
+    body: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Verdict {
+    Approve,
+    RequestChanges,
+    Comment,
+}
+
+impl Verdict {
+    /// Event submitting a review with the verdict.
+    fn event(self) -> &'static str {
+        match self {
+            Self::Approve => "APPROVE",
+            Self::RequestChanges => "REQUEST_CHANGES",
+            Self::Comment => "COMMENT",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Review {
+    id: u64,
+    user: Option<User>,
+    state: String,
+    body: Option<String>,
+    submitted_at: Option<String>,
+    html_url: String,
+}
+
+/// Review as returned by the commands.
+#[derive(Debug, Serialize)]
+pub struct ReviewSummary {
+    id: u64,
+    author: Option<String>,
+    /// `APPROVED`, `CHANGES_REQUESTED`, `COMMENTED`, `DISMISSED` or
+    /// `PENDING`.
+    pub state: String,
+    body: String,
+    submitted_at: Option<String>,
+    url: String,
+}
+
+impl ReviewSummary {
+    pub fn author(&self) -> &str {
+        self.author.as_deref().unwrap_or("ghost")
+    }
+}
+
+impl From<Review> for ReviewSummary {
+    fn from(review: Review) -> Self {
+        Self {
+            id: review.id,
+            author: review.user.map(|user| user.login),
+            state: review.state,
+            body: review.body.unwrap_or_default(),
+            submitted_at: review.submitted_at,
+            url: review.html_url,
+        }
+    }
+}
+
+/// Reviews of the pull request `number` of `repo`, oldest first.
+pub async fn list(api: &Api, repo: &Repository, number: u64) -> Result<Vec<ReviewSummary>> {
+    let url = Api::repo_url(repo, &format!("pulls/{number}/reviews"))?;
+    let (reviews, _) = api.list::<Review>(url).await?;
+    Ok(reviews.into_iter().map(ReviewSummary::from).collect())
+}
+
+/// Submit the review of `options` on its pull request in `repo`.
+pub async fn submit(api: Api, repo: Repository, options: ReviewOptions) -> Result<ReviewSummary> {
+    if options.body.trim().is_empty() && !matches!(options.verdict, Verdict::Approve) {
+        bail!("pass the `body` of the review to request changes or comment");
+    }
+    let url = Api::repo_url(&repo, &format!("pulls/{}/reviews", options.number))?;
+    let body = json!({
+        "event": options.verdict.event(),
+        "body": options.body,
+    });
+    let review: Review = api.send(Method::POST, url, &body).await?;
+    Ok(review.into())
+}
@@ -4,6 +4,8 @@
 };
 use serde_json::Value;
 
+helix_plugin_sdk::assert_protocol_compat!(1);
+
 struct HelloPlugin;
 
 impl Plugin for HelloPlugin {
@@ -11,6 +13,10 @@ fn name(&self) -> &'static str {
         "hello-plugin"
     }
 
+    fn version(&self) -> Option<&'static str> {
+        Some(env!("CARGO_PKG_VERSION"))
+    }
+
     fn initialize(
         &mut self,
         ctx: &mut InitializeContext,
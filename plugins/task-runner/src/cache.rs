@@ -0,0 +1,161 @@
+//! Results of tasks declaring their inputs, reused while the inputs are
+//! unchanged.
+//!
+//! Tasks with `inputs` in `.helix/tasks.toml` are fingerprinted before they
+//! run: the files matching the globs, relative to the working directory of
+//! the task, together with the process the task starts. When the last
+//! successful run had the same fingerprint, the task is not run again and
+//! its result is returned with `cached: true`. Results are kept in the data
+//! directory the host assigns to the plugin.
+
+use crate::{process::Outcome, workspaces, Invocation};
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Result of the last successful run of a task.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    fingerprint: String,
+    outcome: Outcome,
+}
+
+/// Results of the tasks of one workspace, by task.
+#[derive(Debug, Default, Clone)]
+pub struct Cache {
+    /// File holding the results of every workspace; `None` when the host
+    /// did not assign a data directory.
+    path: Option<PathBuf>,
+    workspace: String,
+}
+
+impl Cache {
+    pub fn new(workspace_root: &Path) -> Self {
+        let path = env::var_os("HELIX_PLUGIN_DATA_DIR")
+            .map(|dir| PathBuf::from(dir).join("task-cache.json"));
+        Self {
+            path,
+            workspace: workspace_root.to_string_lossy().into_owned(),
+        }
+    }
+
+    fn load(path: &Path) -> BTreeMap<String, BTreeMap<String, Entry>> {
+        // A missing or corrupt cache resolves to an empty one.
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Result of the task `key` if its last successful run had
+    /// `fingerprint`.
+    pub fn lookup(&self, key: &str, fingerprint: &str) -> Option<Outcome> {
+        let path = self.path.as_ref()?;
+        let entry = Self::load(path).remove(&self.workspace)?.remove(key)?;
+        (entry.fingerprint == fingerprint).then_some(Outcome {
+            cached: true,
+            ..entry.outcome
+        })
+    }
+
+    /// Keep the result of the task `key` run with `fingerprint` if it
+    /// succeeded, or forget the result of the task otherwise.
+    pub fn store(&self, key: &str, fingerprint: &str, outcome: &Outcome) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut workspaces = Self::load(path);
+        let entries = workspaces.entry(self.workspace.clone()).or_default();
+        let summary = &outcome.summary;
+        if summary.exit_code == Some(0) && !summary.cancelled && !summary.timed_out {
+            let entry = Entry {
+                fingerprint: fingerprint.to_string(),
+                outcome: outcome.clone(),
+            };
+            entries.insert(key.to_string(), entry);
+        } else if entries.remove(key).is_none() {
+            return Ok(());
+        }
+
+        let contents = serde_json::to_string(&workspaces)?;
+        fs::write(path, contents)
+            .with_context(|| format!("failed to write task cache `{}`", path.display()))
+    }
+}
+
+/// Fingerprint of the files below the working directory of `invocation`
+/// matching `inputs`, and of the process it starts. Directories never
+/// holding sources, such as `node_modules`, are skipped.
+pub fn fingerprint(inputs: &[String], invocation: &Invocation) -> Result<String> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in inputs {
+        builder.add(
+            GlobBuilder::new(glob.trim_start_matches("./"))
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("invalid input glob `{glob}`"))?,
+        );
+    }
+    let globs = builder.build()?;
+
+    let mut hasher = Sha256::new();
+    for word in std::iter::once(&invocation.program).chain(&invocation.args) {
+        hasher.update(word.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update(invocation.cwd.to_string_lossy().as_bytes());
+    hasher.update([0]);
+    for (key, value) in &invocation.env {
+        hasher.update(format!("{key}={value}\0").as_bytes());
+    }
+
+    let mut files = Vec::new();
+    let mut pending = vec![String::new()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(invocation.cwd.join(&dir)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = if dir.is_empty() {
+                name.clone()
+            } else {
+                format!("{dir}/{name}")
+            };
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => {
+                    if !workspaces::SKIPPED_DIRS.contains(&name.as_str()) {
+                        pending.push(path);
+                    }
+                }
+                Ok(_) if globs.is_match(&path) => files.push(path),
+                _ => {}
+            }
+        }
+    }
+    files.sort();
+    for file in files {
+        let contents = fs::read(invocation.cwd.join(&file))
+            .with_context(|| format!("failed to read input `{file}`"))?;
+        hasher.update(file.as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        }))
+}
@@ -0,0 +1,42 @@
+//! Cargo commands of the root package and of every workspace member.
+
+use crate::{workspaces, Invocation, Task};
+use anyhow::Result;
+use std::path::Path;
+
+pub const PROVIDER: &str = "cargo";
+
+/// Cargo commands offered for every package.
+const COMMANDS: &[(&str, &str)] = &[
+    ("build", "Compile the package"),
+    ("check", "Check the package for errors"),
+    ("test", "Run the tests of the package"),
+];
+
+fn tasks(package: Option<String>) -> impl Iterator<Item = Task> {
+    COMMANDS.iter().map(move |(command, detail)| Task {
+        name: command.to_string(),
+        provider: PROVIDER.to_string(),
+        command: format!("cargo {command}"),
+        detail: Some(detail.to_string()),
+        package: package.clone(),
+        ..Default::default()
+    })
+}
+
+pub fn discover(root: &Path) -> Result<Vec<Task>> {
+    if !root.join("Cargo.toml").exists() {
+        return Ok(Vec::new());
+    }
+    let mut discovered: Vec<Task> = tasks(None).collect();
+    for member in workspaces::cargo_members(root)? {
+        discovered.extend(tasks(Some(member)));
+    }
+    Ok(discovered)
+}
+
+/// Process running `cargo <name>` in `dir`, which selects the package.
+pub fn invocation(dir: &Path, name: &str, extra: &[String]) -> Invocation {
+    let args = std::iter::once(name.to_string()).chain(extra.iter().cloned());
+    Invocation::new("cargo", args, dir)
+}
@@ -0,0 +1,59 @@
+//! Scripts of `composer.json`.
+
+use crate::{Invocation, Task};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{collections::BTreeMap, fs, path::Path};
+
+pub const PROVIDER: &str = "composer";
+
+#[derive(Debug, Default, Deserialize)]
+struct ComposerJson {
+    /// Scripts, given as one command or a list of commands run in turn.
+    #[serde(default)]
+    scripts: BTreeMap<String, Value>,
+    #[serde(default, rename = "scripts-descriptions")]
+    descriptions: BTreeMap<String, String>,
+}
+
+pub fn discover(root: &Path) -> Result<Vec<Task>> {
+    let path = root.join("composer.json");
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(Vec::new());
+    };
+    let mut composer: ComposerJson = serde_json::from_str(&content)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    Ok(composer
+        .scripts
+        .into_iter()
+        .map(|(name, script)| {
+            let command = match script {
+                Value::String(command) => command,
+                Value::Array(commands) => commands
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(" && "),
+                other => other.to_string(),
+            };
+            Task {
+                detail: composer.descriptions.remove(&name),
+                name,
+                provider: PROVIDER.to_string(),
+                command,
+                ..Default::default()
+            }
+        })
+        .collect())
+}
+
+/// Process running the script `name` with `extra` arguments.
+pub fn invocation(root: &Path, name: &str, extra: &[String]) -> Invocation {
+    let mut args = vec!["run-script".to_string(), name.to_string()];
+    if !extra.is_empty() {
+        args.push("--".to_string());
+        args.extend_from_slice(extra);
+    }
+    Invocation::new("composer", args, root)
+}
@@ -0,0 +1,594 @@
+//! Composite tasks of `.helix/tasks.toml`, which run other tasks.
+//!
+//! ```toml
+//! concurrency = 2
+//!
+//! [[task]]
+//! name = "ci"
+//! depends_on = ["npm:lint", "check"]
+//! run = "gradle:build"
+//!
+//! [[task]]
+//! name = "check"
+//! depends_on = ["npm:test", "make:check"]
+//! depends_order = "sequence"
+//! ```
+//!
+//! Dependencies are other tasks of the file or `provider:name` references
+//! to discovered tasks. They run in parallel unless `depends_order` is
+//! `sequence`; `run` names a task run once all of them succeeded. A task
+//! reached through several composites runs once.
+
+use crate::{
+    config::{self, Config, Order},
+    process::{self, RunningTask, RunningTasks},
+    Invocation, RunOptions, Task,
+};
+use anyhow::{bail, Result};
+use helix_plugin_sdk::{EventSender, MessageLevel};
+use serde_json::{json, Value};
+use std::{collections::HashMap, process::ExitStatus, sync::mpsc, thread, time::Instant};
+
+pub const PROVIDER: &str = "composite";
+
+/// Task of a composite together with the jobs it waits for.
+#[derive(Debug)]
+pub struct Job {
+    pub provider: String,
+    pub name: String,
+    /// Indices of the jobs that have to succeed first.
+    after: Vec<usize>,
+}
+
+pub fn discover(config: &Config) -> Vec<Task> {
+    config
+        .tasks
+        .iter()
+        .filter(|task| !task.is_custom())
+        .map(|task| {
+            let command = task
+                .depends_on
+                .iter()
+                .chain(&task.run)
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(match task.depends_order {
+                    Order::Parallel => ", ",
+                    Order::Sequence => " -> ",
+                });
+            Task {
+                name: task.name.clone(),
+                provider: PROVIDER.to_string(),
+                command,
+                detail: task.detail.clone(),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Jobs running the composite task `name`, ordered so that every job comes
+/// after the jobs it waits for.
+pub fn plan(config: &Config, name: &str) -> Result<Vec<Job>> {
+    let mut planner = Planner {
+        config,
+        jobs: Vec::new(),
+        stack: Vec::new(),
+    };
+    planner.expand(name, &[])?;
+    order(planner.jobs)
+}
+
+/// Number of tasks run at the same time.
+pub fn concurrency(config: &Config) -> usize {
+    config
+        .concurrency
+        .or_else(|| thread::available_parallelism().ok().map(usize::from))
+        .unwrap_or(1)
+        .max(1)
+}
+
+struct Planner<'a> {
+    config: &'a Config,
+    jobs: Vec<Job>,
+    /// Composites being expanded, to detect cycles.
+    stack: Vec<&'a str>,
+}
+
+impl<'a> Planner<'a> {
+    /// Add the jobs of `reference` that wait for `after`. Returns the jobs
+    /// whose success completes `reference`.
+    fn expand(&mut self, reference: &'a str, after: &[usize]) -> Result<Vec<usize>> {
+        let Some(composite) = self.config.tasks.iter().find(|task| task.name == reference) else {
+            return self.leaf(reference, after).map(|job| vec![job]);
+        };
+        if composite.is_custom() {
+            let reference = format!("{}:{reference}", config::PROVIDER);
+            return self.leaf(&reference, after).map(|job| vec![job]);
+        }
+        if self.stack.contains(&reference) {
+            bail!("composite task `{reference}` depends on itself");
+        }
+        self.stack.push(reference);
+
+        let mut done = Vec::new();
+        let mut previous = after.to_vec();
+        for dependency in &composite.depends_on {
+            match composite.depends_order {
+                Order::Parallel => done.extend(self.expand(dependency, after)?),
+                Order::Sequence => previous = self.expand(dependency, &previous)?,
+            }
+        }
+        if composite.depends_order == Order::Sequence {
+            done = previous;
+        }
+        if let Some(run) = &composite.run {
+            let run_after = if composite.depends_on.is_empty() {
+                after.to_vec()
+            } else {
+                done
+            };
+            done = self.expand(run, &run_after)?;
+        }
+
+        self.stack.pop();
+        Ok(done)
+    }
+
+    fn leaf(&mut self, reference: &str, after: &[usize]) -> Result<usize> {
+        let Some((provider, name)) = reference.split_once(':') else {
+            bail!("`{reference}` is neither a composite task nor a `provider:name` reference");
+        };
+        let index = match self
+            .jobs
+            .iter()
+            .position(|job| job.provider == provider && job.name == name)
+        {
+            Some(index) => index,
+            None => {
+                self.jobs.push(Job {
+                    provider: provider.to_string(),
+                    name: name.to_string(),
+                    after: Vec::new(),
+                });
+                self.jobs.len() - 1
+            }
+        };
+        let job = &mut self.jobs[index];
+        for &before in after {
+            if before != index && !job.after.contains(&before) {
+                job.after.push(before);
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// Sort `jobs` so that every job comes after the jobs it waits for.
+fn order(jobs: Vec<Job>) -> Result<Vec<Job>> {
+    let mut position = vec![None; jobs.len()];
+    let mut sorted = Vec::with_capacity(jobs.len());
+    while sorted.len() < jobs.len() {
+        let ready = (0..jobs.len()).find(|&index| {
+            position[index].is_none()
+                && jobs[index]
+                    .after
+                    .iter()
+                    .all(|&before| position[before].is_some())
+        });
+        let Some(index) = ready else {
+            let blocked: Vec<_> = (0..jobs.len())
+                .filter(|&index| position[index].is_none())
+                .map(|index| format!("`{}:{}`", jobs[index].provider, jobs[index].name))
+                .collect();
+            bail!("tasks {} wait for each other", blocked.join(", "));
+        };
+        position[index] = Some(sorted.len());
+        sorted.push(index);
+    }
+
+    let mut jobs: Vec<Option<Job>> = jobs.into_iter().map(Some).collect();
+    Ok(sorted
+        .into_iter()
+        .filter_map(|index| jobs[index].take())
+        .map(|mut job| {
+            job.after = job
+                .after
+                .iter()
+                .filter_map(|&before| position[before])
+                .collect();
+            job
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Waiting,
+    Running,
+    Succeeded,
+    Failed,
+    /// Not run because a job it waits for failed.
+    Skipped,
+}
+
+/// Job that can start while `active` jobs run, at most `concurrency` at a
+/// time. Jobs waiting for a failed job are marked as skipped.
+fn next_ready(
+    jobs: &[(Job, Invocation)],
+    states: &mut [State],
+    active: usize,
+    concurrency: usize,
+) -> Option<usize> {
+    for (index, (job, _)) in jobs.iter().enumerate() {
+        if states[index] != State::Waiting {
+            continue;
+        }
+        let waits_for = |state| job.after.iter().any(|&before| states[before] == state);
+        if waits_for(State::Failed) || waits_for(State::Skipped) {
+            states[index] = State::Skipped;
+            continue;
+        }
+        if active < concurrency
+            && job
+                .after
+                .iter()
+                .all(|&before| states[before] == State::Succeeded)
+        {
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// Run `jobs` in the background, at most `concurrency` at a time. Tasks
+/// waiting for a failed task are skipped; the outcome of every task is
+/// reported once all of them ended.
+pub fn execute(
+    composite: &str,
+    jobs: Vec<(Job, Invocation)>,
+    concurrency: usize,
+    running: RunningTasks,
+    events: EventSender,
+    on_exit: impl Fn(&RunningTask, ExitStatus) + Clone + Send + 'static,
+) {
+    let composite = composite.to_string();
+    thread::spawn(move || {
+        let started = Instant::now();
+        let channel = format!("{PROVIDER}:{composite}");
+        let (sender, receiver) = mpsc::channel::<(usize, ExitStatus)>();
+        let mut states = vec![State::Waiting; jobs.len()];
+        let mut results: HashMap<usize, Value> = HashMap::new();
+        let mut active = 0;
+
+        loop {
+            while let Some(index) = next_ready(&jobs, &mut states, active, concurrency) {
+                let (job, invocation) = &jobs[index];
+                let sender = sender.clone();
+                let on_exit = on_exit.clone();
+                let started = running.start(
+                    &job.provider,
+                    &job.name,
+                    &RunOptions::default(),
+                    invocation,
+                    events.clone(),
+                    move |task: &RunningTask, status: ExitStatus| {
+                        on_exit(task, status);
+                        let _ = sender.send((index, status));
+                    },
+                );
+                match started {
+                    Ok(task) => {
+                        let _ = events.append_output(
+                            channel.as_str(),
+                            format!("started `{}` (id {})\n", task.channel(), task.id),
+                        );
+                        states[index] = State::Running;
+                        active += 1;
+                    }
+                    Err(err) => {
+                        let _ = events.append_output(
+                            channel.as_str(),
+                            format!("failed to start `{}:{}`: {err:#}\n", job.provider, job.name),
+                        );
+                        states[index] = State::Failed;
+                    }
+                }
+            }
+            if active == 0 {
+                break;
+            }
+
+            let Ok((index, status)) = receiver.recv() else {
+                break;
+            };
+            active -= 1;
+            let succeeded = status.success();
+            states[index] = if succeeded {
+                State::Succeeded
+            } else {
+                State::Failed
+            };
+            let (job, _) = &jobs[index];
+            let status = status.to_string();
+            let _ = events.append_output(
+                channel.as_str(),
+                format!("finished `{}:{}`: {status}\n", job.provider, job.name),
+            );
+            results.insert(index, json!({ "status": status, "success": succeeded }));
+        }
+
+        let tasks: Vec<Value> = jobs
+            .iter()
+            .enumerate()
+            .map(|(index, (job, _))| {
+                let mut result = json!({
+                    "provider": job.provider,
+                    "name": job.name,
+                    "state": match states[index] {
+                        State::Succeeded => "succeeded",
+                        State::Failed => "failed",
+                        State::Skipped => "skipped",
+                        State::Waiting | State::Running => "not run",
+                    },
+                });
+                if let (Some(object), Some(Value::Object(outcome))) =
+                    (result.as_object_mut(), results.remove(&index))
+                {
+                    object.extend(outcome);
+                }
+                result
+            })
+            .collect();
+        let failed = states
+            .iter()
+            .filter(|&&state| state == State::Failed)
+            .count();
+        let skipped = states
+            .iter()
+            .filter(|&&state| state == State::Skipped)
+            .count();
+        let succeeded = failed == 0 && skipped == 0;
+
+        let summary = if succeeded {
+            format!("task `{channel}` completed: {} tasks succeeded", jobs.len())
+        } else {
+            format!("task `{channel}` failed: {failed} failed, {skipped} skipped")
+        };
+        let _ = events.append_output(channel.as_str(), format!("{summary}\n"));
+        let level = if succeeded {
+            MessageLevel::Info
+        } else {
+            MessageLevel::Error
+        };
+        let _ = events.show_message(level, summary);
+        let _ = events.notify(
+            "composite_finished",
+            json!({
+                "name": composite,
+                "success": succeeded,
+                "duration_ms": process::millis(started.elapsed()),
+                "tasks": tasks,
+            }),
+        );
+    });
+}
+
+/// References of the jobs, for responses.
+pub fn describe(jobs: &[(Job, Invocation)]) -> Vec<String> {
+    jobs.iter()
+        .map(|(job, _)| format!("{}:{}", job.provider, job.name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    /// Jobs of the composite `name` as `provider:name` and the positions
+    /// of the jobs they wait for.
+    fn planned(config: &Config, name: &str) -> Vec<(String, Vec<usize>)> {
+        plan(config, name)
+            .unwrap()
+            .into_iter()
+            .map(|job| (format!("{}:{}", job.provider, job.name), job.after))
+            .collect()
+    }
+
+    fn job(reference: &str, after: &[usize]) -> (String, Vec<usize>) {
+        (reference.to_string(), after.to_vec())
+    }
+
+    #[test]
+    fn plans_parallel_dependencies() {
+        let config = parse(
+            r#"
+            [[task]]
+            name = "ci"
+            depends_on = ["npm:lint", "npm:test"]
+            run = "gradle:build"
+            "#,
+        );
+        assert_eq!(
+            planned(&config, "ci"),
+            [
+                job("npm:lint", &[]),
+                job("npm:test", &[]),
+                job("gradle:build", &[0, 1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn plans_sequences_and_nested_composites() {
+        let config = parse(
+            r#"
+            [[task]]
+            name = "ci"
+            depends_on = ["check", "npm:lint"]
+            run = "deploy"
+
+            [[task]]
+            name = "check"
+            depends_on = ["npm:test", "make:check"]
+            depends_order = "sequence"
+
+            [[task]]
+            name = "deploy"
+            command = "./deploy.sh"
+            "#,
+        );
+        assert_eq!(
+            planned(&config, "ci"),
+            [
+                job("npm:test", &[]),
+                job("make:check", &[0]),
+                job("npm:lint", &[]),
+                job("custom:deploy", &[1, 2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_shared_tasks_once() {
+        let config = parse(
+            r#"
+            [[task]]
+            name = "all"
+            depends_on = ["front", "back"]
+
+            [[task]]
+            name = "front"
+            depends_on = ["npm:install"]
+            run = "npm:build"
+
+            [[task]]
+            name = "back"
+            depends_on = ["npm:install"]
+            run = "cargo:build"
+            "#,
+        );
+        assert_eq!(
+            planned(&config, "all"),
+            [
+                job("npm:install", &[]),
+                job("npm:build", &[0]),
+                job("cargo:build", &[0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let config = parse(
+            r#"
+            [[task]]
+            name = "a"
+            depends_on = ["b"]
+
+            [[task]]
+            name = "b"
+            depends_on = ["npm:test", "a"]
+            "#,
+        );
+        let err = plan(&config, "a").unwrap_err();
+        assert_eq!(err.to_string(), "composite task `a` depends on itself");
+
+        // Each order is fine on its own, but not both together.
+        let config = parse(
+            r#"
+            [[task]]
+            name = "both"
+            depends_on = ["forward", "backward"]
+
+            [[task]]
+            name = "forward"
+            depends_on = ["x:one", "x:two"]
+            depends_order = "sequence"
+
+            [[task]]
+            name = "backward"
+            depends_on = ["x:two", "x:one"]
+            depends_order = "sequence"
+            "#,
+        );
+        let err = plan(&config, "both").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "tasks `x:one`, `x:two` wait for each other"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_references() {
+        let config = parse("");
+        assert!(plan(&config, "missing").is_err());
+    }
+
+    fn jobs(after: &[&[usize]]) -> Vec<(Job, Invocation)> {
+        after
+            .iter()
+            .enumerate()
+            .map(|(index, after)| {
+                let job = Job {
+                    provider: "custom".to_string(),
+                    name: index.to_string(),
+                    after: after.to_vec(),
+                };
+                (job, Invocation::new("true", [], "."))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn starts_jobs_up_to_the_concurrency_limit() {
+        let jobs = jobs(&[&[], &[], &[], &[0, 1]]);
+        let mut states = vec![State::Waiting; jobs.len()];
+        assert_eq!(next_ready(&jobs, &mut states, 0, 2), Some(0));
+        states[0] = State::Running;
+        assert_eq!(next_ready(&jobs, &mut states, 1, 2), Some(1));
+        states[1] = State::Running;
+        assert_eq!(next_ready(&jobs, &mut states, 2, 2), None);
+
+        states[0] = State::Succeeded;
+        assert_eq!(next_ready(&jobs, &mut states, 1, 2), Some(2));
+        states[2] = State::Running;
+        states[1] = State::Succeeded;
+        assert_eq!(next_ready(&jobs, &mut states, 1, 2), Some(3));
+    }
+
+    #[test]
+    fn skips_jobs_waiting_for_failed_jobs() {
+        let jobs = jobs(&[&[], &[0], &[1], &[]]);
+        let mut states = vec![
+            State::Failed,
+            State::Waiting,
+            State::Waiting,
+            State::Waiting,
+        ];
+        // Skipping does not count against the concurrency limit.
+        assert_eq!(next_ready(&jobs, &mut states, 1, 1), None);
+        assert_eq!(
+            states,
+            [
+                State::Failed,
+                State::Skipped,
+                State::Skipped,
+                State::Waiting
+            ]
+        );
+        assert_eq!(next_ready(&jobs, &mut states, 0, 1), Some(3));
+    }
+
+    #[test]
+    fn runs_at_least_one_task() {
+        let config = parse("concurrency = 0");
+        assert_eq!(concurrency(&config), 1);
+        let config = parse("concurrency = 4");
+        assert_eq!(concurrency(&config), 4);
+    }
+}
@@ -0,0 +1,457 @@
+//! Workspace task configuration in `.helix/tasks.toml`.
+//!
+//! ```toml
+//! [[task]]
+//! name = "serve"
+//! command = "cargo run -- --port 8080"
+//! cwd = "server"
+//! env = { RUST_LOG = "debug" }
+//!
+//! [[task]]
+//! name = "test"
+//! command = "cargo test --profile ${input:profile} ${input:pattern}"
+//! args = ["--", "--nocapture"]
+//! params.profile = { choices = ["dev", "release"] }
+//! params.pattern = { description = "Tests to run" }
+//!
+//! [overrides."npm:test"]
+//! env = { CI = "1" }
+//! shell = "bash"
+//! timeout = 600
+//!
+//! [overrides."npm:build"]
+//! inputs = ["src/**", "package.json", "tsconfig.json"]
+//!
+//! [overrides."vscode:lint"]
+//! shell = "none"
+//!
+//! [listing]
+//! favorites = ["serve", "npm:dev"]
+//! hidden = ["npm:pre*", "npm:post*"]
+//! default = "serve"
+//! ```
+//!
+//! Tasks with a `command` are custom tasks, run through `shell` (the
+//! platform shell by default) and listed with the `custom` provider. Tasks
+//! without one are composite tasks, see [`crate::composite`]. Overrides
+//! change the working directory, environment, shell and timeout of
+//! discovered tasks, keyed by `provider:name`. A `shell` of `"none"` runs
+//! the task directly rather than through a shell, see [`crate::shell`].
+//! Timeouts are given in seconds; the top-level `timeout` applies to tasks
+//! without one. Tasks with `inputs` are not run again while their inputs
+//! are unchanged, see [`crate::cache`].
+//!
+//! Custom tasks are run with their `args` unless a run passes arguments of
+//! its own. `${input:<id>}` placeholders in the command and arguments are
+//! replaced as is with the values of the `params` of the task, which runs
+//! pass in `inputs`. Missing values are picked from the `choices` of the
+//! parameter unless it has a `default`. Otherwise they are entered as text,
+//! starting from the value passed last, in editors that support it, and
+//! else picked from the values earlier runs passed.
+//!
+//! The listing puts favorites first, in the order given, and leaves out
+//! tasks matching a `hidden` glob; hidden tasks can still be run. A
+//! `helix.task.run` without arguments runs the `default` task. Tasks of the
+//! file are referred to by their name, discovered tasks as `provider:name`,
+//! where `npm` stands for package scripts of any package manager.
+
+use crate::{
+    composite, package_manager, shell,
+    vscode::{Input, InputKind},
+    Invocation, Task,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use globset::{Glob, GlobSetBuilder};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub const PROVIDER: &str = "custom";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Number of tasks composite tasks run at the same time; defaults to
+    /// the number of CPUs.
+    pub concurrency: Option<usize>,
+    timeout: Option<u64>,
+    /// Whether tasks run with the toolchains pinned for mise, asdf or nvm;
+    /// defaults to true.
+    version_managers: Option<bool>,
+    #[serde(default, rename = "task")]
+    pub tasks: Vec<TaskEntry>,
+    #[serde(default)]
+    pub overrides: BTreeMap<String, Override>,
+    #[serde(default)]
+    pub listing: Listing,
+}
+
+/// How tasks are listed, and the task run by default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Listing {
+    #[serde(default)]
+    favorites: Vec<String>,
+    /// Globs matched against `provider:name`.
+    #[serde(default)]
+    hidden: Vec<String>,
+    default: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskEntry {
+    pub name: String,
+    pub detail: Option<String>,
+    /// Command line of a custom task.
+    pub command: Option<String>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub depends_order: Order,
+    pub run: Option<String>,
+    /// Working directory of a custom task, relative to the workspace root.
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Shell running a custom task.
+    pub shell: Option<String>,
+    timeout: Option<u64>,
+    /// Globs selecting the files a custom task reads, see [`crate::cache`].
+    #[serde(default)]
+    inputs: Vec<String>,
+    /// Arguments appended to the command of a custom task when a run
+    /// passes none.
+    #[serde(default)]
+    args: Vec<String>,
+    /// Parameters of a custom task, by the id of their placeholder.
+    #[serde(default)]
+    params: BTreeMap<String, Param>,
+}
+
+/// Value substituted for the `${input:<id>}` placeholders of a custom task.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Param {
+    description: Option<String>,
+    default: Option<String>,
+    /// Values the user picks from when a run does not pass one.
+    #[serde(default)]
+    choices: Vec<String>,
+}
+
+impl Config {
+    /// Timeout of tasks that do not set their own.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+
+    pub fn uses_version_managers(&self) -> bool {
+        self.version_managers.unwrap_or(true)
+    }
+}
+
+impl TaskEntry {
+    /// Whether the task runs a command of its own rather than other tasks.
+    pub fn is_custom(&self) -> bool {
+        self.command.is_some()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    #[default]
+    Parallel,
+    Sequence,
+}
+
+/// Settings replacing those of a discovered task.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Override {
+    /// Working directory, relative to the workspace root.
+    cwd: Option<PathBuf>,
+    /// Variables set in addition to those of the task.
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    /// Shell the command line of the task is run through.
+    shell: Option<String>,
+    timeout: Option<u64>,
+    #[serde(default)]
+    inputs: Vec<String>,
+}
+
+fn config_file(root: &Path) -> PathBuf {
+    root.join(".helix").join("tasks.toml")
+}
+
+pub fn load(root: &Path) -> Result<Config> {
+    let path = config_file(root);
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    let config: Config =
+        toml::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))?;
+
+    for task in &config.tasks {
+        let composite = !task.depends_on.is_empty() || task.run.is_some();
+        let settings = task.cwd.is_some()
+            || !task.env.is_empty()
+            || task.shell.is_some()
+            || task.timeout.is_some()
+            || !task.inputs.is_empty()
+            || !task.args.is_empty()
+            || !task.params.is_empty();
+        if task.is_custom() && composite {
+            bail!(
+                "task `{}` of {} sets `command` as well as `depends_on` or `run`",
+                task.name,
+                path.display()
+            );
+        }
+        if !task.is_custom() && settings {
+            bail!(
+                "task `{}` of {} sets `cwd`, `env`, `shell`, `timeout`, `inputs`, `args` or \
+                 `params` without a `command`",
+                task.name,
+                path.display()
+            );
+        }
+    }
+    Ok(config)
+}
+
+pub fn discover(config: &Config) -> Vec<Task> {
+    config
+        .tasks
+        .iter()
+        .filter(|task| task.is_custom())
+        .map(|task| Task {
+            name: task.name.clone(),
+            provider: PROVIDER.to_string(),
+            command: task.command.clone().unwrap_or_default(),
+            detail: task.detail.clone(),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn custom_task<'a>(config: &'a Config, name: &str) -> Result<&'a TaskEntry> {
+    config
+        .tasks
+        .iter()
+        .find(|task| task.is_custom() && task.name == name)
+        .ok_or_else(|| anyhow!("no custom task named `{name}`"))
+}
+
+/// Ids of the `${input:<id>}` placeholders in `value`.
+fn placeholders(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split("${input:")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}'))
+        .map(|(id, _)| id)
+}
+
+/// Replace the placeholders in `value` with their value in `inputs`.
+fn substitute(value: &str, inputs: &BTreeMap<String, String>) -> Result<String> {
+    let mut substituted = value.to_string();
+    for id in placeholders(value) {
+        let input = inputs
+            .get(id)
+            .ok_or_else(|| anyhow!("no value was given for `${{input:{id}}}`"))?;
+        substituted = substituted.replace(&format!("${{input:{id}}}"), input);
+    }
+    Ok(substituted)
+}
+
+/// Parameters of the custom task `name` its command and default arguments
+/// refer to.
+pub fn params(config: &Config, name: &str) -> Result<Vec<Input>> {
+    let task = custom_task(config, name)?;
+    let mut inputs: Vec<Input> = Vec::new();
+    for value in task.command.iter().chain(&task.args) {
+        for id in placeholders(value) {
+            if inputs.iter().any(|input| input.id == id) {
+                continue;
+            }
+            let param = task.params.get(id).ok_or_else(|| {
+                anyhow!("`${{input:{id}}}` of task `{name}` is not declared in `params`")
+            })?;
+            let kind = if param.choices.is_empty() {
+                InputKind::Text(param.default.clone())
+            } else {
+                InputKind::Pick(
+                    param
+                        .choices
+                        .iter()
+                        .map(|choice| (choice.clone(), choice.clone()))
+                        .collect(),
+                )
+            };
+            inputs.push(Input {
+                id: id.to_string(),
+                description: param.description.clone(),
+                kind,
+            });
+        }
+    }
+    Ok(inputs)
+}
+
+/// Process running the custom task `name` with `extra` arguments, or its
+/// own when there are none. `inputs` holds the values of its placeholders.
+pub fn invocation(
+    root: &Path,
+    config: &Config,
+    name: &str,
+    extra: &[String],
+    inputs: &BTreeMap<String, String>,
+) -> Result<Invocation> {
+    let task = custom_task(config, name)?;
+    let cwd = match &task.cwd {
+        Some(cwd) => root.join(cwd),
+        None => root.to_path_buf(),
+    };
+    let command = substitute(task.command.as_deref().unwrap_or_default(), inputs)?;
+    let extra = if extra.is_empty() {
+        task.args
+            .iter()
+            .map(|arg| substitute(arg, inputs))
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        extra.to_vec()
+    };
+    let mut invocation = shell::line_invocation(task.shell.as_deref(), &command, &extra, cwd);
+    invocation.env.extend(task.env.clone());
+    invocation.timeout = task.timeout.map(Duration::from_secs);
+    Ok(invocation)
+}
+
+/// Apply the override of the discovered task `provider:name`, if any.
+pub fn apply_override(
+    root: &Path,
+    config: &Config,
+    provider: &str,
+    name: &str,
+    invocation: &mut Invocation,
+) {
+    let Some(settings) = config.overrides.get(&format!("{provider}:{name}")) else {
+        return;
+    };
+    if let Some(cwd) = &settings.cwd {
+        invocation.cwd = root.join(cwd);
+    }
+    invocation.env.extend(settings.env.clone());
+    if let Some(timeout) = settings.timeout {
+        invocation.timeout = Some(Duration::from_secs(timeout));
+    }
+    if let Some(executable) = &settings.shell {
+        shell::select(executable, invocation);
+    }
+}
+
+/// Globs selecting the input files of the task `name` of `provider`.
+pub fn inputs<'a>(config: &'a Config, provider: &str, name: &str) -> &'a [String] {
+    if provider == PROVIDER {
+        config
+            .tasks
+            .iter()
+            .find(|task| task.is_custom() && task.name == name)
+            .map_or(&[], |task| &task.inputs)
+    } else {
+        config
+            .overrides
+            .get(&format!("{provider}:{name}"))
+            .map_or(&[], |settings| &settings.inputs)
+    }
+}
+
+/// Provider and name of the task `reference` refers to.
+pub fn resolve(config: &Config, reference: &str) -> Result<(String, String)> {
+    if let Some(task) = config.tasks.iter().find(|task| task.name == reference) {
+        let provider = if task.is_custom() {
+            PROVIDER
+        } else {
+            composite::PROVIDER
+        };
+        return Ok((provider.to_string(), reference.to_string()));
+    }
+    let (provider, name) = reference.split_once(':').ok_or_else(|| {
+        anyhow!(
+            "`{reference}` is neither a task of the configuration nor a `provider:name` reference"
+        )
+    })?;
+    Ok((provider.to_string(), name.to_string()))
+}
+
+/// Whether `task` is the task `provider:name` refers to.
+fn refers_to(task: &Task, provider: &str, name: &str) -> bool {
+    let scripts = |provider: &str| package_manager::PROVIDERS.contains(&provider);
+    task.name == name && (task.provider == provider || scripts(&task.provider) && scripts(provider))
+}
+
+/// Apply the listing settings to `tasks`: drop hidden tasks, put favorites
+/// first and mark them and the default task.
+pub fn arrange(config: &Config, tasks: Vec<Task>) -> Result<Vec<Task>> {
+    let listing = &config.listing;
+    let mut hidden = GlobSetBuilder::new();
+    for glob in &listing.hidden {
+        hidden.add(Glob::new(glob).with_context(|| format!("invalid glob `{glob}`"))?);
+    }
+    let hidden = hidden.build()?;
+    let favorites = listing
+        .favorites
+        .iter()
+        .map(|reference| resolve(config, reference))
+        .collect::<Result<Vec<_>>>()?;
+    let default = listing
+        .default
+        .as_deref()
+        .map(|reference| resolve(config, reference))
+        .transpose()?;
+
+    let mut tasks: Vec<Task> = tasks
+        .into_iter()
+        .filter(|task| {
+            let mut references = vec![format!("{}:{}", task.provider, task.name)];
+            if package_manager::PROVIDERS.contains(&task.provider.as_str()) {
+                references.push(format!("npm:{}", task.name));
+            }
+            !references
+                .iter()
+                .any(|reference| hidden.is_match(reference))
+        })
+        .collect();
+    for task in &mut tasks {
+        task.favorite = favorites
+            .iter()
+            .any(|(provider, name)| refers_to(task, provider, name));
+        task.default = default
+            .as_ref()
+            .is_some_and(|(provider, name)| refers_to(task, provider, name));
+    }
+    // Stable, so other tasks keep the order of discovery.
+    tasks.sort_by_key(|task| {
+        favorites
+            .iter()
+            .position(|(provider, name)| refers_to(task, provider, name))
+            .unwrap_or(favorites.len())
+    });
+    Ok(tasks)
+}
+
+/// Provider and name of the task run by default.
+pub fn default_task(config: &Config) -> Result<(String, String)> {
+    let reference = config.listing.default.as_deref().ok_or_else(|| {
+        anyhow!("no default task is configured; set `listing.default` in .helix/tasks.toml")
+    })?;
+    resolve(config, reference)
+}
@@ -0,0 +1,115 @@
+//! Tasks of `deno.json` and `deno.jsonc`, including those of the members of
+//! a Deno workspace.
+
+use crate::{jsonc, workspaces, Invocation, Task};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const PROVIDER: &str = "deno";
+
+/// Configuration files Deno looks for, in order.
+const FILE_NAMES: &[&str] = &["deno.json", "deno.jsonc"];
+
+#[derive(Debug, Default, Deserialize)]
+struct DenoConfig {
+    #[serde(default)]
+    tasks: BTreeMap<String, TaskConfig>,
+    #[serde(default)]
+    workspace: Workspace,
+}
+
+/// Task definition; tasks may also be written as their command only.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TaskConfig {
+    Command(String),
+    Full {
+        command: Option<String>,
+        description: Option<String>,
+        #[serde(default)]
+        dependencies: Vec<String>,
+    },
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(untagged)]
+enum Workspace {
+    #[default]
+    None,
+    Members(Vec<String>),
+    Config {
+        #[serde(default)]
+        members: Vec<String>,
+    },
+}
+
+fn config_file(dir: &Path) -> Option<PathBuf> {
+    FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+fn load(path: &Path) -> Result<DenoConfig> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&jsonc::strip(&content))
+        .with_context(|| format!("failed to parse {}", path.display()))
+}
+
+fn tasks(config: DenoConfig, package: Option<&str>) -> impl Iterator<Item = Task> + '_ {
+    config.tasks.into_iter().map(move |(name, task)| {
+        let (command, detail) = match task {
+            TaskConfig::Command(command) => (command, None),
+            TaskConfig::Full {
+                command,
+                description,
+                dependencies,
+            } => {
+                let command = command.unwrap_or_else(|| dependencies.join(", "));
+                (command, description)
+            }
+        };
+        Task {
+            name,
+            provider: PROVIDER.to_string(),
+            command,
+            detail,
+            package: package.map(str::to_string),
+            ..Default::default()
+        }
+    })
+}
+
+pub fn discover(root: &Path) -> Result<Vec<Task>> {
+    let Some(path) = config_file(root) else {
+        return Ok(Vec::new());
+    };
+    let mut config = load(&path)?;
+    let members = match std::mem::take(&mut config.workspace) {
+        Workspace::None => Vec::new(),
+        Workspace::Members(members) | Workspace::Config { members } => members,
+    };
+    let mut discovered: Vec<Task> = tasks(config, None).collect();
+    for member in workspaces::expand(root, &members, &[], FILE_NAMES)? {
+        let Some(path) = config_file(&root.join(&member)) else {
+            continue;
+        };
+        discovered.extend(tasks(load(&path)?, Some(&member)));
+    }
+    Ok(discovered)
+}
+
+/// Process running the task `name` of the configuration in `dir`. Deno
+/// appends `extra` arguments to its command.
+pub fn invocation(dir: &Path, name: &str, extra: &[String]) -> Invocation {
+    let args = ["task".to_string(), name.to_string()]
+        .into_iter()
+        .chain(extra.iter().cloned());
+    Invocation::new("deno", args, dir)
+}
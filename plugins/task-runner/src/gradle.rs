@@ -0,0 +1,129 @@
+//! Gradle tasks, as reported by `gradle tasks --all`.
+//!
+//! Listing tasks configures the whole build, which takes seconds, so the
+//! report is cached until one of the root build scripts changes.
+
+use crate::{local_or_global, Invocation, Task};
+use anyhow::{bail, Context, Result};
+use std::{fs, path::Path, process::Command, time::SystemTime};
+
+pub const PROVIDER: &str = "gradle";
+
+/// Files whose modification invalidates the cached task report.
+const BUILD_FILES: &[&str] = &[
+    "build.gradle",
+    "build.gradle.kts",
+    "settings.gradle",
+    "settings.gradle.kts",
+    "gradle.properties",
+    "gradle/libs.versions.toml",
+];
+
+/// Tasks reported by Gradle and the build files they were reported for.
+#[derive(Debug, Default)]
+pub struct TaskCache {
+    stamp: Vec<Option<SystemTime>>,
+    tasks: Option<Vec<Task>>,
+}
+
+fn stamp(root: &Path) -> Vec<Option<SystemTime>> {
+    BUILD_FILES
+        .iter()
+        .map(|file| {
+            fs::metadata(root.join(file))
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect()
+}
+
+fn is_project(root: &Path) -> bool {
+    BUILD_FILES[..4]
+        .iter()
+        .any(|file| root.join(file).is_file())
+}
+
+fn program(root: &Path) -> String {
+    local_or_global(
+        root,
+        if cfg!(windows) {
+            "gradlew.bat"
+        } else {
+            "gradlew"
+        },
+        "gradle",
+    )
+}
+
+pub fn discover(root: &Path, cache: &mut TaskCache) -> Result<Vec<Task>> {
+    if !is_project(root) {
+        return Ok(Vec::new());
+    }
+    let stamp = stamp(root);
+    if let Some(tasks) = cache.tasks.as_ref().filter(|_| cache.stamp == stamp) {
+        return Ok(tasks.clone());
+    }
+
+    let program = program(root);
+    let output = Command::new(&program)
+        .args(["tasks", "--all", "--quiet", "--console=plain"])
+        .current_dir(root)
+        .output()
+        .with_context(|| format!("failed to spawn `{program}`"))?;
+    if !output.status.success() {
+        bail!(
+            "`{program} tasks` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let tasks = parse_report(&String::from_utf8_lossy(&output.stdout));
+    *cache = TaskCache {
+        stamp,
+        tasks: Some(tasks.clone()),
+    };
+    Ok(tasks)
+}
+
+/// Tasks of the `<Group> tasks` sections of a task report, where every
+/// line is `name - description` or just `name`.
+fn parse_report(report: &str) -> Vec<Task> {
+    let lines: Vec<&str> = report.lines().collect();
+    let mut tasks = Vec::new();
+    let mut index = 0;
+    while index + 1 < lines.len() {
+        let header = lines[index].trim();
+        let underline = lines[index + 1].trim();
+        index += 1;
+        if !header.ends_with(" tasks")
+            || underline.is_empty()
+            || !underline.chars().all(|c| c == '-')
+        {
+            continue;
+        }
+        index += 1;
+        while let Some(line) = lines.get(index).map(|line| line.trim()) {
+            if line.is_empty() {
+                break;
+            }
+            let (name, description) = match line.split_once(" - ") {
+                Some((name, description)) => (name, Some(description.to_string())),
+                None => (line, None),
+            };
+            tasks.push(Task {
+                name: name.to_string(),
+                provider: PROVIDER.to_string(),
+                command: format!("gradle {name}"),
+                detail: description,
+                ..Default::default()
+            });
+            index += 1;
+        }
+    }
+    tasks
+}
+
+/// Process running the task `name`, passing Gradle `extra` arguments.
+pub fn invocation(root: &Path, name: &str, extra: &[String]) -> Invocation {
+    let args = std::iter::once(name.to_string()).chain(extra.iter().cloned());
+    Invocation::new(program(root), args, root)
+}
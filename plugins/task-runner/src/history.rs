@@ -0,0 +1,87 @@
+//! Tasks run in each workspace, persisted across sessions in the data
+//! directory the host assigns to the plugin.
+
+use crate::RunOptions;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Number of runs remembered per workspace.
+const MAX_ENTRIES: usize = 100;
+
+/// Run of a task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub provider: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub options: RunOptions,
+    /// Start of the run, in seconds since the Unix epoch.
+    pub started_at: u64,
+    pub duration_ms: u64,
+    /// Exit code of the task; `None` when it was killed by a signal.
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub cancelled: bool,
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+/// History of the tasks run in one workspace. Runs are written through to
+/// disk, so sessions running side by side do not lose each other's runs.
+#[derive(Debug, Default, Clone)]
+pub struct History {
+    /// File holding the history of every workspace; `None` when the host
+    /// did not assign a data directory.
+    path: Option<PathBuf>,
+    workspace: String,
+}
+
+impl History {
+    pub fn new(workspace_root: &Path) -> Self {
+        let path = env::var_os("HELIX_PLUGIN_DATA_DIR")
+            .map(|dir| PathBuf::from(dir).join("task-history.json"));
+        Self {
+            path,
+            workspace: workspace_root.to_string_lossy().into_owned(),
+        }
+    }
+
+    fn load(path: &Path) -> BTreeMap<String, Vec<Entry>> {
+        // A missing or corrupt history resolves to an empty one.
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Runs in the workspace, most recent first.
+    pub fn entries(&self) -> Vec<Entry> {
+        let Some(path) = &self.path else {
+            return Vec::new();
+        };
+        let mut entries = Self::load(path).remove(&self.workspace).unwrap_or_default();
+        entries.reverse();
+        entries
+    }
+
+    /// Append `entry` to the history of the workspace.
+    pub fn record(&self, entry: Entry) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut workspaces = Self::load(path);
+        let entries = workspaces.entry(self.workspace.clone()).or_default();
+        entries.push(entry);
+        let excess = entries.len().saturating_sub(MAX_ENTRIES);
+        entries.drain(..excess);
+
+        let contents = serde_json::to_string_pretty(&workspaces)?;
+        fs::write(path, contents)
+            .with_context(|| format!("failed to write task history `{}`", path.display()))
+    }
+}
@@ -0,0 +1,87 @@
+//! JSON with comments and trailing commas, as written by VS Code.
+
+/// Strip `//` and `/* */` comments and trailing commas from `source` so it
+/// parses as plain JSON.
+pub fn strip(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|&c| c != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = None;
+                for c in chars.by_ref() {
+                    if previous == Some('*') && c == '/' {
+                        break;
+                    }
+                    previous = Some(c);
+                }
+                // Keep tokens separated by the comment apart.
+                out.push(' ');
+            }
+            '}' | ']' => {
+                let content = out.trim_end().len();
+                if out[..content].ends_with(',') {
+                    out.truncate(content - 1);
+                }
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_comments() {
+        assert_eq!(strip("{\n  // note\n  \"a\": 1\n}"), "{\n  \n  \"a\": 1\n}");
+        assert_eq!(strip("[1,/* two */2]"), "[1, 2]");
+        assert_eq!(strip("[1/**/2]"), "[1 2]");
+    }
+
+    #[test]
+    fn keeps_strings() {
+        assert_eq!(
+            strip(r#"{"url": "https://example.com", "glob": "/*.rs"}"#),
+            r#"{"url": "https://example.com", "glob": "/*.rs"}"#
+        );
+        assert_eq!(
+            strip(r#"["say \"// hi\"", "\\"] // done"#),
+            r#"["say \"// hi\"", "\\"] "#
+        );
+        assert_eq!(strip(r#"["a,]"]"#), r#"["a,]"]"#);
+    }
+
+    #[test]
+    fn strips_trailing_commas() {
+        assert_eq!(strip("[1, 2, ]"), "[1, 2]");
+        assert_eq!(strip(r#"{"a": [1,], "b": 2,}"#), r#"{"a": [1], "b": 2}"#);
+        assert_eq!(strip("{\"a\": 1, // last\n  /* really */\n}"), "{\"a\": 1}");
+        assert_eq!(strip("[1 /* no comma */ ]"), "[1   ]");
+    }
+
+    #[test]
+    fn ends_unterminated_block_comments_at_the_end() {
+        assert_eq!(strip("[1] /* open"), "[1]  ");
+        assert_eq!(strip("[1] /*"), "[1]  ");
+    }
+}
@@ -1,14 +1,59 @@
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use helix_plugin_sdk::{
     run, CommandContext, InitializeContext, MessageLevel, Plugin, PluginCommand, Registrar,
+    Responder,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::{env, fs, path::PathBuf, process::Command};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+mod cache;
+mod cargo;
+mod composer;
+mod composite;
+mod config;
+mod deno;
+mod gradle;
+mod history;
+mod jsonc;
+mod maven;
+mod package_manager;
+mod process;
+mod python;
+mod rake;
+mod scripts;
+mod shell;
+mod taskfile;
+mod toolchain;
+mod vscode;
+mod watch;
+mod workspaces;
+
+/// Time the host may reuse a task listing for.
+const TASK_LIST_CACHE_TTL: Duration = Duration::from_secs(2);
 
 #[derive(Default)]
 struct TaskRunnerPlugin {
     workspace_root: PathBuf,
+    gradle_tasks: gradle::TaskCache,
+    rake_tasks: rake::TaskCache,
+    running: process::RunningTasks,
+    watches: watch::Watches,
+    history: history::History,
+    cache: cache::Cache,
+    /// Task started last in this session, which may still be running.
+    last_started: Option<(String, String, RunOptions)>,
+    /// Whether the editor runs tasks in its terminal.
+    run_in_terminal: bool,
+    /// Whether the editor asks the user for text, e.g. values of parameters.
+    text_input: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -17,11 +62,87 @@ struct PackageJson {
     scripts: serde_json::Map<String, Value>,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Default, Clone, Serialize)]
 struct Task {
     name: String,
     provider: String,
     command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    /// Directory of the monorepo package the task belongs to, relative to
+    /// the workspace root.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    package: Option<String>,
+    /// Names of the problem matchers parsing the task output, e.g. `$tsc`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    problem_matchers: Vec<String>,
+    /// Whether the task is one of the favorites listed first.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    favorite: bool,
+    /// Whether a `helix.task.run` without arguments runs the task.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    default: bool,
+}
+
+/// Arguments, environment and placeholder values a task is run with.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct RunOptions {
+    /// Monorepo package the task belongs to, as listed by `helix.task.list`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    package: Option<String>,
+    /// Arguments appended to the command of the task.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+    /// Variables set in addition to those of the task.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    /// Values of the `${input:...}` placeholders of the task, by id.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    inputs: BTreeMap<String, String>,
+    /// Shell the task is run through, or `none` to run it directly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shell: Option<String>,
+}
+
+/// Arguments of `helix.task.watch` besides the task.
+#[derive(Debug, Deserialize)]
+struct WatchOptions {
+    #[serde(flatten)]
+    run: RunOptions,
+    /// Globs selecting the watched files; every file by default.
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    debounce_ms: Option<u64>,
+}
+
+/// Process a task runs as.
+#[derive(Debug, Clone)]
+struct Invocation {
+    program: String,
+    args: Vec<String>,
+    cwd: PathBuf,
+    env: Vec<(String, String)>,
+    /// Time after which the task is stopped.
+    timeout: Option<Duration>,
+    /// Command line the shell `program` runs, for tasks run through one.
+    line: Option<String>,
+}
+
+impl Invocation {
+    fn new(
+        program: impl Into<String>,
+        args: impl IntoIterator<Item = String>,
+        cwd: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            program: program.into(),
+            args: args.into_iter().collect(),
+            cwd: cwd.into(),
+            env: Vec::new(),
+            timeout: None,
+            line: None,
+        }
+    }
 }
 
 impl TaskRunnerPlugin {
@@ -30,25 +151,58 @@ fn new() -> Result<Self> {
             .map(PathBuf::from)
             .or_else(|_| env::current_dir())?;
         Ok(Self {
+            history: history::History::new(&root),
+            cache: cache::Cache::new(&root),
             workspace_root: root,
+            ..Default::default()
         })
     }
 
-    fn discover_tasks(&self) -> Result<Vec<Task>> {
+    fn discover_tasks(&mut self) -> Result<Vec<Task>> {
         let mut tasks = Vec::new();
 
         tasks.extend(self.extract_package_scripts()?);
+        tasks.extend(deno::discover(&self.workspace_root)?);
         tasks.extend(self.extract_justfile()?);
         tasks.extend(self.extract_makefile()?);
+        tasks.extend(vscode::discover(&self.workspace_root)?);
+        tasks.extend(taskfile::discover(&self.workspace_root)?);
+        tasks.extend(gradle::discover(
+            &self.workspace_root,
+            &mut self.gradle_tasks,
+        )?);
+        tasks.extend(maven::discover(&self.workspace_root)?);
+        tasks.extend(cargo::discover(&self.workspace_root)?);
+        tasks.extend(python::discover(&self.workspace_root)?);
+        tasks.extend(rake::discover(&self.workspace_root, &mut self.rake_tasks)?);
+        tasks.extend(composer::discover(&self.workspace_root)?);
+        tasks.extend(scripts::discover(&self.workspace_root)?);
+        let config = config::load(&self.workspace_root)?;
+        tasks.extend(config::discover(&config));
+        tasks.extend(composite::discover(&config));
 
-        Ok(tasks)
+        config::arrange(&config, tasks)
     }
 
+    /// Scripts of the root package and of the packages of its workspace.
     fn extract_package_scripts(&self) -> Result<Vec<Task>> {
-        let package_json = self.workspace_root.join("package.json");
+        let mut tasks = self.extract_scripts(None)?;
+        for package in workspaces::npm_packages(&self.workspace_root)? {
+            tasks.extend(self.extract_scripts(Some(package))?);
+        }
+        Ok(tasks)
+    }
+
+    fn extract_scripts(&self, package: Option<String>) -> Result<Vec<Task>> {
+        let dir = match &package {
+            Some(package) => self.workspace_root.join(package),
+            None => self.workspace_root.clone(),
+        };
+        let package_json = dir.join("package.json");
         if !package_json.exists() {
             return Ok(Vec::new());
         }
+        let manager = package_manager::PackageManager::detect(&self.workspace_root, &dir);
 
         let content = fs::read_to_string(&package_json)
             .with_context(|| format!("failed to read {}", package_json.display()))?;
@@ -60,8 +214,10 @@ fn extract_package_scripts(&self) -> Result<Vec<Task>> {
             .iter()
             .map(|(name, value)| Task {
                 name: name.clone(),
-                provider: "npm".to_string(),
+                provider: manager.name().to_string(),
                 command: value.as_str().unwrap_or_default().to_string(),
+                package: package.clone(),
+                ..Default::default()
             })
             .collect())
     }
@@ -85,7 +241,7 @@ fn extract_justfile(&self) -> Result<Vec<Task>> {
                 line.split_once(':').map(|(name, _)| Task {
                     name: name.trim().to_string(),
                     provider: "just".to_string(),
-                    command: String::new(),
+                    ..Default::default()
                 })
             })
             .collect();
@@ -112,7 +268,7 @@ fn extract_makefile(&self) -> Result<Vec<Task>> {
                 trimmed.split_once(':').map(|(name, _)| Task {
                     name: name.trim().to_string(),
                     provider: "make".to_string(),
-                    command: String::new(),
+                    ..Default::default()
                 })
             })
             .collect();
@@ -120,40 +276,464 @@ fn extract_makefile(&self) -> Result<Vec<Task>> {
         Ok(tasks)
     }
 
-    fn run_task(&self, provider: &str, name: &str) -> Result<String> {
-        match provider {
-            "npm" | "yarn" | "pnpm" => self.run_package_script(provider, name),
-            "just" => self.exec_process("just", &[name]),
-            "make" => self.exec_process("make", &[name]),
+    /// Process running the task `name` of `provider` with `options`.
+    fn resolve_task(&self, provider: &str, name: &str, options: &RunOptions) -> Result<Invocation> {
+        let config = config::load(&self.workspace_root)?;
+        // Tasks of monorepo packages run in the directory of the package.
+        let root = &match &options.package {
+            Some(package) => self.workspace_root.join(package),
+            None => self.workspace_root.clone(),
+        };
+        if !root.is_dir() {
+            bail!("package directory {} does not exist", root.display());
+        }
+        let args = options.args.as_slice();
+        let mut invocation = match provider {
+            provider if package_manager::PROVIDERS.contains(&provider) => {
+                let manager = package_manager::PackageManager::detect(&self.workspace_root, root);
+                Ok(manager.invocation(root, name, args))
+            }
+            "just" | "make" => {
+                let args = std::iter::once(name.to_string()).chain(args.iter().cloned());
+                Ok(Invocation::new(provider, args, root))
+            }
+            vscode::PROVIDER => vscode::invocation(root, name, args, &options.inputs),
+            taskfile::PROVIDER => Ok(taskfile::invocation(root, name, args)),
+            gradle::PROVIDER => Ok(gradle::invocation(root, name, args)),
+            maven::PROVIDER => Ok(maven::invocation(root, name, args)),
+            cargo::PROVIDER => Ok(cargo::invocation(root, name, args)),
+            deno::PROVIDER => Ok(deno::invocation(root, name, args)),
+            provider if python::PROVIDERS.contains(&provider) => {
+                Ok(python::invocation(provider, root, name, args))
+            }
+            rake::PROVIDER => Ok(rake::invocation(root, name, args)),
+            composer::PROVIDER => Ok(composer::invocation(root, name, args)),
+            scripts::PROVIDER => scripts::invocation(root, name, args),
+            config::PROVIDER => {
+                config::invocation(&self.workspace_root, &config, name, args, &options.inputs)
+            }
             other => Err(anyhow!("task provider `{other}` is not supported")),
+        }?;
+        config::apply_override(
+            &self.workspace_root,
+            &config,
+            provider,
+            name,
+            &mut invocation,
+        );
+        if let Some(executable) = &options.shell {
+            shell::select(executable, &mut invocation);
         }
+        if config.uses_version_managers() {
+            toolchain::apply(&self.workspace_root, &mut invocation);
+        }
+        invocation.env.extend(options.env.clone());
+        invocation.timeout = invocation.timeout.or(config.timeout());
+        Ok(invocation)
     }
 
-    fn run_package_script(&self, provider: &str, script: &str) -> Result<String> {
-        let (cmd, args) = match provider {
-            "npm" => ("npm", vec!["run", script]),
-            "yarn" => ("yarn", vec![script]),
-            "pnpm" => ("pnpm", vec!["run", script]),
-            other => (other, vec![script]),
+    /// Ask for the values of the placeholders of the task `name` of
+    /// `provider` that `options` lacks. Placeholders offering choices are
+    /// prompted for; others fall back to their default, or else to a value
+    /// picked from those earlier runs of the task passed.
+    fn resolve_inputs(
+        &self,
+        provider: &str,
+        name: &str,
+        options: &mut RunOptions,
+        ctx: &CommandContext<'_>,
+    ) -> Result<()> {
+        let inputs = match provider {
+            vscode::PROVIDER => vscode::inputs(&self.workspace_root, name)?,
+            config::PROVIDER => config::params(&config::load(&self.workspace_root)?, name)?,
+            _ => return Ok(()),
         };
-        self.exec_process(cmd, &args)
+        for input in inputs {
+            if options.inputs.contains_key(&input.id) {
+                continue;
+            }
+            // Values earlier runs passed, most recent first.
+            let mut used: Vec<(String, String)> = Vec::new();
+            if matches!(input.kind, vscode::InputKind::Text(None)) {
+                for entry in self.history.entries() {
+                    if entry.provider != provider || entry.name != name {
+                        continue;
+                    }
+                    if let Some(value) = entry.options.inputs.get(&input.id) {
+                        if !used.iter().any(|(_, used)| used == value) {
+                            used.push((value.clone(), value.clone()));
+                        }
+                    }
+                }
+            }
+            let kind = match input.kind {
+                // Editors that cannot ask for text offer the values used
+                // before instead.
+                vscode::InputKind::Text(None) if !self.text_input && !used.is_empty() => {
+                    vscode::InputKind::Pick(std::mem::take(&mut used))
+                }
+                kind => kind,
+            };
+            let message = input
+                .description
+                .unwrap_or_else(|| format!("Value of `{}`", input.id));
+            let value = match kind {
+                vscode::InputKind::Pick(choices) => {
+                    let labels: Vec<String> =
+                        choices.iter().map(|(label, _)| label.clone()).collect();
+                    let picked = ctx.prompt(message, &labels)?.ok_or_else(|| {
+                        anyhow!("no value was picked for `${{input:{}}}`", input.id)
+                    })?;
+                    choices
+                        .into_iter()
+                        .find(|(label, _)| *label == picked)
+                        .map(|(_, value)| value)
+                        .unwrap_or(picked)
+                }
+                vscode::InputKind::Text(Some(default)) => default,
+                vscode::InputKind::Text(None) if self.text_input => {
+                    let last = used.first().map(|(_, value)| value.as_str());
+                    ctx.input(message, last)?.ok_or_else(|| {
+                        anyhow!("no value was entered for `${{input:{}}}`", input.id)
+                    })?
+                }
+                vscode::InputKind::Text(None) => bail!(
+                    "`${{input:{}}}` takes text, which the editor cannot ask for; pass its value \
+                     in `inputs`",
+                    input.id
+                ),
+            };
+            options.inputs.insert(input.id, value);
+        }
+        Ok(())
     }
 
-    fn exec_process(&self, binary: &str, args: &[&str]) -> Result<String> {
-        let output = Command::new(binary)
-            .args(args)
-            .current_dir(&self.workspace_root)
-            .output()
-            .with_context(|| format!("failed to spawn `{binary}`"))?;
+    /// Start the task `name` of `provider` in the background. Its output is
+    /// streamed to a channel per task, so long builds show progress while
+    /// they run. Unless `force` is set, tasks whose inputs are unchanged are
+    /// not run again; their cached result is returned instead.
+    fn start_task(
+        &mut self,
+        provider: &str,
+        name: &str,
+        mut options: RunOptions,
+        force: bool,
+        waiter: &mut Option<Responder>,
+        ctx: &CommandContext<'_>,
+    ) -> Result<Value> {
+        let started = if provider == composite::PROVIDER {
+            self.start_composite(name, &options, ctx)
+        } else {
+            self.resolve_inputs(provider, name, &mut options, ctx)
+                .and_then(|()| self.resolve_task(provider, name, &options))
+                .and_then(|invocation| {
+                    self.start_cached(provider, name, &options, invocation, force, waiter, ctx)
+                })
+        };
+        match started {
+            Ok(response) => {
+                self.last_started = Some((provider.to_string(), name.to_string(), options));
+                Ok(response)
+            }
+            Err(err) => {
+                ctx.show_message(
+                    MessageLevel::Error,
+                    format!(
+                        "task `{}` failed: {err}",
+                        process::label(provider, name, &options)
+                    ),
+                )?;
+                Err(err)
+            }
+        }
+    }
 
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-            Ok(stdout)
+    /// Start `invocation` of the task `name` of `provider`, or return the
+    /// cached result of the task if it declares inputs that are unchanged.
+    /// A started task answers `waiter` with its result once it finished.
+    #[allow(clippy::too_many_arguments)]
+    fn start_cached(
+        &self,
+        provider: &str,
+        name: &str,
+        options: &RunOptions,
+        invocation: Invocation,
+        force: bool,
+        waiter: &mut Option<Responder>,
+        ctx: &CommandContext<'_>,
+    ) -> Result<Value> {
+        let config = config::load(&self.workspace_root)?;
+        let inputs = config::inputs(&config, provider, name);
+        let key = process::label(provider, name, options);
+        let fingerprint = if inputs.is_empty() {
+            None
         } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            Err(anyhow!("task failed: {stderr}"))
+            Some(cache::fingerprint(inputs, &invocation)?)
+        };
+        if let Some(fingerprint) = fingerprint.as_deref().filter(|_| !force) {
+            if let Some(outcome) = self.cache.lookup(&key, fingerprint) {
+                ctx.show_message(
+                    MessageLevel::Info,
+                    format!("task `{key}` is up to date; its inputs did not change"),
+                )?;
+                return Ok(serde_json::to_value(outcome)?);
+            }
+        }
+
+        let record = self.recorder();
+        let cache = self.cache.clone();
+        let running = self.running.clone();
+        // Taken back when the task does not start.
+        let responder = Arc::new(Mutex::new(waiter.take()));
+        let answer = responder.clone();
+        let task = self.running.start(
+            provider,
+            name,
+            options,
+            &invocation,
+            ctx.events(),
+            move |task: &process::RunningTask, status| {
+                record(task, status);
+                let outcome = running.finished(task.id);
+                if let (Some(fingerprint), Some(outcome)) = (&fingerprint, &outcome) {
+                    let _ = cache.store(&key, fingerprint, outcome);
+                }
+                let answer = answer.lock().unwrap_or_else(|err| err.into_inner()).take();
+                if let Some(answer) = answer {
+                    let result = outcome
+                        .ok_or_else(|| anyhow!("the result of task {} was lost", task.id))
+                        .and_then(|outcome| Ok(Some(serde_json::to_value(outcome)?)));
+                    let _ = answer.respond(result);
+                }
+            },
+        );
+        let task = task.inspect_err(|_| {
+            *waiter = responder
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .take();
+        })?;
+        Ok(json!({ "id": task.id, "channel": task.channel() }))
+    }
+
+    /// Run the task `name` of `provider` whenever files change.
+    fn watch_task(
+        &mut self,
+        provider: &str,
+        name: &str,
+        mut options: WatchOptions,
+        ctx: &CommandContext<'_>,
+    ) -> Result<Value> {
+        if provider == composite::PROVIDER {
+            bail!("composite tasks cannot be watched");
         }
+        self.resolve_inputs(provider, name, &mut options.run, ctx)?;
+        let invocation = self.resolve_task(provider, name, &options.run)?;
+        let channel = process::label(provider, name, &options.run);
+        let spec = watch::Spec {
+            provider: provider.to_string(),
+            name: name.to_string(),
+            options: options.run,
+            invocation,
+            include: options.include.unwrap_or_else(|| vec!["**/*".to_string()]),
+            exclude: options.exclude.unwrap_or_else(|| {
+                watch::DEFAULT_EXCLUDE
+                    .iter()
+                    .map(|glob| glob.to_string())
+                    .collect()
+            }),
+            debounce: options
+                .debounce_ms
+                .map_or(watch::DEFAULT_DEBOUNCE, Duration::from_millis),
+        };
+        let id = self.watches.start(
+            &self.workspace_root,
+            spec,
+            self.running.clone(),
+            ctx.events(),
+            self.recorder(),
+        )?;
+        Ok(json!({ "id": id, "channel": channel }))
     }
+
+    /// Run the task `name` of `provider` in a terminal of the editor, which
+    /// gives interactive tasks a TTY. The editor owns the process, so the
+    /// task is neither timed out nor recorded.
+    fn run_in_terminal(
+        &mut self,
+        provider: &str,
+        name: &str,
+        mut options: RunOptions,
+        ctx: &CommandContext<'_>,
+    ) -> Result<Value> {
+        if !self.run_in_terminal {
+            bail!("the editor cannot run tasks in a terminal");
+        }
+        if provider == composite::PROVIDER {
+            bail!("composite tasks cannot run in a terminal");
+        }
+        self.resolve_inputs(provider, name, &mut options, ctx)?;
+        let invocation = self.resolve_task(provider, name, &options)?;
+        let process_id = ctx.run_in_terminal(
+            process::label(provider, name, &options),
+            invocation.program,
+            &invocation.args,
+            Some(&invocation.cwd),
+            &invocation.env.into_iter().collect(),
+        )?;
+        Ok(json!({ "process_id": process_id }))
+    }
+
+    /// Process the task `name` of `provider` would start, without starting
+    /// it; the processes of every task of a composite task.
+    fn dry_run(
+        &self,
+        provider: &str,
+        name: &str,
+        mut options: RunOptions,
+        ctx: &CommandContext<'_>,
+    ) -> Result<Value> {
+        if provider != composite::PROVIDER {
+            self.resolve_inputs(provider, name, &mut options, ctx)?;
+            let invocation = self.resolve_task(provider, name, &options)?;
+            return Ok(serde_json::to_value(process::Plan::new(
+                provider, name, &options, invocation,
+            ))?);
+        }
+        let (_, jobs) = self.resolve_composite(name, &options)?;
+        let plans: Vec<_> = jobs
+            .into_iter()
+            .map(|(job, invocation)| {
+                process::Plan::new(&job.provider, &job.name, &RunOptions::default(), invocation)
+            })
+            .collect();
+        Ok(json!({
+            "provider": composite::PROVIDER,
+            "name": name,
+            "tasks": plans,
+        }))
+    }
+
+    /// Provider, name and options of the task `helix.task.run` and
+    /// `helix.task.run_in_terminal` refer to; the default task without
+    /// arguments.
+    fn run_arguments(&self, arguments: &[Value]) -> Result<(String, String, RunOptions)> {
+        if arguments.is_empty() {
+            let config = config::load(&self.workspace_root)?;
+            let (provider, name) = config::default_task(&config)?;
+            return Ok((provider, name, RunOptions::default()));
+        }
+        let (provider, name, payload) = task_arguments(arguments)?;
+        let options: RunOptions = serde_json::from_value(payload.clone())
+            .context("`args` must be an array of strings, `env` and `inputs` objects of strings")?;
+        Ok((provider.to_string(), name.to_string(), options))
+    }
+
+    /// Configuration and the resolved tasks of the composite task `name`.
+    fn resolve_composite(
+        &self,
+        name: &str,
+        options: &RunOptions,
+    ) -> Result<(config::Config, Vec<(composite::Job, Invocation)>)> {
+        if !options.args.is_empty() || !options.inputs.is_empty() {
+            bail!("composite tasks do not take arguments");
+        }
+        let config = config::load(&self.workspace_root)?;
+        let jobs = composite::plan(&config, name)?
+            .into_iter()
+            .map(|job| {
+                let options = RunOptions {
+                    env: options.env.clone(),
+                    ..Default::default()
+                };
+                let invocation = self.resolve_task(&job.provider, &job.name, &options)?;
+                Ok((job, invocation))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok((config, jobs))
+    }
+
+    /// Start the tasks of the composite task `name`. Every task is resolved
+    /// before the first one starts.
+    fn start_composite(
+        &self,
+        name: &str,
+        options: &RunOptions,
+        ctx: &CommandContext<'_>,
+    ) -> Result<Value> {
+        let (config, jobs) = self.resolve_composite(name, options)?;
+        let tasks = composite::describe(&jobs);
+        composite::execute(
+            name,
+            jobs,
+            composite::concurrency(&config),
+            self.running.clone(),
+            ctx.events(),
+            self.recorder(),
+        );
+        Ok(json!({
+            "channel": format!("{}:{name}", composite::PROVIDER),
+            "tasks": tasks,
+        }))
+    }
+
+    /// Callback recording tasks in the history once they exited.
+    fn recorder(&self) -> impl Fn(&process::RunningTask, ExitStatus) + Clone + Send + 'static {
+        let history = self.history.clone();
+        move |task, status| {
+            let started_at = SystemTime::now()
+                .checked_sub(task.duration())
+                .and_then(|started| started.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_secs());
+            let _ = history.record(history::Entry {
+                provider: task.provider.clone(),
+                name: task.name.clone(),
+                options: task.options.clone(),
+                started_at,
+                duration_ms: process::millis(task.duration()),
+                exit_code: status.code(),
+                cancelled: task.is_cancelled(),
+                timed_out: task.is_timed_out(),
+            });
+        }
+    }
+}
+
+/// Wrapper script `wrapper` of the project when it has one, `binary` looked
+/// up on `PATH` otherwise.
+fn local_or_global(root: &Path, wrapper: &str, binary: &str) -> String {
+    let wrapper = root.join(wrapper);
+    if wrapper.is_file() {
+        wrapper.to_string_lossy().into_owned()
+    } else {
+        binary.to_string()
+    }
+}
+
+/// Id of the task or watch the first argument refers to, given as `{ id }`
+/// or a bare number.
+fn task_id(arguments: &[Value]) -> Result<u64> {
+    arguments
+        .first()
+        .and_then(|payload| payload.get("id").unwrap_or(payload).as_u64())
+        .ok_or_else(|| anyhow!("expected arguments {{ id: number }}"))
+}
+
+/// Provider and name of the task the first argument refers to, and the
+/// argument itself.
+fn task_arguments(arguments: &[Value]) -> Result<(&str, &str, &Value)> {
+    let payload = arguments
+        .first()
+        .ok_or_else(|| anyhow!("expected arguments {{ provider: string, name: string }}"))?;
+    let provider = payload
+        .get("provider")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("missing `provider` field"))?;
+    let name = payload
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("missing `name` field"))?;
+    Ok((provider, name, payload))
 }
 
 impl Plugin for TaskRunnerPlugin {
@@ -161,6 +741,15 @@ fn name(&self) -> &'static str {
         "task-runner"
     }
 
+    fn version(&self) -> Option<&'static str> {
+        Some(env!("CARGO_PKG_VERSION"))
+    }
+
+    fn shutdown(&mut self) {
+        self.watches.stop_all();
+        self.running.shutdown();
+    }
+
     fn initialize(
         &mut self,
         ctx: &mut InitializeContext,
@@ -171,8 +760,47 @@ fn initialize(
                 .with_description("Enumerate runnable tasks discovered in the current workspace"),
         )?;
         registrar.register_command(
-            PluginCommand::new("helix.task.run", "Run project task")
-                .with_description("Execute a task by provider and name"),
+            PluginCommand::new("helix.task.run", "Run project task").with_description(
+                "Execute a task by provider and name, or the default task of the workspace; \
+                 with `wait`, return its exit code, duration and output once it finished; \
+                 with `dry_run`, return the program, arguments, directory and environment \
+                 it would run with instead; with `force`, run it even if its inputs are unchanged",
+            ),
+        )?;
+        self.run_in_terminal = ctx.capabilities().run_in_terminal;
+        self.text_input = ctx.capabilities().input;
+        if self.run_in_terminal {
+            registrar.register_command(
+                PluginCommand::new("helix.task.run_in_terminal", "Run project task in terminal")
+                    .with_description(
+                        "Run a task interactively in a terminal of the editor, e.g. a REPL",
+                    ),
+            )?;
+        }
+        registrar.register_command(
+            PluginCommand::new("helix.task.cancel", "Cancel running task")
+                .with_description("Terminate a task started by helix.task.run"),
+        )?;
+        registrar.register_command(
+            PluginCommand::new("helix.task.result", "Show task result").with_description(
+                "Exit code, duration and output of a task started by helix.task.run",
+            ),
+        )?;
+        registrar.register_command(
+            PluginCommand::new("helix.task.rerun_last", "Rerun last task")
+                .with_description("Run the task run last in this workspace again"),
+        )?;
+        registrar.register_command(
+            PluginCommand::new("helix.task.history", "Show task history")
+                .with_description("List the tasks run in this workspace, most recent first"),
+        )?;
+        registrar.register_command(
+            PluginCommand::new("helix.task.watch", "Watch project task")
+                .with_description("Run a task again whenever matching files change"),
+        )?;
+        registrar.register_command(
+            PluginCommand::new("helix.task.unwatch", "Stop watching task")
+                .with_description("Stop a watch started by helix.task.watch"),
         )?;
 
         if !self.workspace_root.exists() {
@@ -195,40 +823,88 @@ fn execute(
             "helix.task.list" => {
                 let tasks = self.discover_tasks()?;
                 let response = serde_json::to_value(tasks)?;
+                ctx.cache_result_for(TASK_LIST_CACHE_TTL);
                 Ok(Some(response))
             }
             "helix.task.run" => {
-                if arguments.is_empty() {
-                    return Err(anyhow!(
-                        "expected arguments {{ provider: string, name: string }}"
-                    ));
+                let (provider, name, options) = self.run_arguments(&arguments)?;
+                let flag = |key: &str| {
+                    arguments
+                        .first()
+                        .and_then(|payload| payload.get(key))
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false)
+                };
+                if flag("dry_run") {
+                    return self.dry_run(&provider, &name, options, ctx).map(Some);
+                }
+                let wait = flag("wait");
+                if wait && provider == composite::PROVIDER {
+                    bail!("composite tasks cannot be waited for");
+                }
+                // A task that starts answers once it finished, leaving the
+                // plugin free to handle other commands meanwhile.
+                let mut waiter = if wait {
+                    Some(ctx.respond_later()?)
+                } else {
+                    None
+                };
+                let started =
+                    self.start_task(&provider, &name, options, flag("force"), &mut waiter, ctx);
+                match waiter {
+                    Some(waiter) => {
+                        waiter.respond(started.map(Some))?;
+                        Ok(None)
+                    }
+                    None => started.map(Some),
                 }
+            }
+            "helix.task.run_in_terminal" => {
+                let (provider, name, options) = self.run_arguments(&arguments)?;
+                self.run_in_terminal(&provider, &name, options, ctx)
+                    .map(Some)
+            }
+            "helix.task.watch" => {
+                let (provider, name, payload) = task_arguments(&arguments)?;
+                let options: WatchOptions =
+                    serde_json::from_value(payload.clone()).context("invalid watch options")?;
 
-                let payload = &arguments[0];
-                let provider = payload
-                    .get("provider")
-                    .and_then(Value::as_str)
-                    .ok_or_else(|| anyhow!("missing `provider` field"))?;
-                let name = payload
-                    .get("name")
-                    .and_then(Value::as_str)
-                    .ok_or_else(|| anyhow!("missing `name` field"))?;
-
-                match self.run_task(provider, name) {
-                    Ok(stdout) => {
-                        ctx.show_message(
-                            MessageLevel::Info,
-                            format!("task `{provider}:{name}` completed"),
-                        )?;
-                        Ok(Some(json!({ "stdout": stdout })))
+                self.watch_task(provider, name, options, ctx).map(Some)
+            }
+            "helix.task.unwatch" => {
+                let id = task_id(&arguments)?;
+                self.watches.stop(id)?;
+                Ok(None)
+            }
+            "helix.task.rerun_last" => {
+                let (provider, name, options) = match self.last_started.clone() {
+                    Some(last) => last,
+                    None => {
+                        let entry =
+                            self.history.entries().into_iter().next().ok_or_else(|| {
+                                anyhow!("no task has been run in this workspace yet")
+                            })?;
+                        (entry.provider, entry.name, entry.options)
                     }
-                    Err(err) => {
-                        ctx.show_message(
-                            MessageLevel::Error,
-                            format!("task `{provider}:{name}` failed: {err}"),
-                        )?;
-                        Err(err)
+                };
+                self.start_task(&provider, &name, options, false, &mut None, ctx)
+                    .map(Some)
+            }
+            "helix.task.history" => Ok(Some(serde_json::to_value(self.history.entries())?)),
+            "helix.task.cancel" => {
+                let id = task_id(&arguments)?;
+                Ok(Some(serde_json::to_value(self.running.cancel(id)?)?))
+            }
+            "helix.task.result" => {
+                let id = task_id(&arguments)?;
+                match self.running.finished(id) {
+                    Some(outcome) => Ok(Some(serde_json::to_value(outcome)?)),
+                    None if self.running.is_running(id) => {
+                        Ok(Some(json!({ "id": id, "running": true })))
                     }
+                    None => Err(anyhow!(
+                        "no result of task {id}; only those of recently finished tasks are kept"
+                    )),
                 }
             }
             _ => Err(anyhow!("unknown command `{command}`")),
@@ -238,4 +914,4 @@ fn execute(
 
 fn main() -> Result<()> {
     run(TaskRunnerPlugin::new()?)
-}
\ No newline at end of file
+}
@@ -0,0 +1,75 @@
+//! Maven lifecycle phases and the profiles declared in `pom.xml`.
+
+use crate::{local_or_global, Invocation, Task};
+use anyhow::{Context, Result};
+use std::{fs, path::Path};
+
+pub const PROVIDER: &str = "maven";
+
+/// Phases of the default and clean lifecycles worth running on their own.
+const PHASES: &[(&str, &str)] = &[
+    ("clean", "Remove the files generated by previous builds"),
+    ("validate", "Validate the project is correct"),
+    ("compile", "Compile the source code"),
+    ("test", "Run the unit tests"),
+    ("package", "Package the compiled code"),
+    ("verify", "Run the integration tests and checks"),
+    ("install", "Install the package into the local repository"),
+    ("deploy", "Copy the package to the remote repository"),
+];
+
+/// Ids of the profiles declared in `pom`.
+fn profiles(pom: &str) -> Result<Vec<String>> {
+    let document = roxmltree::Document::parse(pom)?;
+    Ok(document
+        .descendants()
+        .filter(|node| {
+            node.has_tag_name("profile")
+                && node
+                    .parent()
+                    .is_some_and(|parent| parent.has_tag_name("profiles"))
+        })
+        .filter_map(|profile| {
+            let id = profile.children().find(|node| node.has_tag_name("id"))?;
+            Some(id.text()?.trim().to_string())
+        })
+        .collect())
+}
+
+pub fn discover(root: &Path) -> Result<Vec<Task>> {
+    let pom = root.join("pom.xml");
+    if !pom.exists() {
+        return Ok(Vec::new());
+    }
+    let content =
+        fs::read_to_string(&pom).with_context(|| format!("failed to read {}", pom.display()))?;
+    let profiles =
+        profiles(&content).with_context(|| format!("failed to parse {}", pom.display()))?;
+
+    let phases = PHASES.iter().map(|(phase, description)| Task {
+        name: phase.to_string(),
+        provider: PROVIDER.to_string(),
+        command: format!("mvn {phase}"),
+        detail: Some(description.to_string()),
+        ..Default::default()
+    });
+    let profiles = profiles.into_iter().map(|profile| Task {
+        name: format!("package -P {profile}"),
+        provider: PROVIDER.to_string(),
+        command: format!("mvn package -P {profile}"),
+        detail: Some(format!("Package with the `{profile}` profile")),
+        ..Default::default()
+    });
+    Ok(phases.chain(profiles).collect())
+}
+
+/// Process running `name`, which holds the phases and options passed to
+/// Maven, e.g. `package -P release`, followed by `extra` arguments.
+pub fn invocation(root: &Path, name: &str, extra: &[String]) -> Invocation {
+    let program = local_or_global(root, if cfg!(windows) { "mvnw.cmd" } else { "mvnw" }, "mvn");
+    let args = name
+        .split_whitespace()
+        .map(str::to_string)
+        .chain(extra.iter().cloned());
+    Invocation::new(program, args, root)
+}
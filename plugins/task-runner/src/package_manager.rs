@@ -0,0 +1,109 @@
+//! Package manager running the scripts of `package.json` files.
+//!
+//! The `packageManager` field of the package, then of the packages above it
+//! up to the workspace root, names the manager. Without one, the lockfile
+//! or configuration file found first on that way decides, falling back to
+//! npm.
+
+use crate::Invocation;
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+/// Providers of package scripts. Scripts are run by the manager detected
+/// for their package whichever of them a task names.
+pub const PROVIDERS: &[&str] = &["npm", "yarn", "pnpm", "bun"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageManager {
+    Npm,
+    Yarn,
+    Pnpm,
+    Bun,
+}
+
+/// Lockfiles and configuration files used by a single manager.
+const MARKERS: &[(&str, PackageManager)] = &[
+    ("pnpm-lock.yaml", PackageManager::Pnpm),
+    ("yarn.lock", PackageManager::Yarn),
+    ("bun.lock", PackageManager::Bun),
+    ("bun.lockb", PackageManager::Bun),
+    ("bunfig.toml", PackageManager::Bun),
+    ("package-lock.json", PackageManager::Npm),
+    ("npm-shrinkwrap.json", PackageManager::Npm),
+];
+
+#[derive(Debug, Deserialize)]
+struct PackageJson {
+    #[serde(rename = "packageManager")]
+    package_manager: Option<String>,
+}
+
+impl PackageManager {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "npm" => Some(Self::Npm),
+            "yarn" => Some(Self::Yarn),
+            "pnpm" => Some(Self::Pnpm),
+            "bun" => Some(Self::Bun),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Npm => "npm",
+            Self::Yarn => "yarn",
+            Self::Pnpm => "pnpm",
+            Self::Bun => "bun",
+        }
+    }
+
+    /// Manager of the package in `dir`, which lies within `root`.
+    pub fn detect(root: &Path, dir: &Path) -> Self {
+        let dirs = || {
+            dir.ancestors()
+                .take_while(|ancestor| ancestor.starts_with(root))
+        };
+        dirs()
+            .find_map(declared)
+            .or_else(|| {
+                dirs().find_map(|dir| {
+                    MARKERS
+                        .iter()
+                        .find(|(marker, _)| dir.join(marker).is_file())
+                        .map(|&(_, manager)| manager)
+                })
+            })
+            .unwrap_or(Self::Npm)
+    }
+
+    /// Process running `script` of the package in `dir` with `extra`
+    /// arguments.
+    pub fn invocation(self, dir: &Path, script: &str, extra: &[String]) -> Invocation {
+        let mut args = match self {
+            Self::Yarn => vec![script.to_string()],
+            _ => vec!["run".to_string(), script.to_string()],
+        };
+        if !extra.is_empty() {
+            // Only npm needs the separator; the others pass it on to the
+            // script.
+            if self == Self::Npm {
+                args.push("--".to_string());
+            }
+            args.extend_from_slice(extra);
+        }
+        Invocation::new(self.name(), args, dir)
+    }
+}
+
+/// Manager named by the `packageManager` field of the package in `dir`,
+/// e.g. `pnpm@9.1.0`.
+fn declared(dir: &Path) -> Option<PackageManager> {
+    let content = fs::read_to_string(dir.join("package.json")).ok()?;
+    let package: PackageJson = serde_json::from_str(&content).ok()?;
+    let field = package.package_manager?;
+    let name = field
+        .split_once('@')
+        .map_or(field.as_str(), |(name, _)| name);
+    PackageManager::from_name(name)
+}
@@ -0,0 +1,662 @@
+//! Task processes running in the background.
+//!
+//! Commands return as soon as a task started; its output is streamed to an
+//! output channel and a `task_finished` event reports how it ended, which is
+//! when its process exits, even if processes it started in the background
+//! keep running. Tasks running longer than their timeout are stopped along
+//! with every process they started. The results of recently finished tasks,
+//! including their output, are kept for `helix.task.result`. Dry runs report
+//! the process a task would start instead.
+//!
+//! When the plugin shuts down, every task still running is stopped along
+//! with the processes tasks left behind, such as servers started in the
+//! background, so that closing the editor leaves no orphans.
+
+use crate::{shell, Invocation, RunOptions};
+use anyhow::{anyhow, Context, Result};
+use helix_plugin_sdk::{EventSender, MessageLevel};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    env,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Time a task gets to exit after `SIGTERM` before it is killed.
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time tasks get to exit after `SIGTERM` when the plugin shuts down. The
+/// host waits two seconds for the plugin to exit.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Interval at which exits of tasks are polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Time output written by a task just before it exited is still read for.
+/// Processes the task left behind may keep its output open; what they write
+/// later is not part of the result.
+const OUTPUT_GRACE: Duration = Duration::from_millis(200);
+
+/// Bytes of each output stream kept for the result of a task. Older output
+/// is dropped first.
+const MAX_CAPTURED: usize = 1024 * 1024;
+
+/// Number of finished tasks whose results are kept.
+const MAX_FINISHED: usize = 32;
+
+/// How a task ended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Summary {
+    pub id: u64,
+    pub provider: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    /// Exit code of the task; `None` when it was killed by a signal.
+    pub exit_code: Option<i32>,
+    pub status: String,
+    pub cancelled: bool,
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    /// Whether output was dropped from the start of `stdout` or `stderr`.
+    pub truncated: bool,
+}
+
+/// Result of a finished task, with its output.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Outcome {
+    #[serde(flatten)]
+    pub summary: Summary,
+    pub stdout: String,
+    pub stderr: String,
+    /// Whether the result is that of an earlier run with the same inputs,
+    /// see [`crate::cache`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+}
+
+/// Process a task would start, as reported by a dry run.
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    pub provider: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    /// Executable found for the program of the task; `None` when it is not
+    /// installed.
+    pub binary: Option<PathBuf>,
+    /// Program and its arguments.
+    pub argv: Vec<String>,
+    pub cwd: PathBuf,
+    /// Variables set in addition to the environment of the plugin.
+    pub env: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+}
+
+impl Plan {
+    pub fn new(provider: &str, name: &str, options: &RunOptions, invocation: Invocation) -> Self {
+        Self {
+            provider: provider.to_string(),
+            name: name.to_string(),
+            package: options.package.clone(),
+            binary: locate(&invocation),
+            timeout_ms: invocation.timeout.map(millis),
+            argv: std::iter::once(invocation.program)
+                .chain(invocation.args)
+                .collect(),
+            cwd: invocation.cwd,
+            env: invocation.env.into_iter().collect(),
+        }
+    }
+}
+
+/// Executable the process of `invocation` would run, searched for on the
+/// `PATH` it runs with unless its program is a path.
+fn locate(invocation: &Invocation) -> Option<PathBuf> {
+    let program = Path::new(&invocation.program);
+    let candidates: Vec<PathBuf> = if program.components().count() > 1 {
+        vec![invocation.cwd.join(program)]
+    } else {
+        let path = invocation
+            .env
+            .iter()
+            .rev()
+            .find(|(key, _)| key == "PATH")
+            .map(|(_, path)| path.into())
+            .or_else(|| env::var_os("PATH"))?;
+        env::split_paths(&path)
+            .map(|dir| dir.join(program))
+            .collect()
+    };
+    // Windows finds programs by the extensions of `PATHEXT` as well.
+    let extensions: Vec<String> = match env::var("PATHEXT") {
+        Ok(extensions) if cfg!(windows) && program.extension().is_none() => extensions
+            .split(';')
+            .map(|extension| extension.trim_start_matches('.').to_string())
+            .collect(),
+        _ => Vec::new(),
+    };
+    candidates.into_iter().find_map(|candidate| {
+        std::iter::once(candidate.clone())
+            .chain(
+                extensions
+                    .iter()
+                    .map(|extension| candidate.with_extension(extension)),
+            )
+            .find(|path| path.is_file())
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Output of a task kept for its result.
+#[derive(Debug, Default)]
+struct Captured {
+    stdout: String,
+    stderr: String,
+    truncated: bool,
+}
+
+impl Captured {
+    fn push(&mut self, stream: Stream, line: &str) {
+        let buffer = match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        };
+        buffer.push_str(line);
+        // Dropping output in chunks keeps appending cheap for tasks writing
+        // far more than is kept.
+        if buffer.len() > 2 * MAX_CAPTURED {
+            self.truncated |= keep_last(buffer, MAX_CAPTURED);
+        }
+    }
+
+    /// Kept stdout and stderr, and whether any output was dropped.
+    fn take(&mut self) -> (String, String, bool) {
+        self.truncated |= keep_last(&mut self.stdout, MAX_CAPTURED);
+        self.truncated |= keep_last(&mut self.stderr, MAX_CAPTURED);
+        (
+            std::mem::take(&mut self.stdout),
+            std::mem::take(&mut self.stderr),
+            self.truncated,
+        )
+    }
+}
+
+/// Drop the start of `buffer` so that at most `keep` bytes remain. Returns
+/// whether anything was dropped.
+fn keep_last(buffer: &mut String, keep: usize) -> bool {
+    if buffer.len() <= keep {
+        return false;
+    }
+    let mut start = buffer.len() - keep;
+    while !buffer.is_char_boundary(start) {
+        start += 1;
+    }
+    buffer.drain(..start);
+    true
+}
+
+/// `provider:name` reference of a task, followed by its package, if any.
+pub fn label(provider: &str, name: &str, options: &RunOptions) -> String {
+    match &options.package {
+        Some(package) => format!("{provider}:{name} ({package})"),
+        None => format!("{provider}:{name}"),
+    }
+}
+
+/// Task process and the output it wrote so far.
+pub struct RunningTask {
+    pub id: u64,
+    pub provider: String,
+    pub name: String,
+    pub options: RunOptions,
+    child: Mutex<Child>,
+    output: Mutex<Captured>,
+    cancelled: AtomicBool,
+    timed_out: AtomicBool,
+    started: Instant,
+}
+
+impl RunningTask {
+    /// Output channel the output of the task is appended to.
+    pub fn channel(&self) -> String {
+        label(&self.provider, &self.name, &self.options)
+    }
+
+    /// Time since the task started.
+    pub fn duration(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Whether the task was stopped by [`RunningTasks::cancel`].
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Whether the task was stopped for running longer than its timeout.
+    pub fn is_timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::SeqCst)
+    }
+
+    /// Terminate the task, killing it when it does not exit within
+    /// [`TERMINATE_TIMEOUT`].
+    fn stop(&self) -> ExitStatus {
+        let status = if self.terminate() {
+            self.wait_timeout(TERMINATE_TIMEOUT)
+        } else {
+            None
+        };
+        status.unwrap_or_else(|| {
+            self.kill();
+            self.wait()
+        })
+    }
+
+    /// Exit status of the task, once it exited.
+    fn try_wait(&self) -> Option<ExitStatus> {
+        let mut child = self.child.lock().unwrap_or_else(|err| err.into_inner());
+        child.try_wait().ok().flatten()
+    }
+
+    fn wait(&self) -> ExitStatus {
+        loop {
+            if let Some(status) = self.try_wait() {
+                return status;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn wait_timeout(&self, timeout: Duration) -> Option<ExitStatus> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self.try_wait() {
+                return Some(status);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Result of the task, which exited with `status`.
+    fn outcome(&self, status: ExitStatus) -> Outcome {
+        let (stdout, stderr, truncated) = self
+            .output
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take();
+        Outcome {
+            summary: Summary {
+                id: self.id,
+                provider: self.provider.clone(),
+                name: self.name.clone(),
+                package: self.options.package.clone(),
+                exit_code: status.code(),
+                status: status.to_string(),
+                cancelled: self.is_cancelled(),
+                timed_out: self.is_timed_out(),
+                duration_ms: millis(self.duration()),
+                truncated,
+            },
+            stdout,
+            stderr,
+            cached: false,
+        }
+    }
+
+    /// Send `SIGTERM` to the task and everything it started.
+    #[cfg(unix)]
+    fn terminate(&self) -> bool {
+        let child = self.child.lock().unwrap_or_else(|err| err.into_inner());
+        signal_group(child.id(), libc::SIGTERM)
+    }
+
+    /// Send `SIGTERM` to the task. Not available on this platform.
+    #[cfg(not(unix))]
+    fn terminate(&self) -> bool {
+        false
+    }
+
+    fn kill(&self) {
+        let mut child = self.child.lock().unwrap_or_else(|err| err.into_inner());
+        #[cfg(unix)]
+        signal_group(child.id(), libc::SIGKILL);
+        let _ = child.kill();
+    }
+}
+
+/// Send `signal` to the process group `group`, which tasks lead. Returns
+/// whether the group had any processes left.
+#[cfg(unix)]
+fn signal_group(group: u32, signal: libc::c_int) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(group) else {
+        return false;
+    };
+    // SAFETY: `kill` has no memory safety preconditions.
+    unsafe { libc::kill(-pid, signal) == 0 }
+}
+
+/// Tasks started by the plugin that have not finished yet, by id, and the
+/// results of the tasks that finished last.
+#[derive(Default, Clone)]
+pub struct RunningTasks {
+    tasks: Arc<Mutex<BTreeMap<u64, Arc<RunningTask>>>>,
+    finished: Arc<Mutex<VecDeque<Outcome>>>,
+    next_id: Arc<AtomicU64>,
+    /// Process groups of every task started, which may outlive the task.
+    groups: Arc<Mutex<Vec<u32>>>,
+    /// Whether [`RunningTasks::shutdown`] was called.
+    closed: Arc<AtomicBool>,
+}
+
+impl RunningTasks {
+    /// Start `invocation` as the task `name` of `provider`, run with `options`.
+    /// Its output is appended to the channel of the task as it is written
+    /// and `on_exit` is called once it exited.
+    pub fn start(
+        &self,
+        provider: &str,
+        name: &str,
+        options: &RunOptions,
+        invocation: &Invocation,
+        events: EventSender,
+        on_exit: impl FnOnce(&RunningTask, ExitStatus) + Send + 'static,
+    ) -> Result<Arc<RunningTask>> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(anyhow!("the task runner is shutting down"));
+        }
+        let binary = &invocation.program;
+        let mut command = Command::new(binary);
+        shell::add_args(&mut command, invocation);
+        command
+            .current_dir(&invocation.cwd)
+            .envs(invocation.env.iter().map(|(key, value)| (key, value)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // Servers started by tasks are terminated along with them.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("failed to spawn `{binary}`"))?;
+        #[cfg(unix)]
+        self.prune_groups();
+        self.groups
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(child.id());
+
+        let (sender, receiver) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            forward_lines(stdout, Stream::Stdout, sender.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward_lines(stderr, Stream::Stderr, sender);
+        }
+
+        let task = Arc::new(RunningTask {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+            provider: provider.to_string(),
+            name: name.to_string(),
+            options: options.clone(),
+            child: Mutex::new(child),
+            output: Mutex::new(Captured::default()),
+            cancelled: AtomicBool::new(false),
+            timed_out: AtomicBool::new(false),
+            started: Instant::now(),
+        });
+        self.tasks
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(task.id, task.clone());
+
+        if let Some(timeout) = invocation.timeout {
+            let running = task.clone();
+            thread::spawn(move || {
+                if running.wait_timeout(timeout).is_none() {
+                    running.timed_out.store(true, Ordering::SeqCst);
+                    running.stop();
+                }
+            });
+        }
+
+        let tasks = self.clone();
+        let running = task.clone();
+        thread::spawn(move || {
+            let channel = running.channel();
+            let record = |(stream, line): (Stream, String)| {
+                let _ = events.append_output(channel.as_str(), line.as_str());
+                running
+                    .output
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .push(stream, &line);
+            };
+            // The task ends when its process exits, even while processes it
+            // left behind keep its output open.
+            let status = loop {
+                match receiver.recv_timeout(POLL_INTERVAL) {
+                    Ok(line) => record(line),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break running.wait(),
+                }
+                if let Some(status) = running.try_wait() {
+                    break status;
+                }
+            };
+            let deadline = Instant::now() + OUTPUT_GRACE;
+            while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+                match receiver.recv_timeout(remaining) {
+                    Ok(line) => record(line),
+                    Err(_) => break,
+                }
+            }
+            // Only groups with processes left behind need stopping later.
+            let group = running
+                .child
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .id();
+            #[cfg(unix)]
+            let left_behind = signal_group(group, 0);
+            #[cfg(not(unix))]
+            let left_behind = false;
+            if !left_behind {
+                tasks
+                    .groups
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .retain(|&started| started != group);
+            }
+            let outcome = running.outcome(status);
+            report_exit(&running.channel(), &outcome.summary, &events);
+            // The result is kept before the task stops being running, so
+            // waiting for it never misses it.
+            {
+                let mut finished = tasks.finished.lock().unwrap_or_else(|err| err.into_inner());
+                finished.push_back(outcome);
+                if finished.len() > MAX_FINISHED {
+                    finished.pop_front();
+                }
+            }
+            tasks
+                .tasks
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .remove(&running.id);
+            on_exit(&running, status);
+        });
+        Ok(task)
+    }
+
+    /// Terminate the task `id`, killing it when it does not exit within
+    /// [`TERMINATE_TIMEOUT`]. Returns its result.
+    pub fn cancel(&self, id: u64) -> Result<Outcome> {
+        let task = self
+            .tasks
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow!("no running task with id {id}"))?;
+
+        task.cancelled.store(true, Ordering::SeqCst);
+        task.stop();
+        self.wait(id)
+    }
+
+    /// Stop every task for good: tasks still running, and the processes
+    /// left behind by tasks that finished, are sent `SIGTERM` and killed
+    /// unless they exit within [`SHUTDOWN_TIMEOUT`]. No task starts
+    /// afterwards.
+    pub fn shutdown(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let tasks: Vec<_> = self
+            .tasks
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        #[cfg(unix)]
+        {
+            self.prune_groups();
+            let groups = self
+                .groups
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .clone();
+            let mut alive: Vec<u32> = groups
+                .into_iter()
+                .filter(|&group| signal_group(group, libc::SIGTERM))
+                .collect();
+            let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+            while !alive.is_empty() && Instant::now() < deadline {
+                thread::sleep(POLL_INTERVAL);
+                // Signal 0 only checks whether the group has processes left.
+                alive.retain(|&group| signal_group(group, 0));
+            }
+            for group in alive {
+                signal_group(group, libc::SIGKILL);
+            }
+        }
+        for task in tasks {
+            task.kill();
+        }
+    }
+
+    /// Forget the process groups without processes left, so their ids can
+    /// be reused by other processes without being signalled.
+    #[cfg(unix)]
+    fn prune_groups(&self) {
+        self.groups
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .retain(|&group| signal_group(group, 0));
+    }
+
+    /// Result of the task `id` once it finished.
+    pub fn wait(&self, id: u64) -> Result<Outcome> {
+        loop {
+            if let Some(outcome) = self.finished(id) {
+                return Ok(outcome);
+            }
+            if !self.is_running(id) {
+                // It may have finished since.
+                return self
+                    .finished(id)
+                    .ok_or_else(|| anyhow!("no task with id {id}"));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Result of the task `id`, if it is one of the tasks that finished last.
+    pub fn finished(&self, id: u64) -> Option<Outcome> {
+        self.finished
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .find(|outcome| outcome.summary.id == id)
+            .cloned()
+    }
+
+    pub fn is_running(&self, id: u64) -> bool {
+        self.tasks
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .contains_key(&id)
+    }
+}
+
+/// Tell the user and editor integrations how the task `label` ended. The
+/// event leaves out the output, which is available from its channel.
+fn report_exit(label: &str, summary: &Summary, events: &EventSender) {
+    let _ = if summary.cancelled {
+        events.show_message(MessageLevel::Info, format!("task `{label}` was cancelled"))
+    } else if summary.timed_out {
+        events.show_message(
+            MessageLevel::Error,
+            format!(
+                "task `{label}` timed out after {}s and was stopped",
+                summary.duration_ms / 1000
+            ),
+        )
+    } else if summary.exit_code == Some(0) {
+        events.show_message(MessageLevel::Info, format!("task `{label}` completed"))
+    } else {
+        events.show_message(
+            MessageLevel::Error,
+            format!("task `{label}` failed: {}", summary.status),
+        )
+    };
+    if let Ok(payload) = serde_json::to_value(summary) {
+        let _ = events.notify("task_finished", payload);
+    }
+}
+
+pub fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Send the lines read from `reader` to `sender` until either closes.
+fn forward_lines(
+    reader: impl Read + Send + 'static,
+    stream: Stream,
+    sender: mpsc::Sender<(Stream, String)>,
+) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut buffer = Vec::new();
+        loop {
+            buffer.clear();
+            match reader.read_until(b'\n', &mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&buffer).into_owned();
+                    if sender.send((stream, line)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
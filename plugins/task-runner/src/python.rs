@@ -0,0 +1,367 @@
+//! Tasks of Python projects: the scripts of poetry, pdm and hatch declared in
+//! `pyproject.toml` (or `hatch.toml`), tox environments of `tox.ini` and nox
+//! sessions of `noxfile.py`.
+//!
+//! Each tool is a provider of its own, running its tasks the way it would be
+//! run from a shell, e.g. `pdm run lint` or `tox -e py312`.
+
+use crate::{Invocation, Task};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+pub const POETRY: &str = "poetry";
+pub const PDM: &str = "pdm";
+pub const HATCH: &str = "hatch";
+pub const TOX: &str = "tox";
+pub const NOX: &str = "nox";
+
+/// Providers of Python tasks.
+pub const PROVIDERS: &[&str] = &[POETRY, PDM, HATCH, TOX, NOX];
+
+/// Environment of hatch whose scripts are run without naming it.
+const HATCH_DEFAULT_ENV: &str = "default";
+
+#[derive(Debug, Default, Deserialize)]
+struct PyProject {
+    #[serde(default)]
+    tool: Tools,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Tools {
+    poetry: Option<Scripts>,
+    pdm: Option<Scripts>,
+    hatch: Option<HatchConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Scripts {
+    #[serde(default)]
+    scripts: BTreeMap<String, toml::Value>,
+}
+
+/// `[tool.hatch]` of `pyproject.toml`, or the whole of `hatch.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct HatchConfig {
+    #[serde(default)]
+    envs: BTreeMap<String, Scripts>,
+}
+
+fn task(provider: &str, name: String, command: String, detail: Option<String>) -> Task {
+    Task {
+        name,
+        provider: provider.to_string(),
+        command,
+        detail,
+        ..Default::default()
+    }
+}
+
+fn read_toml<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Option<T>> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Ok(None);
+    };
+    toml::from_str(&content)
+        .map(Some)
+        .with_context(|| format!("failed to parse {}", path.display()))
+}
+
+pub fn discover(root: &Path) -> Result<Vec<Task>> {
+    let mut tasks = Vec::new();
+    let pyproject: PyProject = read_toml(&root.join("pyproject.toml"))?.unwrap_or_default();
+    if let Some(poetry) = pyproject.tool.poetry {
+        tasks.extend(poetry_scripts(poetry));
+    }
+    if let Some(pdm) = pyproject.tool.pdm {
+        tasks.extend(pdm_scripts(pdm));
+    }
+    // hatch.toml takes precedence over the table in pyproject.toml.
+    let hatch = match read_toml(&root.join("hatch.toml"))? {
+        Some(hatch) => Some(hatch),
+        None => pyproject.tool.hatch,
+    };
+    if let Some(hatch) = hatch {
+        tasks.extend(hatch_scripts(hatch));
+    }
+    let tox = root.join("tox.ini");
+    if let Ok(content) = fs::read_to_string(&tox) {
+        tasks.extend(tox_envs(&content));
+    }
+    let noxfile = root.join("noxfile.py");
+    if let Ok(content) = fs::read_to_string(&noxfile) {
+        tasks.extend(nox_sessions(&content));
+    }
+    Ok(tasks)
+}
+
+/// Entry points of `[tool.poetry.scripts]`, e.g. `serve = "app.main:run"`.
+fn poetry_scripts(poetry: Scripts) -> impl Iterator<Item = Task> {
+    poetry.scripts.into_iter().map(|(name, script)| {
+        let target = match &script {
+            toml::Value::String(target) => Some(target.clone()),
+            toml::Value::Table(table) => table
+                .get("callable")
+                .or_else(|| table.get("reference"))
+                .and_then(toml::Value::as_str)
+                .map(str::to_string),
+            _ => None,
+        };
+        let command = format!("poetry run {name}");
+        task(POETRY, name, command, target)
+    })
+}
+
+/// Scripts of `[tool.pdm.scripts]`, which are commands, or tables running a
+/// command, a shell line, a Python callable or a composite of other scripts.
+fn pdm_scripts(pdm: Scripts) -> impl Iterator<Item = Task> {
+    pdm.scripts
+        .into_iter()
+        // `_` holds options shared by all scripts.
+        .filter(|(name, _)| name != "_")
+        .map(|(name, script)| {
+            let (command, help) = match &script {
+                toml::Value::Table(table) => {
+                    // `cmd` may be given as its arguments.
+                    let command = match table.get("cmd") {
+                        Some(toml::Value::Array(args)) => args
+                            .iter()
+                            .filter_map(toml::Value::as_str)
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                        _ => ["cmd", "shell", "call", "composite"]
+                            .iter()
+                            .find_map(|key| table.get(*key))
+                            .map(command_line)
+                            .unwrap_or_default(),
+                    };
+                    let help = table
+                        .get("help")
+                        .and_then(toml::Value::as_str)
+                        .map(str::to_string);
+                    (command, help)
+                }
+                script => (command_line(script), None),
+            };
+            task(PDM, name, command, help)
+        })
+}
+
+/// Scripts of every hatch environment. Those of other environments than the
+/// default one are named `env:script`, as `hatch run` expects them.
+fn hatch_scripts(hatch: HatchConfig) -> impl Iterator<Item = Task> {
+    hatch.envs.into_iter().flat_map(|(env, scripts)| {
+        scripts.scripts.into_iter().map(move |(script, command)| {
+            let name = if env == HATCH_DEFAULT_ENV {
+                script
+            } else {
+                format!("{env}:{script}")
+            };
+            let detail = Some(format!("Run in the `{env}` environment"));
+            task(HATCH, name, command_line(&command), detail)
+        })
+    })
+}
+
+/// Command shown for a script given as a string or a list of commands.
+fn command_line(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(command) => command.clone(),
+        toml::Value::Array(commands) => commands
+            .iter()
+            .filter_map(toml::Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" && "),
+        other => other.to_string(),
+    }
+}
+
+/// Environments of the `envlist` of `tox.ini`, followed by those only
+/// declared by a `[testenv:name]` section.
+fn tox_envs(content: &str) -> Vec<Task> {
+    let mut section = String::new();
+    let mut key: Option<String> = None;
+    let mut env_list = String::new();
+    let mut envs: Vec<String> = Vec::new();
+    let mut descriptions = BTreeMap::new();
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            section = name.trim().to_string();
+            key = None;
+            if let Some(env) = section.strip_prefix("testenv:") {
+                envs.extend(expand_factors(env.trim()));
+            }
+            continue;
+        }
+        // Indented lines continue the value of the previous key.
+        if line.starts_with([' ', '\t']) {
+            if section == "tox" && key.as_deref().is_some_and(is_env_list) {
+                env_list.push('\n');
+                env_list.push_str(trimmed);
+            }
+            continue;
+        }
+        let Some((name, value)) = trimmed.split_once('=') else {
+            continue;
+        };
+        let (name, value) = (name.trim(), value.trim());
+        key = Some(name.to_string());
+        if section == "tox" && is_env_list(name) {
+            env_list = value.to_string();
+        } else if name == "description" {
+            if let Some(env) = section.strip_prefix("testenv:") {
+                descriptions.insert(env.trim().to_string(), value.to_string());
+            }
+        }
+    }
+
+    let mut names: Vec<String> = split_env_list(&env_list)
+        .into_iter()
+        .map(str::trim)
+        .filter(|env| !env.is_empty())
+        .flat_map(expand_factors)
+        .collect();
+    for env in envs {
+        if !names.contains(&env) {
+            names.push(env);
+        }
+    }
+    names
+        .into_iter()
+        .map(|name| {
+            let detail = descriptions.get(&name).cloned();
+            let command = format!("tox -e {name}");
+            task(TOX, name, command, detail)
+        })
+        .collect()
+}
+
+fn is_env_list(key: &str) -> bool {
+    matches!(key, "envlist" | "env_list")
+}
+
+/// Entries of an `envlist`, separated by commas outside of factors or by
+/// line breaks.
+fn split_env_list(env_list: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, c) in env_list.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            ',' | '\n' if depth == 0 => {
+                entries.push(&env_list[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    entries.push(&env_list[start..]);
+    entries
+}
+
+/// Environment names generated by the factors of `env`, e.g. `py3{11,12}`
+/// for `py311` and `py312`.
+fn expand_factors(env: &str) -> Vec<String> {
+    let Some((prefix, rest)) = env.split_once('{') else {
+        return vec![env.to_string()];
+    };
+    let Some((alternatives, suffix)) = rest.split_once('}') else {
+        return vec![env.to_string()];
+    };
+    let suffixes = expand_factors(suffix);
+    alternatives
+        .split(',')
+        .flat_map(|alternative| {
+            suffixes
+                .iter()
+                .map(move |suffix| format!("{prefix}{}{suffix}", alternative.trim()))
+        })
+        .collect()
+}
+
+/// Sessions of `noxfile.py`: functions decorated with `@nox.session` or
+/// `@session`, named by their `name` argument if any, with the first line of
+/// their docstring as detail.
+fn nox_sessions(content: &str) -> Vec<Task> {
+    let mut sessions = Vec::new();
+    let mut lines = content.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        if !(line.starts_with("@nox.session") || line.starts_with("@session")) {
+            continue;
+        }
+        // Collect the arguments of the decorator, which may span lines.
+        let mut decorator = line.to_string();
+        while decorator.matches('(').count() > decorator.matches(')').count() {
+            match lines.next() {
+                Some(line) => decorator.push_str(line),
+                None => break,
+            }
+        }
+        let Some(function) = lines.by_ref().find_map(|line| {
+            let signature = line
+                .strip_prefix("async def ")
+                .or_else(|| line.strip_prefix("def "))?;
+            signature
+                .split_once('(')
+                .map(|(name, _)| name.trim().to_string())
+        }) else {
+            break;
+        };
+        let name = keyword_argument(&decorator, "name").unwrap_or(function);
+        let detail = lines
+            .next()
+            .and_then(|line| {
+                line.strip_prefix("\"\"\"")
+                    .or_else(|| line.strip_prefix("'''"))
+            })
+            .map(|doc| {
+                doc.trim_end_matches("\"\"\"")
+                    .trim_end_matches("'''")
+                    .trim()
+            })
+            .filter(|doc| !doc.is_empty())
+            .map(str::to_string);
+        let command = format!("nox -s {name}");
+        sessions.push(task(NOX, name, command, detail));
+    }
+    sessions
+}
+
+/// String literal passed as the keyword argument `key` in `call`.
+fn keyword_argument(call: &str, key: &str) -> Option<String> {
+    call.split([',', '('])
+        .filter_map(|argument| argument.split_once('='))
+        .find(|(name, _)| name.trim() == key)
+        .map(|(_, value)| value.trim().trim_end_matches(')').trim())
+        .and_then(|value| {
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'')?.strip_suffix('\''))
+        })
+        .map(str::to_string)
+}
+
+/// Process running the task `name` of `provider` in `dir` with `extra`
+/// arguments.
+pub fn invocation(provider: &str, dir: &Path, name: &str, extra: &[String]) -> Invocation {
+    let mut args: Vec<String> = match provider {
+        TOX => vec!["-e".to_string(), name.to_string()],
+        NOX => vec!["-s".to_string(), name.to_string()],
+        _ => vec!["run".to_string(), name.to_string()],
+    };
+    if !extra.is_empty() {
+        // tox and nox pass the arguments after `--` on to the environment.
+        if matches!(provider, TOX | NOX) {
+            args.push("--".to_string());
+        }
+        args.extend_from_slice(extra);
+    }
+    Invocation::new(provider, args, dir)
+}
@@ -0,0 +1,101 @@
+//! Rake tasks with a description, as reported by `rake -T`.
+//!
+//! Listing tasks loads the Rakefile and the gems it requires, so the report
+//! is cached until the Rakefile, `rakelib` or the bundle changes.
+
+use crate::{local_or_global, Invocation, Task};
+use anyhow::{bail, Context, Result};
+use std::{fs, path::Path, process::Command, time::SystemTime};
+
+pub const PROVIDER: &str = "rake";
+
+/// File names Rake looks for, in order.
+const FILE_NAMES: &[&str] = &["Rakefile", "rakefile", "Rakefile.rb", "rakefile.rb"];
+
+/// Files and directories whose modification invalidates the cached report,
+/// besides the Rakefile.
+const STAMPED: &[&str] = &["rakelib", "Gemfile.lock"];
+
+/// Tasks reported by Rake and the files they were reported for.
+#[derive(Debug, Default)]
+pub struct TaskCache {
+    stamp: Vec<Option<SystemTime>>,
+    tasks: Option<Vec<Task>>,
+}
+
+fn stamp(root: &Path) -> Vec<Option<SystemTime>> {
+    FILE_NAMES
+        .iter()
+        .chain(STAMPED)
+        .map(|file| {
+            fs::metadata(root.join(file))
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect()
+}
+
+/// Binstub of the project when it has one, `rake` looked up on `PATH`
+/// otherwise.
+fn program(root: &Path) -> String {
+    local_or_global(root, "bin/rake", "rake")
+}
+
+pub fn discover(root: &Path, cache: &mut TaskCache) -> Result<Vec<Task>> {
+    if !FILE_NAMES.iter().any(|name| root.join(name).is_file()) {
+        return Ok(Vec::new());
+    }
+    let stamp = stamp(root);
+    if let Some(tasks) = cache.tasks.as_ref().filter(|_| cache.stamp == stamp) {
+        return Ok(tasks.clone());
+    }
+
+    let program = program(root);
+    let output = Command::new(&program)
+        .arg("-T")
+        .current_dir(root)
+        .output()
+        .with_context(|| format!("failed to spawn `{program}`"))?;
+    if !output.status.success() {
+        bail!(
+            "`{program} -T` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let tasks = parse_listing(&String::from_utf8_lossy(&output.stdout));
+    *cache = TaskCache {
+        stamp,
+        tasks: Some(tasks.clone()),
+    };
+    Ok(tasks)
+}
+
+/// Tasks of a listing whose lines are `rake name[args]  # description`.
+fn parse_listing(listing: &str) -> Vec<Task> {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let line = line.strip_prefix("rake ")?;
+            let (usage, description) = match line.split_once(" # ") {
+                Some((usage, description)) => (usage.trim(), Some(description.trim())),
+                None => (line.trim(), None),
+            };
+            // Arguments are passed in brackets after the name, so they are
+            // not part of it.
+            let name = usage.split_once('[').map_or(usage, |(name, _)| name);
+            Some(Task {
+                name: name.to_string(),
+                provider: PROVIDER.to_string(),
+                command: format!("rake {usage}"),
+                detail: description.map(str::to_string),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Process running the task `name`, passing Rake `extra` arguments.
+pub fn invocation(root: &Path, name: &str, extra: &[String]) -> Invocation {
+    let args = std::iter::once(name.to_string()).chain(extra.iter().cloned());
+    Invocation::new(program(root), args, root)
+}
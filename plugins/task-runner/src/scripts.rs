@@ -0,0 +1,99 @@
+//! Executables of the `scripts/` and `script/` directories of the
+//! workspace, run as they are.
+//!
+//! The first comment of a script, below its shebang, describes it.
+
+use crate::{Invocation, Task};
+use anyhow::{bail, Result};
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::{Component, Path},
+};
+
+pub const PROVIDER: &str = "script";
+
+/// Directories holding the scripts of a project.
+const DIRS: &[&str] = &["scripts", "script"];
+
+/// Lines of a script searched for its description.
+const MAX_DESCRIPTION_LINE: usize = 4;
+
+#[cfg(unix)]
+fn is_executable(_path: &Path, metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path, metadata: &fs::Metadata) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    metadata.is_file() && matches!(extension.as_str(), "exe" | "com" | "bat" | "cmd")
+}
+
+/// First comment of the script at `path`, skipping its shebang.
+fn description(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    BufReader::new(file)
+        .lines()
+        .take(MAX_DESCRIPTION_LINE)
+        .map_while(Result::ok)
+        .filter(|line| !line.starts_with("#!"))
+        .find_map(|line| {
+            let comment = line.strip_prefix('#')?.trim();
+            (!comment.is_empty()).then(|| comment.to_string())
+        })
+}
+
+pub fn discover(root: &Path) -> Result<Vec<Task>> {
+    let mut tasks = Vec::new();
+    for dir in DIRS {
+        let Ok(entries) = fs::read_dir(root.join(dir)) else {
+            continue;
+        };
+        let mut scripts: Vec<_> = entries
+            .flatten()
+            .filter(|entry| {
+                entry
+                    .metadata()
+                    .is_ok_and(|metadata| is_executable(&entry.path(), &metadata))
+            })
+            .collect();
+        scripts.sort_by_key(|entry| entry.file_name());
+        tasks.extend(scripts.into_iter().map(|entry| {
+            let name = format!("{dir}/{}", entry.file_name().to_string_lossy());
+            Task {
+                detail: description(&entry.path()),
+                command: name.clone(),
+                name,
+                provider: PROVIDER.to_string(),
+                ..Default::default()
+            }
+        }));
+    }
+    Ok(tasks)
+}
+
+/// Process running the script `name`, a path relative to `root`, with
+/// `extra` arguments.
+pub fn invocation(root: &Path, name: &str, extra: &[String]) -> Result<Invocation> {
+    let path = Path::new(name);
+    let mut components = path.components();
+    let in_scripts_dir = components.next().is_some_and(|dir| {
+        DIRS.iter()
+            .any(|name| dir == Component::Normal(name.as_ref()))
+    });
+    if !in_scripts_dir || !components.all(|component| matches!(component, Component::Normal(_))) {
+        bail!("`{name}` is not a script of the workspace");
+    }
+    Ok(Invocation::new(
+        root.join(path).to_string_lossy(),
+        extra.iter().cloned(),
+        root,
+    ))
+}
@@ -0,0 +1,269 @@
+//! Running command lines through a shell.
+//!
+//! Tasks given as a command line run through the shell configured for them,
+//! the platform shell by default; other tasks run their process directly.
+//! Either can be changed per task or per run: a shell runs the task as a
+//! command line quoted for that shell, and [`DIRECT`] runs the words of a
+//! command line as a process.
+
+use crate::Invocation;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Shell selection running tasks directly, without a shell.
+pub const DIRECT: &str = "none";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Posix,
+    Cmd,
+    PowerShell,
+}
+
+impl Kind {
+    fn of(executable: &str) -> Self {
+        let name = Path::new(executable)
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match name.as_str() {
+            "cmd" => Self::Cmd,
+            "pwsh" | "powershell" => Self::PowerShell,
+            _ => Self::Posix,
+        }
+    }
+}
+
+/// Shell executable and the arguments preceding the command line.
+#[derive(Debug, Clone)]
+pub struct Shell {
+    executable: String,
+    args: Vec<String>,
+    kind: Kind,
+}
+
+impl Shell {
+    /// Shell `executable`, or the platform shell when unset.
+    pub fn new(executable: Option<&str>) -> Self {
+        let executable = executable.map(str::to_string).unwrap_or_else(|| {
+            if cfg!(windows) {
+                "cmd".to_string()
+            } else {
+                "sh".to_string()
+            }
+        });
+        let kind = Kind::of(&executable);
+        let args = match kind {
+            // `/s` strips the quotes around the command line, see
+            // `add_args`.
+            Kind::Cmd => vec!["/d".to_string(), "/s".to_string(), "/c".to_string()],
+            Kind::PowerShell => vec!["-Command".to_string()],
+            Kind::Posix => vec!["-c".to_string()],
+        };
+        Self {
+            executable,
+            args,
+            kind,
+        }
+    }
+
+    /// Replace the arguments preceding the command line.
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Quote `value` as a single word for the shell, unless it is one
+    /// already.
+    pub fn quote(&self, value: &str) -> String {
+        let plain = |extra: &str| {
+            !value.is_empty()
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_-./:".contains(c) || extra.contains(c))
+        };
+        match self.kind {
+            Kind::Posix if plain("=,+@%") => value.to_string(),
+            Kind::Posix => format!("'{}'", value.replace('\'', r"'\''")),
+            Kind::PowerShell if plain("=+\\") => value.to_string(),
+            Kind::PowerShell => format!("'{}'", value.replace('\'', "''")),
+            Kind::Cmd if plain("+@\\") => value.to_string(),
+            Kind::Cmd => quote_windows(value),
+        }
+    }
+
+    /// Invocation running the command `line` in `cwd`.
+    pub fn invocation(&self, line: String, cwd: impl Into<PathBuf>) -> Invocation {
+        let args = self.args.iter().cloned().chain([line.clone()]);
+        let mut invocation = Invocation::new(&self.executable, args, cwd);
+        invocation.line = Some(line);
+        invocation
+    }
+}
+
+/// Quote `value` the way programs on Windows split their command line.
+/// Backslashes are literal unless they precede a quote.
+fn quote_windows(value: &str) -> String {
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in value.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+/// Invocation running the command line `command` followed by `extra`
+/// arguments in `cwd`, through `executable` or the platform shell, or
+/// directly for [`DIRECT`].
+pub fn line_invocation(
+    executable: Option<&str>,
+    command: &str,
+    extra: &[String],
+    cwd: impl Into<PathBuf>,
+) -> Invocation {
+    if executable == Some(DIRECT) {
+        let mut words = split(command).into_iter().chain(extra.iter().cloned());
+        let program = words.next().unwrap_or_default();
+        return Invocation::new(program, words, cwd);
+    }
+    let shell = Shell::new(executable);
+    let line = std::iter::once(command.to_string())
+        .chain(extra.iter().map(|arg| shell.quote(arg)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    shell.invocation(line, cwd)
+}
+
+/// Run `invocation` through the shell `executable` instead of its current
+/// one, or directly for [`DIRECT`], in which case a command line is split
+/// into words.
+pub fn select(executable: &str, invocation: &mut Invocation) {
+    let line = invocation.line.take();
+    if executable == DIRECT {
+        if let Some(line) = line {
+            let mut words = split(&line).into_iter();
+            invocation.program = words.next().unwrap_or_default();
+            invocation.args = words.collect();
+        }
+        return;
+    }
+    let shell = Shell::new(Some(executable));
+    let line = line.unwrap_or_else(|| {
+        std::iter::once(&invocation.program)
+            .chain(&invocation.args)
+            .map(|word| shell.quote(word))
+            .collect::<Vec<_>>()
+            .join(" ")
+    });
+    invocation.program = shell.executable;
+    invocation.args = shell.args;
+    invocation.args.push(line.clone());
+    invocation.line = Some(line);
+}
+
+/// Words of the command `line`, with quotes removed as a POSIX shell would.
+/// On Windows, backslashes only escape quotes.
+pub fn split(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' if matches!(chars.peek(), Some('"' | '\\')) => {
+                            word.extend(chars.next());
+                        }
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' if !cfg!(windows) || chars.peek() == Some(&'"') => {
+                word.get_or_insert_with(String::new).extend(chars.next());
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// Add the arguments of `invocation` to `command`. cmd does not split its
+/// command line like other programs, so the command line it runs is passed
+/// verbatim.
+pub fn add_args(command: &mut Command, invocation: &Invocation) {
+    #[cfg(windows)]
+    if let (Some(line), Some((_, args))) = (&invocation.line, invocation.args.split_last()) {
+        if Kind::of(&invocation.program) == Kind::Cmd {
+            use std::os::windows::process::CommandExt;
+            command.args(args).raw_arg(format!("\"{line}\""));
+            return;
+        }
+    }
+    command.args(&invocation.args);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_posix_words() {
+        let shell = Shell::new(Some("/bin/bash"));
+        assert_eq!(shell.quote("src/main.rs"), "src/main.rs");
+        assert_eq!(shell.quote("--name=a,b+c@d%"), "--name=a,b+c@d%");
+        assert_eq!(shell.quote(""), "''");
+        assert_eq!(shell.quote("two words"), "'two words'");
+        assert_eq!(shell.quote("it's"), r"'it'\''s'");
+        assert_eq!(shell.quote("$HOME"), "'$HOME'");
+    }
+
+    #[test]
+    fn quotes_powershell_words() {
+        let shell = Shell::new(Some("pwsh.exe"));
+        assert_eq!(shell.quote(r"C:\src\main.rs"), r"C:\src\main.rs");
+        assert_eq!(shell.quote("a=b+c"), "a=b+c");
+        assert_eq!(shell.quote(""), "''");
+        assert_eq!(shell.quote("it's here"), "'it''s here'");
+        assert_eq!(shell.quote("a,b"), "'a,b'");
+    }
+
+    #[test]
+    fn quotes_cmd_words() {
+        let shell = Shell::new(Some("cmd.exe"));
+        assert_eq!(shell.quote(r"C:\src\main.rs"), r"C:\src\main.rs");
+        assert_eq!(shell.quote("a+b@c"), "a+b@c");
+        assert_eq!(shell.quote(""), r#""""#);
+        assert_eq!(shell.quote("two words"), r#""two words""#);
+        assert_eq!(shell.quote(r"C:\Program Files\"), r#""C:\Program Files\\""#);
+        assert_eq!(shell.quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(shell.quote(r#"a\"b c"#), r#""a\\\"b c""#);
+        assert_eq!(shell.quote(r"a\b c"), r#""a\b c""#);
+    }
+}
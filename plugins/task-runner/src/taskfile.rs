@@ -0,0 +1,216 @@
+//! Tasks of go-task `Taskfile.yml` files.
+//!
+//! Taskfiles and their includes are parsed directly. When that is ambiguous,
+//! e.g. for templated or remote includes, the tasks are listed by
+//! `task --list-all` instead.
+
+use crate::{Invocation, Task};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{de::IgnoredAny, Deserialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+pub const PROVIDER: &str = "task";
+
+/// File names go-task looks for, in order.
+const FILE_NAMES: &[&str] = &[
+    "Taskfile.yml",
+    "taskfile.yml",
+    "Taskfile.yaml",
+    "taskfile.yaml",
+    "Taskfile.dist.yml",
+    "taskfile.dist.yml",
+    "Taskfile.dist.yaml",
+    "taskfile.dist.yaml",
+];
+
+/// Nesting depth of includes after which they are assumed to be cyclic.
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+#[derive(Debug, Default, Deserialize)]
+struct Taskfile {
+    #[serde(default)]
+    includes: BTreeMap<String, Include>,
+    #[serde(default)]
+    tasks: BTreeMap<String, TaskConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Include {
+    Path(String),
+    Config {
+        taskfile: String,
+        #[serde(default)]
+        optional: bool,
+        #[serde(default)]
+        internal: bool,
+        #[serde(default)]
+        flatten: bool,
+    },
+}
+
+/// Task definition; tasks may also be written as a single command or a
+/// list of commands.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TaskConfig {
+    Full {
+        desc: Option<String>,
+        summary: Option<String>,
+        #[serde(default)]
+        internal: bool,
+    },
+    Short(IgnoredAny),
+}
+
+/// Listing printed by `task --list-all --json`.
+#[derive(Debug, Deserialize)]
+struct Listing {
+    tasks: Vec<ListedTask>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListedTask {
+    name: String,
+    #[serde(default)]
+    desc: String,
+}
+
+/// Taskfile go-task would use in `dir`.
+fn find(dir: &Path) -> Option<PathBuf> {
+    FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+pub fn discover(root: &Path) -> Result<Vec<Task>> {
+    let Some(path) = find(root) else {
+        return Ok(Vec::new());
+    };
+    let mut tasks = Vec::new();
+    match collect(&path, "", 0, &mut tasks) {
+        Ok(()) => Ok(tasks),
+        // Let go-task resolve what could not be parsed.
+        Err(err) => list_all(root)
+            .map_err(|fallback| anyhow!("{err:#}; `task --list-all` failed as well: {fallback:#}")),
+    }
+}
+
+/// Add the public tasks of the Taskfile at `path` and its includes to
+/// `tasks`, prefixing their names with `namespace`.
+fn collect(path: &Path, namespace: &str, depth: usize, tasks: &mut Vec<Task>) -> Result<()> {
+    if depth > MAX_INCLUDE_DEPTH {
+        bail!(
+            "includes of {} nest deeper than {MAX_INCLUDE_DEPTH} levels",
+            path.display()
+        );
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let taskfile: Taskfile = serde_yaml::from_str(&content)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+
+    for (name, task) in taskfile.tasks {
+        let (desc, summary, internal) = match task {
+            TaskConfig::Full {
+                desc,
+                summary,
+                internal,
+            } => (desc, summary, internal),
+            TaskConfig::Short(_) => (None, None, false),
+        };
+        if internal {
+            continue;
+        }
+        tasks.push(Task {
+            name: format!("{namespace}{name}"),
+            provider: PROVIDER.to_string(),
+            command: format!("task {namespace}{name}"),
+            detail: desc.or(summary).map(|detail| detail.trim().to_string()),
+            ..Default::default()
+        });
+    }
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for (name, include) in taskfile.includes {
+        let (taskfile, optional, internal, flatten) = match include {
+            Include::Path(taskfile) => (taskfile, false, false, false),
+            Include::Config {
+                taskfile,
+                optional,
+                internal,
+                flatten,
+            } => (taskfile, optional, internal, flatten),
+        };
+        if internal {
+            continue;
+        }
+        if taskfile.contains("{{") || taskfile.contains("://") {
+            bail!(
+                "include `{name}` of {} is templated or remote",
+                path.display()
+            );
+        }
+        let target = dir.join(&taskfile);
+        let included = if target.is_dir() {
+            find(&target)
+        } else {
+            Some(target).filter(|target| target.is_file())
+        };
+        let Some(included) = included else {
+            if optional {
+                continue;
+            }
+            bail!("include `{name}` of {} was not found", path.display());
+        };
+        let namespace = if flatten {
+            namespace.to_string()
+        } else {
+            format!("{namespace}{name}:")
+        };
+        collect(&included, &namespace, depth + 1, tasks)?;
+    }
+    Ok(())
+}
+
+/// Tasks listed by the `task` binary.
+fn list_all(root: &Path) -> Result<Vec<Task>> {
+    let output = Command::new("task")
+        .args(["--list-all", "--json"])
+        .current_dir(root)
+        .output()
+        .context("failed to spawn `task`")?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let listing: Listing =
+        serde_json::from_slice(&output.stdout).context("failed to parse the task listing")?;
+    Ok(listing
+        .tasks
+        .into_iter()
+        .map(|task| Task {
+            command: format!("task {}", task.name),
+            name: task.name,
+            provider: PROVIDER.to_string(),
+            detail: Some(task.desc).filter(|desc| !desc.is_empty()),
+            ..Default::default()
+        })
+        .collect())
+}
+
+/// Process running the task `name`. Tasks read `extra` arguments from
+/// their `CLI_ARGS` variable.
+pub fn invocation(root: &Path, name: &str, extra: &[String]) -> Invocation {
+    let mut args = vec![name.to_string()];
+    if !extra.is_empty() {
+        args.push("--".to_string());
+        args.extend_from_slice(extra);
+    }
+    Invocation::new("task", args, root)
+}
@@ -0,0 +1,139 @@
+//! Toolchains pinned by version managers.
+//!
+//! Tasks of projects pinning tool versions run with the shims of mise or
+//! asdf, or the Node.js version named by `.nvmrc`, ahead of everything else
+//! on `PATH`. Version files are looked up from the working directory of the
+//! task up to the workspace root.
+
+use crate::Invocation;
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Files pinning tool versions for mise.
+const MISE_FILES: &[&str] = &[
+    "mise.toml",
+    ".mise.toml",
+    "mise.local.toml",
+    ".tool-versions",
+];
+
+/// Files pinning tool versions for asdf.
+const ASDF_FILES: &[&str] = &[".tool-versions"];
+
+/// Aliases of nvm followed before giving up, e.g. `lts/*` to `lts/iron`.
+const MAX_ALIAS_HOPS: usize = 4;
+
+/// Prepend the directories of the pinned toolchains to the `PATH` of
+/// `invocation`.
+pub fn apply(root: &Path, invocation: &mut Invocation) {
+    let dirs = toolchain_dirs(root, &invocation.cwd);
+    if dirs.is_empty() {
+        return;
+    }
+    let path = invocation
+        .env
+        .iter()
+        .rev()
+        .find(|(key, _)| key == "PATH")
+        .map(|(_, path)| OsString::from(path))
+        .or_else(|| env::var_os("PATH"))
+        .unwrap_or_default();
+    let paths = dirs.into_iter().chain(env::split_paths(&path));
+    if let Ok(path) = env::join_paths(paths) {
+        invocation
+            .env
+            .push(("PATH".to_string(), path.to_string_lossy().into_owned()));
+    }
+}
+
+/// Directories to put first on `PATH` for tasks running in `cwd`.
+fn toolchain_dirs(root: &Path, cwd: &Path) -> Vec<PathBuf> {
+    let dirs: Vec<&Path> = cwd
+        .ancestors()
+        .take_while(|dir| dir.starts_with(root))
+        .collect();
+    let pins = |files: &[&str]| {
+        dirs.iter()
+            .any(|dir| files.iter().any(|file| dir.join(file).is_file()))
+    };
+
+    let mut toolchains = Vec::new();
+    if let Some(nvmrc) = dirs
+        .iter()
+        .map(|dir| dir.join(".nvmrc"))
+        .find(|nvmrc| nvmrc.is_file())
+    {
+        toolchains.extend(nvm_bin(&nvmrc));
+    }
+    // mise reads `.tool-versions` as well; asdf is the fallback for it.
+    let mise = pins(MISE_FILES).then(mise_shims).flatten();
+    toolchains.extend(mise.or_else(|| pins(ASDF_FILES).then(asdf_shims).flatten()));
+    toolchains
+}
+
+fn home() -> Option<PathBuf> {
+    env::var_os("HOME").map(PathBuf::from)
+}
+
+/// Shims of mise, if it is installed.
+fn mise_shims() -> Option<PathBuf> {
+    let data_dir = env::var_os("MISE_DATA_DIR")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("XDG_DATA_HOME").map(|dir| PathBuf::from(dir).join("mise")))
+        .or_else(|| home().map(|home| home.join(".local/share/mise")))?;
+    Some(data_dir.join("shims")).filter(|shims| shims.is_dir())
+}
+
+/// Shims of asdf, if it is installed.
+fn asdf_shims() -> Option<PathBuf> {
+    let data_dir = env::var_os("ASDF_DATA_DIR")
+        .map(PathBuf::from)
+        .or_else(|| home().map(|home| home.join(".asdf")))?;
+    Some(data_dir.join("shims")).filter(|shims| shims.is_dir())
+}
+
+/// `bin` directory of the installed Node.js version best matching `nvmrc`.
+fn nvm_bin(nvmrc: &Path) -> Option<PathBuf> {
+    let nvm_dir = env::var_os("NVM_DIR")
+        .map(PathBuf::from)
+        .or_else(|| home().map(|home| home.join(".nvm")))?;
+    let mut version = fs::read_to_string(nvmrc).ok()?.trim().to_string();
+    for _ in 0..MAX_ALIAS_HOPS {
+        let alias = nvm_dir.join("alias").join(&version);
+        match fs::read_to_string(alias) {
+            Ok(target) => version = target.trim().to_string(),
+            Err(_) => break,
+        }
+    }
+
+    let wanted: Vec<u64> = version
+        .trim_start_matches('v')
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect();
+    // `node` and `stable` stand for the latest installed version.
+    if wanted.is_empty() && !matches!(version.as_str(), "node" | "stable") {
+        return None;
+    }
+    fs::read_dir(nvm_dir.join("versions").join("node"))
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let installed: Vec<u64> = name
+                .to_str()?
+                .trim_start_matches('v')
+                .split('.')
+                .map(|part| part.parse().ok())
+                .collect::<Option<_>>()?;
+            installed
+                .starts_with(&wanted)
+                .then(|| (installed, entry.path()))
+        })
+        .max()
+        .map(|(_, dir)| dir.join("bin"))
+}
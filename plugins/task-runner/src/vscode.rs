@@ -0,0 +1,474 @@
+//! Tasks defined in `.vscode/tasks.json`, so teams migrating from VS Code
+//! keep their task definitions.
+//!
+//! `shell`, `process` and `npm` tasks are supported. Tasks of other types are
+//! contributed by VS Code extensions and are skipped.
+
+use crate::{jsonc, package_manager::PackageManager, shell::Shell, Invocation, Task};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    path::{Path, PathBuf, MAIN_SEPARATOR},
+};
+
+pub const PROVIDER: &str = "vscode";
+
+#[derive(Debug, Default, Deserialize)]
+struct TasksFile {
+    #[serde(default)]
+    tasks: Vec<TaskConfig>,
+    /// Defaults for every task.
+    #[serde(default)]
+    options: Options,
+    /// Placeholders tasks refer to as `${input:<id>}`.
+    #[serde(default)]
+    inputs: Vec<InputConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct InputConfig {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    description: Option<String>,
+    default: Option<String>,
+    #[serde(default)]
+    options: Vec<PickOption>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum PickOption {
+    Plain(String),
+    Labelled { label: String, value: String },
+}
+
+/// Placeholder of a task, resolved before the task runs.
+#[derive(Debug, Clone)]
+pub struct Input {
+    pub id: String,
+    pub description: Option<String>,
+    pub kind: InputKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum InputKind {
+    /// Value picked from labelled choices.
+    Pick(Vec<(String, String)>),
+    /// Free text, with its default.
+    Text(Option<String>),
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaskConfig {
+    label: Option<String>,
+    /// Name of the task in version 0.1.0 files.
+    task_name: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    command: Option<Arg>,
+    #[serde(default)]
+    args: Vec<Arg>,
+    /// Script of `npm` tasks.
+    script: Option<String>,
+    /// Directory of the `package.json` of `npm` tasks.
+    path: Option<String>,
+    #[serde(default)]
+    options: Options,
+    problem_matcher: Option<ProblemMatchers>,
+    detail: Option<String>,
+    windows: Option<PlatformConfig>,
+    osx: Option<PlatformConfig>,
+    linux: Option<PlatformConfig>,
+}
+
+/// Properties overridden on one platform.
+#[derive(Debug, Default, Clone, Deserialize)]
+struct PlatformConfig {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    command: Option<Arg>,
+    args: Option<Vec<Arg>>,
+    options: Option<Options>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Arg {
+    Plain(String),
+    /// Argument with explicit quoting, which is left to the shell.
+    Quoted {
+        value: String,
+    },
+}
+
+impl Arg {
+    fn value(&self) -> &str {
+        match self {
+            Self::Plain(value) | Self::Quoted { value } => value,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+struct Options {
+    cwd: Option<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    shell: Option<ShellConfig>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+struct ShellConfig {
+    executable: Option<String>,
+    args: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ProblemMatchers {
+    One(ProblemMatcher),
+    Many(Vec<ProblemMatcher>),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ProblemMatcher {
+    /// Predefined matcher such as `$tsc`.
+    Name(String),
+    /// Inline matcher, named after the matcher it extends or its owner.
+    Inline {
+        base: Option<String>,
+        owner: Option<String>,
+    },
+}
+
+impl ProblemMatcher {
+    fn name(&self) -> Option<&str> {
+        match self {
+            Self::Name(name) => Some(name),
+            Self::Inline { base, owner } => base.as_deref().or(owner.as_deref()),
+        }
+    }
+}
+
+impl TaskConfig {
+    fn label(&self) -> Option<String> {
+        self.label
+            .clone()
+            .or_else(|| self.task_name.clone())
+            .or_else(|| {
+                let script = self.script.as_ref()?;
+                Some(match &self.path {
+                    Some(path) => format!("npm: {script} - {}", path.trim_end_matches('/')),
+                    None => format!("npm: {script}"),
+                })
+            })
+    }
+
+    fn kind(&self) -> &str {
+        self.kind.as_deref().unwrap_or("process")
+    }
+
+    /// Apply the overrides of the current platform.
+    fn for_current_platform(mut self) -> Self {
+        let platform = if cfg!(windows) {
+            self.windows.take()
+        } else if cfg!(target_os = "macos") {
+            self.osx.take()
+        } else {
+            self.linux.take()
+        };
+        let Some(platform) = platform else {
+            return self;
+        };
+        self.kind = platform.kind.or(self.kind);
+        self.command = platform.command.or(self.command);
+        self.args = platform.args.unwrap_or(self.args);
+        if let Some(options) = platform.options {
+            self.options = merge_options(&self.options, options);
+        }
+        self
+    }
+
+    /// Command line shown in task listings, before substitutions.
+    fn display_command(&self) -> String {
+        if self.kind() == "npm" {
+            return format!("npm run {}", self.script.as_deref().unwrap_or_default());
+        }
+        self.command
+            .iter()
+            .chain(&self.args)
+            .map(Arg::value)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Values of the task that variables are substituted in.
+    fn substituted_values(&self) -> impl Iterator<Item = &str> {
+        let options = &self.options;
+        self.command
+            .iter()
+            .chain(&self.args)
+            .map(Arg::value)
+            .chain(self.script.as_deref())
+            .chain(self.path.as_deref())
+            .chain(options.cwd.as_deref())
+            .chain(options.env.values().map(String::as_str))
+    }
+
+    fn problem_matchers(&self) -> Vec<String> {
+        let matchers = match &self.problem_matcher {
+            Some(ProblemMatchers::One(matcher)) => std::slice::from_ref(matcher),
+            Some(ProblemMatchers::Many(matchers)) => matchers.as_slice(),
+            None => &[],
+        };
+        matchers
+            .iter()
+            .filter_map(ProblemMatcher::name)
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Task-level options take precedence over file-level ones.
+fn merge_options(base: &Options, task: Options) -> Options {
+    let mut env = base.env.clone();
+    env.extend(task.env);
+    Options {
+        cwd: task.cwd.or_else(|| base.cwd.clone()),
+        env,
+        shell: task.shell.or_else(|| base.shell.clone()),
+    }
+}
+
+fn tasks_file(root: &Path) -> PathBuf {
+    root.join(".vscode").join("tasks.json")
+}
+
+/// Runnable tasks of `.vscode/tasks.json` by label, with their options
+/// merged in, and the placeholders they may refer to.
+#[derive(Debug, Default)]
+struct Tasks {
+    labelled: Vec<(String, TaskConfig)>,
+    inputs: Vec<InputConfig>,
+}
+
+fn load(root: &Path) -> Result<Tasks> {
+    let path = tasks_file(root);
+    if !path.exists() {
+        return Ok(Default::default());
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    let file: TasksFile = serde_json::from_str(&jsonc::strip(&content))
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+
+    let labelled = file
+        .tasks
+        .into_iter()
+        .map(TaskConfig::for_current_platform)
+        .filter(|task| match task.kind() {
+            "shell" | "process" => task.command.is_some(),
+            "npm" => task.script.is_some(),
+            _ => false,
+        })
+        .filter_map(|mut task| {
+            task.options = merge_options(&file.options, std::mem::take(&mut task.options));
+            Some((task.label()?, task))
+        })
+        .collect();
+    Ok(Tasks {
+        labelled,
+        inputs: file.inputs,
+    })
+}
+
+pub fn discover(root: &Path) -> Result<Vec<Task>> {
+    Ok(load(root)?
+        .labelled
+        .into_iter()
+        .map(|(label, task)| Task {
+            name: label,
+            provider: PROVIDER.to_string(),
+            command: task.display_command(),
+            detail: task.detail.clone(),
+            problem_matchers: task.problem_matchers(),
+            ..Default::default()
+        })
+        .collect())
+}
+
+fn find(root: &Path, label: &str) -> Result<(TaskConfig, Vec<InputConfig>)> {
+    let Tasks { labelled, inputs } = load(root)?;
+    let (_, task) = labelled
+        .into_iter()
+        .find(|(candidate, _)| candidate == label)
+        .ok_or_else(|| {
+            anyhow!(
+                "no task labelled `{label}` in {}",
+                tasks_file(root).display()
+            )
+        })?;
+    Ok((task, inputs))
+}
+
+/// Placeholders the task labelled `label` refers to, in order.
+pub fn inputs(root: &Path, label: &str) -> Result<Vec<Input>> {
+    let (task, configs) = find(root, label)?;
+    let mut inputs: Vec<Input> = Vec::new();
+    for value in task.substituted_values() {
+        for id in value
+            .split("${input:")
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}'))
+            .map(|(id, _)| id)
+        {
+            if inputs.iter().any(|input| input.id == id) {
+                continue;
+            }
+            let config = configs
+                .iter()
+                .find(|config| config.id == id)
+                .ok_or_else(|| anyhow!("`${{input:{id}}}` is not declared in `inputs`"))?;
+            let kind = match config.kind.as_str() {
+                "pickString" => InputKind::Pick(
+                    config
+                        .options
+                        .iter()
+                        .map(|option| match option {
+                            PickOption::Plain(value) => (value.clone(), value.clone()),
+                            PickOption::Labelled { label, value } => (label.clone(), value.clone()),
+                        })
+                        .collect(),
+                ),
+                "promptString" => InputKind::Text(config.default.clone()),
+                other => bail!("`{other}` inputs such as `{id}` are not supported"),
+            };
+            inputs.push(Input {
+                id: id.to_string(),
+                description: config.description.clone(),
+                kind,
+            });
+        }
+    }
+    Ok(inputs)
+}
+
+/// Process running the task labelled `label`, passing it `extra` arguments.
+/// `inputs` holds the values of its placeholders.
+pub fn invocation(
+    root: &Path,
+    label: &str,
+    extra: &[String],
+    inputs: &BTreeMap<String, String>,
+) -> Result<Invocation> {
+    let (task, _) = find(root, label)?;
+
+    let substitute = |value: &str| substitute(value, root, inputs);
+    let cwd = match &task.options.cwd {
+        Some(cwd) => root.join(substitute(cwd)?),
+        None => root.to_path_buf(),
+    };
+    let env = task
+        .options
+        .env
+        .iter()
+        .map(|(key, value)| Ok((key.clone(), substitute(value)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut invocation = match task.kind() {
+        "npm" => {
+            let script = task.script.as_deref().unwrap_or_default();
+            let cwd = match &task.path {
+                Some(path) => root.join(substitute(path)?),
+                None => cwd,
+            };
+            PackageManager::detect(root, &cwd).invocation(&cwd, &substitute(script)?, extra)
+        }
+        "process" => {
+            let command = task.command.as_ref().map(Arg::value).unwrap_or_default();
+            let args = task
+                .args
+                .iter()
+                .map(|arg| substitute(arg.value()))
+                .chain(extra.iter().cloned().map(Ok))
+                .collect::<Result<Vec<_>>>()?;
+            Invocation::new(substitute(command)?, args, cwd)
+        }
+        _ => {
+            // The command is a command line of its own; arguments are quoted.
+            let command = task.command.as_ref().map(Arg::value).unwrap_or_default();
+            let shell = shell(task.options.shell.as_ref());
+            let line = std::iter::once(substitute(command))
+                .chain(task.args.iter().map(|arg| {
+                    let value = substitute(arg.value())?;
+                    Ok(match arg {
+                        Arg::Plain(_) => shell.quote(&value),
+                        Arg::Quoted { .. } => value,
+                    })
+                }))
+                .chain(extra.iter().map(|arg| Ok(shell.quote(arg))))
+                .collect::<Result<Vec<_>>>()?
+                .join(" ");
+            shell.invocation(line, cwd)
+        }
+    };
+    invocation.env = env;
+    Ok(invocation)
+}
+
+/// Shell running `shell` tasks.
+fn shell(config: Option<&ShellConfig>) -> Shell {
+    let shell = Shell::new(config.and_then(|config| config.executable.as_deref()));
+    match config.and_then(|config| config.args.clone()) {
+        Some(args) => shell.with_args(args),
+        None => shell,
+    }
+}
+
+/// Replace the `${...}` variables VS Code substitutes in task properties.
+fn substitute(value: &str, root: &Path, inputs: &BTreeMap<String, String>) -> Result<String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("unterminated variable in `{value}`"))?;
+        let variable = &rest[start + 2..start + end];
+        match variable {
+            "workspaceFolder" | "workspaceRoot" | "cwd" => out.push_str(&root.to_string_lossy()),
+            "workspaceFolderBasename" => out.push_str(
+                &root
+                    .file_name()
+                    .map(|name| name.to_string_lossy())
+                    .unwrap_or_default(),
+            ),
+            "userHome" => out.push_str(
+                &env::var("HOME")
+                    .or_else(|_| env::var("USERPROFILE"))
+                    .unwrap_or_default(),
+            ),
+            "pathSeparator" | "/" => out.push(MAIN_SEPARATOR),
+            _ => {
+                if let Some(name) = variable.strip_prefix("env:") {
+                    out.push_str(&env::var(name).unwrap_or_default());
+                } else if let Some(id) = variable.strip_prefix("input:") {
+                    let value = inputs
+                        .get(id)
+                        .ok_or_else(|| anyhow!("no value was given for `${{input:{id}}}`"))?;
+                    out.push_str(value);
+                } else {
+                    bail!("`${{{variable}}}` is not supported in tasks.json");
+                }
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
@@ -0,0 +1,180 @@
+//! Tasks re-run whenever files of the workspace change.
+//!
+//! Every watch runs its task once when it starts and again after each burst
+//! of changes to files matching its globs. A run still going when files
+//! change is cancelled first, so servers restart on change.
+
+use crate::{
+    process::{self, RunningTask, RunningTasks},
+    Invocation, RunOptions,
+};
+use anyhow::{anyhow, Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use helix_plugin_sdk::EventSender;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+/// Quiet period ending a burst of changes, unless the watch sets another.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Paths never triggering a run unless the watch sets its own exclusions;
+/// tasks commonly write to them.
+pub const DEFAULT_EXCLUDE: &[&str] = &["**/.git/**", "**/node_modules/**", "**/target/**"];
+
+/// Task a watch runs and the files it watches.
+pub struct Spec {
+    pub provider: String,
+    pub name: String,
+    pub options: RunOptions,
+    pub invocation: Invocation,
+    /// Globs relative to the workspace root selecting the watched files.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub debounce: Duration,
+}
+
+enum Message {
+    Changed(Vec<PathBuf>),
+    Stop,
+}
+
+struct Watch {
+    sender: mpsc::Sender<Message>,
+    /// Dropping the watcher stops the delivery of changes.
+    _watcher: RecommendedWatcher,
+}
+
+/// Active watches, by id.
+#[derive(Default)]
+pub struct Watches {
+    watches: BTreeMap<u64, Watch>,
+    next_id: u64,
+}
+
+fn glob_set(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).with_context(|| format!("invalid glob `{glob}`"))?);
+    }
+    Ok(builder.build()?)
+}
+
+impl Watches {
+    /// Watch `root` for changes to the files selected by `spec`, running
+    /// its task with `running` and calling `on_exit` after every run.
+    pub fn start(
+        &mut self,
+        root: &Path,
+        spec: Spec,
+        running: RunningTasks,
+        events: EventSender,
+        on_exit: impl Fn(&RunningTask, ExitStatus) + Clone + Send + 'static,
+    ) -> Result<u64> {
+        let include = glob_set(&spec.include)?;
+        let exclude = glob_set(&spec.exclude)?;
+
+        let (sender, receiver) = mpsc::channel();
+        let changes = sender.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else { return };
+                if !event.kind.is_access() {
+                    let _ = changes.send(Message::Changed(event.paths));
+                }
+            })?;
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("failed to watch `{}`", root.display()))?;
+
+        self.next_id += 1;
+        let id = self.next_id;
+        self.watches.insert(
+            id,
+            Watch {
+                sender,
+                _watcher: watcher,
+            },
+        );
+
+        let root = root.to_path_buf();
+        thread::spawn(move || {
+            let channel = process::label(&spec.provider, &spec.name, &spec.options);
+            let run = || {
+                let started = running.start(
+                    &spec.provider,
+                    &spec.name,
+                    &spec.options,
+                    &spec.invocation,
+                    events.clone(),
+                    on_exit.clone(),
+                );
+                match started {
+                    Ok(task) => Some(task.id),
+                    Err(err) => {
+                        let _ = events
+                            .append_output(channel.as_str(), format!("failed to start: {err:#}\n"));
+                        None
+                    }
+                }
+            };
+            let matches = |path: &Path| {
+                let path = path.strip_prefix(&root).unwrap_or(path);
+                include.is_match(path) && !exclude.is_match(path)
+            };
+
+            let mut current = run();
+            loop {
+                let changed = match receiver.recv() {
+                    Ok(Message::Changed(paths)) => paths.into_iter().find(|path| matches(path)),
+                    Ok(Message::Stop) | Err(_) => return,
+                };
+                let Some(changed) = changed else {
+                    continue;
+                };
+                loop {
+                    match receiver.recv_timeout(spec.debounce) {
+                        Ok(Message::Changed(_)) => continue,
+                        Err(mpsc::RecvTimeoutError::Timeout) => break,
+                        Ok(Message::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+
+                if let Some(id) = current.take() {
+                    // Fails when the previous run already exited.
+                    let _ = running.cancel(id);
+                }
+                let changed = changed.strip_prefix(&root).unwrap_or(&changed);
+                let _ = events.append_output(
+                    channel.as_str(),
+                    format!("{} changed, running again\n", changed.display()),
+                );
+                current = run();
+            }
+        });
+        Ok(id)
+    }
+
+    /// Stop every watch.
+    pub fn stop_all(&mut self) {
+        for (_, watch) in std::mem::take(&mut self.watches) {
+            let _ = watch.sender.send(Message::Stop);
+        }
+    }
+
+    /// Stop the watch `id`. A run in progress is left running.
+    pub fn stop(&mut self, id: u64) -> Result<()> {
+        let watch = self
+            .watches
+            .remove(&id)
+            .ok_or_else(|| anyhow!("no watch with id {id}"))?;
+        let _ = watch.sender.send(Message::Stop);
+        Ok(())
+    }
+}
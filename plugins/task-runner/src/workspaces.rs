@@ -0,0 +1,166 @@
+//! Packages of monorepos: npm, yarn and pnpm workspaces and Cargo workspace
+//! members. Deno workspaces are read along with the rest of `deno.json`,
+//! see [`crate::deno`].
+
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+/// Depth up to which directories are matched against workspace globs.
+const MAX_DEPTH: usize = 4;
+
+/// Directories never holding workspace packages.
+pub const SKIPPED_DIRS: &[&str] = &["node_modules", "target", ".git"];
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum NpmWorkspaces {
+    Globs(Vec<String>),
+    /// Yarn's form, which may also list packages excluded from hoisting.
+    Config {
+        #[serde(default)]
+        packages: Vec<String>,
+    },
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PackageJson {
+    workspaces: Option<NpmWorkspaces>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PnpmWorkspace {
+    #[serde(default)]
+    packages: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CargoManifest {
+    workspace: Option<CargoWorkspace>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CargoWorkspace {
+    #[serde(default)]
+    members: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+/// Directories of the packages of the npm, yarn or pnpm workspace at
+/// `root`, relative to it.
+pub fn npm_packages(root: &Path) -> Result<Vec<String>> {
+    let pnpm = root.join("pnpm-workspace.yaml");
+    let globs = if pnpm.exists() {
+        let content = fs::read_to_string(&pnpm)
+            .with_context(|| format!("failed to read {}", pnpm.display()))?;
+        let workspace: PnpmWorkspace = serde_yaml::from_str(&content)
+            .with_context(|| format!("failed to parse {}", pnpm.display()))?;
+        workspace.packages
+    } else {
+        let path = root.join("package.json");
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Ok(Vec::new());
+        };
+        let package: PackageJson = serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        match package.workspaces {
+            Some(NpmWorkspaces::Globs(globs)) => globs,
+            Some(NpmWorkspaces::Config { packages }) => packages,
+            None => Vec::new(),
+        }
+    };
+    // Negated globs exclude packages, as in pnpm.
+    let (excluded, included): (Vec<_>, Vec<_>) =
+        globs.into_iter().partition(|glob| glob.starts_with('!'));
+    let excluded: Vec<_> = excluded
+        .iter()
+        .map(|glob| glob.trim_start_matches('!').to_string())
+        .collect();
+    expand(root, &included, &excluded, &["package.json"])
+}
+
+/// Directories of the members of the Cargo workspace at `root`, relative to
+/// it, excluding the root package.
+pub fn cargo_members(root: &Path) -> Result<Vec<String>> {
+    let path = root.join("Cargo.toml");
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(Vec::new());
+    };
+    let manifest: CargoManifest =
+        toml::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))?;
+    let Some(workspace) = manifest.workspace else {
+        return Ok(Vec::new());
+    };
+    let members = expand(
+        root,
+        &workspace.members,
+        &workspace.exclude,
+        &["Cargo.toml"],
+    )?;
+    Ok(members.into_iter().filter(|member| member != ".").collect())
+}
+
+fn glob_set(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        let glob = glob.trim_start_matches("./").trim_end_matches('/');
+        builder.add(
+            GlobBuilder::new(glob)
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("invalid workspace glob `{glob}`"))?,
+        );
+    }
+    Ok(builder.build()?)
+}
+
+/// Directories below `root` matching `include` but not `exclude` that hold
+/// one of `manifests`, sorted.
+pub fn expand(
+    root: &Path,
+    include: &[String],
+    exclude: &[String],
+    manifests: &[&str],
+) -> Result<Vec<String>> {
+    if include.is_empty() {
+        return Ok(Vec::new());
+    }
+    let include = glob_set(include)?;
+    let exclude = glob_set(exclude)?;
+    let mut packages = Vec::new();
+    let mut pending = vec![(String::new(), 0)];
+    while let Some((dir, depth)) = pending.pop() {
+        let Ok(entries) = fs::read_dir(root.join(&dir)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if !entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if SKIPPED_DIRS.contains(&name.as_str()) {
+                continue;
+            }
+            let path = if dir.is_empty() {
+                name
+            } else {
+                format!("{dir}/{name}")
+            };
+            if include.is_match(&path)
+                && !exclude.is_match(&path)
+                && manifests
+                    .iter()
+                    .any(|manifest| root.join(&path).join(manifest).is_file())
+            {
+                packages.push(path.clone());
+            }
+            if depth + 1 < MAX_DEPTH {
+                pending.push((path, depth + 1));
+            }
+        }
+    }
+    packages.sort();
+    Ok(packages)
+}